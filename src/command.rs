@@ -1,6 +1,8 @@
-//! Command execution results
+//! Command execution results and command lines
 //!
-//! This module provides types for handling command execution output.
+//! This module provides types for handling command execution output, and [`ShellCommand`] to
+//! build the command lines run on hosts : every value interpolated in a command line is quoted
+//! with [`quote`], so that it reaches the command as a single argument whatever it contains.
 
 /// Result of executing a command on a host.
 ///
@@ -33,3 +35,267 @@ pub struct CommandResult {
     /// The standard error captured from the command execution.
     pub stderr: String,
 }
+
+/// A chunk of output produced by a running command, as it arrives.
+///
+/// Chunks are raw bytes : a multi-byte character may be split across two chunks.
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::command::OutputChunk;
+///
+/// let chunk = OutputChunk::Stderr(b"warning: low disk space\n".to_vec());
+/// assert_eq!(chunk.text(), "warning: low disk space\n");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputChunk {
+    /// Bytes written by the command to its standard output.
+    Stdout(Vec<u8>),
+    /// Bytes written by the command to its standard error.
+    Stderr(Vec<u8>),
+}
+
+impl OutputChunk {
+    /// The content of the chunk, invalid UTF-8 sequences being replaced.
+    pub fn text(&self) -> std::borrow::Cow<'_, str> {
+        match self {
+            OutputChunk::Stdout(bytes) | OutputChunk::Stderr(bytes) => {
+                String::from_utf8_lossy(bytes)
+            }
+        }
+    }
+}
+
+/// Accumulates the chunks of a running command into a [`CommandResult`].
+#[derive(Debug, Default)]
+pub(crate) struct OutputCollector {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl OutputCollector {
+    /// Record a chunk, after passing it to `on_output`.
+    pub(crate) fn push<F: FnMut(OutputChunk)>(&mut self, chunk: OutputChunk, on_output: &mut F) {
+        match &chunk {
+            OutputChunk::Stdout(bytes) => self.stdout.extend_from_slice(bytes),
+            OutputChunk::Stderr(bytes) => self.stderr.extend_from_slice(bytes),
+        }
+        on_output(chunk);
+    }
+
+    pub(crate) fn into_result(self, return_code: i64) -> CommandResult {
+        CommandResult {
+            return_code,
+            stdout: String::from_utf8_lossy(&self.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&self.stderr).to_string(),
+        }
+    }
+}
+
+/// Quote a value as a single shell word.
+///
+/// Values made only of characters with no special meaning to the shell are left as is, others are
+/// wrapped in single quotes (a single quote itself becoming `'\''`).
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::command::quote;
+///
+/// assert_eq!(quote("nginx"), "nginx");
+/// assert_eq!(quote("it's; rm -rf /"), "'it'\\''s; rm -rf /'");
+/// ```
+pub fn quote(value: &str) -> std::borrow::Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c);
+    if !value.is_empty() && value.chars().all(is_safe) {
+        std::borrow::Cow::Borrowed(value)
+    } else {
+        std::borrow::Cow::Owned(format!("'{}'", value.replace('\'', "'\\''")))
+    }
+}
+
+/// A shell command line whose arguments are quoted.
+///
+/// Arguments added with [`ShellCommand::arg`] are always quoted, whereas shell syntax (operators,
+/// redirections...) must be explicitly added with [`ShellCommand::raw`].
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::command::ShellCommand;
+///
+/// let command = ShellCommand::new("usermod")
+///     .arg("-aG")
+///     .arg("docker")
+///     .arg("$(reboot)")
+///     .to_string();
+///
+/// assert_eq!(command, "usermod -aG docker '$(reboot)'");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellCommand {
+    line: String,
+}
+
+impl ShellCommand {
+    /// Start a command line with a program. The program name is trusted and not quoted.
+    pub fn new(program: &str) -> ShellCommand {
+        ShellCommand {
+            line: program.to_string(),
+        }
+    }
+
+    /// Add an argument, quoted.
+    pub fn arg<S: AsRef<str>>(mut self, value: S) -> ShellCommand {
+        self.line.push(' ');
+        self.line.push_str(&quote(value.as_ref()));
+        self
+    }
+
+    /// Add several arguments, each of them quoted.
+    pub fn args<I, S>(self, values: I) -> ShellCommand
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        values
+            .into_iter()
+            .fold(self, |command, value| command.arg(value))
+    }
+
+    /// Add shell syntax (`&&`, `|`, `>`...) as is. Never pass values to this method.
+    pub fn raw(mut self, syntax: &str) -> ShellCommand {
+        self.line.push(' ');
+        self.line.push_str(syntax);
+        self
+    }
+
+    /// Chain another command, run only if this one succeeded (`&&`).
+    pub fn and(self, next: ShellCommand) -> ShellCommand {
+        self.raw("&&").raw(&next.line)
+    }
+
+    /// Pipe the output of this command to another one (`|`).
+    pub fn pipe(self, next: ShellCommand) -> ShellCommand {
+        self.raw("|").raw(&next.line)
+    }
+
+    /// The command line, ready to be run.
+    pub fn as_str(&self) -> &str {
+        &self.line
+    }
+}
+
+impl std::fmt::Display for ShellCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.line)
+    }
+}
+
+/// Values a user could give to an attribute, trying to run another command
+#[cfg(test)]
+pub(crate) const HOSTILE_VALUES: [&str; 7] = [
+    "name'; touch /tmp/pwned; echo '",
+    "name; touch /tmp/pwned",
+    "$(touch /tmp/pwned)",
+    "`touch /tmp/pwned`",
+    "name\ntouch /tmp/pwned",
+    "name\n touch /tmp/pwned",
+    "name && touch /tmp/pwned || \"",
+];
+
+/// Check that no part of hostile values ended up outside of single quotes in a command line
+#[cfg(test)]
+pub(crate) fn assert_no_injection(command: &str) {
+    assert!(
+        command.contains("pwned"),
+        "no hostile value in : {}",
+        command
+    );
+
+    let mut unquoted = String::new();
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\'' => {
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                }
+            }
+            _ => unquoted.push(c),
+        }
+    }
+    assert!(!unquoted.contains("pwned"), "injection in : {}", command);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting_values() {
+        assert_eq!(quote("nginx=1.24.0-1"), "nginx=1.24.0-1");
+        assert_eq!(quote("/etc/nginx/nginx.conf"), "/etc/nginx/nginx.conf");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("nginx=1.24.*"), "'nginx=1.24.*'");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn hostile_values_stay_single_arguments() {
+        for value in HOSTILE_VALUES {
+            let command = ShellCommand::new("printf '%s\\0'").arg(value).arg("end");
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(command.as_str())
+                .output()
+                .unwrap();
+            assert_eq!(
+                output.stdout,
+                format!("{}\0end\0", value).into_bytes(),
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn chaining_commands() {
+        let command = ShellCommand::new("hostname").arg("web 01").and(
+            ShellCommand::new("echo")
+                .arg("web 01")
+                .raw(">")
+                .arg("/etc/hostname"),
+        );
+        assert_eq!(
+            command.as_str(),
+            "hostname 'web 01' && echo 'web 01' > /etc/hostname"
+        );
+
+        let command = ShellCommand::new("crontab")
+            .arg("-l")
+            .pipe(ShellCommand::new("grep").arg("-F").arg("$HOME"));
+        assert_eq!(command.as_str(), "crontab -l | grep -F '$HOME'");
+    }
+
+    #[test]
+    fn detecting_injections() {
+        for value in HOSTILE_VALUES {
+            assert_no_injection(ShellCommand::new("id").arg(value).as_str());
+
+            // Values interpolated as is, or between single quotes, are caught
+            let interpolated = [format!("id {}", value), format!("id '{}'", value)];
+            let caught = interpolated
+                .iter()
+                .any(|command| std::panic::catch_unwind(|| assert_no_injection(command)).is_err());
+            assert!(caught, "{}", value);
+        }
+    }
+}
@@ -17,6 +17,7 @@ use thiserror::Error;
 /// - `FailureToParseContent`: Parsing errors (YAML, JSON, etc.)
 /// - `FailureToRunCommand`: Command execution failed
 /// - `FailureToEstablishConnection`: Connection to host failed
+/// - `HostKeyMismatch`: The host presented a key differing from the expected one
 /// - `FailedInitialization`: Initialization or setup error
 /// - `FailedTcpBinding`: TCP binding failed
 /// - `FailedTaskDryRun`: Dry run of a task failed
@@ -74,6 +75,9 @@ pub enum RegentError {
     #[error("Failure to establish connection: '{0}'")]
     FailureToEstablishConnection(String),
 
+    #[error("Host key mismatch: '{0}'")]
+    HostKeyMismatch(String),
+
     #[error("Failed initialization: '{0}'")]
     FailedInitialization(String),
 
@@ -97,8 +101,8 @@ pub enum RegentError {
     #[error("Missing initialization: '{0}'")]
     MissingInitialization(String),
 
-    #[error("Group not found")]
-    GroupNotFound,
+    #[error("Group not found: '{0}'")]
+    GroupNotFound(String),
 
     #[error("Missing groups list")]
     MissingGroupsList,
@@ -135,6 +139,9 @@ pub enum RegentError {
     #[error("Failed to get file: '{0}'")]
     FailedToGetFile(String),
 
+    #[error("Failed to put file: '{0}'")]
+    FailedToPutFile(String),
+
     #[error("Incompatible host: '{0}'")]
     IncompatibleHost(String),
 }
@@ -0,0 +1,653 @@
+//! Host facts
+//!
+//! This module provides [`HostFacts`], the details of a host collected along its
+//! [`HostProperties`](crate::hosts::properties::HostProperties) : distribution, kernel, hardware,
+//! mounted filesystems, network interfaces and packages. Facts are collected on a best-effort
+//! basis : a fact which can't be collected on a host is left empty.
+//!
+//! Site-specific facts are gathered by [`FactProbe`]s, registered on hosts or inventories.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::Privilege;
+use crate::error::RegentError;
+use crate::hosts::handlers::HostHandler;
+use crate::hosts::properties::OsKind;
+
+/// Details of a host, exposed to templates and conditions.
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::hosts::facts::HostFacts;
+///
+/// fn describe(facts: &HostFacts) {
+///     if let (Some(architecture), Some(memory_bytes)) = (&facts.architecture, facts.memory_bytes) {
+///         println!("{} with {} MiB of memory", architecture, memory_bytes / 1024 / 1024);
+///     }
+/// }
+/// ```
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct HostFacts {
+    /// Name of the distribution (`NAME` of os-release).
+    pub distribution: Option<String>,
+    /// Identifier of the distribution (`ID` of os-release).
+    pub distribution_id: Option<String>,
+    /// Version of the distribution (`VERSION_ID` of os-release).
+    pub distribution_version: Option<String>,
+    /// Codename of the distribution release (`VERSION_CODENAME` of os-release).
+    pub distribution_codename: Option<String>,
+    /// Kernel release (`uname -r`).
+    pub kernel: Option<String>,
+    /// Machine hardware name (`uname -m`).
+    pub architecture: Option<String>,
+    /// Number of online CPUs.
+    pub cpu_count: Option<u32>,
+    /// Total memory.
+    pub memory_bytes: Option<u64>,
+    pub mounts: Vec<Mount>,
+    pub interfaces: Vec<NetworkInterface>,
+    pub package_manager: Option<PackageManager>,
+    /// Number of packages installed through the package manager.
+    pub package_count: Option<u64>,
+}
+
+/// A mounted filesystem.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Mount {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: Option<String>,
+    pub size_bytes: u64,
+    pub available_bytes: u64,
+}
+
+/// A network interface and its addresses, in CIDR notation.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac_address: Option<String>,
+    pub ipv4: Vec<String>,
+    pub ipv6: Vec<String>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum PackageManager {
+    Apt,
+    Dnf,
+    Yum,
+    Zypper,
+    Pacman,
+    Apk,
+    Portage,
+    Pkg,
+    Homebrew,
+}
+
+impl PackageManager {
+    /// Package managers with the command revealing them, in detection order
+    const DETECTION_ORDER: [(&'static str, PackageManager); 9] = [
+        ("apt-get", PackageManager::Apt),
+        ("dnf", PackageManager::Dnf),
+        ("yum", PackageManager::Yum),
+        ("zypper", PackageManager::Zypper),
+        ("pacman", PackageManager::Pacman),
+        ("apk", PackageManager::Apk),
+        ("emerge", PackageManager::Portage),
+        ("pkg", PackageManager::Pkg),
+        ("brew", PackageManager::Homebrew),
+    ];
+
+    /// Command printing one line per installed package
+    fn list_command(&self) -> &'static str {
+        match self {
+            PackageManager::Apt => "dpkg-query -W -f '.\\n'",
+            PackageManager::Dnf | PackageManager::Yum | PackageManager::Zypper => "rpm -qa",
+            PackageManager::Pacman => "pacman -Qq",
+            PackageManager::Apk => "apk info",
+            PackageManager::Portage => "ls -d /var/db/pkg/*/*",
+            PackageManager::Pkg => "pkg info -q",
+            PackageManager::Homebrew => "brew list -1",
+        }
+    }
+}
+
+impl HostFacts {
+    /// Collect the facts of a Unix-like host. `os_release` holds the content of `/etc/os-release`.
+    pub(crate) async fn collect<Handler: HostHandler>(
+        host_handler: &mut Handler,
+        os_kind: &OsKind,
+        os_release: &HashMap<String, String>,
+    ) -> HostFacts {
+        let mut facts = HostFacts::default();
+        if let OsKind::Unknown | OsKind::Windows(_) = os_kind {
+            return facts;
+        }
+
+        facts.distribution = os_release.get("NAME").cloned();
+        facts.distribution_id = os_release.get("ID").cloned();
+        facts.distribution_version = os_release.get("VERSION_ID").cloned();
+        facts.distribution_codename = os_release
+            .get("VERSION_CODENAME")
+            .or(os_release.get("UBUNTU_CODENAME"))
+            .filter(|codename| !codename.is_empty())
+            .cloned();
+        if let OsKind::MacOs(_) = os_kind {
+            facts.distribution = Some("macOS".to_string());
+            facts.distribution_version = output_of(host_handler, "sw_vers -productVersion").await;
+        }
+
+        facts.kernel = output_of(host_handler, "uname -r").await;
+        facts.architecture = output_of(host_handler, "uname -m").await;
+        facts.cpu_count = output_of(host_handler, "getconf _NPROCESSORS_ONLN")
+            .await
+            .and_then(|count| count.parse().ok());
+
+        facts.memory_bytes = match os_kind {
+            OsKind::Linux(_) => output_of(host_handler, "cat /proc/meminfo")
+                .await
+                .and_then(|meminfo| parse_meminfo(&meminfo)),
+            OsKind::MacOs(_) => output_of(host_handler, "sysctl -n hw.memsize")
+                .await
+                .and_then(|memory| memory.parse().ok()),
+            _ => output_of(host_handler, "sysctl -n hw.physmem")
+                .await
+                .and_then(|memory| memory.parse().ok()),
+        };
+
+        if let Some(df) = output_of(host_handler, "df -P -k").await {
+            let fs_types = match output_of(host_handler, "cat /proc/mounts").await {
+                Some(proc_mounts) => parse_proc_mounts(&proc_mounts),
+                None => HashMap::new(),
+            };
+            facts.mounts = parse_df(&df, &fs_types);
+        }
+
+        if let Some(addresses) = output_of(host_handler, "ip -o addr show").await {
+            let links = output_of(host_handler, "ip -o link show")
+                .await
+                .unwrap_or_default();
+            facts.interfaces = parse_ip_addr(&addresses, &links);
+        }
+
+        for (command, package_manager) in PackageManager::DETECTION_ORDER {
+            if let Ok(true) = host_handler
+                .is_this_command_available(command, &Privilege::None)
+                .await
+            {
+                facts.package_manager = Some(package_manager);
+                facts.package_count = output_of(host_handler, package_manager.list_command())
+                    .await
+                    .map(|packages| packages.lines().count() as u64);
+                break;
+            }
+        }
+
+        facts
+    }
+
+    /// Addresses of all interfaces but the loopback one, without their prefix length.
+    pub fn ip_addresses(&self) -> Vec<String> {
+        self.interfaces
+            .iter()
+            .filter(|interface| interface.name != "lo")
+            .flat_map(|interface| interface.ipv4.iter().chain(interface.ipv6.iter()))
+            .map(|address| address.split('/').next().unwrap_or(address).to_string())
+            .collect()
+    }
+
+    /// Expose the collected facts as template variables, named after the fields, plus
+    /// `ip_addresses`.
+    pub fn context(&self) -> tera::Context {
+        let mut context = tera::Context::new();
+        let optional_facts = [
+            ("distribution", &self.distribution),
+            ("distribution_id", &self.distribution_id),
+            ("distribution_version", &self.distribution_version),
+            ("distribution_codename", &self.distribution_codename),
+            ("kernel", &self.kernel),
+            ("architecture", &self.architecture),
+        ];
+        for (key, value) in optional_facts {
+            if let Some(value) = value {
+                context.insert(key, value);
+            }
+        }
+        if let Some(cpu_count) = self.cpu_count {
+            context.insert("cpu_count", &cpu_count);
+        }
+        if let Some(memory_bytes) = self.memory_bytes {
+            context.insert("memory_bytes", &memory_bytes);
+        }
+        if let Some(package_manager) = &self.package_manager {
+            context.insert("package_manager", &format!("{:?}", package_manager));
+        }
+        if let Some(package_count) = self.package_count {
+            context.insert("package_count", &package_count);
+        }
+        context.insert("mounts", &self.mounts);
+        context.insert("interfaces", &self.interfaces);
+        context.insert("ip_addresses", &self.ip_addresses());
+        context
+    }
+}
+
+/// A site-specific fact, read from a file or the output of a command on the host, exposed to
+/// templates and conditions under `custom_facts.<name>`.
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::hosts::facts::{FactParser, FactProbe};
+///
+/// let datacenter = FactProbe::file("datacenter", "/etc/datacenter");
+/// let app = FactProbe::command("app", "/opt/app/bin/app --build-info").with_parser(FactParser::Json);
+/// ```
+///
+/// In YAML (`FactProbes` of a host or an inventory) :
+///
+/// ```yaml
+/// FactProbes:
+///   - Name: datacenter
+///     Source: !File /etc/datacenter
+///   - Name: app
+///     Source: !Command /opt/app/bin/app --build-info
+///     Parser: Json
+/// ```
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct FactProbe {
+    name: String,
+    source: FactSource,
+    #[serde(default)]
+    parser: FactParser,
+}
+
+/// Where the content of a [`FactProbe`] comes from.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum FactSource {
+    /// Standard output of a command, which must succeed.
+    Command(String),
+    /// Content of a file.
+    File(String),
+}
+
+/// How the content of a [`FactProbe`] is turned into a fact.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum FactParser {
+    /// The whole content as a string, without surrounding whitespaces.
+    #[default]
+    Raw,
+    /// A JSON document.
+    Json,
+    /// `KEY=VALUE` lines, as a map of strings.
+    KeyValue,
+}
+
+impl FactProbe {
+    pub fn command(name: &str, command: &str) -> Self {
+        FactProbe {
+            name: name.to_string(),
+            source: FactSource::Command(command.to_string()),
+            parser: FactParser::default(),
+        }
+    }
+
+    pub fn file(name: &str, path: &str) -> Self {
+        FactProbe {
+            name: name.to_string(),
+            source: FactSource::File(path.to_string()),
+            parser: FactParser::default(),
+        }
+    }
+
+    pub fn with_parser(mut self, parser: FactParser) -> Self {
+        self.parser = parser;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Read the fact on the host.
+    pub async fn probe<Handler: HostHandler>(
+        &self,
+        host_handler: &mut Handler,
+    ) -> Result<serde_json::Value, RegentError> {
+        let content = match &self.source {
+            FactSource::Command(command) => {
+                let cmd_result = host_handler.run_command(command, &Privilege::None).await?;
+                if cmd_result.return_code != 0 {
+                    return Err(RegentError::FailureToRunCommand(format!(
+                        "'{}' exited with {} : {}",
+                        command,
+                        cmd_result.return_code,
+                        cmd_result.stderr.trim()
+                    )));
+                }
+                cmd_result.stdout
+            }
+            FactSource::File(path) => {
+                let file_content = host_handler.get_file(PathBuf::from(path)).await?;
+                String::from_utf8_lossy(&file_content).to_string()
+            }
+        };
+        self.parser.parse(&content)
+    }
+}
+
+impl FactParser {
+    fn parse(&self, content: &str) -> Result<serde_json::Value, RegentError> {
+        match self {
+            FactParser::Raw => Ok(serde_json::Value::String(content.trim().to_string())),
+            FactParser::Json => serde_json::from_str(content)
+                .map_err(|details| RegentError::FailureToParseContent(format!("{:?}", details))),
+            FactParser::KeyValue => Ok(serde_json::Value::Object(
+                parse_key_values(content)
+                    .into_iter()
+                    .map(|(key, value)| (key, serde_json::Value::String(value)))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// Trimmed standard output of a command, if it succeeded with some output
+async fn output_of<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    command: &str,
+) -> Option<String> {
+    let cmd_result = host_handler
+        .run_command(command, &Privilege::None)
+        .await
+        .ok()?;
+    let output = cmd_result.stdout.trim();
+    (cmd_result.return_code == 0 && !output.is_empty()).then(|| output.to_string())
+}
+
+/// Variables of a KEY=VALUE file such as os-release, without their quotes
+pub(crate) fn parse_key_values(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .or_else(|| {
+                    value
+                        .strip_prefix('\'')
+                        .and_then(|value| value.strip_suffix('\''))
+                })
+                .unwrap_or(value);
+            (key.trim().to_string(), value.to_string())
+        })
+        .collect()
+}
+
+/// Total memory out of /proc/meminfo
+fn parse_meminfo(meminfo: &str) -> Option<u64> {
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))
+        .and_then(|total| total.split_whitespace().next())
+        .and_then(|kilobytes| kilobytes.parse::<u64>().ok())
+        .map(|kilobytes| kilobytes * 1024)
+}
+
+/// Filesystem type of each mount point, out of /proc/mounts
+fn parse_proc_mounts(proc_mounts: &str) -> HashMap<String, String> {
+    proc_mounts
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                // Spaces in mount points are written as \040
+                [_, mount_point, fs_type, ..] => {
+                    Some((mount_point.replace("\\040", " "), fs_type.to_string()))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Mounted filesystems out of the output of `df -P -k`
+fn parse_df(df: &str, fs_types: &HashMap<String, String>) -> Vec<Mount> {
+    df.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            let mount_point = fields[5..].join(" ");
+            Some(Mount {
+                device: fields[0].to_string(),
+                fs_type: fs_types.get(&mount_point).cloned(),
+                size_bytes: fields[1].parse::<u64>().ok()? * 1024,
+                available_bytes: fields[3].parse::<u64>().ok()? * 1024,
+                mount_point,
+            })
+        })
+        .collect()
+}
+
+/// Network interfaces out of the outputs of `ip -o addr show` and `ip -o link show`
+fn parse_ip_addr(addresses: &str, links: &str) -> Vec<NetworkInterface> {
+    // "2: eth0@if7: <BROADCAST,MULTICAST,UP> ..." -> "eth0"
+    let interface_name = |field: &str| {
+        let name = field.trim_end_matches(':');
+        name.split('@').next().unwrap_or(name).to_string()
+    };
+
+    let mut interfaces: Vec<NetworkInterface> = Vec::new();
+    for line in addresses.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [_, name, family, address, ..] = fields[..] else {
+            continue;
+        };
+        let name = interface_name(name);
+        let position = match interfaces
+            .iter()
+            .position(|interface| interface.name == name)
+        {
+            Some(position) => position,
+            None => {
+                interfaces.push(NetworkInterface {
+                    name,
+                    ..Default::default()
+                });
+                interfaces.len() - 1
+            }
+        };
+        match family {
+            "inet" => interfaces[position].ipv4.push(address.to_string()),
+            "inet6" => interfaces[position].ipv6.push(address.to_string()),
+            _ => {}
+        }
+    }
+
+    for line in links.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (Some(name), Some(link_position)) = (
+            fields.get(1),
+            fields.iter().position(|field| *field == "link/ether"),
+        ) else {
+            continue;
+        };
+        let name = interface_name(name);
+        if let Some(interface) = interfaces
+            .iter_mut()
+            .find(|interface| interface.name == name)
+        {
+            interface.mac_address = fields.get(link_position + 1).map(|mac| mac.to_string());
+        }
+    }
+
+    interfaces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_os_release() {
+        let os_release = parse_key_values(
+            r#"PRETTY_NAME="Ubuntu 24.04.1 LTS"
+NAME="Ubuntu"
+VERSION_ID="24.04"
+VERSION_CODENAME=noble
+# comment
+ID_LIKE='debian'
+"#,
+        );
+        assert_eq!(os_release["NAME"], "Ubuntu");
+        assert_eq!(os_release["VERSION_ID"], "24.04");
+        assert_eq!(os_release["VERSION_CODENAME"], "noble");
+        assert_eq!(os_release["ID_LIKE"], "debian");
+        assert_eq!(os_release.len(), 5);
+    }
+
+    #[test]
+    fn parsing_memory_and_mounts() {
+        let meminfo = "MemTotal:       16314468 kB\nMemFree:         8372892 kB\n";
+        assert_eq!(parse_meminfo(meminfo), Some(16314468 * 1024));
+        assert_eq!(parse_meminfo("MemFree: 12 kB\n"), None);
+
+        let fs_types = parse_proc_mounts(
+            "/dev/sda1 / ext4 rw,relatime 0 0\n/dev/sdb1 /mnt/my\\040data xfs rw 0 0\n",
+        );
+        let mounts = parse_df(
+            r#"Filesystem     1024-blocks     Used Available Capacity Mounted on
+/dev/sda1         61252420 20128580  37982012      35% /
+/dev/sdb1             1024      512       512      50% /mnt/my data
+tmpfs               815676        0    815676       0% /run/user/1000
+"#,
+            &fs_types,
+        );
+
+        assert_eq!(mounts.len(), 3);
+        assert_eq!(
+            mounts[0],
+            Mount {
+                device: "/dev/sda1".to_string(),
+                mount_point: "/".to_string(),
+                fs_type: Some("ext4".to_string()),
+                size_bytes: 61252420 * 1024,
+                available_bytes: 37982012 * 1024,
+            }
+        );
+        assert_eq!(mounts[1].mount_point, "/mnt/my data");
+        assert_eq!(mounts[1].fs_type.as_deref(), Some("xfs"));
+        assert_eq!(mounts[2].fs_type, None);
+    }
+
+    #[test]
+    fn parsing_network_interfaces() {
+        let interfaces = parse_ip_addr(
+            r#"1: lo    inet 127.0.0.1/8 scope host lo\       valid_lft forever preferred_lft forever
+1: lo    inet6 ::1/128 scope host noprefixroute \       valid_lft forever preferred_lft forever
+2: eth0    inet 10.0.0.5/24 brd 10.0.0.255 scope global eth0\       valid_lft forever preferred_lft forever
+2: eth0    inet6 fe80::42:acff:fe11:2/64 scope link \       valid_lft forever preferred_lft forever
+"#,
+            r#"1: lo: <LOOPBACK,UP,LOWER_UP> mtu 65536 qdisc noqueue state UNKNOWN mode DEFAULT group default qlen 1000\    link/loopback 00:00:00:00:00:00 brd 00:00:00:00:00:00
+2: eth0@if7: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500 qdisc noqueue state UP mode DEFAULT group default \    link/ether 02:42:ac:11:00:02 brd ff:ff:ff:ff:ff:ff link-netnsid 0
+"#,
+        );
+
+        assert_eq!(
+            interfaces,
+            vec![
+                NetworkInterface {
+                    name: "lo".to_string(),
+                    mac_address: None,
+                    ipv4: vec!["127.0.0.1/8".to_string()],
+                    ipv6: vec!["::1/128".to_string()],
+                },
+                NetworkInterface {
+                    name: "eth0".to_string(),
+                    mac_address: Some("02:42:ac:11:00:02".to_string()),
+                    ipv4: vec!["10.0.0.5/24".to_string()],
+                    ipv6: vec!["fe80::42:acff:fe11:2/64".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parsing_probed_facts() {
+        assert_eq!(
+            FactParser::Raw.parse("  par1\n").unwrap(),
+            serde_json::json!("par1")
+        );
+        assert_eq!(
+            FactParser::Json
+                .parse(r#"{"version": "1.2.3", "features": ["a", "b"]}"#)
+                .unwrap(),
+            serde_json::json!({"version": "1.2.3", "features": ["a", "b"]})
+        );
+        assert_eq!(
+            FactParser::KeyValue
+                .parse("# comment\nDATACENTER=par1\nRACK=\"b12\"\n")
+                .unwrap(),
+            serde_json::json!({"DATACENTER": "par1", "RACK": "b12"})
+        );
+        assert!(matches!(
+            FactParser::Json.parse("version: 1.2.3"),
+            Err(RegentError::FailureToParseContent(_))
+        ));
+
+        let fact_probes: Vec<FactProbe> = yaml_serde::from_str(
+            r#"---
+- Name: datacenter
+  Source: !File /etc/datacenter
+- Name: app
+  Source: !Command /opt/app/bin/app --build-info
+  Parser: Json
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            fact_probes,
+            vec![
+                FactProbe::file("datacenter", "/etc/datacenter"),
+                FactProbe::command("app", "/opt/app/bin/app --build-info")
+                    .with_parser(FactParser::Json),
+            ]
+        );
+    }
+
+    #[test]
+    fn exposing_facts_to_templates() {
+        let facts = HostFacts {
+            distribution: Some("Debian GNU/Linux".to_string()),
+            distribution_codename: Some("bookworm".to_string()),
+            cpu_count: Some(4),
+            package_manager: Some(PackageManager::Apt),
+            interfaces: vec![NetworkInterface {
+                name: "eth0".to_string(),
+                ipv4: vec!["10.0.0.5/24".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let rendered = tera::Tera::one_off(
+            "{{ distribution_codename }} {{ cpu_count * 2 }} {{ package_manager }} {{ ip_addresses | join(sep=',') }} {{ interfaces[0].name }}{% if kernel is defined %} {{ kernel }}{% endif %}",
+            &facts.context(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(rendered, "bookworm 8 Apt 10.0.0.5 eth0");
+    }
+}
@@ -2,18 +2,26 @@
 //!
 //! This module provides the [`LocalHostHandler`] for executing operations
 //! on the local machine. It implements the [`HostHandler`] trait and provides
-//! command execution, file retrieval, and connection management for local operations.
+//! command execution, file transfer, and connection management for local operations.
 
 use crate::command::CommandResult;
+use crate::command::{OutputChunk, OutputCollector, ShellCommand};
 use crate::error::RegentError;
 use crate::hosts::handlers::HostHandler;
 use crate::hosts::handlers::final_command;
+use crate::hosts::handlers::temporary_path;
+use crate::hosts::handlers::{PromptAnswer, PromptResponder};
 use crate::hosts::privilege::Credentials;
 use crate::hosts::privilege::Privilege;
+use crate::secrets::Secret;
 use crate::secrets::SecretProvider;
 
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command;
 // use std::process::Command;
 
@@ -45,6 +53,9 @@ pub struct LocalHostHandler {
     ///
     /// This determines how commands are executed (as current user or with credentials).
     pub user: WhichUser,
+    /// Password answering the prompt of privilege escalation commands.
+    #[serde(skip)]
+    become_password: Option<Secret<String>>,
 }
 
 impl LocalHostHandler {
@@ -67,7 +78,19 @@ impl LocalHostHandler {
     /// // let handler = LocalHostHandler::from(WhichUser::UsernamePassword(creds));
     /// ```
     pub fn from(user: WhichUser) -> Self {
-        Self { user }
+        Self {
+            user,
+            become_password: None,
+        }
+    }
+
+    /// Set the password answering the prompt of privilege escalation commands (sudo...).
+    ///
+    /// The prompt is answered through a pipe : commands reading the password from a terminal
+    /// (su, doas) are not supported on the local host.
+    pub fn with_become_password(mut self, become_password: Secret<String>) -> Self {
+        self.become_password = Some(become_password);
+        self
     }
 }
 
@@ -96,7 +119,7 @@ impl HostHandler for LocalHostHandler {
         // TODO : use privilege (some commands do no exist for every user (PATH and so on...))
         let check_cmd_result = Command::new("sh")
             .arg("-c")
-            .arg(format!("command -v {}", command))
+            .arg(ShellCommand::new("command -v").arg(command).as_str())
             .output()
             .await;
 
@@ -130,31 +153,98 @@ impl HostHandler for LocalHostHandler {
         command: &str,
         privilege: &Privilege,
     ) -> Result<CommandResult, RegentError> {
-        let final_command = final_command(command, privilege, &self.user);
+        self.run_command_streaming(command, privilege, |_| {}).await
+    }
+
+    async fn run_command_streaming<F: FnMut(OutputChunk) + Send>(
+        &mut self,
+        command: &str,
+        privilege: &Privilege,
+        mut on_output: F,
+    ) -> Result<CommandResult, RegentError> {
+        let become_password = self.become_password.clone().map(Secret::inner);
+        let final_command =
+            final_command(command, privilege, &self.user, become_password.as_deref());
+        let mut prompt_responder = PromptResponder::from(&final_command);
 
-        let result = Command::new("sh")
+        let mut child = match Command::new("sh")
             .arg("-c")
-            .arg(final_command)
-            .output()
-            .await;
+            .arg(&final_command.command)
+            .stdin(match final_command.password {
+                Some(_) => Stdio::piped(),
+                None => Stdio::null(),
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => return Err(RegentError::FailureToRunCommand(format!("{}", e))),
+        };
 
-        match result {
-            Ok(output) => {
-                match output.status.code() {
-                    Some(code) => Ok(CommandResult {
-                        return_code: code.into(),
-                        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                    }),
-                    None => {
-                        // Process terminated by a signal -> consider this as a failure to run the command to completion
-                        Err(RegentError::FailureToRunCommand(format!(
-                            "Process terminated by a signal : {:?}",
-                            output
-                        )))
+        let (Some(mut stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take())
+        else {
+            return Err(RegentError::FailureToRunCommand(
+                "Failed to capture the output of the command".to_string(),
+            ));
+        };
+
+        let mut stdin = child.stdin.take();
+
+        // Both streams are read as they are written, until the command closes them
+        let mut collector = OutputCollector::default();
+        let mut stdout_buffer = [0u8; 8192];
+        let mut stderr_buffer = [0u8; 8192];
+        let mut stdout_open = true;
+        let mut stderr_open = true;
+        while stdout_open || stderr_open {
+            let mut chunk = tokio::select! {
+                read = stdout.read(&mut stdout_buffer), if stdout_open => match read {
+                    Ok(0) | Err(_) => {
+                        stdout_open = false;
+                        continue;
+                    }
+                    Ok(n) => OutputChunk::Stdout(stdout_buffer[..n].to_vec()),
+                },
+                read = stderr.read(&mut stderr_buffer), if stderr_open => match read {
+                    Ok(0) | Err(_) => {
+                        stderr_open = false;
+                        continue;
+                    }
+                    Ok(n) => OutputChunk::Stderr(stderr_buffer[..n].to_vec()),
+                },
+            };
+
+            let (OutputChunk::Stdout(bytes) | OutputChunk::Stderr(bytes)) = &mut chunk;
+            match prompt_responder.inspect(bytes) {
+                PromptAnswer::Nothing => {}
+                PromptAnswer::Password(answer) => {
+                    if let Some(stdin) = &mut stdin {
+                        let _ = stdin.write_all(&answer).await;
                     }
                 }
+                PromptAnswer::Abort => {
+                    let _ = child.start_kill();
+                }
+            }
+            // Nothing else is written to the command, which must not wait for more input
+            if !prompt_responder.awaits_prompt() {
+                stdin = None;
             }
+            collector.push(chunk, &mut on_output);
+        }
+
+        match child.wait().await {
+            Ok(status) => match status.code() {
+                Some(code) => Ok(collector.into_result(code.into())),
+                None => {
+                    // Process terminated by a signal -> consider this as a failure to run the command to completion
+                    Err(RegentError::FailureToRunCommand(format!(
+                        "Process terminated by a signal : {:?}",
+                        status
+                    )))
+                }
+            },
             Err(e) => Err(RegentError::FailureToRunCommand(format!("{}", e))),
         }
     }
@@ -193,6 +283,50 @@ impl HostHandler for LocalHostHandler {
             }
         }
     }
+
+    async fn put_file(
+        &mut self,
+        path: PathBuf,
+        content: &[u8],
+        mode: u32,
+    ) -> Result<(), RegentError> {
+        let temporary_path = temporary_path(&path);
+
+        let write_result = match write_with_mode(&temporary_path, content, mode).await {
+            Ok(()) => tokio::fs::rename(&temporary_path, &path).await,
+            Err(details) => Err(details),
+        };
+
+        if let Err(details) = write_result {
+            let _ = tokio::fs::remove_file(&temporary_path).await;
+            return Err(RegentError::FailedToPutFile(format!(
+                "{} : {:?}",
+                path.display(),
+                details
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Create a new file with the given permissions before any content is written to it.
+async fn write_with_mode(path: &Path, content: &[u8], mode: u32) -> std::io::Result<()> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut file = options.open(path).await?;
+    file.write_all(content).await?;
+    // The mode given at creation is subject to the umask
+    #[cfg(unix)]
+    file.set_permissions(std::fs::Permissions::from_mode(mode))
+        .await?;
+    // Writes are done in the background until flushed
+    file.flush().await?;
+    Ok(())
 }
 
 /// Specifies which user to execute commands as on the local machine.
@@ -222,3 +356,53 @@ pub enum WhichUser {
     /// Execute commands as a specific user with provided credentials.
     UsernamePassword(Credentials),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streaming_stdout_and_stderr_separately() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut handler = LocalHostHandler::from(WhichUser::CurrentUser);
+
+        let mut chunks = Vec::new();
+        let result = runtime
+            .block_on(handler.run_command_streaming(
+                "echo out; echo err >&2; exit 3",
+                &Privilege::None,
+                |chunk| chunks.push(chunk),
+            ))
+            .unwrap();
+
+        assert_eq!(result.return_code, 3);
+        assert_eq!(result.stdout, "out\n");
+        assert_eq!(result.stderr, "err\n");
+        assert!(chunks.contains(&OutputChunk::Stdout(b"out\n".to_vec())));
+        assert!(chunks.contains(&OutputChunk::Stderr(b"err\n".to_vec())));
+    }
+
+    #[test]
+    fn putting_files_with_exact_mode() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut handler = LocalHostHandler::from(WhichUser::CurrentUser);
+        let directory = std::env::temp_dir().join(format!("regent-put-file-{}", nanoid::nanoid!()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("config");
+        std::fs::write(&path, "old").unwrap();
+
+        runtime
+            .block_on(handler.put_file(path.clone(), b"new", 0o664))
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o7777,
+            0o664
+        );
+        // The temporary file was renamed over the destination
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -7,9 +7,12 @@
 pub mod localhost;
 pub mod ssh2;
 
+use nanoid::nanoid;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::command::OutputChunk;
+use crate::command::quote;
 use crate::error::RegentError;
 use crate::hosts::handlers::localhost::WhichUser;
 use crate::hosts::handlers::ssh2::Ssh2Auth;
@@ -106,12 +109,34 @@ impl TargetUser {
 ///
 /// ```no_run
 /// use regent_sdk::hosts::handlers::{ConnectionMethod, TargetUser, Ssh2Auth};
+/// use regent_sdk::HostKeyPolicy;
+/// use regent_sdk::hosts::handlers::ssh2::{Ssh2AuthReference, Ssh2JumpHostReference};
+/// use regent_sdk::secrets::SecretReference;
 ///
 /// // Localhost connection
 /// let method = ConnectionMethod::Localhost(TargetUser::current_user());
 ///
-/// // SSH connection
+/// // SSH connection, only accepting hosts recorded in ~/.ssh/known_hosts
 /// let method = ConnectionMethod::Ssh2(Ssh2Auth::username_password("creds", Some("files")));
+///
+/// // SSH connection to a host whose key fingerprint is known beforehand
+/// let method = ConnectionMethod::Ssh2(
+///     Ssh2Auth::username_password("creds", Some("files")).with_host_key_policy(
+///         HostKeyPolicy::Pinned {
+///             fingerprint: "SHA256:ANFBtUO7Q2LKmYCe505PB7oAOxqYtNQhkEZxLobzkck".to_string(),
+///         },
+///     ),
+/// );
+///
+/// // SSH connection through a bastion
+/// let method = ConnectionMethod::Ssh2(
+///     Ssh2Auth::username_password("creds", Some("files")).with_jump_host(
+///         Ssh2JumpHostReference::from(
+///             "bastion.example.com:22",
+///             Ssh2AuthReference::UsernamePassword(SecretReference::from("bastion-creds", None)),
+///         ),
+///     ),
+/// );
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -134,8 +159,10 @@ pub enum ConnectionMethod {
 /// - [`disconnect`]: Close the connection
 /// - [`is_this_command_available`]: Check if a command exists on the host
 /// - [`run_command`]: Execute a command on the host
+/// - [`run_command_streaming`]: Execute a command on the host, following its output live
 /// - [`run_windows_command`]: Execute a Windows command
 /// - [`get_file`]: Retrieve a file from the host
+/// - [`put_file`]: Write a file on the host
 ///
 /// # Example
 ///
@@ -204,6 +231,28 @@ pub trait HostHandler: Sized {
         privilege: &Privilege,
     ) -> Result<CommandResult, RegentError>;
 
+    /// Execute a command on the host, passing its output to `on_output` as it arrives.
+    ///
+    /// Useful to follow long-running commands (e.g. package upgrades) live. The whole output is
+    /// still returned once the command completes, like with [`HostHandler::run_command`].
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to execute
+    /// * `privilege` - The privilege level to use
+    /// * `on_output` - Called with each chunk of stdout or stderr, in order of arrival
+    ///
+    /// # Returns
+    ///
+    /// A [`CommandResult`] containing the exit code, stdout, and stderr,
+    /// or a [`RegentError`] if execution failed.
+    fn run_command_streaming<F: FnMut(OutputChunk) + Send>(
+        &mut self,
+        command: &str,
+        privilege: &Privilege,
+        on_output: F,
+    ) -> impl Future<Output = Result<CommandResult, RegentError>> + Send;
+
     /// Execute a Windows command on the host.
     ///
     /// This method is specifically for Windows command execution.
@@ -227,6 +276,28 @@ pub trait HostHandler: Sized {
     ///
     /// The file contents as a byte vector, or a [`RegentError`] if retrieval failed.
     async fn get_file(&mut self, path: PathBuf) -> Result<Vec<u8>, RegentError>;
+
+    /// Write a file on the host, as the connected user.
+    ///
+    /// The content is first written to a temporary file in the same directory (see
+    /// [`temporary_path`]), then renamed over `path`, so that the file is never seen partially
+    /// written. To write files the connected user has no access to, see [`install_file`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file to write
+    /// * `content` - The raw content of the file
+    /// * `mode` - The permissions of the file (e.g. `0o644`)
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the file is in place, or a [`RegentError`] if the upload failed.
+    fn put_file(
+        &mut self,
+        path: PathBuf,
+        content: &[u8],
+        mode: u32,
+    ) -> impl Future<Output = Result<(), RegentError>> + Send;
 }
 
 /// Enum that can hold any type of host handler.
@@ -355,6 +426,26 @@ impl HostHandler for Handler {
         }
     }
 
+    async fn run_command_streaming<F: FnMut(OutputChunk) + Send>(
+        &mut self,
+        command: &str,
+        privilege: &Privilege,
+        on_output: F,
+    ) -> Result<CommandResult, RegentError> {
+        match self {
+            Handler::LocalHost(handler) => {
+                handler
+                    .run_command_streaming(command, privilege, on_output)
+                    .await
+            }
+            Handler::Ssh2(handler) => {
+                handler
+                    .run_command_streaming(command, privilege, on_output)
+                    .await
+            }
+        }
+    }
+
     async fn run_windows_command(&mut self, command: &str) -> Result<CommandResult, RegentError> {
         match self {
             Handler::LocalHost(handler) => handler.run_windows_command(command).await,
@@ -368,6 +459,82 @@ impl HostHandler for Handler {
             Handler::Ssh2(handler) => handler.get_file(path).await,
         }
     }
+
+    async fn put_file(
+        &mut self,
+        path: PathBuf,
+        content: &[u8],
+        mode: u32,
+    ) -> Result<(), RegentError> {
+        match self {
+            Handler::LocalHost(handler) => handler.put_file(path, content, mode).await,
+            Handler::Ssh2(handler) => handler.put_file(path, content, mode).await,
+        }
+    }
+}
+
+/// Hidden path next to `path`, where a file is written before being renamed over `path`.
+pub fn temporary_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.regent-{}", file_name, nanoid!(8)))
+}
+
+/// Install a file on the host, with the given privilege.
+///
+/// [`HostHandler::put_file`] runs as the connected user, who usually cannot write system files.
+/// The content is thus uploaded to a staging file only this user can read, then installed with
+/// `privilege` : copied next to `path`, checked with the `validate` command if any (`%s` being
+/// replaced by the path of the copy), given the ownership and permissions of the file it
+/// replaces (read with `stat -c`, which BusyBox supports as well), and renamed over it.
+///
+/// # Returns
+///
+/// The result of the installation command, or a [`RegentError`] if the upload failed.
+pub async fn install_file<H: HostHandler>(
+    host_handler: &mut H,
+    path: &str,
+    content: &[u8],
+    validate: Option<&str>,
+    privilege: &Privilege,
+) -> Result<CommandResult, RegentError> {
+    let staging_path = format!("/tmp/.regent-staging-{}", nanoid!(12));
+    host_handler
+        .put_file(PathBuf::from(&staging_path), content, 0o600)
+        .await?;
+
+    host_handler
+        .run_command(&install_command(&staging_path, path, validate), privilege)
+        .await
+}
+
+/// Shell command moving a staging file into place (see [`install_file`]). The staging file is
+/// removed whatever happens.
+fn install_command(staging_path: &str, path: &str, validate: Option<&str>) -> String {
+    let temporary_path = temporary_path(Path::new(path)).display().to_string();
+    let (staging, tmp, path) = (quote(staging_path), quote(&temporary_path), quote(path));
+    let validation = match validate {
+        Some(validate) => format!("{} && ", validate.replace("%s", &tmp)),
+        None => String::new(),
+    };
+
+    let script = format!(
+        "trap 'rm -f {staging}' EXIT; cat {staging} > {tmp} && {validation}\
+         {{ test ! -e {path} || {{ chown \"$(stat -c %u:%g {path})\" {tmp} && chmod \"$(stat -c %a {path})\" {tmp}; }}; }} && \
+         mv -f {tmp} {path} || {{ rm -f {tmp}; exit 1; }}",
+    );
+    format!("sh -c \"{}\"", escape_for_double_quotes(&script))
+}
+
+/// Escape a command for use inside a double-quoted `sh -c` argument.
+fn escape_for_double_quotes(command: &str) -> String {
+    command
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('`', "\\`")
 }
 
 // #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -376,60 +543,379 @@ impl HostHandler for Handler {
 //     // Ssh2(NewSsh2ConnectionDetails),
 // }
 
+/// Prompt given to sudo, so that it can be told apart from the output of the command
+const BECOME_PROMPT: &str = "[regent-become-password]";
+
+/// A command line escalating privileges, and how to answer the password prompt of the escalation
+/// command
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrivilegedCommand {
+    /// The command line to run
+    pub command: String,
+    /// Password to answer the prompt with, if any
+    pub password: Option<String>,
+    /// Whether the escalation command reads the password from a terminal rather than from stdin
+    /// (su, doas)
+    pub needs_terminal: bool,
+}
+
+impl PrivilegedCommand {
+    fn from(command: String, password: Option<&str>, needs_terminal: bool) -> Self {
+        Self {
+            command,
+            password: password.map(str::to_string),
+            needs_terminal: needs_terminal && password.is_some(),
+        }
+    }
+}
+
 // TODO : add some syntax checks
-pub fn final_command(cmd: &str, privilege: &Privilege, user: &WhichUser) -> String {
+/// Command line running `cmd` with `privilege`, as `user`. A `become_password` is only used if the
+/// escalation command prompts for it.
+pub fn final_command(
+    cmd: &str,
+    privilege: &Privilege,
+    user: &WhichUser,
+    become_password: Option<&str>,
+) -> PrivilegedCommand {
     match user {
         WhichUser::CurrentUser => match privilege {
-            Privilege::None => format!("{} 2>&1", cmd),
-            Privilege::WithSudo => format!("sudo {} 2>&1", cmd),
-            Privilege::WithSudoRs => format!("sudo-rs {} 2>&1", cmd),
-        },
-        WhichUser::UsernamePassword(credentials) => match privilege {
-            Privilege::None => format!(
-                "echo {} | su - {} -c \"{}\" 2>&1", // echo <otherpwd> | su - otheruser -c "my command line"
-                credentials.password(),
-                credentials.username(),
-                cmd
-            ),
-            Privilege::WithSudo => format!(
-                "echo {} | sudo -S -u {} {} 2>&1",
-                credentials.password(),
-                credentials.username(),
-                cmd
-            ),
-            Privilege::WithSudoRs => format!(
-                "echo {} | sudo-rs -S -u {} {} 2>&1",
-                credentials.password(),
-                credentials.username(),
-                cmd
-            ),
+            Privilege::None => PrivilegedCommand::from(cmd.to_string(), None, false),
+            Privilege::WithSudo => sudo_command("sudo", None, cmd, become_password),
+            Privilege::WithSudoRs => sudo_command("sudo-rs", None, cmd, become_password),
+            Privilege::WithDoas => {
+                PrivilegedCommand::from(format!("doas sh -c {}", quote(cmd)), become_password, true)
+            }
+            Privilege::WithSu => {
+                PrivilegedCommand::from(format!("su root -c {}", quote(cmd)), become_password, true)
+            }
         },
+        WhichUser::UsernamePassword(credentials) => {
+            let username = credentials.username();
+            let password = Some(credentials.password());
+            match privilege {
+                Privilege::None | Privilege::WithSu => PrivilegedCommand::from(
+                    format!("su - {} -c {}", quote(username), quote(cmd)),
+                    password,
+                    true,
+                ),
+                Privilege::WithSudo => sudo_command("sudo", Some(username), cmd, password),
+                Privilege::WithSudoRs => sudo_command("sudo-rs", Some(username), cmd, password),
+                Privilege::WithDoas => PrivilegedCommand::from(
+                    format!("doas -u {} sh -c {}", quote(username), quote(cmd)),
+                    password,
+                    true,
+                ),
+            }
+        }
+    }
+}
+
+/// sudo reads the password from stdin (`-S`), after printing a prompt recognizable in stderr
+fn sudo_command(
+    sudo: &str,
+    username: Option<&str>,
+    cmd: &str,
+    password: Option<&str>,
+) -> PrivilegedCommand {
+    let target_user = match username {
+        Some(username) => format!("-u {} ", quote(username)),
+        None => String::new(),
+    };
+    match password {
+        Some(_) => PrivilegedCommand::from(
+            format!("{} -S -p '{}' {}{}", sudo, BECOME_PROMPT, target_user, cmd),
+            password,
+            false,
+        ),
+        None => PrivilegedCommand::from(format!("{} {}{}", sudo, target_user, cmd), None, false),
+    }
+}
+
+/// What to do after a chunk of output was inspected by a [`PromptResponder`]
+#[derive(Debug, PartialEq)]
+pub(crate) enum PromptAnswer {
+    /// No password prompt in the chunk
+    Nothing,
+    /// Write these bytes to the stdin of the command
+    Password(Vec<u8>),
+    /// The password was prompted for again, hence refused : the command must be stopped
+    Abort,
+}
+
+/// Answers the password prompt of a privilege escalation command, once
+pub(crate) struct PromptResponder {
+    password: Option<String>,
+    /// Only sudo prompts can be told apart from the output of the command for sure
+    sudo_prompt_only: bool,
+    answered: bool,
+    /// The command produced output other than a prompt : escalation is over and the rest of the
+    /// output belongs to the command
+    command_started: bool,
+}
+
+impl PromptResponder {
+    pub(crate) fn from(privileged_command: &PrivilegedCommand) -> Self {
+        Self {
+            password: privileged_command.password.clone(),
+            sudo_prompt_only: !privileged_command.needs_terminal,
+            answered: false,
+            command_started: false,
+        }
+    }
+
+    /// Responder for commands which never prompt for a password
+    pub(crate) fn none() -> Self {
+        Self {
+            password: None,
+            sudo_prompt_only: true,
+            answered: false,
+            command_started: false,
+        }
+    }
+
+    /// Whether a password prompt may still have to be answered. Once it may not, nothing else is
+    /// written to the command and its stdin can be closed.
+    pub(crate) fn awaits_prompt(&self) -> bool {
+        self.password.is_some() && !self.answered && !self.command_started
+    }
+
+    /// Look for a password prompt at the end of a chunk of output. The prompt is removed from the
+    /// chunk. The sudo prompt cannot be mistaken for the output of the command, whereas su and
+    /// doas prompts are only looked for until the password is given or the command outputs
+    /// something else.
+    pub(crate) fn inspect(&mut self, chunk: &mut Vec<u8>) -> PromptAnswer {
+        let Some(password) = &self.password else {
+            return PromptAnswer::Nothing;
+        };
+
+        let text = String::from_utf8_lossy(chunk).to_string();
+        let prompt = match text.find(BECOME_PROMPT) {
+            Some(position) => Some((position, position + BECOME_PROMPT.len())),
+            None if self.sudo_prompt_only || self.answered || self.command_started => None,
+            None => {
+                // su and doas prompts : "Password:", "doas (user@host) password:"...
+                let last_line_start = text.rfind('\n').map(|position| position + 1).unwrap_or(0);
+                let last_line = text[last_line_start..].trim_end();
+                if last_line.to_lowercase().contains("password") && last_line.ends_with(':') {
+                    Some((last_line_start, text.len()))
+                } else {
+                    None
+                }
+            }
+        };
+        let Some((prompt_start, prompt_end)) = prompt else {
+            // Blank lines are echoed back by terminals after the password
+            if !text.trim().is_empty() {
+                self.command_started = true;
+            }
+            return PromptAnswer::Nothing;
+        };
+
+        let mut remaining = text[..prompt_start].to_string();
+        remaining.push_str(&text[prompt_end..]);
+        *chunk = remaining.into_bytes();
+
+        if self.answered {
+            PromptAnswer::Abort
+        } else {
+            self.answered = true;
+            PromptAnswer::Password(format!("{}\n", password).into_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosts::privilege::Credentials;
+
+    #[test]
+    fn building_temporary_paths() {
+        let temporary = temporary_path(Path::new("/etc/nginx/nginx.conf"));
+        assert_eq!(temporary.parent(), Some(Path::new("/etc/nginx")));
+        assert!(
+            temporary
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with(".nginx.conf.regent-")
+        );
+    }
+
+    #[test]
+    fn building_install_commands() {
+        let command = install_command(
+            "/tmp/.regent-staging-x",
+            "/etc/sudoers",
+            Some("visudo -cf %s"),
+        );
+        assert!(command.starts_with(
+            "sh -c \"trap 'rm -f /tmp/.regent-staging-x' EXIT; cat /tmp/.regent-staging-x > /etc/.sudoers.regent-"
+        ));
+        assert!(command.contains("visudo -cf /etc/.sudoers.regent-"));
+        assert!(command.contains(" /etc/sudoers || { rm -f /etc/.sudoers.regent-"));
+        // GNU-only options are not available on BusyBox hosts
+        assert!(!command.contains("--reference"));
+
+        assert_eq!(
+            escape_for_double_quotes("sh -c \"echo $HOME\""),
+            "sh -c \\\"echo \\$HOME\\\""
+        );
+    }
+
+    #[test]
+    fn keeping_mode_of_replaced_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = std::env::temp_dir().join(format!("regent-install-{}", nanoid!()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let staging_path = directory.join("staging");
+        let path = directory.join("config");
+        std::fs::write(&staging_path, "new").unwrap();
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(install_command(
+                &staging_path.display().to_string(),
+                &path.display().to_string(),
+                None,
+            ))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o7777,
+            0o640
+        );
+        assert!(!staging_path.exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
     }
 
-    // match privilege {
-    //     Privilege::None => {
-    //         let final_cmd = format!("{} 2>&1", cmd);
-    //         return final_cmd;
-    //     }
-    //     // Privilege::WithSuAsUser(credentials) => {
-    //     //     let final_cmd = format!("echo {} | su - {} -c {} 2>&1", credentials.password(), credentials.username(), cmd);
-    //     //     return final_cmd;
-    //     // }
-    //     Privilege::WithSudo => {
-    //         let final_cmd = format!("sudo {} 2>&1", cmd);
-    //         return final_cmd;
-    //     }
-    //     // Privilege::WithSudoAsUser(credentials) => {
-    //     //     let final_cmd = format!("echo {} | sudo -S -u {} {} 2>&1", credentials.password(), credentials.username(), cmd);
-    //     //     return final_cmd;
-    //     // }
-    //     Privilege::WithSudoRs => {
-    //         let final_cmd = format!("sudo-rs {} 2>&1", cmd);
-    //         return final_cmd;
-    //     }
-    //     // Privilege::WithSudoRsAsUser(credentials) => {
-    //     //     let final_cmd = format!("echo {} | sudo-rs -u {} {} 2>&1", credentials.password(), credentials.username(), cmd);
-    //     //     return final_cmd;
-    //     // }
-    // }
+    #[test]
+    fn building_privileged_commands() {
+        let command = final_command("id", &Privilege::WithSudo, &WhichUser::CurrentUser, None);
+        assert_eq!(command.command, "sudo id");
+        assert_eq!(command.password, None);
+
+        let command = final_command(
+            "id",
+            &Privilege::WithSudo,
+            &WhichUser::CurrentUser,
+            Some("p@ss'word"),
+        );
+        assert_eq!(command.command, "sudo -S -p '[regent-become-password]' id");
+        assert!(!command.command.contains("p@ss"));
+        assert_eq!(command.password.as_deref(), Some("p@ss'word"));
+        assert!(!command.needs_terminal);
+
+        let command = final_command(
+            "echo 'hello'",
+            &Privilege::WithSu,
+            &WhichUser::CurrentUser,
+            Some("secret"),
+        );
+        assert_eq!(command.command, r#"su root -c 'echo '\''hello'\'''"#);
+        assert!(command.needs_terminal);
+
+        let command = final_command("id", &Privilege::WithDoas, &WhichUser::CurrentUser, None);
+        assert_eq!(command.command, "doas sh -c id");
+        assert!(!command.needs_terminal);
+    }
+
+    #[test]
+    fn escalating_whole_compound_commands() {
+        let compound = "cat /etc/shadow | wc -l > /root/count && echo done";
+        let credentials = Credentials::from("deploy", "secret");
+        let users = [
+            WhichUser::CurrentUser,
+            WhichUser::UsernamePassword(credentials),
+        ];
+        for user in &users {
+            for privilege in [Privilege::WithDoas, Privilege::WithSu] {
+                let command = final_command(compound, &privilege, user, Some("secret"));
+                // The whole command is a single quoted argument of the escalation command
+                assert!(
+                    command
+                        .command
+                        .ends_with(&format!("-c {}", quote(compound))),
+                    "{}",
+                    command.command
+                );
+            }
+        }
+
+        let command = final_command(
+            compound,
+            &Privilege::WithDoas,
+            &WhichUser::UsernamePassword(Credentials::from("deploy", "secret")),
+            None,
+        );
+        assert_eq!(
+            command.command,
+            "doas -u deploy sh -c 'cat /etc/shadow | wc -l > /root/count && echo done'"
+        );
+    }
+
+    #[test]
+    fn answering_password_prompts() {
+        let command = final_command(
+            "id",
+            &Privilege::WithSudo,
+            &WhichUser::CurrentUser,
+            Some("secret"),
+        );
+        let mut responder = PromptResponder::from(&command);
+
+        let mut chunk = b"Password: is not a prompt here".to_vec();
+        assert_eq!(responder.inspect(&mut chunk), PromptAnswer::Nothing);
+
+        let mut chunk = b"[regent-become-password]".to_vec();
+        assert_eq!(
+            responder.inspect(&mut chunk),
+            PromptAnswer::Password(b"secret\n".to_vec())
+        );
+        assert!(chunk.is_empty());
+
+        let mut chunk = b"Sorry, try again.\n[regent-become-password]".to_vec();
+        assert_eq!(responder.inspect(&mut chunk), PromptAnswer::Abort);
+        assert_eq!(chunk, b"Sorry, try again.\n");
+
+        let command = final_command(
+            "id",
+            &Privilege::WithSu,
+            &WhichUser::CurrentUser,
+            Some("secret"),
+        );
+        let mut responder = PromptResponder::from(&command);
+        assert!(responder.awaits_prompt());
+        let mut chunk = b"Password: ".to_vec();
+        assert_eq!(
+            responder.inspect(&mut chunk),
+            PromptAnswer::Password(b"secret\n".to_vec())
+        );
+        assert!(!responder.awaits_prompt());
+
+        // Output of the command looking like a prompt is left alone
+        let mut chunk = b"\r\n".to_vec();
+        assert_eq!(responder.inspect(&mut chunk), PromptAnswer::Nothing);
+        let mut chunk = b"Changing password for deploy.\nCurrent password:".to_vec();
+        assert_eq!(responder.inspect(&mut chunk), PromptAnswer::Nothing);
+        assert_eq!(
+            chunk,
+            b"Changing password for deploy.\nCurrent password:".to_vec()
+        );
+        let mut chunk = b"New password:".to_vec();
+        assert_eq!(responder.inspect(&mut chunk), PromptAnswer::Nothing);
+
+        // Commands which need no password do not wait for a prompt
+        let mut responder = PromptResponder::from(&command);
+        let mut chunk = b"root\n".to_vec();
+        assert_eq!(responder.inspect(&mut chunk), PromptAnswer::Nothing);
+        assert!(!responder.awaits_prompt());
+    }
 }
@@ -3,28 +3,40 @@ use russh::Disconnect;
 use russh::Preferred;
 use russh::client::AuthResult;
 use russh::client::{Config, Handle, Handler};
+use russh::keys::agent::AgentIdentity;
+use russh::keys::agent::client::AgentClient;
 use russh::keys::key::PrivateKeyWithHashAlg;
+use russh::keys::known_hosts::{
+    check_known_hosts, check_known_hosts_path, learn_known_hosts, learn_known_hosts_path,
+};
+use russh::keys::{Certificate, HashAlg};
 use russh::keys::{load_secret_key, ssh_key};
 use russh::{Channel, ChannelMsg};
 use serde::Deserialize;
 use serde::Serialize;
 use std::io::Cursor;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::time::{Duration, timeout};
 #[allow(unused)]
 use tracing::{debug, error, info, trace, warn};
 
 use crate::command::CommandResult;
+use crate::command::{OutputChunk, OutputCollector, ShellCommand};
 use crate::error::RegentError;
 use crate::hosts::handlers::HostHandler;
 use crate::hosts::handlers::final_command;
 use crate::hosts::handlers::localhost::WhichUser;
+use crate::hosts::handlers::temporary_path;
+use crate::hosts::handlers::{PromptAnswer, PromptResponder};
+use crate::hosts::privilege::AgentLogin;
 use crate::hosts::privilege::Credentials;
 use crate::hosts::privilege::LoginKey;
 use crate::hosts::privilege::LoginKeyRef;
 use crate::hosts::privilege::Privilege;
 // use crate::secrets::SecretProvider;
+use crate::secrets::Secret;
+use crate::secrets::SecretProvidersPool;
 use crate::secrets::SecretReference;
 
 // #[derive(Clone)]
@@ -34,20 +46,200 @@ use crate::secrets::SecretReference;
 // }
 
 pub enum Ssh2HostHandler {
-    NotConnected(Ssh2AuthMethod),
-    Connected(Ssh2AuthMethod, Handle<Ssh2Client>),
+    NotConnected(Ssh2Settings),
+    /// Session with the host, and the sessions with the jump hosts it goes through
+    Connected(Ssh2Settings, Handle<Ssh2Client>, Vec<Handle<Ssh2Client>>),
 }
 
-struct Ssh2Client {}
+/// Everything needed to open a session with a host
+#[derive(Debug, Clone)]
+pub struct Ssh2Settings {
+    pub auth_method: Ssh2AuthMethod,
+    pub host_key_policy: HostKeyPolicy,
+    /// Hosts to go through, in order, to reach the host
+    pub jump_hosts: Vec<Ssh2JumpHost>,
+    /// Password answering the prompt of privilege escalation commands
+    pub become_password: Option<Secret<String>>,
+}
+
+impl Ssh2Settings {
+    pub fn from(auth_method: Ssh2AuthMethod, host_key_policy: HostKeyPolicy) -> Self {
+        Self {
+            auth_method,
+            host_key_policy,
+            jump_hosts: Vec::new(),
+            become_password: None,
+        }
+    }
+
+    pub fn with_become_password(mut self, become_password: Secret<String>) -> Self {
+        self.become_password = Some(become_password);
+        self
+    }
+
+    pub fn with_jump_host(mut self, jump_host: Ssh2JumpHost) -> Self {
+        self.jump_hosts.push(jump_host);
+        self
+    }
+}
+
+/// A jump host (bastion) with resolved credentials
+#[derive(Debug, Clone)]
+pub struct Ssh2JumpHost {
+    pub endpoint: String,
+    pub auth_method: Ssh2AuthMethod,
+    pub host_key_policy: HostKeyPolicy,
+}
+
+/// How the key presented by a host is verified before authenticating
+///
+/// Defaults to [`HostKeyPolicy::TrustOnFirstUse`] with `~/.ssh/known_hosts`, like OpenSSH's
+/// `StrictHostKeyChecking=accept-new` : hosts reached for the first time are accepted and recorded,
+/// while a host presenting a key different from the recorded one is rejected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", rename_all_fields = "PascalCase")]
+pub enum HostKeyPolicy {
+    /// Only accept keys recorded in an OpenSSH known_hosts file (`~/.ssh/known_hosts` by default)
+    Strict { known_hosts: Option<PathBuf> },
+    /// Record the key of unknown hosts in the known_hosts file, reject changed keys afterwards
+    TrustOnFirstUse { known_hosts: Option<PathBuf> },
+    /// Only accept the key with this fingerprint (e.g. `SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s`)
+    Pinned { fingerprint: String },
+    /// Accept any key. Leaves the connection open to man-in-the-middle attacks.
+    Insecure,
+}
+
+impl Default for HostKeyPolicy {
+    fn default() -> Self {
+        HostKeyPolicy::TrustOnFirstUse { known_hosts: None }
+    }
+}
+
+/// Client side of the SSH sessions held by a connected [`Ssh2HostHandler`], verifying the key
+/// presented by the host against its [`HostKeyPolicy`]
+pub struct Ssh2Client {
+    address: String,
+    port: u16,
+    host_key_policy: HostKeyPolicy,
+}
+
+impl Ssh2Client {
+    fn from(address: &str, port: u16, host_key_policy: HostKeyPolicy) -> Self {
+        Self {
+            address: address.to_string(),
+            port,
+            host_key_policy,
+        }
+    }
+
+    fn verify_server_key(&self, server_public_key: &ssh_key::PublicKey) -> Result<(), RegentError> {
+        let fingerprint = server_public_key.fingerprint(HashAlg::Sha256).to_string();
+
+        match &self.host_key_policy {
+            HostKeyPolicy::Insecure => {
+                warn!(
+                    "Accepting host key {} of {} without verification",
+                    fingerprint, self.address
+                );
+                Ok(())
+            }
+            HostKeyPolicy::Pinned {
+                fingerprint: pinned_fingerprint,
+            } => {
+                if fingerprint_matches(&fingerprint, pinned_fingerprint) {
+                    Ok(())
+                } else {
+                    Err(RegentError::HostKeyMismatch(format!(
+                        "{} presented {}, expected {}",
+                        self.address, fingerprint, pinned_fingerprint
+                    )))
+                }
+            }
+            HostKeyPolicy::Strict { known_hosts }
+            | HostKeyPolicy::TrustOnFirstUse { known_hosts } => {
+                let is_known = match known_hosts {
+                    Some(path) => {
+                        check_known_hosts_path(&self.address, self.port, server_public_key, path)
+                    }
+                    None => check_known_hosts(&self.address, self.port, server_public_key),
+                };
+
+                match is_known {
+                    Ok(true) => Ok(()),
+                    Ok(false) => {
+                        if let HostKeyPolicy::Strict { .. } = self.host_key_policy {
+                            return Err(RegentError::FailureToEstablishConnection(format!(
+                                "Host key {} of {} is not in known_hosts",
+                                fingerprint, self.address
+                            )));
+                        }
+                        info!(
+                            "Trusting host key {} of {} on first use",
+                            fingerprint, self.address
+                        );
+                        let learn_result = match known_hosts {
+                            Some(path) => learn_known_hosts_path(
+                                &self.address,
+                                self.port,
+                                server_public_key,
+                                path,
+                            ),
+                            None => learn_known_hosts(&self.address, self.port, server_public_key),
+                        };
+                        learn_result.map_err(|details| {
+                            RegentError::FailureToEstablishConnection(format!(
+                                "Failed to record host key of {} : {:?}",
+                                self.address, details
+                            ))
+                        })
+                    }
+                    Err(russh::keys::Error::KeyChanged { line }) => {
+                        Err(RegentError::HostKeyMismatch(format!(
+                            "{} presented {}, which differs from the key recorded at line {} of known_hosts",
+                            self.address, fingerprint, line
+                        )))
+                    }
+                    Err(details) => Err(RegentError::FailureToEstablishConnection(format!(
+                        "Failed to read known_hosts : {:?}",
+                        details
+                    ))),
+                }
+            }
+        }
+    }
+}
+
+/// Fingerprints are compared with or without their `SHA256:` prefix
+fn fingerprint_matches(fingerprint: &str, pinned_fingerprint: &str) -> bool {
+    let pinned_fingerprint = pinned_fingerprint.trim();
+    fingerprint == pinned_fingerprint
+        || fingerprint.strip_prefix("SHA256:") == Some(pinned_fingerprint)
+}
+
+/// Errors raised by the russh client while the session is running
+#[derive(Debug)]
+pub enum Ssh2ClientError {
+    Russh(russh::Error),
+    HostKey(RegentError),
+}
+
+impl From<russh::Error> for Ssh2ClientError {
+    fn from(error: russh::Error) -> Self {
+        Ssh2ClientError::Russh(error)
+    }
+}
 
 impl Handler for Ssh2Client {
-    type Error = russh::Error;
+    type Error = Ssh2ClientError;
 
     async fn check_server_key(
         &mut self,
-        _server_public_key: &ssh_key::PublicKey,
+        server_public_key: &ssh_key::PublicKey,
     ) -> Result<bool, Self::Error> {
-        Ok(true)
+        match self.verify_server_key(server_public_key) {
+            Ok(()) => Ok(true),
+            Err(details) => Err(Ssh2ClientError::HostKey(details)),
+        }
     }
 }
 
@@ -55,7 +247,7 @@ impl Clone for Ssh2HostHandler {
     fn clone(&self) -> Self {
         match self {
             Ssh2HostHandler::NotConnected(auth) => Ssh2HostHandler::NotConnected(auth.clone()),
-            Ssh2HostHandler::Connected(auth, _) => {
+            Ssh2HostHandler::Connected(auth, ..) => {
                 // When cloning a connected handler, return a new NotConnected handler
                 // The caller will need to reconnect
                 Ssh2HostHandler::NotConnected(auth.clone())
@@ -70,7 +262,7 @@ impl std::fmt::Debug for Ssh2HostHandler {
             Ssh2HostHandler::NotConnected(auth) => {
                 f.debug_tuple("NotConnected").field(auth).finish()
             }
-            Ssh2HostHandler::Connected(auth, _) => f
+            Ssh2HostHandler::Connected(auth, ..) => f
                 .debug_tuple("Connected")
                 .field(auth)
                 .field(&"*SESSION HANDLE*")
@@ -87,28 +279,12 @@ impl HostHandler for Ssh2HostHandler {
             return Ok(());
         }
 
-        let address_and_port: Vec<&str> = endpoint.split(':').collect();
-        if address_and_port.is_empty() {
-            return Err(RegentError::FailedInitialization(
-                "empty address".to_string(),
-            ));
-        }
-
-        let address = address_and_port[0];
-        let ssh_port: u16 = match address_and_port.get(1) {
-            Some(port) => match port.parse::<u16>() {
-                Ok(p) => p,
-                Err(e) => {
-                    return Err(RegentError::FailedInitialization(format!(
-                        "invalid port: {}",
-                        e
-                    )));
-                }
-            },
-            None => 22,
+        let Ssh2HostHandler::NotConnected(settings) = self else {
+            return Ok(());
         };
+        let settings = settings.clone();
 
-        let addrs = format!("{}:{}", address, ssh_port);
+        let (address, ssh_port) = parse_endpoint(endpoint)?;
 
         // Create russh configuration
         let config = Arc::new(Config {
@@ -123,122 +299,45 @@ impl HostHandler for Ssh2HostHandler {
             ..Default::default()
         });
 
-        // Connect using russh
-        let client_handler = Ssh2Client {};
-        let connection_timeout = Duration::from_secs(10);
-        let mut handle = match timeout(
-            connection_timeout,
-            russh::client::connect(config, addrs, client_handler),
+        // Each jump host is reached through the session opened with the previous one, like
+        // OpenSSH ProxyJump
+        let mut jump_handles: Vec<Handle<Ssh2Client>> = Vec::new();
+        for jump_host in &settings.jump_hosts {
+            let (jump_address, jump_port) = parse_endpoint(&jump_host.endpoint)?;
+            debug!("Connecting to jump host {}:{}", jump_address, jump_port);
+            let mut jump_handle = open_session(
+                config.clone(),
+                jump_handles.last(),
+                &jump_address,
+                jump_port,
+                jump_host.host_key_policy.clone(),
+            )
+            .await?;
+            authenticate(&mut jump_handle, &jump_host.auth_method).await?;
+            jump_handles.push(jump_handle);
+        }
+
+        let mut handle = open_session(
+            config,
+            jump_handles.last(),
+            &address,
+            ssh_port,
+            settings.host_key_policy.clone(),
         )
-        .await
-        {
-            Ok(connection_result) => match connection_result {
-                Ok(h) => h,
-                Err(e) => {
-                    return Err(RegentError::FailedTcpBinding(format!("{:?}", e)));
-                }
-            },
-            Err(_details) => {
-                return Err(RegentError::FailureToEstablishConnection(format!(
-                    "Connection timeout elapsed ({} ms)",
-                    connection_timeout.as_millis()
-                )));
-            }
-        };
+        .await?;
+        authenticate(&mut handle, &settings.auth_method).await?;
 
-        // Authenticate based on the auth method
-        if let Ssh2HostHandler::NotConnected(auth_method) = self {
-            match auth_method {
-                Ssh2AuthMethod::UsernamePassword(credentials) => {
-                    match handle
-                        .authenticate_password(credentials.username(), credentials.password())
-                        .await
-                    {
-                        Ok(auth_result) => match auth_result {
-                            AuthResult::Success => {
-                                *self = Ssh2HostHandler::Connected(auth_method.clone(), handle);
-                                return Ok(());
-                            }
-                            AuthResult::Failure {
-                                remaining_methods,
-                                partial_success,
-                            } => {
-                                return Err(RegentError::FailedInitialization(
-                                    "Password authentication failed".to_string(),
-                                ));
-                            }
-                        },
-                        Err(e) => {
-                            return Err(RegentError::FailedInitialization(format!(
-                                "Password authentication error: {:?}",
-                                e
-                            )));
-                        }
-                    }
-                }
-                Ssh2AuthMethod::Key(login_key) => {
-                    // Load the private key from the string
-                    match load_secret_key(login_key.key(), None) {
-                        Ok(key_pair) => {
-                            let best_hash = match handle.best_supported_rsa_hash().await {
-                                Ok(h) => h.flatten(),
-                                Err(e) => {
-                                    return Err(RegentError::FailedInitialization(format!(
-                                        "Failed to get supported hash: {:?}",
-                                        e
-                                    )));
-                                }
-                            };
-                            let key_with_alg =
-                                PrivateKeyWithHashAlg::new(Arc::new(key_pair), best_hash);
-                            match handle
-                                .authenticate_publickey(login_key.username(), key_with_alg)
-                                .await
-                            {
-                                Ok(auth_result) => match auth_result {
-                                    AuthResult::Success => {
-                                        *self =
-                                            Ssh2HostHandler::Connected(auth_method.clone(), handle);
-                                        return Ok(());
-                                    }
-                                    AuthResult::Failure {
-                                        remaining_methods,
-                                        partial_success,
-                                    } => {
-                                        return Err(RegentError::FailedInitialization(
-                                            "Public key authentication failed".to_string(),
-                                        ));
-                                    }
-                                },
-                                Err(e) => {
-                                    return Err(RegentError::FailedInitialization(format!(
-                                        "Public key authentication error: {:?}",
-                                        e
-                                    )));
-                                }
-                            }
-                        }
-                        Err(error_details) => {
-                            return Err(RegentError::FailedInitialization(format!(
-                                "Failed to load key: {:?}",
-                                error_details
-                            )));
-                        }
-                    }
-                }
-            }
-        } else {
-            Ok(())
-        }
+        *self = Ssh2HostHandler::Connected(settings, handle, jump_handles);
+        Ok(())
     }
 
     async fn is_connected(&mut self) -> bool {
         match self {
             Ssh2HostHandler::NotConnected(_) => false,
-            Ssh2HostHandler::Connected(auth_method, session) => match session.send_ping().await {
+            Ssh2HostHandler::Connected(settings, session, _) => match session.send_ping().await {
                 Ok(()) => true,
                 Err(_error_details) => {
-                    *self = Ssh2HostHandler::NotConnected(auth_method.clone());
+                    *self = Ssh2HostHandler::NotConnected(settings.clone());
                     false
                 }
             },
@@ -248,13 +347,22 @@ impl HostHandler for Ssh2HostHandler {
     async fn disconnect(&mut self) -> Result<(), RegentError> {
         match self {
             Ssh2HostHandler::NotConnected(_) => Ok(()),
-            Ssh2HostHandler::Connected(auth_method, session) => {
+            Ssh2HostHandler::Connected(settings, session, jump_sessions) => {
                 match session
                     .disconnect(Disconnect::ByApplication, "regent", "English")
                     .await
                 {
                     Ok(()) => {
-                        *self = Ssh2HostHandler::NotConnected(auth_method.clone());
+                        // Jump hosts are left from the closest to the host
+                        for jump_session in jump_sessions.iter().rev() {
+                            if let Err(error_details) = jump_session
+                                .disconnect(Disconnect::ByApplication, "regent", "English")
+                                .await
+                            {
+                                warn!("Failed to disconnect from jump host : {:?}", error_details);
+                            }
+                        }
+                        *self = Ssh2HostHandler::NotConnected(settings.clone());
                         Ok(())
                     }
                     Err(error_details) => Err(RegentError::ProblemWithHostConnection(format!(
@@ -271,7 +379,7 @@ impl HostHandler for Ssh2HostHandler {
         command: &str,
         privilege: &Privilege,
     ) -> Result<bool, RegentError> {
-        let check_cmd_content = format!("command -v {}", command);
+        let check_cmd_content = ShellCommand::new("command -v").arg(command).to_string();
         let check_cmd_result = self
             .run_command(check_cmd_content.as_str(), privilege)
             .await;
@@ -294,47 +402,55 @@ impl HostHandler for Ssh2HostHandler {
         &mut self,
         command: &str,
         privilege: &Privilege,
+    ) -> Result<CommandResult, RegentError> {
+        self.run_command_streaming(command, privilege, |_| {}).await
+    }
+
+    async fn run_command_streaming<F: FnMut(OutputChunk) + Send>(
+        &mut self,
+        command: &str,
+        privilege: &Privilege,
+        mut on_output: F,
     ) -> Result<CommandResult, RegentError> {
         match self {
-            Ssh2HostHandler::NotConnected(_auth_method) => {
-                return Err(RegentError::NotConnectedToHost);
-            }
-            Ssh2HostHandler::Connected(_auth_method, session_handle) => {
+            Ssh2HostHandler::NotConnected(_settings) => Err(RegentError::NotConnectedToHost),
+            Ssh2HostHandler::Connected(settings, session_handle, _) => {
                 match session_handle.channel_open_session().await {
                     Ok(mut channel) => {
-                        let final_command =
-                            final_command(command, privilege, &WhichUser::CurrentUser);
+                        let become_password = settings.become_password.clone().map(Secret::inner);
+                        let final_command = final_command(
+                            command,
+                            privilege,
+                            &WhichUser::CurrentUser,
+                            become_password.as_deref(),
+                        );
 
-                        if let Err(details) = channel.exec(true, final_command).await {
-                            return Err(RegentError::FailureToRunCommand(format!("{:?}", details)));
+                        // su and doas only read the password from a terminal. Within a
+                        // terminal, stderr is merged into stdout.
+                        if final_command.needs_terminal
+                            && let Err(details) =
+                                channel.request_pty(true, "dumb", 200, 50, 0, 0, &[]).await
+                        {
+                            return Err(RegentError::FailureToRunCommand(format!(
+                                "Failed to request a terminal : {:?}",
+                                details
+                            )));
                         }
 
-                        let mut return_code = 1;
-                        let mut command_output = String::new();
-
-                        loop {
-                            // There's an event available on the session channel
-                            let Some(msg) = channel.wait().await else {
-                                break;
-                            };
-                            match msg {
-                                // Write data to the buffer
-                                ChannelMsg::Data { ref data } => {
-                                    command_output.push_str(&String::from_utf8_lossy(data));
-                                }
-                                ChannelMsg::ExitStatus { exit_status } => {
-                                    return_code = exit_status;
-                                    // cannot leave the loop immediately, there might still be more data to receive
-                                }
-                                _ => {}
-                            }
+                        if let Err(details) =
+                            channel.exec(true, final_command.command.as_str()).await
+                        {
+                            return Err(RegentError::FailureToRunCommand(format!("{:?}", details)));
                         }
 
-                        Ok(CommandResult {
-                            return_code: return_code.into(),
-                            stdout: command_output,
-                            stderr: String::new(),
-                        })
+                        let mut prompt_responder = PromptResponder::from(&final_command);
+                        let mut result =
+                            collect_output(&mut channel, &mut prompt_responder, &mut on_output)
+                                .await;
+                        if final_command.needs_terminal {
+                            result.stdout = result.stdout.replace("\r\n", "\n");
+                        }
+                        Ok(result)
                     }
                     Err(e) => Err(RegentError::FailureToEstablishConnection(e.to_string())),
                 }
@@ -344,10 +460,8 @@ impl HostHandler for Ssh2HostHandler {
 
     async fn run_windows_command(&mut self, command: &str) -> Result<CommandResult, RegentError> {
         match self {
-            Ssh2HostHandler::NotConnected(_auth_method) => {
-                return Err(RegentError::NotConnectedToHost);
-            }
-            Ssh2HostHandler::Connected(_auth_method, session_handle) => {
+            Ssh2HostHandler::NotConnected(_settings) => Err(RegentError::NotConnectedToHost),
+            Ssh2HostHandler::Connected(_settings, session_handle, _) => {
                 match session_handle.channel_open_session().await {
                     Ok(mut channel) => {
                         let final_command = format!("cmd /C {}", command);
@@ -356,32 +470,10 @@ impl HostHandler for Ssh2HostHandler {
                             return Err(RegentError::FailureToRunCommand(format!("{:?}", details)));
                         }
 
-                        let mut return_code = 1;
-                        let mut command_output = String::new();
-
-                        loop {
-                            // There's an event available on the session channel
-                            let Some(msg) = channel.wait().await else {
-                                break;
-                            };
-                            match msg {
-                                // Write data to the buffer
-                                ChannelMsg::Data { ref data } => {
-                                    command_output.push_str(&String::from_utf8_lossy(data));
-                                }
-                                ChannelMsg::ExitStatus { exit_status } => {
-                                    return_code = exit_status;
-                                    // cannot leave the loop immediately, there might still be more data to receive
-                                }
-                                _ => {}
-                            }
-                        }
-
-                        Ok(CommandResult {
-                            return_code: return_code.into(),
-                            stdout: command_output,
-                            stderr: String::new(),
-                        })
+                        Ok(
+                            collect_output(&mut channel, &mut PromptResponder::none(), &mut |_| {})
+                                .await,
+                        )
                     }
 
                     Err(e) => Err(RegentError::FailureToEstablishConnection(e.to_string())),
@@ -392,14 +484,16 @@ impl HostHandler for Ssh2HostHandler {
 
     async fn get_file(&mut self, path: PathBuf) -> Result<Vec<u8>, RegentError> {
         match self {
-            Ssh2HostHandler::NotConnected(_auth_method) => {
+            Ssh2HostHandler::NotConnected(_settings) => {
                 return Err(RegentError::NotConnectedToHost);
             }
-            Ssh2HostHandler::Connected(_auth_method, session_handle) => {
+            Ssh2HostHandler::Connected(_settings, session_handle, _) => {
                 match session_handle.channel_open_session().await {
                     Ok(mut channel) => {
                         // 1. SCP request in "source" mode (-f)
-                        let cmd = format!("scp -f \"{}\"", path.display());
+                        let cmd = ShellCommand::new("scp -f")
+                            .arg(path.display().to_string())
+                            .to_string();
                         if let Err(e) = channel.exec(true, cmd).await {
                             return Err(RegentError::FailedToGetFile(format!(
                                 "Russh exec error: {:?}",
@@ -538,6 +632,100 @@ impl HostHandler for Ssh2HostHandler {
             }
         }
     }
+
+    async fn put_file(
+        &mut self,
+        path: PathBuf,
+        content: &[u8],
+        mode: u32,
+    ) -> Result<(), RegentError> {
+        let temporary_path = temporary_path(&path);
+
+        match self {
+            Ssh2HostHandler::NotConnected(_settings) => {
+                return Err(RegentError::NotConnectedToHost);
+            }
+            Ssh2HostHandler::Connected(_settings, session_handle, _) => {
+                let mut channel = match session_handle.channel_open_session().await {
+                    Ok(channel) => channel,
+                    Err(e) => return Err(RegentError::FailureToEstablishConnection(e.to_string())),
+                };
+
+                // 1. SCP request in "sink" mode (-t), targeting the temporary file
+                let cmd = ShellCommand::new("scp -t")
+                    .arg(temporary_path.display().to_string())
+                    .to_string();
+                if let Err(e) = channel.exec(true, cmd).await {
+                    return Err(RegentError::FailedToPutFile(format!(
+                        "Russh exec error: {:?}",
+                        e
+                    )));
+                }
+                wait_for_scp_ack(&mut channel).await?;
+
+                // 2. Send the file header : permissions, size and name
+                let file_name = temporary_path
+                    .file_name()
+                    .map(|file_name| file_name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let header = format!("C{:04o} {} {}\n", mode & 0o7777, content.len(), file_name);
+                if let Err(e) = channel.data(Cursor::new(header.as_bytes())).await {
+                    return Err(RegentError::FailedToPutFile(format!(
+                        "Russh data transfer error: {:?}",
+                        e
+                    )));
+                }
+                wait_for_scp_ack(&mut channel).await?;
+
+                // 3. Send the binary content, followed by a NULL byte
+                if let Err(e) = channel.data(Cursor::new(content)).await {
+                    return Err(RegentError::FailedToPutFile(format!(
+                        "Russh data transfer error: {:?}",
+                        e
+                    )));
+                }
+                if let Err(e) = channel.data(Cursor::new(&[0x00][..])).await {
+                    return Err(RegentError::FailedToPutFile(format!(
+                        "Russh data transfer error: {:?}",
+                        e
+                    )));
+                }
+                wait_for_scp_ack(&mut channel).await?;
+
+                // 4. Close the protocol
+                if let Err(e) = channel.eof().await {
+                    return Err(RegentError::FailedToPutFile(format!(
+                        "Russh channel EOF error: {:?}",
+                        e
+                    )));
+                }
+            }
+        }
+
+        // 5. Set the exact mode, since the one sent in the header is subject to the remote umask,
+        // then atomically replace the destination
+        let rename_result = self
+            .run_command(
+                install_command(&temporary_path, &path, mode).as_str(),
+                &Privilege::None,
+            )
+            .await?;
+        if rename_result.return_code != 0 {
+            let _ = self
+                .run_command(
+                    ShellCommand::new("rm -f")
+                        .arg(temporary_path.display().to_string())
+                        .as_str(),
+                    &Privilege::None,
+                )
+                .await;
+            return Err(RegentError::FailedToPutFile(format!(
+                "Failed to move file into place : {}",
+                rename_result.stderr
+            )));
+        }
+        Ok(())
+    }
 }
 
 // impl Ssh2HostHandler {
@@ -586,6 +774,8 @@ pub enum Ssh2AuthMethod {
     UsernamePassword(Credentials),
 
     Key(LoginKey),
+
+    Agent(AgentLogin),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -593,6 +783,8 @@ pub enum Ssh2AuthMethod {
 pub enum Ssh2AuthReference {
     UsernamePassword(SecretReference),
     Key(LoginKeyRef),
+    /// Keys held by the SSH agent, which requires no secret
+    Agent(AgentLogin),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -600,6 +792,93 @@ pub enum Ssh2AuthReference {
 #[serde(deny_unknown_fields)]
 pub struct Ssh2Auth {
     pub auth_method: Ssh2AuthReference,
+    #[serde(default)]
+    pub host_key_policy: HostKeyPolicy,
+    /// Hosts to go through, in order, to reach the host (OpenSSH `ProxyJump`)
+    pub jump_hosts: Option<Vec<Ssh2JumpHostReference>>,
+}
+
+/// A jump host (bastion), whose credentials are retrieved from the secret providers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct Ssh2JumpHostReference {
+    pub endpoint: String,
+    pub auth_method: Ssh2AuthReference,
+    #[serde(default)]
+    pub host_key_policy: HostKeyPolicy,
+}
+
+impl Ssh2JumpHostReference {
+    pub fn from(endpoint: &str, auth_method: Ssh2AuthReference) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            auth_method,
+            host_key_policy: HostKeyPolicy::default(),
+        }
+    }
+
+    pub fn with_host_key_policy(mut self, host_key_policy: HostKeyPolicy) -> Self {
+        self.host_key_policy = host_key_policy;
+        self
+    }
+
+    pub async fn resolve(
+        &self,
+        optional_secret_provider: &Option<SecretProvidersPool>,
+    ) -> Result<Ssh2JumpHost, RegentError> {
+        Ok(Ssh2JumpHost {
+            endpoint: self.endpoint.clone(),
+            auth_method: self.auth_method.resolve(optional_secret_provider).await?,
+            host_key_policy: self.host_key_policy.clone(),
+        })
+    }
+}
+
+impl Ssh2AuthReference {
+    /// Retrieve the credentials from the secret providers
+    pub async fn resolve(
+        &self,
+        optional_secret_provider: &Option<SecretProvidersPool>,
+    ) -> Result<Ssh2AuthMethod, RegentError> {
+        match self {
+            Ssh2AuthReference::UsernamePassword(secret_reference) => {
+                let secret = required_secret_provider(optional_secret_provider)?
+                    .get_secret_typed::<Credentials>(secret_reference)
+                    .await?;
+                Ok(Ssh2AuthMethod::UsernamePassword(secret.inner()))
+            }
+            Ssh2AuthReference::Key(login_key_ref) => {
+                let secret_provider = required_secret_provider(optional_secret_provider)?;
+                let secret = secret_provider
+                    .get_secret_raw(login_key_ref.key_ref())
+                    .await?;
+                let mut login_key =
+                    LoginKey::from(login_key_ref.username().to_string(), secret.inner());
+
+                if let Some(passphrase_ref) = login_key_ref.passphrase_ref() {
+                    let passphrase = secret_provider.get_secret_raw(passphrase_ref).await?;
+                    login_key = login_key.with_passphrase(passphrase.inner());
+                }
+                if let Some(certificate_ref) = login_key_ref.certificate_ref() {
+                    let certificate = secret_provider.get_secret_raw(certificate_ref).await?;
+                    login_key = login_key.with_certificate(certificate.inner());
+                }
+                Ok(Ssh2AuthMethod::Key(login_key))
+            }
+            Ssh2AuthReference::Agent(agent_login) => Ok(Ssh2AuthMethod::Agent(agent_login.clone())),
+        }
+    }
+}
+
+fn required_secret_provider(
+    optional_secret_provider: &Option<SecretProvidersPool>,
+) -> Result<&SecretProvidersPool, RegentError> {
+    optional_secret_provider.as_ref().ok_or_else(|| {
+        RegentError::WrongInitialization(
+            "secret required to connect to host but secret_provider unset".to_string(),
+        )
+    })
 }
 
 impl Ssh2Auth {
@@ -610,12 +889,16 @@ impl Ssh2Auth {
                     secret_reference,
                     Some(name.to_string()),
                 )),
+                host_key_policy: HostKeyPolicy::default(),
+                jump_hosts: None,
             },
             None => Self {
                 auth_method: Ssh2AuthReference::UsernamePassword(SecretReference::from(
                     secret_reference,
                     None,
                 )),
+                host_key_policy: HostKeyPolicy::default(),
+                jump_hosts: None,
             },
         }
     }
@@ -626,8 +909,28 @@ impl Ssh2Auth {
                 username.to_string(),
                 key_secret_reference,
             )),
+            host_key_policy: HostKeyPolicy::default(),
+            jump_hosts: None,
+        }
+    }
+
+    pub fn agent(username: &str) -> Self {
+        Self {
+            auth_method: Ssh2AuthReference::Agent(AgentLogin::from(username.to_string())),
+            host_key_policy: HostKeyPolicy::default(),
+            jump_hosts: None,
         }
     }
+
+    pub fn with_host_key_policy(mut self, host_key_policy: HostKeyPolicy) -> Self {
+        self.host_key_policy = host_key_policy;
+        self
+    }
+
+    pub fn with_jump_host(mut self, jump_host: Ssh2JumpHostReference) -> Self {
+        self.jump_hosts.get_or_insert_with(Vec::new).push(jump_host);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -655,6 +958,232 @@ mod tests {
         let auth_method = yaml_serde::from_str::<Ssh2AuthMethod>(yaml);
         matches!(auth_method, Ok(Ssh2AuthMethod::Key(_)));
     }
+
+    const HOST_KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIJvmeTNoKhx+tI51I5sTdyFWLiHGFFLEIStwLfxzzApt";
+    const HOST_KEY_FINGERPRINT: &str = "SHA256:ANFBtUO7Q2LKmYCe505PB7oAOxqYtNQhkEZxLobzkck";
+    const OTHER_HOST_KEY: &str =
+        "AAAAC3NzaC1lZDI1NTE5AAAAIAnqX2F9COUfGsJR9KkJ8FkKpFxwOxa9c0NOt56alz5m";
+
+    fn public_key(base64: &str) -> ssh_key::PublicKey {
+        russh::keys::parse_public_key_base64(base64).unwrap()
+    }
+
+    #[test]
+    fn test_deserialize_host_key_policy() {
+        let yaml = r#"
+            AuthMethod: !Key
+              Username: testuser
+              Key:
+                SecRef: ssh-key
+            HostKeyPolicy: !Pinned
+              Fingerprint: SHA256:ANFBtUO7Q2LKmYCe505PB7oAOxqYtNQhkEZxLobzkck
+        "#;
+        let ssh2_auth = yaml_serde::from_str::<Ssh2Auth>(yaml).unwrap();
+        assert_eq!(
+            ssh2_auth.host_key_policy,
+            HostKeyPolicy::Pinned {
+                fingerprint: HOST_KEY_FINGERPRINT.to_string()
+            }
+        );
+
+        let yaml = r#"
+            AuthMethod: !UsernamePassword
+              SecRef: creds
+        "#;
+        let ssh2_auth = yaml_serde::from_str::<Ssh2Auth>(yaml).unwrap();
+        assert_eq!(
+            ssh2_auth.host_key_policy,
+            HostKeyPolicy::TrustOnFirstUse { known_hosts: None }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_encrypted_key_and_agent() {
+        let yaml = r#"
+            !Key
+              Username: deploy
+              Key:
+                SecRef: deploy-key
+              Passphrase:
+                SecRef: deploy-key-passphrase
+                Provider: vault
+              Certificate:
+                SecRef: deploy-key-cert
+        "#;
+        let Ssh2AuthReference::Key(login_key_ref) =
+            yaml_serde::from_str::<Ssh2AuthReference>(yaml).unwrap()
+        else {
+            panic!("expected a key reference");
+        };
+        assert!(login_key_ref.passphrase_ref().is_some());
+        assert!(login_key_ref.certificate_ref().is_some());
+
+        let yaml = r#"
+            !Agent
+              Username: deploy
+        "#;
+        let auth_reference = yaml_serde::from_str::<Ssh2AuthReference>(yaml).unwrap();
+        assert!(matches!(auth_reference, Ssh2AuthReference::Agent(_)));
+    }
+
+    #[test]
+    fn agent_logins_need_no_secret() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let agent = Ssh2AuthReference::Agent(AgentLogin::from("deploy".to_string()));
+        assert!(matches!(
+            runtime.block_on(agent.resolve(&None)),
+            Ok(Ssh2AuthMethod::Agent(_))
+        ));
+
+        let key = Ssh2AuthReference::Key(LoginKeyRef::from(
+            "deploy".to_string(),
+            SecretReference::from("deploy-key", None),
+        ));
+        assert!(matches!(
+            runtime.block_on(key.resolve(&None)),
+            Err(RegentError::WrongInitialization(_))
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_jump_hosts() {
+        let yaml = r#"
+            AuthMethod: !Key
+              Username: deploy
+              Key:
+                SecRef: app-key
+            JumpHosts:
+              - Endpoint: bastion.example.com
+                AuthMethod: !Key
+                  Username: jump
+                  Key:
+                    SecRef: bastion-key
+                HostKeyPolicy: !TrustOnFirstUse
+                  KnownHosts: /tmp/known_hosts
+              - Endpoint: 10.0.0.1:2222
+                AuthMethod: !UsernamePassword
+                  SecRef: inner-bastion-creds
+        "#;
+        let ssh2_auth = yaml_serde::from_str::<Ssh2Auth>(yaml).unwrap();
+        let jump_hosts = ssh2_auth.jump_hosts.unwrap();
+        assert_eq!(jump_hosts.len(), 2);
+        assert_eq!(jump_hosts[0].endpoint, "bastion.example.com");
+        assert_eq!(
+            jump_hosts[0].host_key_policy,
+            HostKeyPolicy::TrustOnFirstUse {
+                known_hosts: Some(PathBuf::from("/tmp/known_hosts"))
+            }
+        );
+        assert_eq!(jump_hosts[1].host_key_policy, HostKeyPolicy::default());
+    }
+
+    #[test]
+    fn parsing_endpoints() {
+        assert_eq!(
+            parse_endpoint("bastion.example.com").unwrap(),
+            ("bastion.example.com".to_string(), 22)
+        );
+        assert_eq!(
+            parse_endpoint("10.0.0.1:2222").unwrap(),
+            ("10.0.0.1".to_string(), 2222)
+        );
+        assert!(parse_endpoint("").is_err());
+        assert!(parse_endpoint("10.0.0.1:ssh").is_err());
+    }
+
+    #[test]
+    fn verifying_pinned_host_keys() {
+        let client = Ssh2Client::from(
+            "web01",
+            22,
+            HostKeyPolicy::Pinned {
+                fingerprint: HOST_KEY_FINGERPRINT.to_string(),
+            },
+        );
+        assert!(client.verify_server_key(&public_key(HOST_KEY)).is_ok());
+        assert!(matches!(
+            client.verify_server_key(&public_key(OTHER_HOST_KEY)),
+            Err(RegentError::HostKeyMismatch(_))
+        ));
+
+        assert!(fingerprint_matches(
+            HOST_KEY_FINGERPRINT,
+            "ANFBtUO7Q2LKmYCe505PB7oAOxqYtNQhkEZxLobzkck"
+        ));
+    }
+
+    #[test]
+    fn verifying_host_keys_against_known_hosts() {
+        let known_hosts =
+            std::env::temp_dir().join(format!("regent-known-hosts-{}", nanoid::nanoid!()));
+
+        // Unknown hosts are rejected in strict mode
+        let strict = Ssh2Client::from(
+            "web01",
+            2222,
+            HostKeyPolicy::Strict {
+                known_hosts: Some(known_hosts.clone()),
+            },
+        );
+        assert!(matches!(
+            strict.verify_server_key(&public_key(HOST_KEY)),
+            Err(RegentError::FailureToEstablishConnection(_))
+        ));
+
+        // ... and recorded on first use
+        let trust_on_first_use = Ssh2Client::from(
+            "web01",
+            2222,
+            HostKeyPolicy::TrustOnFirstUse {
+                known_hosts: Some(known_hosts.clone()),
+            },
+        );
+        trust_on_first_use
+            .verify_server_key(&public_key(HOST_KEY))
+            .unwrap();
+        assert!(
+            std::fs::read_to_string(&known_hosts)
+                .unwrap()
+                .contains("[web01]:2222 ssh-ed25519 ")
+        );
+
+        strict.verify_server_key(&public_key(HOST_KEY)).unwrap();
+        for client in [&strict, &trust_on_first_use] {
+            assert!(matches!(
+                client.verify_server_key(&public_key(OTHER_HOST_KEY)),
+                Err(RegentError::HostKeyMismatch(_))
+            ));
+        }
+
+        std::fs::remove_file(&known_hosts).unwrap();
+    }
+
+    #[test]
+    fn installing_uploaded_files_with_exact_mode() {
+        let directory = std::env::temp_dir().join(format!("regent-put-file-{}", nanoid::nanoid!()));
+        std::fs::create_dir(&directory).unwrap();
+        let temporary_path = directory.join(".motd.regent-tmp");
+        let path = directory.join("motd");
+
+        // Files created by the SCP sink are subject to the umask
+        let cmd = ShellCommand::new("umask 022 && touch")
+            .arg(temporary_path.display().to_string())
+            .and(install_command(&temporary_path, &path, 0o664));
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(cmd.as_str())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o664);
+        assert!(!temporary_path.exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
 }
 
 impl std::fmt::Debug for Ssh2AuthMethod {
@@ -670,10 +1199,298 @@ impl std::fmt::Debug for Ssh2AuthMethod {
             Ssh2AuthMethod::Key(login_key_path) => {
                 write!(f, "Key(({:?}, *REDACTED*))", login_key_path.username())
             }
+            Ssh2AuthMethod::Agent(agent_login) => {
+                write!(f, "Agent({:?})", agent_login.username())
+            }
+        }
+    }
+}
+
+/// Split an endpoint into its address and port (22 by default)
+fn parse_endpoint(endpoint: &str) -> Result<(String, u16), RegentError> {
+    let address_and_port: Vec<&str> = endpoint.split(':').collect();
+    if address_and_port[0].is_empty() {
+        return Err(RegentError::FailedInitialization(
+            "empty address".to_string(),
+        ));
+    }
+
+    let address = address_and_port[0].to_string();
+    let ssh_port: u16 = match address_and_port.get(1) {
+        Some(port) => match port.parse::<u16>() {
+            Ok(p) => p,
+            Err(e) => {
+                return Err(RegentError::FailedInitialization(format!(
+                    "invalid port: {}",
+                    e
+                )));
+            }
+        },
+        None => 22,
+    };
+    Ok((address, ssh_port))
+}
+
+/// Open an SSH session with a host, either directly or tunneled through a `direct-tcpip` channel
+/// of the session opened with a jump host
+async fn open_session(
+    config: Arc<Config>,
+    jump_handle: Option<&Handle<Ssh2Client>>,
+    address: &str,
+    ssh_port: u16,
+    host_key_policy: HostKeyPolicy,
+) -> Result<Handle<Ssh2Client>, RegentError> {
+    let client_handler = Ssh2Client::from(address, ssh_port, host_key_policy);
+    let connection_timeout = Duration::from_secs(10);
+
+    let connection = async {
+        match jump_handle {
+            None => {
+                russh::client::connect(config, format!("{}:{}", address, ssh_port), client_handler)
+                    .await
+            }
+            Some(jump_handle) => {
+                let channel = jump_handle
+                    .channel_open_direct_tcpip(address, ssh_port.into(), "127.0.0.1", 0)
+                    .await?;
+                russh::client::connect_stream(config, channel.into_stream(), client_handler).await
+            }
+        }
+    };
+
+    match timeout(connection_timeout, connection).await {
+        Ok(connection_result) => match connection_result {
+            Ok(h) => Ok(h),
+            Err(Ssh2ClientError::HostKey(details)) => Err(details),
+            Err(Ssh2ClientError::Russh(e)) => Err(RegentError::FailedTcpBinding(format!(
+                "{}:{} : {:?}",
+                address, ssh_port, e
+            ))),
+        },
+        Err(_details) => Err(RegentError::FailureToEstablishConnection(format!(
+            "Connection timeout elapsed ({} ms)",
+            connection_timeout.as_millis()
+        ))),
+    }
+}
+
+async fn authenticate(
+    handle: &mut Handle<Ssh2Client>,
+    auth_method: &Ssh2AuthMethod,
+) -> Result<(), RegentError> {
+    match auth_method {
+        Ssh2AuthMethod::UsernamePassword(credentials) => {
+            match handle
+                .authenticate_password(credentials.username(), credentials.password())
+                .await
+            {
+                Ok(auth_result) => match auth_result {
+                    AuthResult::Success => Ok(()),
+                    AuthResult::Failure { .. } => Err(RegentError::FailedInitialization(
+                        "Password authentication failed".to_string(),
+                    )),
+                },
+                Err(e) => Err(RegentError::FailedInitialization(format!(
+                    "Password authentication error: {:?}",
+                    e
+                ))),
+            }
+        }
+        Ssh2AuthMethod::Key(login_key) => {
+            // Load the private key from the string, decrypting it if needed
+            let key_pair = load_secret_key(login_key.key(), login_key.passphrase()).map_err(
+                |error_details| {
+                    RegentError::FailedInitialization(format!(
+                        "Failed to load key: {:?}",
+                        error_details
+                    ))
+                },
+            )?;
+
+            let auth_result = match login_key.certificate() {
+                Some(raw_certificate) => {
+                    let certificate =
+                        Certificate::from_openssh(raw_certificate.trim()).map_err(|e| {
+                            RegentError::FailedInitialization(format!(
+                                "Failed to load certificate: {:?}",
+                                e
+                            ))
+                        })?;
+                    handle
+                        .authenticate_openssh_cert(
+                            login_key.username(),
+                            Arc::new(key_pair),
+                            certificate,
+                        )
+                        .await
+                }
+                None => {
+                    let best_hash = best_supported_rsa_hash(handle).await?;
+                    let key_with_alg = PrivateKeyWithHashAlg::new(Arc::new(key_pair), best_hash);
+                    handle
+                        .authenticate_publickey(login_key.username(), key_with_alg)
+                        .await
+                }
+            };
+
+            match auth_result {
+                Ok(AuthResult::Success) => Ok(()),
+                Ok(AuthResult::Failure { .. }) => Err(RegentError::FailedInitialization(
+                    "Public key authentication failed".to_string(),
+                )),
+                Err(e) => Err(RegentError::FailedInitialization(format!(
+                    "Public key authentication error: {:?}",
+                    e
+                ))),
+            }
+        }
+        Ssh2AuthMethod::Agent(agent_login) => {
+            let mut agent = AgentClient::connect_env().await.map_err(|e| {
+                RegentError::FailedInitialization(format!(
+                    "Failed to connect to the SSH agent: {:?}",
+                    e
+                ))
+            })?;
+            let identities = agent.request_identities().await.map_err(|e| {
+                RegentError::FailedInitialization(format!(
+                    "Failed to list the identities of the SSH agent: {:?}",
+                    e
+                ))
+            })?;
+            let best_hash = best_supported_rsa_hash(handle).await?;
+
+            // Each identity is offered in turn until one is accepted
+            for identity in identities {
+                let auth_result = match identity {
+                    AgentIdentity::PublicKey { key, .. } => {
+                        handle
+                            .authenticate_publickey_with(
+                                agent_login.username(),
+                                key,
+                                best_hash,
+                                &mut agent,
+                            )
+                            .await
+                    }
+                    AgentIdentity::Certificate { certificate, .. } => {
+                        handle
+                            .authenticate_certificate_with(
+                                agent_login.username(),
+                                certificate,
+                                best_hash,
+                                &mut agent,
+                            )
+                            .await
+                    }
+                };
+
+                match auth_result {
+                    Ok(AuthResult::Success) => return Ok(()),
+                    Ok(AuthResult::Failure { .. }) => {}
+                    Err(e) => {
+                        return Err(RegentError::FailedInitialization(format!(
+                            "SSH agent authentication error: {:?}",
+                            e
+                        )));
+                    }
+                }
+            }
+
+            Err(RegentError::FailedInitialization(
+                "No identity of the SSH agent was accepted".to_string(),
+            ))
         }
     }
 }
 
+async fn best_supported_rsa_hash(
+    handle: &Handle<Ssh2Client>,
+) -> Result<Option<HashAlg>, RegentError> {
+    match handle.best_supported_rsa_hash().await {
+        Ok(h) => Ok(h.flatten()),
+        Err(e) => Err(RegentError::FailedInitialization(format!(
+            "Failed to get supported hash: {:?}",
+            e
+        ))),
+    }
+}
+
+/// Gather the output of the command running on the channel until the channel closes
+async fn collect_output<F: FnMut(OutputChunk)>(
+    channel: &mut Channel<russh::client::Msg>,
+    prompt_responder: &mut PromptResponder,
+    on_output: &mut F,
+) -> CommandResult {
+    let mut return_code = 1;
+    let mut collector = OutputCollector::default();
+    let mut eof_sent = false;
+
+    loop {
+        // Nothing else is written to the command, which must not wait for more input
+        if !eof_sent && !prompt_responder.awaits_prompt() {
+            let _ = channel.eof().await;
+            eof_sent = true;
+        }
+
+        // There's an event available on the session channel
+        let Some(msg) = channel.wait().await else {
+            break;
+        };
+        let mut chunk = match msg {
+            ChannelMsg::Data { data } => OutputChunk::Stdout(data.to_vec()),
+            // Extended data of type 1 is stderr (RFC 4254)
+            ChannelMsg::ExtendedData { data, ext: 1 } => OutputChunk::Stderr(data.to_vec()),
+            ChannelMsg::ExitStatus { exit_status } => {
+                return_code = exit_status;
+                // cannot leave the loop immediately, there might still be more data to receive
+                continue;
+            }
+            _ => continue,
+        };
+
+        let (OutputChunk::Stdout(bytes) | OutputChunk::Stderr(bytes)) = &mut chunk;
+        match prompt_responder.inspect(bytes) {
+            PromptAnswer::Nothing => {}
+            PromptAnswer::Password(answer) => {
+                let _ = channel.data(Cursor::new(answer)).await;
+            }
+            PromptAnswer::Abort => {
+                let _ = channel.close().await;
+            }
+        }
+        collector.push(chunk, on_output);
+    }
+
+    collector.into_result(return_code.into())
+}
+
+/// Command giving an uploaded temporary file its mode, then moving it to its destination.
+fn install_command(temporary_path: &Path, path: &Path, mode: u32) -> ShellCommand {
+    ShellCommand::new("chmod")
+        .arg(format!("{:04o}", mode & 0o7777))
+        .arg(temporary_path.display().to_string())
+        .and(
+            ShellCommand::new("mv -f")
+                .arg(temporary_path.display().to_string())
+                .arg(path.display().to_string()),
+        )
+}
+
+/// Wait for the acknowledgement of the remote SCP sink : a NULL byte, or a warning (\x01) or
+/// fatal error (\x02) byte followed by a message.
+async fn wait_for_scp_ack(channel: &mut Channel<russh::client::Msg>) -> Result<(), RegentError> {
+    let response = fetch_next_chunk(channel)
+        .await
+        .map_err(|_| RegentError::FailedToPutFile("Unexpected end of SCP session".to_string()))?;
+
+    match response.first() {
+        Some(0x00) => Ok(()),
+        _ => Err(RegentError::FailedToPutFile(
+            String::from_utf8_lossy(&response[1..]).trim().to_string(),
+        )),
+    }
+}
+
 async fn fetch_next_chunk(channel: &mut Channel<russh::client::Msg>) -> Result<Bytes, RegentError> {
     while let Some(msg) = channel.wait().await {
         if let ChannelMsg::Data { data } = msg {
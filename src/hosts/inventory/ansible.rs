@@ -0,0 +1,950 @@
+//! Import of Ansible inventories
+//!
+//! This module converts Ansible INI and YAML inventories into an [`Inventory`]. Groups, children
+//! and variables are kept as they are, whereas Ansible connection variables (`ansible_host`,
+//! `ansible_port`, `ansible_user`, `ansible_ssh_private_key_file`...) become the endpoint and the
+//! [`ConnectionMethod`] of each host. Other `ansible_*` variables have no equivalent : they are
+//! left out and reported as warnings.
+
+use std::collections::{BTreeMap, HashMap};
+use yaml_serde::Value;
+
+use super::{ALL_HOSTS, HostGroup, Inventory, InventoryBuilder, groups_of, resolve_groups};
+use crate::error::RegentError;
+use crate::hosts::handlers::ssh2::Ssh2Auth;
+use crate::hosts::handlers::{ConnectionMethod, TargetUser};
+use crate::hosts::managed_host::ManagedHostBuilder;
+use crate::secrets::SecretReference;
+
+#[allow(unused)]
+use tracing::{Level, debug, error, info, span, trace, warn};
+
+/// Ansible variables turned into endpoints and connection methods
+const CONNECTION_VARS: [&str; 9] = [
+    "ansible_host",
+    "ansible_ssh_host",
+    "ansible_port",
+    "ansible_ssh_port",
+    "ansible_user",
+    "ansible_ssh_user",
+    "ansible_ssh_private_key_file",
+    "ansible_private_key_file",
+    "ansible_connection",
+];
+
+/// An inventory imported from Ansible, with what could not be imported.
+#[derive(Debug)]
+pub struct AnsibleImport {
+    pub inventory: Inventory,
+    /// Parts of the Ansible inventory which were left out.
+    pub warnings: Vec<String>,
+}
+
+impl Inventory {
+    /// Import an Ansible INI inventory.
+    ///
+    /// Hosts with an `ansible_user` are reached over SSH, with their `ansible_ssh_private_key_file`
+    /// as a secret reference or through the SSH agent. Hosts with `ansible_connection=local` are
+    /// reached locally. Others use `default_connection_method`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use regent_sdk::hosts::inventory::Inventory;
+    ///
+    /// let import = Inventory::from_ansible_ini(
+    ///     r#"
+    /// [webservers]
+    /// web[01:03].example.com ansible_user=deploy
+    ///
+    /// [webservers:vars]
+    /// http_port=8080
+    /// "#,
+    ///     None,
+    /// )
+    /// .unwrap();
+    ///
+    /// for warning in import.warnings {
+    ///     println!("{}", warning);
+    /// }
+    /// ```
+    pub fn from_ansible_ini(
+        raw_ini: &str,
+        default_connection_method: Option<ConnectionMethod>,
+    ) -> Result<AnsibleImport, RegentError> {
+        AnsibleInventory::from_ini(raw_ini)?.import(default_connection_method)
+    }
+
+    /// Import an Ansible YAML inventory. Connection variables are handled as with
+    /// [`Inventory::from_ansible_ini`].
+    pub fn from_ansible_yaml(
+        raw_yaml: &str,
+        default_connection_method: Option<ConnectionMethod>,
+    ) -> Result<AnsibleImport, RegentError> {
+        AnsibleInventory::from_yaml(raw_yaml)?.import(default_connection_method)
+    }
+
+    /// Import the JSON output of an Ansible dynamic inventory called with `--list`. Connection
+    /// variables are handled as with [`Inventory::from_ansible_ini`].
+    ///
+    /// Host variables are only read from `_meta.hostvars`.
+    pub fn from_ansible_json(
+        raw_json: &str,
+        default_connection_method: Option<ConnectionMethod>,
+    ) -> Result<AnsibleImport, RegentError> {
+        AnsibleInventory::from_json(raw_json)?.import(default_connection_method)
+    }
+}
+
+/// Hosts, groups and variables of an Ansible inventory, as written
+#[derive(Debug, Default)]
+struct AnsibleInventory {
+    hosts: Vec<String>, // Hostnames, in order of appearance
+    host_vars: HashMap<String, HashMap<String, String>>,
+    groups: BTreeMap<String, AnsibleGroup>,
+    global_vars: HashMap<String, String>, // Vars of the "all" group
+    warnings: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct AnsibleGroup {
+    hosts: Vec<String>,
+    children: Vec<String>,
+    vars: HashMap<String, String>,
+}
+
+/// Section of an INI inventory, with the name of its group
+enum IniSection {
+    Hosts(String),
+    Vars(String),
+    Children(String),
+}
+
+impl AnsibleInventory {
+    fn from_ini(raw_ini: &str) -> Result<Self, RegentError> {
+        let mut inventory = Self::default();
+        // Hosts before any section are ungrouped
+        let mut section = IniSection::Hosts(ALL_HOSTS.to_string());
+
+        for (index, line) in raw_ini.lines().enumerate() {
+            let parse_error = |details: &str| {
+                RegentError::FailureToParseContent(format!("line {} : {}", index + 1, details))
+            };
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = match header.split_once(':') {
+                    None => {
+                        inventory.declare_group(header);
+                        IniSection::Hosts(header.to_string())
+                    }
+                    Some((group, "vars")) => IniSection::Vars(group.to_string()),
+                    Some((group, "children")) => IniSection::Children(group.to_string()),
+                    Some(_) => return Err(parse_error("unknown kind of section")),
+                };
+                continue;
+            }
+
+            match &section {
+                IniSection::Hosts(group) => {
+                    let tokens = split_ini_line(line).map_err(|details| parse_error(&details))?;
+                    let Some((pattern, assignments)) = tokens.split_first() else {
+                        continue;
+                    };
+
+                    let mut vars = HashMap::new();
+                    for assignment in assignments {
+                        let (key, value) = assignment
+                            .split_once('=')
+                            .ok_or_else(|| parse_error("host variables must be key=value"))?;
+                        vars.insert(key.to_string(), value.to_string());
+                    }
+
+                    // "host:port" is a shorthand for ansible_port
+                    let pattern = match split_port(pattern) {
+                        Some((pattern, port)) => {
+                            vars.entry("ansible_port".to_string())
+                                .or_insert(port.to_string());
+                            pattern
+                        }
+                        None => pattern,
+                    };
+
+                    for hostname in
+                        expand_host_pattern(pattern).map_err(|details| parse_error(&details))?
+                    {
+                        inventory.add_host(&hostname, group, vars.clone());
+                    }
+                }
+                IniSection::Vars(group) => {
+                    let (key, value) = line
+                        .split_once('=')
+                        .ok_or_else(|| parse_error("group variables must be key=value"))?;
+                    let value = split_ini_line(value.trim())
+                        .map_err(|details| parse_error(&details))?
+                        .join(" ");
+                    inventory
+                        .add_group_vars(group, HashMap::from([(key.trim().to_string(), value)]));
+                }
+                IniSection::Children(group) => inventory.add_child(group, line),
+            }
+        }
+        Ok(inventory)
+    }
+
+    fn from_yaml(raw_yaml: &str) -> Result<Self, RegentError> {
+        let value: Value = yaml_serde::from_str(raw_yaml)
+            .map_err(|details| RegentError::FailureToParseContent(format!("{:?}", details)))?;
+
+        let mut inventory = Self::default();
+        for (name, group) in yaml_entries(&value, "inventory")? {
+            inventory.read_yaml_group(&name, group)?;
+        }
+        Ok(inventory)
+    }
+
+    fn from_json(raw_json: &str) -> Result<Self, RegentError> {
+        let parse_error = |details: String| RegentError::FailureToParseContent(details);
+        let value: serde_json::Value = serde_json::from_str(raw_json)
+            .map_err(|details| parse_error(format!("{:?}", details)))?;
+        let groups = value
+            .as_object()
+            .ok_or_else(|| parse_error("inventory must be an object".to_string()))?;
+
+        let mut inventory = Self::default();
+        for (name, group) in groups {
+            if name == "_meta" {
+                let hostvars = group
+                    .get("hostvars")
+                    .and_then(|hostvars| hostvars.as_object());
+                for (hostname, host_vars) in hostvars.into_iter().flatten() {
+                    let vars = inventory.json_vars(&format!("host {}", hostname), host_vars)?;
+                    inventory.add_host(hostname, ALL_HOSTS, vars);
+                }
+                continue;
+            }
+
+            inventory.declare_group(name);
+            // A group is either a list of hosts or an object
+            let (hosts, group) = match group {
+                serde_json::Value::Array(hosts) => (Some(hosts), None),
+                serde_json::Value::Object(group) => (
+                    group.get("hosts").and_then(|hosts| hosts.as_array()),
+                    Some(group),
+                ),
+                _ => {
+                    return Err(parse_error(format!(
+                        "group {} must be a list or an object",
+                        name
+                    )));
+                }
+            };
+
+            for host in hosts.into_iter().flatten() {
+                let hostname = host
+                    .as_str()
+                    .ok_or_else(|| parse_error(format!("hosts of {} must be strings", name)))?;
+                inventory.add_host(hostname, name, HashMap::new());
+            }
+
+            for (key, value) in group.into_iter().flatten() {
+                match key.as_str() {
+                    "hosts" => {}
+                    "vars" => {
+                        let vars = inventory.json_vars(&format!("group {}", name), value)?;
+                        inventory.add_group_vars(name, vars);
+                    }
+                    "children" => {
+                        for child in value.as_array().into_iter().flatten() {
+                            let child = child.as_str().ok_or_else(|| {
+                                parse_error(format!("children of {} must be strings", name))
+                            })?;
+                            inventory.add_child(name, child);
+                        }
+                    }
+                    _ => inventory
+                        .warnings
+                        .push(format!("group {} : unsupported key {} ignored", name, key)),
+                }
+            }
+        }
+        Ok(inventory)
+    }
+
+    /// Variables given as an object. Only scalar values are kept.
+    fn json_vars(
+        &mut self,
+        owner: &str,
+        vars: &serde_json::Value,
+    ) -> Result<HashMap<String, String>, RegentError> {
+        let vars = vars.as_object().ok_or_else(|| {
+            RegentError::FailureToParseContent(format!("variables of {} must be an object", owner))
+        })?;
+
+        let mut scalar_vars = HashMap::new();
+        for (key, value) in vars {
+            let value = match value {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::String(string) => string.clone(),
+                serde_json::Value::Bool(_) | serde_json::Value::Number(_) => value.to_string(),
+                _ => {
+                    self.warnings.push(format!(
+                        "{} : variable {} is not a scalar, ignored",
+                        owner, key
+                    ));
+                    continue;
+                }
+            };
+            scalar_vars.insert(key.clone(), value);
+        }
+        Ok(scalar_vars)
+    }
+
+    fn read_yaml_group(&mut self, name: &str, group: &Value) -> Result<(), RegentError> {
+        self.declare_group(name);
+
+        for (key, value) in yaml_entries(group, &format!("group {}", name))? {
+            match key.as_str() {
+                "hosts" => {
+                    for (hostname, host_vars) in yaml_entries(value, &format!("hosts of {}", name))?
+                    {
+                        let vars = self.yaml_vars(&format!("host {}", hostname), host_vars)?;
+                        self.add_host(&hostname, name, vars);
+                    }
+                }
+                "vars" => {
+                    let vars = self.yaml_vars(&format!("group {}", name), value)?;
+                    self.add_group_vars(name, vars);
+                }
+                "children" => {
+                    for (child, child_group) in
+                        yaml_entries(value, &format!("children of {}", name))?
+                    {
+                        self.add_child(name, &child);
+                        self.read_yaml_group(&child, child_group)?;
+                    }
+                }
+                _ => self
+                    .warnings
+                    .push(format!("group {} : unsupported key {} ignored", name, key)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Variables given as a mapping. Only scalar values are kept.
+    fn yaml_vars(
+        &mut self,
+        owner: &str,
+        vars: &Value,
+    ) -> Result<HashMap<String, String>, RegentError> {
+        let mut scalar_vars = HashMap::new();
+        for (key, value) in yaml_entries(vars, owner)? {
+            match yaml_scalar(value) {
+                Some(value) => {
+                    scalar_vars.insert(key, value);
+                }
+                None => self.warnings.push(format!(
+                    "{} : variable {} is not a scalar, ignored",
+                    owner, key
+                )),
+            }
+        }
+        Ok(scalar_vars)
+    }
+
+    fn declare_group(&mut self, name: &str) {
+        if name != ALL_HOSTS {
+            self.groups.entry(name.to_string()).or_default();
+        }
+    }
+
+    fn add_host(&mut self, hostname: &str, group: &str, vars: HashMap<String, String>) {
+        if !self.host_vars.contains_key(hostname) {
+            self.hosts.push(hostname.to_string());
+        }
+        self.host_vars
+            .entry(hostname.to_string())
+            .or_default()
+            .extend(vars);
+
+        if group != ALL_HOSTS {
+            let group = self.groups.entry(group.to_string()).or_default();
+            if !group.hosts.iter().any(|host| host == hostname) {
+                group.hosts.push(hostname.to_string());
+            }
+        }
+    }
+
+    fn add_group_vars(&mut self, group: &str, vars: HashMap<String, String>) {
+        if group == ALL_HOSTS {
+            self.global_vars.extend(vars);
+        } else {
+            self.groups
+                .entry(group.to_string())
+                .or_default()
+                .vars
+                .extend(vars);
+        }
+    }
+
+    fn add_child(&mut self, group: &str, child: &str) {
+        if child == ALL_HOSTS {
+            self.warnings.push(format!(
+                "group {} : all can't be a child group, ignored",
+                group
+            ));
+            return;
+        }
+        self.declare_group(child);
+        if group != ALL_HOSTS {
+            let group = self.groups.entry(group.to_string()).or_default();
+            if !group.children.iter().any(|name| name == child) {
+                group.children.push(child.to_string());
+            }
+        }
+    }
+
+    fn import(
+        mut self,
+        default_connection_method: Option<ConnectionMethod>,
+    ) -> Result<AnsibleImport, RegentError> {
+        let mut warnings = std::mem::take(&mut self.warnings);
+
+        let (global_connection_vars, global_vars) =
+            split_connection_vars("group all", self.global_vars, &mut warnings);
+
+        let mut groups = Vec::new();
+        let mut connection_groups = Vec::new();
+        for (name, group) in self.groups {
+            let (connection_vars, vars) =
+                split_connection_vars(&format!("group {}", name), group.vars, &mut warnings);
+            let host_group = |vars: HashMap<String, String>| HostGroup {
+                name: name.clone(),
+                hosts: Some(group.hosts.clone()),
+                children: Some(group.children.clone()),
+                vars: (!vars.is_empty()).then_some(vars),
+                connection_method: None,
+            };
+            groups.push(host_group(vars));
+            connection_groups.push(host_group(connection_vars));
+        }
+
+        // Connection variables are merged like other variables before being turned into endpoints
+        // and connection methods
+        let connection_groups = resolve_groups(connection_groups, &self.hosts)?;
+        let mut hosts = Vec::new();
+        for hostname in &self.hosts {
+            let (host_connection_vars, vars) = split_connection_vars(
+                &format!("host {}", hostname),
+                self.host_vars.remove(hostname).unwrap_or_default(),
+                &mut warnings,
+            );
+
+            let mut connection_vars = global_connection_vars.clone();
+            for group in groups_of(hostname, &connection_groups) {
+                connection_vars.extend(group.vars.clone().unwrap_or_default());
+            }
+            connection_vars.extend(host_connection_vars);
+
+            let (endpoint, connection_method) =
+                connection_of(hostname, &connection_vars, &mut warnings);
+            let mut host = ManagedHostBuilder::new(hostname, &endpoint, connection_method);
+            if !vars.is_empty() {
+                host.set_host_vars(Some(vars));
+            }
+            hosts.push(host);
+        }
+
+        let inventory = InventoryBuilder {
+            name: None,
+            hosts,
+            default_connection_method,
+            global_vars: (!global_vars.is_empty()).then_some(global_vars),
+            groups: Some(groups),
+            fact_probes: None,
+        }
+        .build()?;
+
+        for warning in &warnings {
+            warn!(target: "inventory", "{}", warning);
+        }
+        Ok(AnsibleImport {
+            inventory,
+            warnings,
+        })
+    }
+}
+
+/// Split variables into connection variables and the others, leaving out other `ansible_*`
+/// variables
+fn split_connection_vars(
+    owner: &str,
+    vars: HashMap<String, String>,
+    warnings: &mut Vec<String>,
+) -> (HashMap<String, String>, HashMap<String, String>) {
+    let mut connection_vars = HashMap::new();
+    let mut other_vars = HashMap::new();
+    for (key, value) in vars.into_iter().collect::<BTreeMap<String, String>>() {
+        if CONNECTION_VARS.contains(&key.as_str()) {
+            connection_vars.insert(key, value);
+        } else if key.starts_with("ansible_") {
+            warnings.push(format!("{} : unsupported variable {} ignored", owner, key));
+        } else {
+            other_vars.insert(key, value);
+        }
+    }
+    (connection_vars, other_vars)
+}
+
+/// Endpoint and connection method of a host, out of its connection variables
+fn connection_of(
+    hostname: &str,
+    connection_vars: &HashMap<String, String>,
+    warnings: &mut Vec<String>,
+) -> (String, Option<ConnectionMethod>) {
+    let var = |keys: &[&str]| keys.iter().find_map(|key| connection_vars.get(*key));
+
+    let address = var(&["ansible_host", "ansible_ssh_host"]).map_or(hostname, String::as_str);
+    let endpoint = match var(&["ansible_port", "ansible_ssh_port"]) {
+        Some(port) => format!("{}:{}", address, port),
+        None => address.to_string(),
+    };
+
+    let connection_method = match var(&["ansible_connection"]).map(String::as_str) {
+        Some("local") => Some(ConnectionMethod::Localhost(TargetUser::current_user())),
+        None | Some("ssh") | Some("smart") | Some("paramiko") => {
+            let user = var(&["ansible_user", "ansible_ssh_user"]);
+            let key_file = var(&["ansible_ssh_private_key_file", "ansible_private_key_file"]);
+            match (user, key_file) {
+                (Some(user), Some(key_file)) => Some(ConnectionMethod::Ssh2(Ssh2Auth::key(
+                    user,
+                    SecretReference::from(key_file, None),
+                ))),
+                (Some(user), None) => Some(ConnectionMethod::Ssh2(Ssh2Auth::agent(user))),
+                (None, Some(_)) => {
+                    warnings.push(format!(
+                        "host {} : ansible_ssh_private_key_file ignored without ansible_user",
+                        hostname
+                    ));
+                    None
+                }
+                (None, None) => None,
+            }
+        }
+        Some(other) => {
+            warnings.push(format!(
+                "host {} : unsupported ansible_connection {} ignored",
+                hostname, other
+            ));
+            None
+        }
+    };
+
+    (endpoint, connection_method)
+}
+
+/// Split a line into words, as a shell would for quotes. A word starting with `#` starts a
+/// comment.
+fn split_ini_line(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, '#') if word.is_none() => break,
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Split the port of a `host:port` pattern
+fn split_port(pattern: &str) -> Option<(&str, &str)> {
+    let (host, port) = pattern.rsplit_once(':')?;
+    let is_port = !port.is_empty() && port.chars().all(|c| c.is_ascii_digit());
+    let is_outside_range = host.matches('[').count() == host.matches(']').count();
+    (is_port && is_outside_range).then_some((host, port))
+}
+
+/// Expand the ranges of a host pattern : `web[01:03]` gives `web01`, `web02` and `web03`, and
+/// `db-[a:c]` gives `db-a`, `db-b` and `db-c`. A range may end with a stride (`[0:10:5]`).
+fn expand_host_pattern(pattern: &str) -> Result<Vec<String>, String> {
+    let (Some(start), Some(end)) = (pattern.find('['), pattern.find(']')) else {
+        return Ok(vec![pattern.to_string()]);
+    };
+    if end < start {
+        return Err(format!("wrong range in {}", pattern));
+    }
+    let (prefix, range, suffix) = (
+        &pattern[..start],
+        &pattern[start + 1..end],
+        &pattern[end + 1..],
+    );
+
+    let bounds: Vec<&str> = range.split(':').collect();
+    let (first, last, stride) = match bounds[..] {
+        [first, last] => (first, last, "1"),
+        [first, last, stride] => (first, last, stride),
+        _ => return Err(format!("wrong range in {}", pattern)),
+    };
+    let stride = match stride.parse::<usize>() {
+        Ok(stride) if stride > 0 => stride,
+        _ => return Err(format!("wrong stride in {}", pattern)),
+    };
+
+    let values: Vec<String> = match (first.parse::<u64>(), last.parse::<u64>()) {
+        (Ok(first_number), Ok(last_number)) => {
+            // Leading zeros give the width of all values
+            let width = if first.starts_with('0') {
+                first.len()
+            } else {
+                0
+            };
+            (first_number..=last_number)
+                .step_by(stride)
+                .map(|number| format!("{:0width$}", number, width = width))
+                .collect()
+        }
+        _ => {
+            let letter = |bound: &str| {
+                let mut chars = bound.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii_alphabetic() => Some(c),
+                    _ => None,
+                }
+            };
+            match (letter(first), letter(last)) {
+                (Some(first), Some(last)) => {
+                    (first..=last).step_by(stride).map(String::from).collect()
+                }
+                _ => return Err(format!("wrong range in {}", pattern)),
+            }
+        }
+    };
+
+    let mut hostnames = Vec::new();
+    for value in values {
+        for rest in expand_host_pattern(suffix)? {
+            hostnames.push(format!("{}{}{}", prefix, value, rest));
+        }
+    }
+    Ok(hostnames)
+}
+
+/// Entries of a YAML mapping, with their keys as strings. Null is an empty mapping.
+fn yaml_entries<'a>(
+    value: &'a Value,
+    owner: &str,
+) -> Result<Vec<(String, &'a Value)>, RegentError> {
+    match value {
+        Value::Null => Ok(Vec::new()),
+        Value::Mapping(mapping) => mapping
+            .iter()
+            .map(|(key, value)| match yaml_scalar(key) {
+                Some(key) => Ok((key, value)),
+                None => Err(RegentError::FailureToParseContent(format!(
+                    "{} : keys must be scalars",
+                    owner
+                ))),
+            })
+            .collect(),
+        _ => Err(RegentError::FailureToParseContent(format!(
+            "{} must be a mapping",
+            owner
+        ))),
+    }
+}
+
+fn yaml_scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some(String::new()),
+        Value::Bool(boolean) => Some(boolean.to_string()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::String(string) => Some(string.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosts::handlers::ssh2::Ssh2AuthReference;
+
+    fn endpoint<'a>(inventory: &'a Inventory, host_id: &str) -> &'a str {
+        inventory.hosts[host_id].endpoint()
+    }
+
+    fn host_var(inventory: &Inventory, host_id: &str, key: &str) -> Option<String> {
+        inventory.hosts[host_id]
+            .host_vars
+            .as_ref()
+            .and_then(|vars| vars.get(key).cloned())
+    }
+
+    const INI_INVENTORY: &str = r#"
+# Ansible INI inventory
+bastion.example.com:2222 ansible_user=admin
+
+[webservers]
+web[01:03].example.com
+web-canary.example.com ansible_host=10.0.0.50 http_port="8 080" ansible_become=true
+
+[databases]
+db-[a:b] ansible_user=postgres ansible_ssh_private_key_file=~/.ssh/db.pem
+
+[webservers:vars]
+http_port=80
+ansible_user=deploy
+
+[prod:children]
+webservers
+databases
+
+[prod:vars]
+env=prod
+
+[all:vars]
+ntp_server=ntp.example.com
+"#;
+
+    #[test]
+    fn importing_ini_inventory() {
+        let import = Inventory::from_ansible_ini(INI_INVENTORY, None).unwrap();
+        let inventory = &import.inventory;
+
+        assert_eq!(inventory.hosts.len(), 7);
+        assert_eq!(inventory.groups["webservers"].len(), 4);
+        assert_eq!(inventory.groups["prod"].len(), 6);
+        assert!(inventory.hosts.contains_key("web02.example.com"));
+        assert!(inventory.hosts.contains_key("db-b"));
+
+        assert_eq!(
+            endpoint(inventory, "bastion.example.com"),
+            "bastion.example.com:2222"
+        );
+        assert_eq!(endpoint(inventory, "web-canary.example.com"), "10.0.0.50");
+
+        assert_eq!(
+            host_var(inventory, "web01.example.com", "http_port").unwrap(),
+            "80"
+        );
+        assert_eq!(
+            host_var(inventory, "web-canary.example.com", "http_port").unwrap(),
+            "8 080"
+        );
+        assert_eq!(
+            host_var(inventory, "db-a", "ntp_server").unwrap(),
+            "ntp.example.com"
+        );
+        assert_eq!(host_var(inventory, "db-a", "env").unwrap(), "prod");
+        assert!(host_var(inventory, "web01.example.com", "ansible_user").is_none());
+
+        match &inventory.hosts["web01.example.com"].host_connection_method {
+            Some(ConnectionMethod::Ssh2(auth)) => {
+                assert!(
+                    matches!(&auth.auth_method, Ssh2AuthReference::Agent(agent) if agent.username() == "deploy")
+                );
+            }
+            other => panic!("{:?}", other),
+        }
+        match &inventory.hosts["db-a"].host_connection_method {
+            Some(ConnectionMethod::Ssh2(auth)) => {
+                assert!(matches!(&auth.auth_method, Ssh2AuthReference::Key(_)));
+            }
+            other => panic!("{:?}", other),
+        }
+
+        assert_eq!(
+            import.warnings,
+            vec!["host web-canary.example.com : unsupported variable ansible_become ignored"]
+        );
+    }
+
+    #[test]
+    fn importing_yaml_inventory() {
+        let import = Inventory::from_ansible_yaml(
+            r#"
+all:
+  vars:
+    ansible_user: deploy
+    ntp_server: ntp.example.com
+  hosts:
+    localhost:
+      ansible_connection: local
+  children:
+    webservers:
+      hosts:
+        web01:
+          ansible_host: 10.0.0.1
+          ansible_port: 2222
+          http_port: 8080
+        web02:
+      vars:
+        http_port: 80
+        packages: [nginx, certbot]
+    prod:
+      children:
+        webservers:
+      vars:
+        ansible_password: hunter2
+"#,
+            None,
+        )
+        .unwrap();
+        let inventory = &import.inventory;
+
+        assert_eq!(inventory.hosts.len(), 3);
+        assert_eq!(inventory.groups["prod"].len(), 2);
+        assert_eq!(endpoint(inventory, "web01"), "10.0.0.1:2222");
+        assert_eq!(endpoint(inventory, "web02"), "web02");
+        assert_eq!(host_var(inventory, "web01", "http_port").unwrap(), "8080");
+        assert_eq!(host_var(inventory, "web02", "http_port").unwrap(), "80");
+        assert_eq!(
+            host_var(inventory, "localhost", "ntp_server").unwrap(),
+            "ntp.example.com"
+        );
+        assert!(matches!(
+            inventory.hosts["localhost"].host_connection_method,
+            Some(ConnectionMethod::Localhost(_))
+        ));
+        assert!(matches!(
+            inventory.hosts["web02"].host_connection_method,
+            Some(ConnectionMethod::Ssh2(_))
+        ));
+
+        assert_eq!(
+            import.warnings,
+            vec![
+                "group webservers : variable packages is not a scalar, ignored",
+                "group prod : unsupported variable ansible_password ignored",
+            ]
+        );
+    }
+
+    #[test]
+    fn importing_json_list() {
+        let import = Inventory::from_ansible_json(
+            r#"{
+                "_meta": {
+                    "hostvars": {
+                        "web01": { "ansible_host": "10.0.0.1", "http_port": 8080 },
+                        "db01": { "ansible_user": "postgres", "tags": ["db"] }
+                    }
+                },
+                "all": { "children": ["ungrouped", "webservers", "prod"] },
+                "webservers": ["web01", "web02"],
+                "prod": {
+                    "hosts": ["db01"],
+                    "children": ["webservers"],
+                    "vars": { "ansible_user": "deploy", "env": "prod" }
+                }
+            }"#,
+            None,
+        )
+        .unwrap();
+        let inventory = &import.inventory;
+
+        assert_eq!(inventory.hosts.len(), 3);
+        assert_eq!(inventory.groups["prod"].len(), 3);
+        assert_eq!(endpoint(inventory, "web01"), "10.0.0.1");
+        assert_eq!(host_var(inventory, "web01", "http_port").unwrap(), "8080");
+        assert_eq!(host_var(inventory, "web02", "env").unwrap(), "prod");
+        match &inventory.hosts["db01"].host_connection_method {
+            Some(ConnectionMethod::Ssh2(auth)) => {
+                assert!(
+                    matches!(&auth.auth_method, Ssh2AuthReference::Agent(agent) if agent.username() == "postgres")
+                );
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(
+            import.warnings,
+            vec!["host db01 : variable tags is not a scalar, ignored"]
+        );
+
+        assert!(matches!(
+            Inventory::from_ansible_json(r#"{ "webservers": "web01" }"#, None),
+            Err(RegentError::FailureToParseContent(_))
+        ));
+    }
+
+    #[test]
+    fn falling_back_to_default_connection_method() {
+        let ini = "web01\nweb02 ansible_ssh_private_key_file=~/.ssh/id\n";
+        assert!(matches!(
+            Inventory::from_ansible_ini(ini, None),
+            Err(RegentError::WrongInitialization(_))
+        ));
+
+        let import = Inventory::from_ansible_ini(
+            ini,
+            Some(ConnectionMethod::Localhost(TargetUser::current_user())),
+        )
+        .unwrap();
+        assert!(matches!(
+            import.inventory.hosts["web01"].host_connection_method,
+            Some(ConnectionMethod::Localhost(_))
+        ));
+        assert_eq!(import.warnings.len(), 1);
+    }
+
+    #[test]
+    fn expanding_host_patterns() {
+        assert_eq!(
+            expand_host_pattern("web[08:10].example.com").unwrap(),
+            vec![
+                "web08.example.com",
+                "web09.example.com",
+                "web10.example.com"
+            ]
+        );
+        assert_eq!(
+            expand_host_pattern("rack[1:2]-[a:b]").unwrap(),
+            vec!["rack1-a", "rack1-b", "rack2-a", "rack2-b"]
+        );
+        assert_eq!(
+            expand_host_pattern("node[0:10:5]").unwrap(),
+            vec!["node0", "node5", "node10"]
+        );
+        assert!(expand_host_pattern("web[1:a]").is_err());
+        assert!(expand_host_pattern("web[1:3:0]").is_err());
+
+        assert_eq!(split_port("web01:2222"), Some(("web01", "2222")));
+        assert_eq!(split_port("web[01:03]"), None);
+        assert_eq!(split_port("web[01:03]:22"), Some(("web[01:03]", "22")));
+    }
+
+    #[test]
+    fn rejecting_wrong_ini() {
+        assert!(matches!(
+            Inventory::from_ansible_ini("[webservers:hosts]\n", None),
+            Err(RegentError::FailureToParseContent(_))
+        ));
+        assert!(matches!(
+            Inventory::from_ansible_ini("web01 http_port=\"80\n", None),
+            Err(RegentError::FailureToParseContent(_))
+        ));
+        assert!(matches!(
+            Inventory::from_ansible_ini("web01 http_port\n", None),
+            Err(RegentError::FailureToParseContent(_))
+        ));
+    }
+}
@@ -0,0 +1,1460 @@
+pub mod ansible;
+pub mod source;
+
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use tokio::task::JoinSet;
+
+use crate::ExpectedState;
+use crate::error::RegentError;
+use crate::hosts::facts::FactProbe;
+use crate::hosts::handlers::ConnectionMethod;
+use crate::hosts::managed_host::ManagedHost;
+use crate::hosts::managed_host::ManagedHostBuilder;
+use crate::hosts::rollout::{BatchHook, RolloutStrategy};
+use crate::secrets::SecretProvider;
+use crate::secrets::SecretProvidersPool;
+use crate::state::compliance::HostStatus;
+use crate::state::compliance::ManagedHostStatus;
+
+#[allow(unused)]
+use tracing::{Level, debug, error, info, span, trace, warn};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+struct InventoryBuilder {
+    name: Option<String>,
+    hosts: Vec<ManagedHostBuilder>,
+    default_connection_method: Option<ConnectionMethod>,
+    global_vars: Option<HashMap<String, String>>,
+    groups: Option<Vec<HostGroup>>,
+    fact_probes: Option<Vec<FactProbe>>,
+}
+
+/// A named set of hosts sharing variables and a connection method.
+///
+/// Hosts of the `Children` groups are also hosts of this group. Variables of a child group take
+/// precedence over the ones of its parents, and host variables over all group variables.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+struct HostGroup {
+    name: String,
+    hosts: Option<Vec<String>>,    // HostIds
+    children: Option<Vec<String>>, // Group names
+    vars: Option<HashMap<String, String>>,
+    connection_method: Option<ConnectionMethod>,
+}
+
+impl InventoryBuilder {
+    pub fn from_raw_yaml(raw_yaml: &str) -> Result<Inventory, RegentError> {
+        match yaml_serde::from_str::<Self>(raw_yaml) {
+            Ok(inventory_builder) => {
+                debug!("Successfully parsed YAML inventory");
+                inventory_builder.build()
+            }
+            Err(details) => {
+                error!("Failed to parse YAML inventory: {:?}", details);
+                Err(RegentError::FailureToParseContent(format!("{:?}", details)))
+            }
+        }
+    }
+
+    pub fn from_raw_json(raw_json: &str) -> Result<Inventory, RegentError> {
+        match serde_json::from_str::<Self>(raw_json) {
+            Ok(inventory_builder) => {
+                debug!("Successfully parsed JSON inventory");
+                inventory_builder.build()
+            }
+            Err(details) => {
+                error!("Failed to parse JSON inventory: {:?}", details);
+                Err(RegentError::FailureToParseContent(format!("{:?}", details)))
+            }
+        }
+    }
+
+    pub fn build(self) -> Result<Inventory, RegentError> {
+        let mut final_hosts: HashMap<String, ManagedHostBuilder> = HashMap::new();
+        let inventory_name = match self.name {
+            Some(name_value) => name_value,
+            None => nanoid!(
+                12,
+                &[
+                    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f'
+                ]
+            ),
+        };
+
+        let span = span!(
+            Level::INFO,
+            "inventory_building",
+            inventory = inventory_name
+        );
+        let _enter = span.enter();
+
+        let host_ids: Vec<String> = self.hosts.iter().map(|host| host.id.clone()).collect();
+        let groups = resolve_groups(self.groups.unwrap_or_default(), &host_ids)?;
+
+        for mut host in self.hosts {
+            let host_groups = groups_of(&host.id, &groups);
+
+            // Vars merging and overloading : global < group < host
+            if self.global_vars.is_some() || host_groups.iter().any(|group| group.vars.is_some()) {
+                let mut final_host_vars: HashMap<String, String> =
+                    self.global_vars.clone().unwrap_or_default();
+
+                for group in &host_groups {
+                    if let Some(group_vars) = &group.vars {
+                        final_host_vars.extend(group_vars.clone());
+                    }
+                }
+
+                if let Some(host_vars) = &host.host_vars {
+                    final_host_vars.extend(host_vars.clone());
+                }
+
+                host.set_host_vars(Some(final_host_vars));
+            }
+
+            // FactProbes merging : host probes win over global ones with the same name
+            if let Some(fact_probes) = &self.fact_probes {
+                let host_fact_probes = host.fact_probes.take().unwrap_or_default();
+                for fact_probe in fact_probes.iter().chain(host_fact_probes.iter()) {
+                    host.add_fact_probe(fact_probe.clone());
+                }
+            }
+
+            // ConnectionMethod overloading : the most specific group wins over the default one
+            if let None = host.host_connection_method {
+                let group_connection_method = host_groups
+                    .iter()
+                    .rev()
+                    .find_map(|group| group.connection_method.as_ref());
+                match group_connection_method.or(self.default_connection_method.as_ref()) {
+                    Some(connection_method) => {
+                        host.set_connection_method(connection_method.clone());
+                    }
+                    None => {
+                        // In this branch, neither host, group nor global ConnectionMethod are set. We don't know how to connect to this host. Abord
+                        let error_msg = format!(
+                            "No HostConnectionMethod or GlobalConnectionMethod set. At least one of them must be set.",
+                        );
+                        error!(name = host.id, "{}", error_msg);
+                        return Err(RegentError::WrongInitialization(error_msg));
+                    }
+                }
+            }
+
+            // When saving ManageHostBuilder for final result, check unicity of hosts by their id
+            if let Some(old_managed_host_builder) = final_hosts.insert(host.id.to_string(), host) {
+                error!(name = old_managed_host_builder.id, "duplicate host id");
+                return Err(RegentError::WrongInitialization(format!(
+                    "duplicate host id : {}",
+                    old_managed_host_builder.id
+                )));
+            }
+        }
+
+        info!(target: "inventory","Inventory built with {} host(s) and {} group(s)", final_hosts.len(), groups.len());
+        let mut inventory = Inventory::from(inventory_name, final_hosts);
+        inventory.groups = groups
+            .into_values()
+            .map(|group| (group.name, group.members))
+            .collect();
+        Ok(inventory)
+    }
+}
+
+/// A group whose children were expanded
+struct ResolvedGroup {
+    name: String,
+    members: BTreeSet<String>, // HostIds, including the ones of children groups
+    depth: usize,              // 0 for top level groups, 1 + depth of the deepest parent otherwise
+    vars: Option<HashMap<String, String>>,
+    connection_method: Option<ConnectionMethod>,
+}
+
+/// Groups of a host, from the least to the most specific
+fn groups_of<'a>(
+    host_id: &str,
+    groups: &'a HashMap<String, ResolvedGroup>,
+) -> Vec<&'a ResolvedGroup> {
+    let mut host_groups: Vec<&ResolvedGroup> = groups
+        .values()
+        .filter(|group| group.members.contains(host_id))
+        .collect();
+    host_groups.sort_by(|a, b| (a.depth, &a.name).cmp(&(b.depth, &b.name)));
+    host_groups
+}
+
+fn resolve_groups(
+    groups: Vec<HostGroup>,
+    host_ids: &[String],
+) -> Result<HashMap<String, ResolvedGroup>, RegentError> {
+    let mut groups_by_name: HashMap<String, HostGroup> = HashMap::new();
+    for group in groups {
+        if group.name == ALL_HOSTS {
+            return Err(RegentError::WrongInitialization(format!(
+                "'{}' is a reserved group name",
+                ALL_HOSTS
+            )));
+        }
+        if let Some(old_group) = groups_by_name.insert(group.name.clone(), group) {
+            error!(name = old_group.name, "duplicate group name");
+            return Err(RegentError::WrongInitialization(format!(
+                "duplicate group name : {}",
+                old_group.name
+            )));
+        }
+    }
+
+    for group in groups_by_name.values() {
+        for host_id in group.hosts.iter().flatten() {
+            if !host_ids.contains(host_id) {
+                return Err(RegentError::WrongInitialization(format!(
+                    "group {} refers to unknown host {}",
+                    group.name, host_id
+                )));
+            }
+        }
+        for child in group.children.iter().flatten() {
+            if !groups_by_name.contains_key(child) {
+                return Err(RegentError::GroupNotFound(child.clone()));
+            }
+        }
+    }
+
+    let mut resolved_groups = HashMap::new();
+    for name in groups_by_name.keys() {
+        let members = group_members(name, &groups_by_name, &mut Vec::new())?;
+        let depth = group_depth(name, &groups_by_name);
+        let group = &groups_by_name[name];
+        resolved_groups.insert(
+            name.clone(),
+            ResolvedGroup {
+                name: name.clone(),
+                members,
+                depth,
+                vars: group.vars.clone(),
+                connection_method: group.connection_method.clone(),
+            },
+        );
+    }
+    Ok(resolved_groups)
+}
+
+/// Hosts of a group and of its children, recursively. `path` holds the groups being expanded, to
+/// detect cycles.
+fn group_members(
+    name: &str,
+    groups: &HashMap<String, HostGroup>,
+    path: &mut Vec<String>,
+) -> Result<BTreeSet<String>, RegentError> {
+    if path.iter().any(|parent| parent == name) {
+        return Err(RegentError::WrongInitialization(format!(
+            "cycle in groups : {} -> {}",
+            path.join(" -> "),
+            name
+        )));
+    }
+    path.push(name.to_string());
+
+    let group = &groups[name];
+    let mut members: BTreeSet<String> = group.hosts.iter().flatten().cloned().collect();
+    for child in group.children.iter().flatten() {
+        members.extend(group_members(child, groups, path)?);
+    }
+
+    path.pop();
+    Ok(members)
+}
+
+/// Only called once cycles were ruled out by [`group_members`]
+fn group_depth(name: &str, groups: &HashMap<String, HostGroup>) -> usize {
+    groups
+        .values()
+        .filter(|group| group.children.iter().flatten().any(|child| child == name))
+        .map(|parent| 1 + group_depth(&parent.name, groups))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Implicit group holding every host of an inventory
+const ALL_HOSTS: &str = "all";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct Inventory {
+    name: String,
+    hosts: HashMap<String, ManagedHostBuilder>, // HostId -> ManagedHostBuilder
+    #[serde(default)]
+    groups: HashMap<String, BTreeSet<String>>, // Group name -> HostIds, children groups included
+}
+
+impl Inventory {
+    pub fn from(name: String, hosts: HashMap<String, ManagedHostBuilder>) -> Self {
+        Self {
+            name,
+            hosts,
+            groups: HashMap::new(),
+        }
+    }
+
+    pub fn from_raw_yaml(raw_yaml: &str) -> Result<Inventory, RegentError> {
+        InventoryBuilder::from_raw_yaml(raw_yaml)
+    }
+
+    pub fn from_raw_json(raw_json: &str) -> Result<Inventory, RegentError> {
+        InventoryBuilder::from_raw_json(raw_json)
+    }
+
+    /// Add a probe of a site-specific fact to every host which doesn't have a probe with the
+    /// same name.
+    pub fn add_fact_probe(&mut self, fact_probe: FactProbe) {
+        for host in self.hosts.values_mut() {
+            let already_probed = host
+                .fact_probes
+                .iter()
+                .flatten()
+                .any(|host_fact_probe| host_fact_probe.name() == fact_probe.name());
+            if !already_probed {
+                host.add_fact_probe(fact_probe.clone());
+            }
+        }
+    }
+
+    /// Sub-inventory of the hosts matching a pattern.
+    ///
+    /// A pattern is a list of groups or host ids separated by `:` (or `,`). Hosts of the plain
+    /// terms are selected, then only the ones also in the terms prefixed with `&` are kept, and
+    /// the ones in the terms prefixed with `!` are removed. `all` (or `*`) matches every host.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use regent_sdk::hosts::inventory::Inventory;
+    ///
+    /// let inventory = Inventory::from_raw_yaml(r#"---
+    /// DefaultConnectionMethod: !Localhost
+    ///     UserKind: !CurrentUser
+    /// Hosts:
+    ///   - Id: web-01
+    ///     Endpoint: 10.0.0.1
+    ///   - Id: web-02
+    ///     Endpoint: 10.0.0.2
+    /// Groups:
+    ///   - Name: webservers
+    ///     Hosts: [web-01, web-02]
+    ///   - Name: prod
+    ///     Hosts: [web-01, web-02]
+    ///   - Name: canary
+    ///     Hosts: [web-02]
+    /// "#).unwrap();
+    ///
+    /// // Only web-01
+    /// let selection = inventory.select("webservers:&prod:!canary").unwrap();
+    /// ```
+    pub fn select(&self, pattern: &str) -> Result<Inventory, RegentError> {
+        let mut selected: Option<BTreeSet<String>> = None;
+        let mut intersections = Vec::new();
+        let mut exclusions = Vec::new();
+
+        for term in pattern
+            .split([':', ','])
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+        {
+            if let Some(term) = term.strip_prefix('&') {
+                intersections.push(self.hosts_of(term)?);
+            } else if let Some(term) = term.strip_prefix('!') {
+                exclusions.push(self.hosts_of(term)?);
+            } else {
+                selected
+                    .get_or_insert_with(BTreeSet::new)
+                    .extend(self.hosts_of(term)?);
+            }
+        }
+
+        // A pattern made only of intersections and exclusions applies to all hosts
+        let mut selected = match selected {
+            Some(selected) => selected,
+            None => self.hosts_of(ALL_HOSTS)?,
+        };
+        for intersection in intersections {
+            selected.retain(|host_id| intersection.contains(host_id));
+        }
+        for exclusion in exclusions {
+            selected.retain(|host_id| !exclusion.contains(host_id));
+        }
+
+        debug!(pattern, "{} host(s) selected", selected.len());
+        Ok(Inventory {
+            name: self.name.clone(),
+            hosts: self
+                .hosts
+                .iter()
+                .filter(|(host_id, _)| selected.contains(*host_id))
+                .map(|(host_id, host)| (host_id.clone(), host.clone()))
+                .collect(),
+            groups: self
+                .groups
+                .iter()
+                .map(|(name, members)| {
+                    (
+                        name.clone(),
+                        members.intersection(&selected).cloned().collect(),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    /// Hosts designated by a term of a pattern : a group, a host id or all hosts
+    fn hosts_of(&self, term: &str) -> Result<BTreeSet<String>, RegentError> {
+        if term == ALL_HOSTS || term == "*" {
+            Ok(self.hosts.keys().cloned().collect())
+        } else if let Some(members) = self.groups.get(term) {
+            Ok(members.clone())
+        } else if self.hosts.contains_key(term) {
+            Ok(BTreeSet::from([term.to_string()]))
+        } else if self.groups.is_empty() {
+            Err(RegentError::MissingGroupsList)
+        } else {
+            Err(RegentError::GroupNotFound(term.to_string()))
+        }
+    }
+
+    /// Build and connect every host of the inventory. Fails if any host can't be reached.
+    pub async fn init(
+        &mut self,
+        optional_secret_provider: Option<SecretProvidersPool>,
+    ) -> Result<LivingInventory, RegentError> {
+        self.init_with_policy(optional_secret_provider, FailurePolicy::strict())
+            .await
+    }
+
+    /// Build and connect every host of the inventory, going on with the reachable ones as long as
+    /// the share of unreachable hosts stays within the policy.
+    ///
+    /// Unreachable hosts are listed by [`LivingInventory::unreachable_hosts`]. The policy is then
+    /// applied to [`LivingInventory::assess_compliance`] and [`LivingInventory::reach_compliance`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use regent_sdk::hosts::inventory::{FailurePolicy, Inventory};
+    ///
+    /// # async fn example(mut inventory: Inventory) {
+    /// let living_inventory = inventory
+    ///     .init_with_policy(None, FailurePolicy::max_fail_percentage(20.0))
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// for failure in living_inventory.unreachable_hosts() {
+    ///     println!("{} : {}", failure.host_id, failure.error);
+    /// }
+    /// # }
+    /// ```
+    pub async fn init_with_policy(
+        &mut self,
+        optional_secret_provider: Option<SecretProvidersPool>,
+        failure_policy: FailurePolicy,
+    ) -> Result<LivingInventory, RegentError> {
+        let span = span!(Level::INFO, "inventory_init", inventory = self.name);
+        let _enter = span.enter();
+
+        let mut set = JoinSet::new();
+
+        for (host_id, managed_host_builder) in self.hosts.clone() {
+            let optional_secret_provider_clone = optional_secret_provider.clone();
+            set.spawn(async move {
+                // Try to build a ManagedHost out of a ManagedHostBuilder (implies fetching secrets when needed)
+                match managed_host_builder
+                    .build(optional_secret_provider_clone)
+                    .await
+                {
+                    Ok(mut managed_host) => {
+                        let host_span = span!(Level::DEBUG, "host_connection", host_id);
+                        let _host_enter = host_span.enter();
+
+                        match managed_host.connect().await {
+                            Ok(()) => {
+                                debug!(host_id, "Successfully connected to host");
+                                Ok(managed_host)
+                            }
+                            Err(connection_error) => {
+                                error!(
+                                    host_id,
+                                    "Failed to connect to host : {:?}", connection_error
+                                );
+                                Err((managed_host.id().to_string(), connection_error))
+                            }
+                        }
+                    }
+                    Err(detail) => {
+                        error!(host_id, "Failed to build host: {:?}", detail);
+                        Err((host_id, detail))
+                    }
+                }
+            });
+        }
+
+        let mut managed_hosts: HashMap<String, ManagedHost> = HashMap::new();
+        let results = set.join_all().await;
+        let mut failures = Vec::new();
+
+        for result in results {
+            match result {
+                Ok(managed_host) => {
+                    managed_hosts.insert(managed_host.id().to_string(), managed_host);
+                }
+                Err((host_id, error)) => {
+                    failures.push(HostFailure { host_id, error });
+                }
+            }
+        }
+
+        failure_policy.check("init", &failures, self.hosts.len())?;
+
+        info!(target: "inventory","Successfully connected to {} host(s)", managed_hosts.len());
+        let mut living_inventory = LivingInventory::from(self.name.clone(), managed_hosts);
+        living_inventory.failure_policy = failure_policy;
+        living_inventory.unreachable_hosts = failures;
+        Ok(living_inventory)
+    }
+}
+
+/// How many hosts may fail before an operation on a whole inventory fails.
+///
+/// The default policy is strict : a single failing host fails the operation.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct FailurePolicy {
+    max_fail_percentage: f32,
+}
+
+impl FailurePolicy {
+    /// No host is allowed to fail.
+    pub fn strict() -> Self {
+        Self {
+            max_fail_percentage: 0.0,
+        }
+    }
+
+    /// Up to `percentage` % of the hosts are allowed to fail (clamped between 0 and 100).
+    pub fn max_fail_percentage(percentage: f32) -> Self {
+        Self {
+            max_fail_percentage: percentage.clamp(0.0, 100.0),
+        }
+    }
+
+    /// Whether `failed` failing hosts out of `total` are within the policy.
+    pub fn tolerates(&self, failed: usize, total: usize) -> bool {
+        failed == 0 || (failed as f32) * 100.0 <= self.max_fail_percentage * (total as f32)
+    }
+
+    fn check(
+        &self,
+        operation: &str,
+        failures: &[HostFailure],
+        total: usize,
+    ) -> Result<(), RegentError> {
+        let failures_list = failures
+            .iter()
+            .map(|failure| format!("{}: {}", failure.host_id, failure.error))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        if self.tolerates(failures.len(), total) {
+            if !failures.is_empty() {
+                warn!(
+                    "{}/{} host(s) failed to {}, within the {}% allowed : {}",
+                    failures.len(),
+                    total,
+                    operation,
+                    self.max_fail_percentage,
+                    failures_list
+                );
+            }
+            Ok(())
+        } else {
+            Err(RegentError::ProblemWithHostConnection(format!(
+                "Following hosts encountered problems while trying to {}: {}",
+                operation, failures_list
+            )))
+        }
+    }
+}
+
+/// A host which failed an operation on its inventory.
+#[derive(Debug, Clone)]
+pub struct HostFailure {
+    pub host_id: String,
+    pub error: RegentError,
+}
+
+pub struct LivingInventory {
+    name: String,
+    hosts: HashMap<String, ManagedHost>,
+    failure_policy: FailurePolicy,
+    unreachable_hosts: Vec<HostFailure>, // Hosts left out at init
+    failed_hosts: Vec<HostFailure>,      // Hosts which failed the last assessment or enforcement
+}
+
+impl LivingInventory {
+    pub fn from(name: String, hosts: HashMap<String, ManagedHost>) -> Self {
+        Self {
+            name,
+            hosts,
+            failure_policy: FailurePolicy::strict(),
+            unreachable_hosts: Vec::new(),
+            failed_hosts: Vec::new(),
+        }
+    }
+
+    pub fn set_failure_policy(&mut self, failure_policy: FailurePolicy) {
+        self.failure_policy = failure_policy;
+    }
+
+    /// Hosts which could not be built or connected at init, and are thus left out.
+    pub fn unreachable_hosts(&self) -> &[HostFailure] {
+        &self.unreachable_hosts
+    }
+
+    /// Hosts which failed the last compliance assessment or enforcement, within the failure
+    /// policy.
+    pub fn failed_hosts(&self) -> &[HostFailure] {
+        &self.failed_hosts
+    }
+
+    // TODO : is it worth it to make this parallel through tokio tasks ?
+    pub fn add_var(&mut self, key: String, value: String) {
+        let span = span!(Level::DEBUG, "living_inventory_add_var");
+        let _enter = span.enter();
+
+        debug!(key, value, "Adding variable");
+
+        let _ = self.hosts.iter_mut().map(|(host_id, managed_host)| {
+            trace!(host_id, key, value, "Adding variable to host");
+            managed_host.add_var(key.clone(), value.clone())
+        });
+
+        info!(key, "Added variable to all hosts");
+    }
+
+    pub async fn collect_properties(&mut self) -> Result<(), RegentError> {
+        let span = span!(Level::INFO, "living_inventory_collect_properties");
+        let _enter = span.enter();
+
+        info!(
+            "Starting property collection for {} hosts",
+            self.hosts.len()
+        );
+
+        let mut set = JoinSet::new();
+
+        for (host_id, mut managed_host) in std::mem::take(&mut self.hosts) {
+            set.spawn(async move {
+                let host_span = span!(Level::DEBUG, "collect_properties_host", host_id);
+                let _host_enter = host_span.enter();
+
+                debug!("Collecting properties");
+                let result = managed_host.collect_properties().await;
+                (host_id, managed_host, result)
+            });
+        }
+
+        let mut failures: Vec<(String, RegentError)> = Vec::new();
+        for (host_id, managed_host, result) in set.join_all().await {
+            if let Err(details) = result {
+                failures.push((host_id.clone(), details));
+            }
+            self.hosts.insert(host_id, managed_host);
+        }
+
+        if failures.is_empty() {
+            info!("Successfully collected properties from all hosts");
+            Ok(())
+        } else {
+            error!("Failed to collect properties for {:?}", failures);
+            return Err(RegentError::AnyOtherError(format!(
+                "Failure to collect properties for {:?}",
+                failures
+            )));
+        }
+    }
+
+    pub async fn disconnect(&mut self) -> Result<(), RegentError> {
+        let span = span!(Level::INFO, "inventory_disconnect");
+        let _enter = span.enter();
+
+        info!("Disconnecting from {} hosts", self.hosts.len());
+
+        // Take ownership of hosts to avoid borrowing issues
+        let hosts = std::mem::take(&mut self.hosts);
+
+        let mut set = JoinSet::new();
+
+        for (host_id, mut managed_host) in hosts {
+            // for (host_id, mut managed_host) in hosts.drain() {
+            set.spawn(async move {
+                let host_span = span!(Level::DEBUG, "disconnect_host", host_id);
+                let _host_enter = host_span.enter();
+
+                debug!("Disconnecting from host {}", host_id);
+                match managed_host.disconnect().await {
+                    Ok(()) => Ok(managed_host),
+                    Err(error_details) => Err((managed_host, error_details)),
+                }
+            });
+        }
+
+        let results = set.join_all().await;
+
+        let mut failures = Vec::new();
+
+        for result in results {
+            match result {
+                Ok(managed_host) => {
+                    self.hosts
+                        .insert(managed_host.id().to_string(), managed_host);
+                }
+                Err((managed_host, error_details)) => {
+                    failures.push(format!("{}: {}", managed_host.id(), error_details));
+                    self.hosts
+                        .insert(managed_host.id().to_string(), managed_host);
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(RegentError::ProblemWithHostConnection(format!(
+                "Following hosts encountered problems while trying to disconnect: {}",
+                failures.join(", ")
+            )))
+        }
+    }
+
+    pub async fn assess_compliance(
+        &mut self,
+        expected_state: &ExpectedState,
+    ) -> Result<HashMap<String, ManagedHostStatus>, RegentError> {
+        let job_span = span!(Level::INFO, "job", inventory = self.name, goal = "assess");
+        let _enter = job_span.enter();
+
+        info!("Assessing compliance for {} hosts", self.hosts.len());
+
+        // Take ownership of hosts to avoid borrowing issues
+        let hosts = std::mem::take(&mut self.hosts);
+
+        let mut set = JoinSet::new();
+
+        for (host_id, mut managed_host) in hosts {
+            let expected_state_clone = expected_state.clone();
+            set.spawn(async move {
+                let host_span = span!(Level::DEBUG, "host", host_id);
+                let _host_enter = host_span.enter();
+
+                debug!(name = host_id, "Assessing compliance");
+                match managed_host.assess_compliance(&expected_state_clone).await {
+                    Ok(managed_host_status) => {
+                        debug!("Compliance assessment complete");
+                        Ok((managed_host, managed_host_status))
+                    }
+                    Err(details) => {
+                        error!("Failed to assess compliance : {:?}", details);
+                        Err((managed_host, details))
+                    }
+                }
+            });
+        }
+
+        let results = set.join_all().await;
+        let total = results.len();
+        let mut results_map = HashMap::new();
+        let mut failures = Vec::new();
+
+        for result in results {
+            match result {
+                Ok((managed_host, managed_host_status)) => {
+                    let host_id = managed_host.id().to_string();
+                    self.hosts.insert(host_id.clone(), managed_host);
+                    results_map.insert(host_id, managed_host_status);
+                }
+                Err((managed_host, error)) => {
+                    let host_id = managed_host.id().to_string();
+                    self.hosts.insert(host_id.clone(), managed_host);
+                    failures.push(HostFailure { host_id, error });
+                }
+            }
+        }
+
+        self.failed_hosts = failures;
+        self.failure_policy
+            .check("assess compliance", &self.failed_hosts, total)?;
+
+        info!(
+            "Completed compliance assessment for {} hosts",
+            results_map.len()
+        );
+        Ok(results_map)
+    }
+
+    pub async fn reach_compliance(
+        &mut self,
+        expected_state: &ExpectedState,
+    ) -> Result<HashMap<String, ManagedHostStatus>, RegentError> {
+        self.reach_compliance_rolling(expected_state, &RolloutStrategy::default(), &mut ())
+            .await
+    }
+
+    /// Reach compliance batch after batch, as described by the strategy.
+    ///
+    /// Hosts are taken in the order of their ids. Once a batch is done, the remaining ones are
+    /// aborted if the share of hosts of this batch which failed (with an error or without reaching
+    /// compliance) is beyond the failure policy of the strategy.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use regent_sdk::ExpectedState;
+    /// use regent_sdk::hosts::inventory::{FailurePolicy, LivingInventory};
+    /// use regent_sdk::hosts::rollout::{BatchSize, RolloutStrategy};
+    ///
+    /// # async fn example(living_inventory: &mut LivingInventory, expected_state: &ExpectedState) {
+    /// let strategy = RolloutStrategy::default()
+    ///     .with_serial(BatchSize::Count(2))
+    ///     .with_failure_policy(FailurePolicy::strict());
+    ///
+    /// let statuses = living_inventory
+    ///     .reach_compliance_rolling(expected_state, &strategy, &mut ())
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn reach_compliance_rolling<H: BatchHook>(
+        &mut self,
+        expected_state: &ExpectedState,
+        strategy: &RolloutStrategy,
+        hook: &mut H,
+    ) -> Result<HashMap<String, ManagedHostStatus>, RegentError> {
+        let job_span = span!(Level::INFO, "job", inventory = self.name, goal = "enforce");
+        let _enter = job_span.enter();
+
+        info!("Enforcing compliance for {} hosts", self.hosts.len());
+
+        let total = self.hosts.len();
+        let mut host_ids: Vec<String> = self.hosts.keys().cloned().collect();
+        host_ids.sort();
+        let batches = strategy.batches(host_ids);
+        let batch_failure_policy = strategy.failure_policy().unwrap_or(self.failure_policy);
+
+        let mut results_map = HashMap::new();
+        let mut failures = Vec::new();
+
+        for batch in &batches {
+            if batch.count > 1 {
+                info!(target: "run",
+                    "Starting batch {}/{} ({} host(s))",
+                    batch.index + 1,
+                    batch.count,
+                    batch.host_ids.len()
+                );
+            }
+            hook.before_batch(batch).await?;
+
+            let mut batch_results = HashMap::new();
+            let mut batch_failures = 0;
+            let mut pending_hosts = batch.host_ids.iter();
+            let mut set = JoinSet::new();
+            let mut task_hosts = HashMap::new();
+            let mut task_errors = Vec::new();
+
+            loop {
+                // Start hosts until the concurrency cap is reached. Once a task failed, the
+                // remaining hosts are left alone and the running ones are waited for.
+                while task_errors.is_empty()
+                    && strategy
+                        .max_concurrency()
+                        .is_none_or(|max_concurrency| set.len() < max_concurrency)
+                {
+                    match pending_hosts.next() {
+                        Some(host_id) => {
+                            // Taking ownership of hosts to avoid borrowing issues
+                            if let Some(managed_host) = self.hosts.remove(host_id) {
+                                let task = set.spawn(reach_host_compliance(
+                                    managed_host,
+                                    expected_state.clone(),
+                                ));
+                                task_hosts.insert(task.id(), host_id.clone());
+                            }
+                        }
+                        None => break,
+                    }
+                }
+
+                match set.join_next().await {
+                    None => break,
+                    Some(Ok(Ok((managed_host, managed_host_status)))) => {
+                        let host_id = managed_host.id().to_string();
+                        if let HostStatus::ReachComplianceFailed = managed_host_status.state {
+                            batch_failures += 1;
+                        }
+                        self.hosts.insert(host_id.clone(), managed_host);
+                        batch_results.insert(host_id, managed_host_status);
+                    }
+                    Some(Ok(Err((managed_host, error)))) => {
+                        let host_id = managed_host.id().to_string();
+                        batch_failures += 1;
+                        self.hosts.insert(host_id.clone(), managed_host);
+                        failures.push(HostFailure { host_id, error });
+                    }
+                    Some(Err(join_error)) => {
+                        let host_id = task_hosts
+                            .get(&join_error.id())
+                            .cloned()
+                            .unwrap_or_default();
+                        error!("Task of host {} failed : {}", host_id, join_error);
+                        task_errors.push(format!("{} ({})", host_id, join_error));
+                    }
+                }
+            }
+
+            if !task_errors.is_empty() {
+                self.failed_hosts = failures;
+                return Err(RegentError::InternalLogicError(format!(
+                    "Host task failed : {}",
+                    task_errors.join(", ")
+                )));
+            }
+
+            hook.after_batch(batch, &batch_results).await?;
+            results_map.extend(batch_results);
+
+            let is_last_batch = batch.index + 1 == batch.count;
+            if !is_last_batch
+                && !batch_failure_policy.tolerates(batch_failures, batch.host_ids.len())
+            {
+                let error_msg = format!(
+                    "Rollout aborted after batch {}/{} : {} host(s) out of {} failed",
+                    batch.index + 1,
+                    batch.count,
+                    batch_failures,
+                    batch.host_ids.len()
+                );
+                error!("{}", error_msg);
+                self.failed_hosts = failures;
+                return Err(RegentError::FailedToApplyExpectedState(error_msg));
+            }
+        }
+
+        self.failed_hosts = failures;
+        self.failure_policy
+            .check("reach compliance", &self.failed_hosts, total)?;
+
+        info!(target: "run","All hosts handled");
+        Ok(results_map)
+    }
+}
+
+async fn reach_host_compliance(
+    mut managed_host: ManagedHost,
+    expected_state: ExpectedState,
+) -> Result<(ManagedHost, ManagedHostStatus), (ManagedHost, RegentError)> {
+    let host_span = span!(parent: None, Level::INFO, "host", id = managed_host.id());
+    let _host_enter = host_span.enter();
+
+    info!(target: "run",
+        "Starting to enforce compliance (described by {} attribute(s))",
+        expected_state.attributes.len()
+    );
+    match managed_host.reach_compliance(&expected_state).await {
+        Ok(managed_host_status) => {
+            match managed_host_status.state {
+                HostStatus::AlreadyCompliant => {
+                    info!(target: "run","Already compliant");
+                }
+                HostStatus::NotCompliant => {
+                    warn!("Not compliant");
+                }
+                HostStatus::ReachComplianceSuccess => {
+                    info!(target: "run","Compliance reached")
+                }
+                HostStatus::ReachComplianceFailed => {
+                    warn!("Failed to reach compliance");
+                }
+            }
+            Ok((managed_host, managed_host_status))
+        }
+        Err(details) => {
+            warn!("Failed to reach compliance");
+            Err((managed_host, details))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosts::managed_host::InternalApiCallOutcome;
+    use crate::hosts::rollout::{Batch, BatchSize};
+
+    const GROUPED_INVENTORY: &str = r#"---
+DefaultConnectionMethod: !Localhost
+    UserKind: !CurrentUser
+GlobalVars:
+    env: dev
+    port: "80"
+Hosts:
+  - Id: web-01
+    Endpoint: 10.0.0.1
+  - Id: web-02
+    Endpoint: 10.0.0.2
+    HostVars:
+      port: "8080"
+  - Id: db-01
+    Endpoint: 10.0.0.3
+Groups:
+  - Name: prod
+    Children: [webservers, databases]
+    Vars:
+      env: prod
+      tier: front
+  - Name: webservers
+    Hosts: [web-01, web-02]
+    Vars:
+      port: "443"
+  - Name: databases
+    Hosts: [db-01]
+    Vars:
+      tier: back
+    ConnectionMethod: !Ssh2
+      AuthMethod: !UsernamePassword
+        SecRef: ./db/credentials.secret
+  - Name: canary
+    Hosts: [web-02]
+"#;
+
+    fn host_var(inventory: &Inventory, host_id: &str, key: &str) -> Option<String> {
+        inventory.hosts[host_id]
+            .host_vars
+            .as_ref()
+            .and_then(|vars| vars.get(key).cloned())
+    }
+
+    fn host_ids(inventory: &Inventory) -> BTreeSet<String> {
+        inventory.hosts.keys().cloned().collect()
+    }
+
+    #[test]
+    fn merging_vars_of_nested_groups() {
+        let inventory = Inventory::from_raw_yaml(GROUPED_INVENTORY).unwrap();
+
+        // Global < parent group < child group < host
+        assert_eq!(host_var(&inventory, "web-01", "env").unwrap(), "prod");
+        assert_eq!(host_var(&inventory, "web-01", "port").unwrap(), "443");
+        assert_eq!(host_var(&inventory, "web-02", "port").unwrap(), "8080");
+        assert_eq!(host_var(&inventory, "web-01", "tier").unwrap(), "front");
+        assert_eq!(host_var(&inventory, "db-01", "tier").unwrap(), "back");
+        assert_eq!(host_var(&inventory, "db-01", "port").unwrap(), "80");
+
+        assert_eq!(
+            inventory.groups["prod"],
+            BTreeSet::from(["web-01".into(), "web-02".into(), "db-01".into()])
+        );
+    }
+
+    #[test]
+    fn overloading_connection_method_with_groups() {
+        let inventory = Inventory::from_raw_yaml(GROUPED_INVENTORY).unwrap();
+
+        assert!(matches!(
+            inventory.hosts["db-01"].host_connection_method,
+            Some(ConnectionMethod::Ssh2(_))
+        ));
+        assert!(matches!(
+            inventory.hosts["web-01"].host_connection_method,
+            Some(ConnectionMethod::Localhost(_))
+        ));
+    }
+
+    #[test]
+    fn selecting_hosts_with_patterns() {
+        let inventory = Inventory::from_raw_yaml(GROUPED_INVENTORY).unwrap();
+        let select = |pattern: &str| host_ids(&inventory.select(pattern).unwrap());
+
+        assert_eq!(
+            select("webservers:&prod:!canary"),
+            BTreeSet::from(["web-01".into()])
+        );
+        assert_eq!(
+            select("databases, web-02"),
+            BTreeSet::from(["db-01".into(), "web-02".into()])
+        );
+        assert_eq!(select("all:!webservers"), BTreeSet::from(["db-01".into()]));
+        assert_eq!(
+            select("!canary"),
+            BTreeSet::from(["web-01".into(), "db-01".into()])
+        );
+        assert_eq!(select("*").len(), 3);
+
+        let selection = inventory.select("prod:!databases").unwrap();
+        assert!(selection.groups["databases"].is_empty());
+        assert_eq!(selection.name, inventory.name);
+
+        assert!(matches!(
+            inventory.select("webservers:&staging"),
+            Err(RegentError::GroupNotFound(name)) if name == "staging"
+        ));
+    }
+
+    #[test]
+    fn selecting_hosts_without_groups() {
+        let inventory = Inventory::from_raw_yaml(
+            r#"---
+DefaultConnectionMethod: !Localhost
+    UserKind: !CurrentUser
+Hosts:
+  - Id: web-01
+    Endpoint: 10.0.0.1
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(host_ids(&inventory.select("web-01").unwrap()).len(), 1);
+        assert!(matches!(
+            inventory.select("webservers"),
+            Err(RegentError::MissingGroupsList)
+        ));
+    }
+
+    #[test]
+    fn rejecting_wrong_groups() {
+        let with_groups = |groups: &str| {
+            Inventory::from_raw_yaml(&format!(
+                r#"---
+DefaultConnectionMethod: !Localhost
+    UserKind: !CurrentUser
+Hosts:
+  - Id: web-01
+    Endpoint: 10.0.0.1
+Groups:
+{}"#,
+                groups
+            ))
+        };
+
+        assert!(matches!(
+            with_groups("  - Name: prod\n    Children: [staging]\n"),
+            Err(RegentError::GroupNotFound(name)) if name == "staging"
+        ));
+        assert!(matches!(
+            with_groups("  - Name: prod\n    Hosts: [web-02]\n"),
+            Err(RegentError::WrongInitialization(_))
+        ));
+        assert!(matches!(
+            with_groups("  - Name: prod\n  - Name: prod\n"),
+            Err(RegentError::WrongInitialization(_))
+        ));
+        assert!(matches!(
+            with_groups(
+                "  - Name: a\n    Children: [b]\n  - Name: b\n    Children: [a]\n    Hosts: [web-01]\n"
+            ),
+            Err(RegentError::WrongInitialization(_))
+        ));
+        assert!(matches!(
+            with_groups("  - Name: all\n"),
+            Err(RegentError::WrongInitialization(_))
+        ));
+    }
+
+    const PARTLY_REACHABLE_INVENTORY: &str = r#"---
+DefaultConnectionMethod: !Localhost
+    UserKind: !CurrentUser
+Hosts:
+  - Id: local-01
+    Endpoint: localhost
+  - Id: local-02
+    Endpoint: localhost
+  - Id: local-03
+    Endpoint: localhost
+  - Id: remote-01
+    Endpoint: 10.0.0.1
+    HostConnectionMethod: !Ssh2
+      AuthMethod: !UsernamePassword
+        SecRef: ./remote/credentials.secret
+"#;
+
+    #[test]
+    fn tolerating_failures_within_policy() {
+        assert!(FailurePolicy::strict().tolerates(0, 4));
+        assert!(!FailurePolicy::strict().tolerates(1, 4));
+        assert!(FailurePolicy::max_fail_percentage(25.0).tolerates(1, 4));
+        assert!(!FailurePolicy::max_fail_percentage(25.0).tolerates(2, 4));
+        assert!(FailurePolicy::max_fail_percentage(250.0).tolerates(4, 4));
+        assert!(FailurePolicy::max_fail_percentage(50.0).tolerates(0, 0));
+    }
+
+    #[test]
+    fn initializing_partly_reachable_inventory() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut inventory = Inventory::from_raw_yaml(PARTLY_REACHABLE_INVENTORY).unwrap();
+
+        // No secret provider : the credentials of remote-01 can't be fetched
+        assert!(matches!(
+            runtime.block_on(inventory.init(None)),
+            Err(RegentError::ProblemWithHostConnection(_))
+        ));
+        assert!(
+            runtime
+                .block_on(
+                    inventory.init_with_policy(None, FailurePolicy::max_fail_percentage(20.0))
+                )
+                .is_err()
+        );
+
+        let living_inventory = runtime
+            .block_on(inventory.init_with_policy(None, FailurePolicy::max_fail_percentage(25.0)))
+            .unwrap();
+        assert_eq!(living_inventory.hosts.len(), 3);
+        assert_eq!(living_inventory.unreachable_hosts().len(), 1);
+        assert_eq!(living_inventory.unreachable_hosts()[0].host_id, "remote-01");
+        assert!(living_inventory.failed_hosts().is_empty());
+    }
+
+    /// Records the batches it sees
+    #[derive(Default)]
+    struct RecordingHook {
+        batches: Vec<Vec<String>>,
+    }
+
+    impl BatchHook for RecordingHook {
+        async fn before_batch(&mut self, batch: &Batch) -> Result<(), RegentError> {
+            self.batches.push(batch.host_ids.clone());
+            Ok(())
+        }
+    }
+
+    fn living_local_inventory(exit_codes: &[u8]) -> LivingInventory {
+        let hosts = exit_codes
+            .iter()
+            .enumerate()
+            .map(|(i, exit_code)| {
+                format!(
+                    "  - Id: local-{:02}\n    Endpoint: localhost\n    HostVars:\n      exit_code: \"{}\"\n",
+                    i + 1,
+                    exit_code
+                )
+            })
+            .collect::<String>();
+        let mut inventory = Inventory::from_raw_yaml(&format!(
+            "---\nDefaultConnectionMethod: !Localhost\n    UserKind: !CurrentUser\nHosts:\n{}",
+            hosts
+        ))
+        .unwrap();
+
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(inventory.init(None))
+            .unwrap()
+    }
+
+    #[test]
+    fn probing_custom_facts() {
+        let fact_file =
+            std::env::temp_dir().join(format!("regent-custom-facts-{}", nanoid::nanoid!()));
+        std::fs::write(&fact_file, "DATACENTER=par1\nRACK=\"b12\"\n").unwrap();
+
+        let mut inventory = Inventory::from_raw_yaml(
+            &r#"---
+DefaultConnectionMethod: !Localhost
+    UserKind: !CurrentUser
+FactProbes:
+  - Name: site
+    Source: !File FACT_FILE
+    Parser: KeyValue
+  - Name: release
+    Source: !Command echo global
+Hosts:
+  - Id: local-01
+    Endpoint: localhost
+    FactProbes:
+      - Name: release
+        Source: !Command "echo '{\"version\": \"1.2.3\"}'"
+        Parser: Json
+  - Id: local-02
+    Endpoint: localhost
+"#
+            .replace("FACT_FILE", &fact_file.display().to_string()),
+        )
+        .unwrap();
+        inventory.add_fact_probe(FactProbe::command("failing", "exit 1"));
+        inventory.add_fact_probe(FactProbe::command("release", "echo ignored"));
+
+        let living_inventory = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut living_inventory = inventory.init(None).await.unwrap();
+            living_inventory.collect_properties().await.unwrap();
+            living_inventory
+        });
+        std::fs::remove_file(&fact_file).unwrap();
+
+        let host_properties = |host_id: &str| {
+            living_inventory.hosts[host_id]
+                .get_host_properties()
+                .clone()
+                .unwrap()
+        };
+        let custom_facts = host_properties("local-01").custom_facts().clone();
+        assert_eq!(
+            custom_facts["site"],
+            serde_json::json!({"DATACENTER": "par1", "RACK": "b12"})
+        );
+        assert_eq!(
+            custom_facts["release"],
+            serde_json::json!({"version": "1.2.3"})
+        );
+        assert!(!custom_facts.contains_key("failing"));
+        assert_eq!(
+            host_properties("local-02").custom_facts()["release"],
+            serde_json::json!("global")
+        );
+
+        let rendered = tera::Tera::one_off(
+            "{{ custom_facts.site.DATACENTER }} {{ custom_facts.release.version }}",
+            &host_properties("local-01").context(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(rendered, "par1 1.2.3");
+    }
+
+    #[test]
+    fn looping_over_host_vars() {
+        let mut inventory = Inventory::from_raw_yaml(
+            r#"---
+DefaultConnectionMethod: !Localhost
+    UserKind: !CurrentUser
+GlobalVars:
+    greetings: '["hello", "hi"]'
+Hosts:
+  - Id: local-01
+    Endpoint: localhost
+  - Id: local-02
+    Endpoint: localhost
+    HostVars:
+      greetings: "[bonjour, salut, coucou]"
+"#,
+        )
+        .unwrap();
+        let expected_state = ExpectedState::from_raw_yaml(
+            r#"---
+Attributes:
+  - Name: greet
+    Privilege: !None
+    Loop: greetings
+    Detail: !Command
+      Cmd: "echo {{ item }}"
+"#,
+        )
+        .unwrap();
+
+        let statuses = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut living_inventory = inventory.init(None).await.unwrap();
+            living_inventory
+                .reach_compliance(&expected_state)
+                .await
+                .unwrap()
+        });
+        let outputs = |host_id: &str| {
+            statuses[host_id]
+                .actions()
+                .iter()
+                .map(|action| match action.action_result() {
+                    Some(InternalApiCallOutcome::Success(Some(output))) => {
+                        output.trim().to_string()
+                    }
+                    other => panic!("unexpected outcome {:?}", other),
+                })
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(outputs("local-01"), vec!["hello", "hi"]);
+        assert_eq!(outputs("local-02"), vec!["bonjour", "salut", "coucou"]);
+    }
+
+    const EXITING_EXPECTED_STATE: &str = r#"---
+Attributes:
+  - Name: exit with host code
+    Privilege: !None
+    Detail: !Command
+      Cmd: "exit {{ exit_code }}"
+"#;
+
+    #[test]
+    fn reaching_compliance_in_batches() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let expected_state = ExpectedState::from_raw_yaml(EXITING_EXPECTED_STATE).unwrap();
+        let mut living_inventory = living_local_inventory(&[0, 0, 0]);
+
+        let strategy = RolloutStrategy::default()
+            .with_serial(BatchSize::Count(2))
+            .with_max_concurrency(1);
+        let mut hook = RecordingHook::default();
+        let statuses = runtime
+            .block_on(living_inventory.reach_compliance_rolling(
+                &expected_state,
+                &strategy,
+                &mut hook,
+            ))
+            .unwrap();
+
+        assert_eq!(statuses.len(), 3);
+        assert_eq!(
+            hook.batches,
+            vec![
+                vec!["local-01".to_string(), "local-02".to_string()],
+                vec!["local-03".to_string()]
+            ]
+        );
+        assert_eq!(living_inventory.hosts.len(), 3);
+    }
+
+    #[test]
+    fn aborting_rollout_beyond_failure_policy() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let expected_state = ExpectedState::from_raw_yaml(EXITING_EXPECTED_STATE).unwrap();
+        let mut living_inventory = living_local_inventory(&[0, 1, 0, 0]);
+
+        let strategy = RolloutStrategy::default()
+            .with_serial(BatchSize::Count(2))
+            .with_failure_policy(FailurePolicy::max_fail_percentage(25.0));
+        let mut hook = RecordingHook::default();
+        assert!(matches!(
+            runtime.block_on(living_inventory.reach_compliance_rolling(
+                &expected_state,
+                &strategy,
+                &mut hook,
+            )),
+            Err(RegentError::FailedToApplyExpectedState(_))
+        ));
+        assert_eq!(hook.batches.len(), 1);
+        assert_eq!(living_inventory.hosts.len(), 4);
+
+        // Half of the first batch may fail
+        let strategy = strategy.with_failure_policy(FailurePolicy::max_fail_percentage(50.0));
+        let mut hook = RecordingHook::default();
+        runtime
+            .block_on(living_inventory.reach_compliance_rolling(
+                &expected_state,
+                &strategy,
+                &mut hook,
+            ))
+            .unwrap();
+        assert_eq!(hook.batches.len(), 2);
+    }
+}
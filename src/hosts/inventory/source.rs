@@ -0,0 +1,467 @@
+//! Dynamic inventories
+//!
+//! This module provides the [`InventorySource`] trait, for inventories regenerated before each
+//! run, and its implementations :
+//!
+//! - [`ExecutableSource`] : an executable printing an inventory in JSON (Ansible dynamic
+//!   inventories called with `--list` by default)
+//! - [`DirectorySource`] : a directory of YAML files, each of them describing a host
+//! - `HttpSource` : an HTTP endpoint returning an inventory in JSON (requires the `http-inventory`
+//!   feature)
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+use super::{Inventory, InventoryBuilder};
+use crate::error::RegentError;
+use crate::hosts::handlers::ConnectionMethod;
+use crate::hosts::managed_host::ManagedHostBuilder;
+
+#[allow(unused)]
+use tracing::{Level, debug, error, info, span, trace, warn};
+
+/// A place inventories are built from, each time they are loaded.
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::hosts::inventory::source::{ExecutableSource, InventorySource};
+///
+/// # async fn example() {
+/// let source = ExecutableSource::from("./inventory/ec2.py");
+///
+/// // Before each run, get the current hosts
+/// let mut inventory = source.load().await.unwrap();
+/// let mut living_inventory = inventory.init(None).await.unwrap();
+/// # }
+/// ```
+pub trait InventorySource {
+    /// Build an inventory out of the current content of the source.
+    fn load(&self) -> impl Future<Output = Result<Inventory, RegentError>> + Send;
+}
+
+/// Layout of an inventory given in JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JsonFormat {
+    /// An inventory as read by [`Inventory::from_raw_json`].
+    Regent,
+    /// The output of an Ansible dynamic inventory called with `--list`, as read by
+    /// [`Inventory::from_ansible_json`].
+    AnsibleList,
+}
+
+impl JsonFormat {
+    fn parse(
+        &self,
+        raw_json: &str,
+        default_connection_method: &Option<ConnectionMethod>,
+    ) -> Result<Inventory, RegentError> {
+        match self {
+            JsonFormat::Regent => Inventory::from_raw_json(raw_json),
+            JsonFormat::AnsibleList => {
+                Inventory::from_ansible_json(raw_json, default_connection_method.clone())
+                    .map(|import| import.inventory)
+            }
+        }
+    }
+}
+
+/// An executable printing an inventory in JSON on its standard output.
+///
+/// By default, it is called with `--list` and prints an Ansible inventory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct ExecutableSource {
+    program: PathBuf,
+    args: Vec<String>,
+    format: JsonFormat,
+    default_connection_method: Option<ConnectionMethod>,
+}
+
+impl ExecutableSource {
+    pub fn from(program: &str) -> Self {
+        Self {
+            program: PathBuf::from(program),
+            args: vec!["--list".to_string()],
+            format: JsonFormat::AnsibleList,
+            default_connection_method: None,
+        }
+    }
+
+    /// Replace the arguments given to the executable (`--list` by default).
+    pub fn with_args(mut self, args: &[&str]) -> Self {
+        self.args = args.iter().map(|arg| arg.to_string()).collect();
+        self
+    }
+
+    pub fn with_format(mut self, format: JsonFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Connection method of the hosts of an Ansible inventory whose variables don't tell how to
+    /// reach them.
+    pub fn with_default_connection_method(mut self, connection_method: ConnectionMethod) -> Self {
+        self.default_connection_method = Some(connection_method);
+        self
+    }
+}
+
+impl InventorySource for ExecutableSource {
+    async fn load(&self) -> Result<Inventory, RegentError> {
+        debug!(program = %self.program.display(), "Running inventory executable");
+
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .output()
+            .await
+            .map_err(|details| {
+                RegentError::FailureToRunCommand(format!(
+                    "{} : {}",
+                    self.program.display(),
+                    details
+                ))
+            })?;
+
+        if !output.status.success() {
+            return Err(RegentError::FailureToRunCommand(format!(
+                "{} exited with {} : {}",
+                self.program.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        self.format.parse(
+            &String::from_utf8_lossy(&output.stdout),
+            &self.default_connection_method,
+        )
+    }
+}
+
+/// A directory of YAML files, each of them describing a host as a [`ManagedHostBuilder`].
+///
+/// Only files ending with `.yaml` or `.yml` are read.
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::hosts::handlers::{ConnectionMethod, TargetUser};
+/// use regent_sdk::hosts::inventory::source::DirectorySource;
+///
+/// // hosts/web-01.yaml :
+/// //
+/// // Id: web-01
+/// // Endpoint: 10.0.0.1
+/// let source = DirectorySource::from("./hosts")
+///     .with_default_connection_method(ConnectionMethod::Localhost(TargetUser::current_user()));
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct DirectorySource {
+    directory: PathBuf,
+    name: Option<String>,
+    default_connection_method: Option<ConnectionMethod>,
+    global_vars: Option<HashMap<String, String>>,
+}
+
+impl DirectorySource {
+    pub fn from(directory: &str) -> Self {
+        Self {
+            directory: PathBuf::from(directory),
+            name: None,
+            default_connection_method: None,
+            global_vars: None,
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn with_default_connection_method(mut self, connection_method: ConnectionMethod) -> Self {
+        self.default_connection_method = Some(connection_method);
+        self
+    }
+
+    pub fn with_global_vars(mut self, global_vars: HashMap<String, String>) -> Self {
+        self.global_vars = Some(global_vars);
+        self
+    }
+}
+
+impl InventorySource for DirectorySource {
+    async fn load(&self) -> Result<Inventory, RegentError> {
+        let read_error = |details: std::io::Error| {
+            RegentError::FailureToParseContent(format!(
+                "{} : {}",
+                self.directory.display(),
+                details
+            ))
+        };
+
+        let mut paths = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.directory)
+            .await
+            .map_err(read_error)?;
+        while let Some(entry) = entries.next_entry().await.map_err(read_error)? {
+            let path = entry.path();
+            let is_yaml = path
+                .extension()
+                .is_some_and(|extension| extension == "yaml" || extension == "yml");
+            if is_yaml
+                && tokio::fs::metadata(&path)
+                    .await
+                    .is_ok_and(|metadata| metadata.is_file())
+            {
+                paths.push(path);
+            }
+        }
+        // Same order whatever the file system
+        paths.sort();
+
+        let mut hosts = Vec::new();
+        for path in paths {
+            trace!(path = %path.display(), "Reading host file");
+            let raw_yaml = tokio::fs::read_to_string(&path).await.map_err(read_error)?;
+            let host = ManagedHostBuilder::from_raw_yaml(&raw_yaml).map_err(|details| {
+                RegentError::FailureToParseContent(format!("{} : {}", path.display(), details))
+            })?;
+            hosts.push(host);
+        }
+
+        InventoryBuilder {
+            name: self.name.clone(),
+            hosts,
+            default_connection_method: self.default_connection_method.clone(),
+            global_vars: self.global_vars.clone(),
+            groups: None,
+            fact_probes: None,
+        }
+        .build()
+    }
+}
+
+/// An HTTP endpoint returning an inventory in JSON to a GET request.
+///
+/// By default, the inventory is expected as read by [`Inventory::from_raw_json`].
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::hosts::inventory::source::{HttpSource, JsonFormat};
+///
+/// let source = HttpSource::from("https://cmdb.example.com/api/inventory")
+///     .with_header("Accept", "application/json")
+///     .with_format(JsonFormat::AnsibleList);
+/// ```
+#[cfg(feature = "http-inventory")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct HttpSource {
+    url: String,
+    headers: Vec<(String, String)>,
+    format: JsonFormat,
+    default_connection_method: Option<ConnectionMethod>,
+}
+
+#[cfg(feature = "http-inventory")]
+impl HttpSource {
+    pub fn from(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            headers: Vec::new(),
+            format: JsonFormat::Regent,
+            default_connection_method: None,
+        }
+    }
+
+    /// Add a header to the request (`Authorization`...).
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_format(mut self, format: JsonFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Connection method of the hosts of an Ansible inventory whose variables don't tell how to
+    /// reach them.
+    pub fn with_default_connection_method(mut self, connection_method: ConnectionMethod) -> Self {
+        self.default_connection_method = Some(connection_method);
+        self
+    }
+}
+
+#[cfg(feature = "http-inventory")]
+impl InventorySource for HttpSource {
+    async fn load(&self) -> Result<Inventory, RegentError> {
+        debug!(url = self.url, "Fetching inventory");
+
+        let request_error = |details: reqwest::Error| {
+            RegentError::FailedInitialization(format!("GET {} : {}", self.url, details))
+        };
+
+        let mut request = reqwest::Client::new().get(&self.url);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let raw_json = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(request_error)?
+            .text()
+            .await
+            .map_err(request_error)?;
+
+        self.format
+            .parse(&raw_json, &self.default_connection_method)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosts::handlers::TargetUser;
+
+    fn temporary_directory() -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("regent-inventory-{}", nanoid::nanoid!()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn loading_inventory_from_executable() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let directory = temporary_directory();
+        let script = directory.join("inventory.sh");
+        std::fs::write(
+            &script,
+            r#"#!/bin/sh
+[ "$1" = "--list" ] || exit 1
+echo '{"webservers": {"hosts": ["web01"], "vars": {"ansible_user": "deploy"}}, "_meta": {"hostvars": {}}}'
+"#,
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let source = ExecutableSource::from(&script.display().to_string());
+        let inventory = runtime.block_on(source.load()).unwrap();
+        assert!(inventory.hosts.contains_key("web01"));
+        assert!(inventory.groups.contains_key("webservers"));
+
+        // The script fails without --list
+        let source = source.with_args(&["--host", "web01"]);
+        assert!(matches!(
+            runtime.block_on(source.load()),
+            Err(RegentError::FailureToRunCommand(_))
+        ));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn loading_inventory_from_directory() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let directory = temporary_directory();
+        std::fs::write(
+            directory.join("web-01.yaml"),
+            "Id: web-01\nEndpoint: 10.0.0.1\nHostVars:\n  port: \"8080\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("web-02.yml"),
+            "Id: web-02\nEndpoint: 10.0.0.2\n",
+        )
+        .unwrap();
+        std::fs::write(directory.join("README.md"), "Not a host").unwrap();
+
+        let source = DirectorySource::from(&directory.display().to_string())
+            .with_name("web")
+            .with_global_vars(HashMap::from([("port".to_string(), "80".to_string())]));
+
+        // No connection method for these hosts
+        assert!(matches!(
+            runtime.block_on(source.load()),
+            Err(RegentError::WrongInitialization(_))
+        ));
+
+        let source = source.with_default_connection_method(ConnectionMethod::Localhost(
+            TargetUser::current_user(),
+        ));
+        let inventory = runtime.block_on(source.load()).unwrap();
+        assert_eq!(inventory.name, "web");
+        assert_eq!(inventory.hosts.len(), 2);
+        assert_eq!(
+            inventory.hosts["web-01"].host_vars.as_ref().unwrap()["port"],
+            "8080"
+        );
+        assert_eq!(
+            inventory.hosts["web-02"].host_vars.as_ref().unwrap()["port"],
+            "80"
+        );
+
+        // A wrong file fails the whole inventory
+        std::fs::write(
+            directory.join("web-03.yaml"),
+            "Id: web-03\nUnknown: field\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            runtime.block_on(source.load()),
+            Err(RegentError::FailureToParseContent(_))
+        ));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[cfg(feature = "http-inventory")]
+    #[test]
+    fn loading_inventory_from_http_endpoint() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let address = runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let app = axum::Router::new().route(
+                "/inventory",
+                axum::routing::get(|headers: axum::http::HeaderMap| async move {
+                    match headers.get("Authorization") {
+                        Some(token) if token == "Bearer token" => (
+                            axum::http::StatusCode::OK,
+                            r#"{"DefaultConnectionMethod": {"Localhost": {"UserKind": "CurrentUser"}}, "Hosts": [{"Id": "web-01", "Endpoint": "localhost"}]}"#,
+                        ),
+                        _ => (axum::http::StatusCode::UNAUTHORIZED, ""),
+                    }
+                }),
+            );
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            address
+        });
+
+        let url = format!("http://{}/inventory", address);
+        assert!(matches!(
+            runtime.block_on(HttpSource::from(&url).load()),
+            Err(RegentError::FailedInitialization(_))
+        ));
+
+        let source = HttpSource::from(&url).with_header("Authorization", "Bearer token");
+        let inventory = runtime.block_on(source.load()).unwrap();
+        assert!(inventory.hosts.contains_key("web-01"));
+    }
+}
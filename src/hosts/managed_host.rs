@@ -4,7 +4,7 @@
 //! managing connections to target hosts and executing compliance operations.
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::time::Duration;
 use tracing::Level;
 use tracing::span;
@@ -12,26 +12,30 @@ use tracing::span;
 use tracing::{debug, error, info, trace, warn};
 
 use crate::LocalHostHandler;
-use crate::Ssh2AuthMethod;
 use crate::Ssh2HostHandler;
 use crate::WhichUser;
 use crate::error::RegentError;
+use crate::hosts::facts::FactProbe;
 use crate::hosts::handlers::ConnectionMethod;
 use crate::hosts::handlers::Handler;
 use crate::hosts::handlers::HostHandler;
 use crate::hosts::handlers::TargetUserKind;
-use crate::hosts::handlers::ssh2::Ssh2AuthReference;
+use crate::hosts::handlers::ssh2::{HostKeyPolicy, Ssh2Settings};
 use crate::hosts::privilege::Credentials;
-use crate::hosts::privilege::LoginKey;
 use crate::hosts::privilege::Privilege;
 use crate::hosts::properties::HostProperties;
+use crate::secrets::Secret;
 use crate::secrets::SecretProvidersPool;
+use crate::secrets::SecretReference;
 use crate::state::ExpectedState;
+use crate::state::attribute::Attribute;
+use crate::state::attribute::RegisteredOutcome;
 use crate::state::attribute::Remediation;
 use crate::state::compliance::Action;
 use crate::state::compliance::AttributeComplianceAssessment;
 use crate::state::compliance::HostStatus;
 use crate::state::compliance::ManagedHostStatus;
+use crate::state::graph::AttributeGraph;
 
 /// Represents the connection state of a managed host.
 ///
@@ -116,6 +120,14 @@ pub struct ManagedHostBuilder {
     /// These variables are available during template rendering and can be used
     /// to customize attribute behavior per host.
     pub host_vars: Option<HashMap<String, String>>,
+    /// Optional reference to the password answering the prompt of privilege escalation
+    /// commands (sudo, doas, su).
+    pub become_password: Option<SecretReference>,
+    /// Optional probes of site-specific facts, run when collecting host properties.
+    pub fact_probes: Option<Vec<FactProbe>>,
+    /// Optional fingerprint the SSH host key must have, overriding the host key policy of the
+    /// connection method (see [`HostKeyPolicy::Pinned`]).
+    pub host_key_fingerprint: Option<String>,
 }
 
 impl ManagedHostBuilder {
@@ -150,6 +162,9 @@ impl ManagedHostBuilder {
             host_connection_method: connection_method,
             host_properties: None,
             host_vars: None,
+            become_password: None,
+            fact_probes: None,
+            host_key_fingerprint: None,
         }
     }
 
@@ -157,6 +172,10 @@ impl ManagedHostBuilder {
         &self.id
     }
 
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
     /// Set the connection method for this host.
     ///
     /// # Arguments
@@ -186,7 +205,7 @@ impl ManagedHostBuilder {
     ///
     /// ```no_run
     /// use regent_sdk::hosts::managed_host::ManagedHostBuilder;
-    /// use std::collections::HashMap;
+    /// use std::collections::{BTreeSet, HashMap, HashSet};
     ///
     /// let mut builder = ManagedHostBuilder::new("host-01", "localhost", None);
     ///
@@ -200,6 +219,66 @@ impl ManagedHostBuilder {
         self.host_vars = host_vars;
     }
 
+    /// Set the reference to the password answering the prompt of privilege escalation commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `become_password` - Reference to the secret holding the password
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use regent_sdk::hosts::managed_host::ManagedHostBuilder;
+    /// use regent_sdk::secrets::SecretReference;
+    ///
+    /// let mut builder = ManagedHostBuilder::new("host-01", "localhost", None);
+    /// builder.set_become_password(Some(SecretReference::from("sudo_password", None)));
+    /// ```
+    pub fn set_become_password(&mut self, become_password: Option<SecretReference>) {
+        self.become_password = become_password;
+    }
+
+    /// Add a probe of a site-specific fact, replacing any probe with the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `fact_probe` - The probe to run when collecting host properties
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use regent_sdk::hosts::facts::FactProbe;
+    /// use regent_sdk::hosts::managed_host::ManagedHostBuilder;
+    ///
+    /// let mut builder = ManagedHostBuilder::new("host-01", "localhost", None);
+    /// builder.add_fact_probe(FactProbe::file("datacenter", "/etc/datacenter"));
+    /// ```
+    pub fn add_fact_probe(&mut self, fact_probe: FactProbe) {
+        let fact_probes = self.fact_probes.get_or_insert_default();
+        fact_probes.retain(|existing_probe| existing_probe.name() != fact_probe.name());
+        fact_probes.push(fact_probe);
+    }
+
+    /// Pin the fingerprint of the SSH host key : any other key presented by the host is
+    /// rejected, whatever the host key policy of the connection method. Only applies to SSH
+    /// connections.
+    ///
+    /// # Arguments
+    ///
+    /// * `fingerprint` - SHA256 fingerprint of the host key, as printed by `ssh-keygen -lf`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use regent_sdk::hosts::managed_host::ManagedHostBuilder;
+    ///
+    /// let mut builder = ManagedHostBuilder::new("host-01", "192.168.1.100:22", None);
+    /// builder.set_host_key_fingerprint("SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s");
+    /// ```
+    pub fn set_host_key_fingerprint(&mut self, fingerprint: &str) {
+        self.host_key_fingerprint = Some(fingerprint.to_string());
+    }
+
     /// Parse a managed host builder from raw YAML content.
     ///
     /// # Arguments
@@ -270,10 +349,32 @@ impl ManagedHostBuilder {
             )));
         }
 
+        let become_password = match &self.become_password {
+            Some(secret_reference) => match &optional_secret_provider {
+                Some(secret_provider) => {
+                    Some(secret_provider.get_secret_raw(secret_reference).await?)
+                }
+                None => {
+                    return Err(RegentError::WrongInitialization(
+                        "secret required for the become password but secret_provider unset"
+                            .to_string(),
+                    ));
+                }
+            },
+            None => None,
+        };
+
+        let fact_probes = self.fact_probes.unwrap_or_default();
+
         // Retrieve connection secrets when needed
-        match self.host_connection_method {
+        let managed_host = match self.host_connection_method {
             Some(connection) => {
                 match connection {
+                    ConnectionMethod::Localhost(_) if self.host_key_fingerprint.is_some() => {
+                        Err(RegentError::WrongInitialization(
+                            "host key fingerprint set for a host not reached over SSH".to_string(),
+                        ))
+                    }
                     ConnectionMethod::Localhost(target_user) => {
                         match target_user.user_kind {
                             TargetUserKind::CurrentUser => {
@@ -281,8 +382,9 @@ impl ManagedHostBuilder {
                                 Ok(ManagedHost::new(
                                     self.id,
                                     &self.endpoint,
-                                    Handler::localhost(LocalHostHandler::from(
+                                    Handler::localhost(localhost_handler(
                                         WhichUser::CurrentUser,
+                                        become_password,
                                     )),
                                     self.host_vars,
                                     self.host_properties,
@@ -299,8 +401,9 @@ impl ManagedHostBuilder {
                                             Ok(secret) => Ok(ManagedHost::new(
                                                 self.id,
                                                 &self.endpoint,
-                                                Handler::localhost(LocalHostHandler::from(
+                                                Handler::localhost(localhost_handler(
                                                     WhichUser::UsernamePassword(secret.inner()),
+                                                    become_password,
                                                 )),
                                                 self.host_vars,
                                                 self.host_properties,
@@ -317,70 +420,52 @@ impl ManagedHostBuilder {
                         }
                     }
                     ConnectionMethod::Ssh2(ssh2_auth_reference) => {
-                        match ssh2_auth_reference.auth_method {
-                            Ssh2AuthReference::UsernamePassword(secret_reference) => {
-                                match &optional_secret_provider {
-                                    Some(secret_provider) => {
-                                        match secret_provider
-                                            .get_secret_typed::<Credentials>(&secret_reference)
-                                            .await
-                                        {
-                                            Ok(secret) => Ok(ManagedHost::new(
-                                                self.id,
-                                                &self.endpoint,
-                                                Handler::ss2(Ssh2HostHandler::NotConnected(
-                                                    Ssh2AuthMethod::UsernamePassword(
-                                                        secret.inner(),
-                                                    ),
-                                                )),
-                                                self.host_vars,
-                                                self.host_properties,
-                                                optional_secret_provider,
-                                            )),
-                                            Err(details) => Err(details),
-                                        }
-                                    }
-                                    None => Err(RegentError::WrongInitialization(format!(
-                                        "secret required to connect to host but secret_provider unset"
-                                    ))),
-                                }
-                            }
-                            Ssh2AuthReference::Key(login_key_ref) => {
-                                match &optional_secret_provider {
-                                    Some(secret_provider) => {
-                                        match secret_provider
-                                            .get_secret_raw(login_key_ref.key_ref())
-                                            .await
-                                        {
-                                            Ok(secret) => Ok(ManagedHost::new(
-                                                self.id,
-                                                &self.endpoint,
-                                                Handler::ss2(Ssh2HostHandler::NotConnected(
-                                                    Ssh2AuthMethod::Key(LoginKey::from(
-                                                        login_key_ref.username().to_string(),
-                                                        secret.inner(),
-                                                    )),
-                                                )),
-                                                self.host_vars,
-                                                self.host_properties,
-                                                optional_secret_provider,
-                                            )),
-                                            Err(details) => Err(details),
-                                        }
-                                    }
-                                    None => Err(RegentError::WrongInitialization(format!(
-                                        "secret required to connect to host but secret_provider unset"
-                                    ))),
-                                }
-                            }
+                        let mut settings = Ssh2Settings::from(
+                            ssh2_auth_reference
+                                .auth_method
+                                .resolve(&optional_secret_provider)
+                                .await?,
+                            match self.host_key_fingerprint {
+                                Some(fingerprint) => HostKeyPolicy::Pinned { fingerprint },
+                                None => ssh2_auth_reference.host_key_policy,
+                            },
+                        );
+                        for jump_host in ssh2_auth_reference.jump_hosts.unwrap_or_default() {
+                            settings = settings.with_jump_host(
+                                jump_host.resolve(&optional_secret_provider).await?,
+                            );
+                        }
+                        if let Some(become_password) = become_password {
+                            settings = settings.with_become_password(become_password);
                         }
+
+                        Ok(ManagedHost::new(
+                            self.id,
+                            &self.endpoint,
+                            Handler::ss2(Ssh2HostHandler::NotConnected(settings)),
+                            self.host_vars,
+                            self.host_properties,
+                            optional_secret_provider,
+                        ))
                     }
                 }
             }
             None => Err(RegentError::WrongInitialization(format!(
                 "connection_method unset"
             ))),
-        }
+        };
+
+        managed_host.map(|mut managed_host| {
+            managed_host.set_fact_probes(fact_probes);
+            managed_host
+        })
+    }
+}
+
+fn localhost_handler(user: WhichUser, become_password: Option<Secret<String>>) -> LocalHostHandler {
+    match become_password {
+        Some(become_password) => LocalHostHandler::from(user).with_become_password(become_password),
+        None => LocalHostHandler::from(user),
     }
 }
 
@@ -427,6 +512,8 @@ pub struct ManagedHost {
     pub handler: Handler,
     /// Tera context for template rendering with host variables.
     context: tera::Context,
+    /// Outcomes registered by attributes during the current run, layered over host variables.
+    registered_vars: tera::Context,
     /// Cached host properties (OS, architecture, etc.).
     host_properties: Option<HostProperties>,
     /// Secret providers pool for retrieving secrets.
@@ -437,6 +524,8 @@ pub struct ManagedHost {
     /// This tracks whether the host is connected, disconnected, or in an unknown state,
     /// enabling proper idempotency for connection/disconnection operations.
     connection_state: ConnectionState,
+    /// Probes of site-specific facts, run when collecting host properties.
+    fact_probes: Vec<FactProbe>,
 }
 
 impl Clone for ManagedHost {
@@ -446,9 +535,11 @@ impl Clone for ManagedHost {
             endpoint: self.endpoint.clone(),
             handler: self.handler.clone(),
             context: self.context.clone(),
+            registered_vars: self.registered_vars.clone(),
             host_properties: self.host_properties.clone(),
             secret_providers: self.secret_providers.clone(),
             connection_state: self.connection_state.clone(),
+            fact_probes: self.fact_probes.clone(),
         }
     }
 }
@@ -492,9 +583,11 @@ impl ManagedHost {
             endpoint: endpoint.to_string(),
             handler,
             context,
+            registered_vars: tera::Context::new(),
             host_properties,
             secret_providers: secret_providers.clone(),
             connection_state: ConnectionState::Disconnected,
+            fact_probes: Vec::new(),
         }
     }
 
@@ -540,9 +633,11 @@ impl ManagedHost {
             endpoint: endpoint.to_string(),
             handler,
             context: tera::Context::from_serialize(&final_vars).unwrap(),
+            registered_vars: tera::Context::new(),
             host_properties,
             secret_providers: secret_providers.clone(),
             connection_state: ConnectionState::Disconnected,
+            fact_probes: Vec::new(),
         }
     }
 
@@ -584,6 +679,15 @@ impl ManagedHost {
         self.host_properties = host_properties;
     }
 
+    /// Set the probes of site-specific facts, run by [`collect_properties()`](Self::collect_properties).
+    ///
+    /// # Arguments
+    ///
+    /// * `fact_probes` - The probes to run
+    pub fn set_fact_probes(&mut self, fact_probes: Vec<FactProbe>) {
+        self.fact_probes = fact_probes;
+    }
+
     /// Enable secret caching for this managed host.
     ///
     /// This ensures that secrets retrieved during assessment and enforcement
@@ -605,7 +709,7 @@ impl ManagedHost {
     /// Collect host properties dynamically from the host.
     ///
     /// This method connects to the host and collects information about its
-    /// operating system, architecture, and other properties.
+    /// operating system, architecture, and other properties, then runs its fact probes.
     ///
     /// # Returns
     ///
@@ -613,7 +717,10 @@ impl ManagedHost {
     pub async fn collect_properties(&mut self) -> Result<(), RegentError> {
         if matches!(self.host_properties, None) {
             match HostProperties::collect_dynamically(&mut self.handler).await {
-                Ok(host_properties) => {
+                Ok(mut host_properties) => {
+                    host_properties
+                        .collect_custom_facts(&mut self.handler, &self.fact_probes)
+                        .await;
                     self.host_properties = Some(host_properties);
                 }
                 Err(details) => {
@@ -760,67 +867,179 @@ impl ManagedHost {
         &mut self,
         expected_state: &ExpectedState,
     ) -> Result<ManagedHostStatus, RegentError> {
+        let actions = self.collect_actions(expected_state).await?;
+        if only_skipped(&actions) {
+            Ok(ManagedHostStatus::already_compliant_with_actions(actions))
+        } else {
+            Ok(ManagedHostStatus::not_compliant_with_actions(actions))
+        }
+    }
+
+    /// Assess compliance of the host with the expected state, in check mode.
+    ///
+    /// Like [`ManagedHost::assess_compliance`], nothing is changed on the host. In addition,
+    /// each remediation which would modify a file comes with a unified diff between the current
+    /// content of the file and the content it would have (see [`crate::state::diff`]).
+    /// Secrets are never resolved to build these diffs.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected_state` - The expected state to check against
+    ///
+    /// # Returns
+    ///
+    /// A [`ManagedHostStatus`] whose actions carry the diffs (see [`Action::diff`]).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let status = host.check_compliance(&expected_state).await.unwrap();
+    ///
+    /// for action in status.actions() {
+    ///     if let Some(diff) = action.diff() {
+    ///         println!("{:?}\n{}", action.remediation(), diff);
+    ///     }
+    /// }
+    /// ```
+    pub async fn check_compliance(
+        &mut self,
+        expected_state: &ExpectedState,
+    ) -> Result<ManagedHostStatus, RegentError> {
+        let actions = self.collect_actions(expected_state).await?;
+        if only_skipped(&actions) {
+            return Ok(ManagedHostStatus::already_compliant_with_actions(actions));
+        }
+
+        let mut actions_with_diffs: Vec<Action> = Vec::with_capacity(actions.len());
+        for action in actions {
+            // Failed and skipped attributes have nothing to preview
+            if action.action_result().is_some() {
+                actions_with_diffs.push(action);
+                continue;
+            }
+            let diff = action.remediation().preview(&mut self.handler).await?;
+            actions_with_diffs.push(action.with_diff(diff));
+        }
+        Ok(ManagedHostStatus::not_compliant_with_actions(
+            actions_with_diffs,
+        ))
+    }
+
+    /// Assess every attribute of the expected state, in the order of the dependency graph.
+    ///
+    /// Returns the remediations to run, as actions not tried yet. An attribute whose assessment
+    /// fails gives a `Failure` action and the attributes requiring it a `Skipped` action;
+    /// independent attributes are still assessed. Attributes whose `When` condition is not met
+    /// give a `Skipped` action as well. Only `Skipped` actions mean the host is compliant.
+    async fn collect_actions(
+        &mut self,
+        expected_state: &ExpectedState,
+    ) -> Result<Vec<Action>, RegentError> {
         if !self.is_connected().await {
             return Err(RegentError::NotConnectedToHost);
         }
 
         // Enable secret caching to ensure idempotency
         self.enable_secret_caching();
+        // Outcomes registered by a previous run are not relevant anymore
+        self.registered_vars = tera::Context::new();
+
+        let attribute_graph = AttributeGraph::from(&expected_state.attributes)?;
 
-        let mut already_compliant = true;
-        let mut final_remediations_list: Vec<Remediation> = Vec::new();
+        let mut actions: Vec<Action> = Vec::new();
+        // Attributes which failed or were skipped : whatever requires them is skipped as well
+        let mut failed_attributes: HashSet<usize> = HashSet::new();
 
-        for attribute in expected_state.attributes.clone().iter_mut() {
+        for index in attribute_graph.execution_order() {
+            let attribute = &expected_state.attributes[*index];
             let span = span!(Level::INFO, "attribute", name = attribute.name());
             let _enter = span.enter();
 
-            // Taking context into account before working on the Attribute
-            match attribute.consider_context(&self.context) {
-                Ok(context_aware_attribute) => {
-                    match context_aware_attribute
-                        .assess(
-                            &mut self.handler,
-                            &self.host_properties,
-                            &self.secret_providers,
-                        )
-                        .await
-                    {
-                        Ok(attribute_compliance) => {
-                            if let AttributeComplianceAssessment::NonCompliant(remediations) =
-                                attribute_compliance
-                            {
-                                already_compliant = false;
-                                final_remediations_list.extend(remediations);
-                            }
-                        }
-                        Err(details) => {
-                            return Err(details);
-                        }
-                    }
-                }
+            if let Some(reason) =
+                failed_prerequisite(expected_state, &attribute_graph, &failed_attributes, *index)
+            {
+                warn!(target: "run", "Skipped : {}", reason);
+                actions.push(Action::from(
+                    Remediation::None(attribute.name()),
+                    Some(InternalApiCallOutcome::Skipped(reason)),
+                ));
+                self.register_outcome(attribute, RegisteredOutcome::skipped());
+                failed_attributes.insert(*index);
+                continue;
+            }
+
+            match self.assess_attribute_compliance(attribute).await {
+                Ok(attribute_actions) => actions.extend(attribute_actions),
                 Err(details) => {
-                    let content = match &details {
-                        RegentError::FailureToConsiderContext(content) => content,
-                        _ => &format!("{:?}", details),
-                    };
-                    error!("{}", content);
-                    return Err(details);
+                    self.record_attribute_error(attribute, details, &mut actions);
+                    failed_attributes.insert(*index);
                 }
             }
         }
 
-        if already_compliant {
-            Ok(ManagedHostStatus::already_compliant())
-        } else {
-            Ok(ManagedHostStatus::not_compliant(final_remediations_list))
+        Ok(actions)
+    }
+
+    /// Assess a single attribute, each loop item in turn, and return the remediations it needs
+    /// as actions not tried yet, or a `Skipped` action if its `When` condition is not met.
+    ///
+    /// Nothing runs, so the registered outcome only tells whether the attribute would change
+    /// something.
+    async fn assess_attribute_compliance(
+        &mut self,
+        attribute: &Attribute,
+    ) -> Result<Vec<Action>, RegentError> {
+        if !self.is_attribute_applicable(attribute)? {
+            let reason = format!(
+                "When condition not met : {}",
+                attribute.when().unwrap_or_default()
+            );
+            info!(target: "run", "Skipped : {}", reason);
+            self.register_outcome(attribute, RegisteredOutcome::skipped());
+            return Ok(vec![Action::from(
+                Remediation::None(attribute.name()),
+                Some(InternalApiCallOutcome::Skipped(reason)),
+            )]);
+        }
+
+        // Taking context into account before working on the Attribute (one per loop item)
+        let mut remediations: Vec<Remediation> = Vec::new();
+        let mut item_outcomes: Vec<RegisteredOutcome> = Vec::new();
+        for context_aware_attribute in attribute.expand(&self.host_context())? {
+            let registered_outcome = match context_aware_attribute
+                .assess(
+                    &mut self.handler,
+                    &self.host_properties,
+                    &self.secret_providers,
+                )
+                .await?
+            {
+                AttributeComplianceAssessment::Compliant => RegisteredOutcome::default(),
+                AttributeComplianceAssessment::NonCompliant(item_remediations) => {
+                    remediations.extend(item_remediations);
+                    RegisteredOutcome {
+                        changed: true,
+                        ..Default::default()
+                    }
+                }
+            };
+            item_outcomes.push(registered_outcome);
         }
+        self.register_item_outcomes(attribute, item_outcomes);
+        Ok(remediations
+            .into_iter()
+            .map(|remediation| Action::from(remediation, None))
+            .collect())
     }
 
     /// Automatically reach compliance with the expected state.
     ///
     /// This method assesses compliance and then automatically performs the necessary
-    /// remediations to bring the host into the expected state. It will stop at the
-    /// first failure unless all remediations are marked as allowed to fail.
+    /// remediations to bring the host into the expected state. Attributes are handled in
+    /// the order given by their dependency graph (see [`AttributeGraph`]). When an attribute
+    /// fails, only the attributes requiring it (directly or not) are skipped; independent
+    /// attributes keep converging. Handlers notified by attributes which changed something
+    /// run last, once each.
     ///
     /// # Arguments
     ///
@@ -860,121 +1079,396 @@ impl ManagedHost {
 
         // Enable secret caching to ensure idempotency
         self.enable_secret_caching();
+        // Outcomes registered by a previous run are not relevant anymore
+        self.registered_vars = tera::Context::new();
+
+        let attribute_graph = AttributeGraph::from(&expected_state.attributes)?;
+        expected_state.check_handlers()?;
 
         let mut final_host_status = HostStatus::AlreadyCompliant;
-        let mut reaching_compliance_failed = false;
         let mut actions_taken: Vec<Action> = Vec::new();
+        // Attributes which failed or were skipped : whatever requires them is skipped as well
+        let mut failed_attributes: HashSet<usize> = HashSet::new();
+        // Handlers notified by attributes which changed something, by position
+        let mut notified_handlers: BTreeSet<usize> = BTreeSet::new();
 
-        for attribute in &expected_state.attributes {
+        for index in attribute_graph.execution_order() {
+            let attribute = &expected_state.attributes[*index];
             let span = span!(Level::INFO, "attribute", name = attribute.name());
             let _enter = span.enter();
-            match attribute.consider_context(&self.context) {
-                Ok(context_aware_attribute) => {
-                    let timeout_duration = context_aware_attribute.timeout()?;
-
-                    match context_aware_attribute
-                        .assess(
-                            &mut self.handler,
-                            &self.host_properties,
-                            &self.secret_providers,
-                        )
-                        .await
-                    {
-                        Ok(attribute_compliance) => {
-                            let outcome = attribute_compliance.clone();
-                            match attribute_compliance {
-                                AttributeComplianceAssessment::Compliant => {
-                                    info!(target: "run",assesment_outcome = ?outcome, "Attribute already met");
-                                    // Nothing to do
-                                }
-                                AttributeComplianceAssessment::NonCompliant(remediations) => {
-                                    warn!(target: "run",assesment_outcome = ?outcome, "Not compliant. Trying to remedy.");
-
-                                    // Host is not compliant as there are remediations to perform
-                                    // Host status switches from AlreadyCompliant to ReachComplianceSuccess by default
-                                    final_host_status = HostStatus::ReachComplianceSuccess;
-
-                                    // Try to remedy
-
-                                    for remediation in remediations {
-                                        match remediation
-                                            .reach_compliance(
-                                                &mut self.handler,
-                                                &self.host_properties,
-                                                &self.secret_providers,
-                                                timeout_duration,
-                                            )
-                                            .await
-                                        {
-                                            Ok(internal_api_call_outcome) => {
-                                                actions_taken.push(Action::from(
-                                                    remediation.clone(),
-                                                    Some(internal_api_call_outcome.clone()),
-                                                ));
-
-                                                match &internal_api_call_outcome {
-                                                    InternalApiCallOutcome::Success(details) => {
-                                                        info!(target: "run",remediation_outcome = "Success", "{:?} : {}", remediation, details.clone().unwrap_or("no details".to_string()));
-                                                    }
-                                                    InternalApiCallOutcome::AllowedFailure(
-                                                        details,
-                                                    ) => {
-                                                        info!(target: "run",remediation_outcome = "AllowedFailure", "Allowed failure occured : {}", details);
-                                                    }
-                                                    InternalApiCallOutcome::Failure(details) => {
-                                                        reaching_compliance_failed = true;
-                                                        final_host_status =
-                                                            HostStatus::ReachComplianceFailed;
-
-                                                        warn!(
-                                                            remediation_outcome = "Failure",
-                                                            "Attribute not met : {}", details
-                                                        );
-
-                                                        // Stop processing more remediations for this attribute
-                                                        break;
-                                                    }
-                                                }
-                                            }
-                                            Err(details) => {
-                                                warn!("Failed to apply remediation");
-                                                return Err(details);
-                                            }
-                                        }
-                                    }
 
-                                    if reaching_compliance_failed {
-                                        // Stop processing more attributes
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        Err(details) => {
-                            warn!(reason = ?details, "Failed assessment");
-                            return Err(details);
-                        }
+            if let Some(reason) =
+                failed_prerequisite(expected_state, &attribute_graph, &failed_attributes, *index)
+            {
+                warn!(target: "run", "Skipped : {}", reason);
+                actions_taken.push(Action::from(
+                    Remediation::None(attribute.name()),
+                    Some(InternalApiCallOutcome::Skipped(reason)),
+                ));
+                self.register_outcome(attribute, RegisteredOutcome::skipped());
+                failed_attributes.insert(*index);
+                continue;
+            }
+
+            let attribute_outcome = match self
+                .reach_attribute_compliance(attribute, &mut actions_taken)
+                .await
+            {
+                Ok(attribute_outcome) => attribute_outcome,
+                Err(details) => {
+                    self.record_attribute_error(attribute, details, &mut actions_taken);
+                    AttributeRunOutcome::Failed
+                }
+            };
+            match attribute_outcome {
+                AttributeRunOutcome::AlreadyCompliant | AttributeRunOutcome::Skipped => {}
+                AttributeRunOutcome::Remedied { changed } => {
+                    // Host status switches from AlreadyCompliant to ReachComplianceSuccess unless
+                    // another attribute already failed
+                    if let HostStatus::AlreadyCompliant = final_host_status {
+                        final_host_status = HostStatus::ReachComplianceSuccess;
+                    }
+                    if changed {
+                        notified_handlers.extend(expected_state.notified_handlers(attribute)?);
                     }
                 }
+                AttributeRunOutcome::Failed => {
+                    final_host_status = HostStatus::ReachComplianceFailed;
+                    failed_attributes.insert(*index);
+                }
+            }
+        }
+
+        // Notified handlers run once each, in declaration order. A handler may notify other
+        // handlers, which are then queued as long as they did not run yet.
+        let mut handlers_already_run: HashSet<usize> = HashSet::new();
+        while let Some(index) = notified_handlers.pop_first() {
+            if !handlers_already_run.insert(index) {
+                continue;
+            }
+            let handler = &expected_state.handlers()[index];
+            let span = span!(Level::INFO, "handler", name = handler.name());
+            let _enter = span.enter();
+
+            let handler_outcome = match self
+                .reach_attribute_compliance(handler, &mut actions_taken)
+                .await
+            {
+                Ok(handler_outcome) => handler_outcome,
                 Err(details) => {
-                    let content = match &details {
-                        RegentError::FailureToConsiderContext(content) => content,
-                        _ => &format!("{:?}", details),
-                    };
-                    error!("{}", content);
-                    return Err(details);
+                    self.record_attribute_error(handler, details, &mut actions_taken);
+                    AttributeRunOutcome::Failed
+                }
+            };
+            match handler_outcome {
+                AttributeRunOutcome::AlreadyCompliant | AttributeRunOutcome::Skipped => {}
+                AttributeRunOutcome::Remedied { changed } => {
+                    if let HostStatus::AlreadyCompliant = final_host_status {
+                        final_host_status = HostStatus::ReachComplianceSuccess;
+                    }
+                    if changed {
+                        notified_handlers.extend(expected_state.notified_handlers(handler)?);
+                    }
+                }
+                AttributeRunOutcome::Failed => {
+                    final_host_status = HostStatus::ReachComplianceFailed;
                 }
             }
         }
 
         match final_host_status {
-            HostStatus::AlreadyCompliant => Ok(ManagedHostStatus::already_compliant()),
+            HostStatus::AlreadyCompliant => Ok(ManagedHostStatus::already_compliant_with_actions(
+                actions_taken,
+            )),
             HostStatus::ReachComplianceFailed => {
                 Ok(ManagedHostStatus::reach_compliance_failed(actions_taken))
             }
             _ => Ok(ManagedHostStatus::reach_compliance_success(actions_taken)),
         }
     }
+
+    /// Assess a single attribute and run its remediations if it is not compliant.
+    ///
+    /// Attributes with a loop are expanded first, each item being handled in turn. Every
+    /// remediation tried is appended to `actions_taken`. Remediations of an item stop at its
+    /// first failure, the following items being handled anyway. The outcome of the whole
+    /// attribute is registered once all items are done.
+    async fn reach_attribute_compliance(
+        &mut self,
+        attribute: &Attribute,
+        actions_taken: &mut Vec<Action>,
+    ) -> Result<AttributeRunOutcome, RegentError> {
+        if !self.is_attribute_applicable(attribute)? {
+            let reason = format!(
+                "When condition not met : {}",
+                attribute.when().unwrap_or_default()
+            );
+            info!(target: "run", "Skipped : {}", reason);
+            actions_taken.push(Action::from(
+                Remediation::None(attribute.name()),
+                Some(InternalApiCallOutcome::Skipped(reason)),
+            ));
+            self.register_outcome(attribute, RegisteredOutcome::skipped());
+            return Ok(AttributeRunOutcome::Skipped);
+        }
+
+        let context_aware_attributes = attribute.expand(&self.host_context())?;
+
+        let mut attribute_outcome = AttributeRunOutcome::AlreadyCompliant;
+        let mut item_outcomes: Vec<RegisteredOutcome> = Vec::new();
+        for context_aware_attribute in context_aware_attributes {
+            let (item_outcome, registered_outcome) = self
+                .reach_context_aware_attribute_compliance(&context_aware_attribute, actions_taken)
+                .await?;
+            item_outcomes.push(registered_outcome);
+            attribute_outcome = match (attribute_outcome, item_outcome) {
+                (AttributeRunOutcome::Failed, _) | (_, AttributeRunOutcome::Failed) => {
+                    AttributeRunOutcome::Failed
+                }
+                (
+                    AttributeRunOutcome::Remedied { changed },
+                    AttributeRunOutcome::Remedied {
+                        changed: item_changed,
+                    },
+                ) => AttributeRunOutcome::Remedied {
+                    changed: changed || item_changed,
+                },
+                (AttributeRunOutcome::Remedied { changed }, _)
+                | (_, AttributeRunOutcome::Remedied { changed }) => {
+                    AttributeRunOutcome::Remedied { changed }
+                }
+                (current_outcome, _) => current_outcome,
+            };
+        }
+        self.register_item_outcomes(attribute, item_outcomes);
+
+        Ok(attribute_outcome)
+    }
+
+    /// Assess a single context-aware attribute (i.e. a loop item) and run its remediations if
+    /// it is not compliant. Returns the outcome of the item along with the outcome to register.
+    async fn reach_context_aware_attribute_compliance(
+        &mut self,
+        context_aware_attribute: &Attribute,
+        actions_taken: &mut Vec<Action>,
+    ) -> Result<(AttributeRunOutcome, RegisteredOutcome), RegentError> {
+        let span = span!(Level::INFO, "item", name = context_aware_attribute.name());
+        let _enter = span.enter();
+
+        let timeout_duration = context_aware_attribute.timeout()?;
+
+        let attribute_compliance = match context_aware_attribute
+            .assess(
+                &mut self.handler,
+                &self.host_properties,
+                &self.secret_providers,
+            )
+            .await
+        {
+            Ok(attribute_compliance) => attribute_compliance,
+            Err(details) => {
+                warn!(reason = ?details, "Failed assessment");
+                return Err(details);
+            }
+        };
+
+        let outcome = attribute_compliance.clone();
+        let remediations = match attribute_compliance {
+            AttributeComplianceAssessment::Compliant => {
+                info!(target: "run",assesment_outcome = ?outcome, "Attribute already met");
+                return Ok((
+                    AttributeRunOutcome::AlreadyCompliant,
+                    RegisteredOutcome::default(),
+                ));
+            }
+            AttributeComplianceAssessment::NonCompliant(remediations) => {
+                warn!(target: "run",assesment_outcome = ?outcome, "Not compliant. Trying to remedy.");
+                remediations
+            }
+        };
+
+        let mut changed = false;
+        let mut registered_outcome = RegisteredOutcome::default();
+
+        for remediation in remediations {
+            match remediation
+                .reach_compliance_with_output(
+                    &mut self.handler,
+                    &self.host_properties,
+                    &self.secret_providers,
+                    timeout_duration,
+                )
+                .await
+            {
+                Ok((internal_api_call_outcome, cmd_result)) => {
+                    actions_taken.push(Action::from(
+                        remediation.clone(),
+                        Some(internal_api_call_outcome.clone()),
+                    ));
+                    if let Some(cmd_result) = &cmd_result {
+                        registered_outcome = registered_outcome.with_command_result(cmd_result);
+                    }
+
+                    match &internal_api_call_outcome {
+                        InternalApiCallOutcome::Success(details) => {
+                            changed = true;
+                            info!(target: "run",remediation_outcome = "Success", "{:?} : {}", remediation, details.clone().unwrap_or("no details".to_string()));
+                        }
+                        InternalApiCallOutcome::Unchanged(details) => {
+                            info!(target: "run",remediation_outcome = "Unchanged", "{:?} : {}", remediation, details.clone().unwrap_or("no details".to_string()));
+                        }
+                        InternalApiCallOutcome::AllowedFailure(details) => {
+                            registered_outcome.failed = true;
+                            info!(target: "run",remediation_outcome = "AllowedFailure", "Allowed failure occured : {}", details);
+                        }
+                        InternalApiCallOutcome::Skipped(details) => {
+                            info!(target: "run",remediation_outcome = "Skipped", "{}", details);
+                        }
+                        InternalApiCallOutcome::Failure(details) => {
+                            warn!(
+                                remediation_outcome = "Failure",
+                                "Attribute not met : {}", details
+                            );
+
+                            registered_outcome.changed = changed;
+                            registered_outcome.failed = true;
+
+                            // Stop processing more remediations for this attribute
+                            return Ok((AttributeRunOutcome::Failed, registered_outcome));
+                        }
+                    }
+                }
+                Err(details) => {
+                    warn!("Failed to apply remediation");
+                    return Err(details);
+                }
+            }
+        }
+
+        registered_outcome.changed = changed;
+
+        Ok((
+            AttributeRunOutcome::Remedied { changed },
+            registered_outcome,
+        ))
+    }
+
+    /// Record an attribute whose assessment or remediation ended with an error, so that only
+    /// the attributes requiring it are skipped.
+    fn record_attribute_error(
+        &mut self,
+        attribute: &Attribute,
+        details: RegentError,
+        actions: &mut Vec<Action>,
+    ) {
+        let content = match &details {
+            RegentError::FailureToConsiderContext(content) => content.clone(),
+            _ => format!("{:?}", details),
+        };
+        error!(target: "run", "Failed : {}", content);
+        actions.push(Action::from(
+            Remediation::None(attribute.name()),
+            Some(InternalApiCallOutcome::Failure(content)),
+        ));
+        self.register_outcome(
+            attribute,
+            RegisteredOutcome {
+                failed: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Store the outcome of an attribute in the run variable named by its `Register`, if any.
+    fn register_outcome(&mut self, attribute: &Attribute, registered_outcome: RegisteredOutcome) {
+        if let Some(variable) = attribute.register() {
+            debug!(variable, "Registering attribute outcome");
+            self.registered_vars
+                .insert(variable.to_string(), &registered_outcome);
+        }
+    }
+
+    /// Store the outcome of an attribute from the outcomes of its items : the single item of an
+    /// attribute without loop, or all of them (see [`RegisteredOutcome::from_items`]).
+    fn register_item_outcomes(
+        &mut self,
+        attribute: &Attribute,
+        mut item_outcomes: Vec<RegisteredOutcome>,
+    ) {
+        let registered_outcome = match attribute.loop_items() {
+            Some(_) => RegisteredOutcome::from_items(item_outcomes),
+            None => item_outcomes.pop().unwrap_or_default(),
+        };
+        self.register_outcome(attribute, registered_outcome);
+    }
+
+    /// Context attributes, conditions and templates are rendered against : host properties
+    /// (`os_kind`, `linux_flavor`, `init_system`, `hostname`, facts and `custom_facts`), host
+    /// variables and outcomes registered during the current run, each taking precedence over
+    /// the previous ones.
+    fn host_context(&self) -> tera::Context {
+        let mut host_context = match &self.host_properties {
+            Some(host_properties) => host_properties.context(),
+            None => tera::Context::new(),
+        };
+        host_context.extend(self.context.clone());
+        host_context.extend(self.registered_vars.clone());
+        host_context
+    }
+
+    /// Evaluate the `When` condition of an attribute against the host context.
+    fn is_attribute_applicable(&self, attribute: &Attribute) -> Result<bool, RegentError> {
+        if attribute.when().is_none() {
+            return Ok(true);
+        }
+
+        attribute
+            .is_applicable(&self.host_context())
+            .inspect_err(|details| {
+                error!("{:?}", details);
+            })
+    }
+}
+
+/// Whether assessed actions leave nothing to do : no action at all, or only skipped attributes.
+fn only_skipped(actions: &[Action]) -> bool {
+    actions.iter().all(|action| {
+        matches!(
+            action.action_result(),
+            Some(InternalApiCallOutcome::Skipped(_))
+        )
+    })
+}
+
+/// Reason why an attribute is skipped, if one of its prerequisites failed or was skipped.
+fn failed_prerequisite(
+    expected_state: &ExpectedState,
+    attribute_graph: &AttributeGraph,
+    failed_attributes: &HashSet<usize>,
+    index: usize,
+) -> Option<String> {
+    attribute_graph
+        .prerequisites(index)
+        .iter()
+        .find(|prerequisite| failed_attributes.contains(prerequisite))
+        .map(|failed_prerequisite| {
+            format!(
+                "prerequisite '{}' did not reach compliance",
+                expected_state.attributes[*failed_prerequisite].name()
+            )
+        })
+}
+
+/// Outcome of [`ManagedHost::reach_attribute_compliance`] for a single attribute.
+enum AttributeRunOutcome {
+    AlreadyCompliant,
+    /// Remediations were run. `changed` is true if at least one of them succeeded.
+    Remedied {
+        changed: bool,
+    },
+    /// The `When` condition of the attribute is not met on this host.
+    Skipped,
+    Failed,
 }
 
 /// Trait for types that can assess compliance of a host.
@@ -1035,6 +1529,28 @@ pub trait ReachCompliance<Handler: HostHandler> {
     ) -> Result<InternalApiCallOutcome, RegentError>;
 }
 
+/// Trait for remediations which modify files on the host.
+///
+/// Used in check mode (see [`ManagedHost::check_compliance`]) to show what a remediation would
+/// change without running it.
+///
+/// # Type Parameters
+///
+/// * `Handler` - The type of host handler
+pub(crate) trait PreviewChange<Handler: HostHandler> {
+    /// Compute the changes the remediation would make, without modifying the host.
+    ///
+    /// # Arguments
+    ///
+    /// * `host_handler` - The handler used to fetch the current content of files
+    ///
+    /// # Returns
+    ///
+    /// A unified diff between the current and the would-be content of the modified file, or
+    /// `None` if nothing would change.
+    async fn preview(&self, host_handler: &mut Handler) -> Result<Option<String>, RegentError>;
+}
+
 /// Trait for types that have a default timeout.
 ///
 /// Implement this trait for operations that should have a configurable timeout.
@@ -1088,14 +1604,20 @@ pub enum AttributeLevelOperationOutcome {
 /// # Variants
 ///
 /// - `Success`: The remediation succeeded, with optional details
+/// - `Unchanged`: The remediation succeeded without changing anything, with optional details
 /// - `Failure`: The remediation failed, with error details
 /// - `AllowedFailure`: The remediation failed but was allowed to fail
+/// - `Skipped`: The remediation was not tried, with the reason why
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum InternalApiCallOutcome {
     /// The remediation succeeded.
     ///
     /// Contains optional details about the success.
     Success(Option<String>),
+    /// The remediation succeeded without changing anything on the host.
+    ///
+    /// Contains optional details about the success.
+    Unchanged(Option<String>),
     /// The remediation failed.
     ///
     /// Contains error details.
@@ -1104,4 +1626,294 @@ pub enum InternalApiCallOutcome {
     ///
     /// This allows for "best effort" compliance where some failures are acceptable.
     AllowedFailure(String),
+    /// The remediation was not tried.
+    ///
+    /// Contains the reason why (e.g. a prerequisite attribute failed).
+    Skipped(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosts::handlers::TargetUser;
+    use crate::hosts::handlers::ssh2::Ssh2Auth;
+
+    const ERRORING_EXPECTED_STATE: &str = r#"---
+Attributes:
+  - Name: broken
+    Id: broken
+    Privilege: !None
+    Detail: !Command
+      Cmd: "echo {{ undefined_variable }}"
+  - Name: dependent
+    Privilege: !None
+    Requires: [broken]
+    Detail: !Command
+      Cmd: "echo dependent"
+  - Name: independent
+    Privilege: !None
+    Detail: !Command
+      Cmd: "echo independent"
+"#;
+
+    fn connected_localhost(runtime: &tokio::runtime::Runtime) -> ManagedHost {
+        runtime.block_on(async {
+            let mut managed_host = ManagedHostBuilder::new(
+                "local-01",
+                "localhost",
+                Some(ConnectionMethod::Localhost(TargetUser::current_user())),
+            )
+            .build(None)
+            .await
+            .unwrap();
+            managed_host.connect().await.unwrap();
+            managed_host
+        })
+    }
+
+    #[test]
+    fn going_on_after_an_attribute_error() {
+        let expected_state = ExpectedState::from_raw_yaml(ERRORING_EXPECTED_STATE).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut managed_host = connected_localhost(&runtime);
+
+        let host_status = runtime
+            .block_on(managed_host.assess_compliance(&expected_state))
+            .unwrap();
+        assert!(host_status.is_not_compliant());
+        let actions = host_status.actions();
+        assert_eq!(actions.len(), 3);
+        assert!(matches!(
+            actions[0].action_result(),
+            Some(InternalApiCallOutcome::Failure(_))
+        ));
+        assert!(matches!(
+            actions[1].action_result(),
+            Some(InternalApiCallOutcome::Skipped(_))
+        ));
+        assert!(matches!(actions[2].remediation(), Remediation::Command(_)));
+        assert!(actions[2].action_result().is_none());
+
+        let host_status = runtime
+            .block_on(managed_host.reach_compliance(&expected_state))
+            .unwrap();
+        assert!(host_status.is_reach_compliance_failed());
+        let actions = host_status.actions();
+        assert_eq!(actions.len(), 3);
+        assert!(matches!(
+            actions[0].action_result(),
+            Some(InternalApiCallOutcome::Failure(_))
+        ));
+        assert!(matches!(
+            actions[1].action_result(),
+            Some(InternalApiCallOutcome::Skipped(_))
+        ));
+        assert!(matches!(
+            actions[2].action_result(),
+            Some(InternalApiCallOutcome::Success(Some(output))) if output == "independent\n"
+        ));
+    }
+
+    #[test]
+    fn pinning_host_key_fingerprint() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let fingerprint = "SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s";
+
+        let mut builder = ManagedHostBuilder::new(
+            "web-01",
+            "10.0.0.1:22",
+            Some(ConnectionMethod::Ssh2(Ssh2Auth::agent("deploy"))),
+        );
+        builder.set_host_key_fingerprint(fingerprint);
+        let managed_host = runtime.block_on(builder.build(None)).unwrap();
+        assert!(matches!(
+            &managed_host.handler,
+            Handler::Ssh2(Ssh2HostHandler::NotConnected(settings))
+                if settings.host_key_policy == HostKeyPolicy::Pinned { fingerprint: fingerprint.to_string() }
+        ));
+
+        let mut builder = ManagedHostBuilder::from_raw_yaml(&format!(
+            "Id: local-01\nEndpoint: localhost\nHostConnectionMethod: !Localhost\n  UserKind: !CurrentUser\nHostKeyFingerprint: {}\n",
+            fingerprint
+        ))
+        .unwrap();
+        assert!(matches!(
+            runtime.block_on(builder.clone().build(None)),
+            Err(RegentError::WrongInitialization(_))
+        ));
+        builder.host_key_fingerprint = None;
+        assert!(runtime.block_on(builder.build(None)).is_ok());
+    }
+
+    const REGISTERING_EXPECTED_STATE: &str = r#"---
+Attributes:
+  - Name: read app version
+    Privilege: !None
+    Register: app
+    Detail: !Command
+      Cmd: "echo '{\"version\": \"1.2.3\"}'"
+  - Name: migrate
+    Privilege: !None
+    Register: migration
+    When: app.rc == 0 and app.json.version != "0.0.0"
+    Detail: !Command
+      Cmd: "echo from {{ app.json.version }} >&2; exit 3"
+  - Name: rollback
+    Privilege: !None
+    Register: rollback
+    When: not migration.failed
+    Detail: !Command
+      Cmd: "echo rollback"
+"#;
+
+    #[test]
+    fn registering_attribute_outcomes() {
+        let expected_state = ExpectedState::from_raw_yaml(REGISTERING_EXPECTED_STATE).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut managed_host = connected_localhost(&runtime);
+        let host_status = runtime
+            .block_on(managed_host.reach_compliance(&expected_state))
+            .unwrap();
+        assert!(matches!(
+            host_status.actions()[2].action_result(),
+            Some(InternalApiCallOutcome::Skipped(_))
+        ));
+
+        let registered = |variable: &str| -> RegisteredOutcome {
+            let value =
+                serde_json::to_value(managed_host.registered_vars.get(variable).unwrap()).unwrap();
+            serde_json::from_value(value).unwrap()
+        };
+        assert_eq!(
+            registered("app"),
+            RegisteredOutcome {
+                changed: true,
+                rc: Some(0),
+                stdout: Some("{\"version\": \"1.2.3\"}\n".to_string()),
+                stderr: Some(String::new()),
+                json: Some(serde_json::json!({"version": "1.2.3"})),
+                ..Default::default()
+            }
+        );
+
+        let migration = registered("migration");
+        assert!(migration.failed);
+        assert_eq!(migration.rc, Some(3));
+        assert_eq!(migration.stderr.as_deref(), Some("from 1.2.3\n"));
+        assert_eq!(migration.json, None);
+
+        assert_eq!(registered("rollback"), RegisteredOutcome::skipped());
+        // Host variables are left untouched
+        assert!(!managed_host.context.contains_key("app"));
+    }
+
+    const CONDITIONAL_EXPECTED_STATE: &str = r#"---
+Attributes:
+  - Name: never
+    Privilege: !None
+    When: 1 == 2
+    Detail: !Command
+      Cmd: "echo never"
+"#;
+
+    #[test]
+    fn reporting_skipped_attributes_when_checking() {
+        let expected_state = ExpectedState::from_raw_yaml(CONDITIONAL_EXPECTED_STATE).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut managed_host = connected_localhost(&runtime);
+
+        for host_status in [
+            runtime
+                .block_on(managed_host.check_compliance(&expected_state))
+                .unwrap(),
+            runtime
+                .block_on(managed_host.assess_compliance(&expected_state))
+                .unwrap(),
+        ] {
+            assert!(host_status.is_already_compliant());
+            assert_eq!(host_status.actions().len(), 1);
+            assert!(matches!(
+                host_status.actions()[0].action_result(),
+                Some(InternalApiCallOutcome::Skipped(_))
+            ));
+        }
+    }
+
+    const REGISTERING_LOOP_EXPECTED_STATE: &str = r#"---
+Attributes:
+  - Name: check items
+    Privilege: !None
+    Register: checks
+    Loop: [broken, fine]
+    Detail: !Command
+      Cmd: "echo {{ item }}; test {{ item }} = fine"
+  - Name: after checks
+    Privilege: !None
+    Register: after
+    When: not checks.failed
+    Detail: !Command
+      Cmd: "echo after"
+"#;
+
+    #[test]
+    fn registering_outcomes_of_all_loop_items() {
+        let expected_state = ExpectedState::from_raw_yaml(REGISTERING_LOOP_EXPECTED_STATE).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut managed_host = connected_localhost(&runtime);
+        runtime
+            .block_on(managed_host.reach_compliance(&expected_state))
+            .unwrap();
+
+        let registered = |variable: &str| -> RegisteredOutcome {
+            let value =
+                serde_json::to_value(managed_host.registered_vars.get(variable).unwrap()).unwrap();
+            serde_json::from_value(value).unwrap()
+        };
+        // The last item succeeded, but the first one failed
+        let checks = registered("checks");
+        assert!(checks.failed);
+        assert!(checks.changed);
+        assert_eq!(checks.rc, None);
+        assert_eq!(checks.results.len(), 2);
+        assert!(checks.results[0].failed);
+        assert_eq!(checks.results[0].stdout.as_deref(), Some("broken\n"));
+        assert!(!checks.results[1].failed);
+        assert_eq!(checks.results[1].rc, Some(0));
+
+        assert_eq!(registered("after"), RegisteredOutcome::skipped());
+    }
+
+    #[test]
+    fn registering_attribute_outcomes_when_assessing() {
+        let expected_state = ExpectedState::from_raw_yaml(REGISTERING_EXPECTED_STATE).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut managed_host = connected_localhost(&runtime);
+        runtime
+            .block_on(managed_host.reach_compliance(&expected_state))
+            .unwrap();
+        let host_status = runtime
+            .block_on(managed_host.assess_compliance(&expected_state))
+            .unwrap();
+
+        // Conditions on registered outcomes are evaluated instead of failing
+        assert!(host_status.actions().iter().all(|action| !matches!(
+            action.action_result(),
+            Some(InternalApiCallOutcome::Failure(_))
+        )));
+
+        // Outcomes of the previous run are replaced, commands not being run
+        let registered = |variable: &str| -> RegisteredOutcome {
+            let value =
+                serde_json::to_value(managed_host.registered_vars.get(variable).unwrap()).unwrap();
+            serde_json::from_value(value).unwrap()
+        };
+        assert_eq!(
+            registered("app"),
+            RegisteredOutcome {
+                changed: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(registered("migration"), RegisteredOutcome::skipped());
+    }
 }
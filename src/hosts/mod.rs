@@ -1,5 +1,7 @@
+pub mod facts;
 pub mod handlers;
 pub mod inventory;
 pub mod managed_host;
 pub mod privilege;
 pub mod properties;
+pub mod rollout;
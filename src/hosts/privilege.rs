@@ -17,6 +17,12 @@ use crate::secrets::SecretReference;
 /// - `None`: Execute as the currently authenticated user
 /// - `WithSudo`: Execute with `sudo` for root privileges
 /// - `WithSudoRs`: Execute with `sudo-rs` for root privileges
+/// - `WithDoas`: Execute with `doas` for root privileges
+/// - `WithSu`: Execute with `su` as root
+///
+/// The password prompted for by the escalation command is answered with the become password of
+/// the host (see [`crate::hosts::managed_host::ManagedHostBuilder::set_become_password`]), never
+/// written on a command line.
 ///
 /// # Example
 ///
@@ -31,6 +37,9 @@ use crate::secrets::SecretReference;
 ///
 /// // Execute with sudo-rs
 /// let privilege = Privilege::WithSudoRs;
+///
+/// // Execute with doas
+/// let privilege = Privilege::WithDoas;
 /// ```
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Privilege {
@@ -46,6 +55,10 @@ pub enum Privilege {
     WithSudoRs,
     // /// Run cmd as another user using sudo-rs
     // WithSudoRsAsUser(Credentials),
+    /// Run command with doas for root privileges.
+    WithDoas,
+    /// Run command as root through su.
+    WithSu,
 }
 
 /// User credentials for authentication.
@@ -135,6 +148,10 @@ pub struct LoginKey {
     username: String,
     /// The private key for SSH authentication.
     key: String,
+    /// The passphrase decrypting the private key, if it is encrypted.
+    passphrase: Option<String>,
+    /// The OpenSSH certificate of the key, signed by a CA trusted by the host.
+    certificate: Option<String>,
 }
 
 impl LoginKey {
@@ -153,7 +170,24 @@ impl LoginKey {
     /// let login_key = LoginKey::from("admin".to_string(), "private_key_pem".to_string());
     /// ```
     pub fn from(username: String, key: String) -> Self {
-        Self { username, key }
+        Self {
+            username,
+            key,
+            passphrase: None,
+            certificate: None,
+        }
+    }
+
+    /// Set the passphrase decrypting the private key.
+    pub fn with_passphrase(mut self, passphrase: String) -> Self {
+        self.passphrase = Some(passphrase);
+        self
+    }
+
+    /// Set the OpenSSH certificate (e.g. the content of `id_ed25519-cert.pub`) to authenticate with.
+    pub fn with_certificate(mut self, certificate: String) -> Self {
+        self.certificate = Some(certificate);
+        self
     }
 
     /// Get the username.
@@ -175,6 +209,18 @@ impl LoginKey {
     pub fn key(&self) -> &str {
         &self.key
     }
+
+    /// Get the passphrase of the private key, if any.
+    ///
+    /// **Warning**: Be cautious with this method as it exposes the passphrase in plain text.
+    pub fn passphrase(&self) -> Option<&str> {
+        self.passphrase.as_deref()
+    }
+
+    /// Get the OpenSSH certificate of the key, if any.
+    pub fn certificate(&self) -> Option<&str> {
+        self.certificate.as_deref()
+    }
 }
 
 /// Reference to an SSH login key stored in a secret provider.
@@ -200,6 +246,10 @@ pub struct LoginKeyRef {
     username: String,
     /// Reference to the secret containing the SSH private key.
     key: SecretReference,
+    /// Reference to the secret containing the passphrase of the private key, if it is encrypted.
+    passphrase: Option<SecretReference>,
+    /// Reference to the secret containing the OpenSSH certificate of the key.
+    certificate: Option<SecretReference>,
 }
 
 impl LoginKeyRef {
@@ -220,7 +270,24 @@ impl LoginKeyRef {
     /// let login_key_ref = LoginKeyRef::from("admin".to_string(), key_ref);
     /// ```
     pub fn from(username: String, key: SecretReference) -> Self {
-        Self { username, key }
+        Self {
+            username,
+            key,
+            passphrase: None,
+            certificate: None,
+        }
+    }
+
+    /// Set the reference to the secret containing the passphrase of the private key.
+    pub fn with_passphrase(mut self, passphrase: SecretReference) -> Self {
+        self.passphrase = Some(passphrase);
+        self
+    }
+
+    /// Set the reference to the secret containing the OpenSSH certificate of the key.
+    pub fn with_certificate(mut self, certificate: SecretReference) -> Self {
+        self.certificate = Some(certificate);
+        self
     }
 
     /// Get the username.
@@ -240,4 +307,46 @@ impl LoginKeyRef {
     pub fn key_ref(&self) -> &SecretReference {
         &self.key
     }
+
+    /// Get the secret reference for the passphrase of the SSH key, if any.
+    pub fn passphrase_ref(&self) -> Option<&SecretReference> {
+        self.passphrase.as_ref()
+    }
+
+    /// Get the secret reference for the OpenSSH certificate of the SSH key, if any.
+    pub fn certificate_ref(&self) -> Option<&SecretReference> {
+        self.certificate.as_ref()
+    }
+}
+
+/// SSH login through the keys held by the running SSH agent (found through `SSH_AUTH_SOCK`).
+///
+/// Every identity of the agent is tried in turn, OpenSSH certificates included.
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::hosts::privilege::AgentLogin;
+///
+/// let agent_login = AgentLogin::from("admin".to_string());
+/// assert_eq!(agent_login.username(), "admin");
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct AgentLogin {
+    /// The username for SSH authentication.
+    username: String,
+}
+
+impl AgentLogin {
+    /// Create a new agent login for the given username.
+    pub fn from(username: String) -> Self {
+        Self { username }
+    }
+
+    /// Get the username.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
 }
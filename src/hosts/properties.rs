@@ -1,7 +1,10 @@
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
 
+use crate::hosts::facts::{FactProbe, HostFacts, parse_key_values};
 use crate::hosts::handlers::HostHandler;
 
 use crate::{Privilege, RegentError};
@@ -11,6 +14,10 @@ use crate::{Privilege, RegentError};
 pub struct HostProperties {
     os_kind: OsKind,
     hostname: Option<String>,
+    #[serde(default)]
+    facts: HostFacts,
+    #[serde(default)]
+    custom_facts: HashMap<String, serde_json::Value>, // FactProbe name -> fact
 }
 
 impl HostProperties {
@@ -22,55 +29,23 @@ impl HostProperties {
         }
 
         let mut os_kind = OsKind::Unknown;
+        let mut os_release = HashMap::new();
 
         // Linux & FreeBSD -> try to get file /etc/os-release
         if let Ok(os_release_file_content) = host_handler
             .get_file(PathBuf::from("/etc/os-release"))
             .await
         {
-            let content = String::from_utf8_lossy(&os_release_file_content);
-            for line in content.lines() {
-                if line.starts_with("NAME=") {
-                    // Some distributions are using quotes, others are not...
-                    let mut equals_iterator = line.split('=');
-                    equals_iterator.next().unwrap(); // 'NAME'
-                    let os_name_part = equals_iterator.next().unwrap();
-
-                    let os_name = if os_name_part.starts_with('"') {
-                        &os_name_part[1..os_name_part.len() - 1]
-                    } else {
-                        os_name_part
-                    };
-
-                    if os_name.contains("FreeBSD") {
-                        os_kind = OsKind::FreeBsd(FreeBsdSpecifics);
-                    } else {
-                        let linux_flavor = if os_name.contains("Arch") {
-                            LinuxFlavor::Arch
-                        } else if os_name.contains("CentOS") {
-                            LinuxFlavor::Fedora
-                        } else if os_name.contains("Debian") {
-                            LinuxFlavor::Debian
-                        } else if os_name.contains("Ubuntu") {
-                            LinuxFlavor::Debian
-                        } else if os_name.contains("Arch") {
-                            LinuxFlavor::Arch
-                        } else if os_name.contains("openSUSE") {
-                            LinuxFlavor::Suse
-                        } else {
-                            // Unknown Linux flavor, but still Linux
-                            LinuxFlavor::Debian
-                        };
-
-                        let init_system = Self::detect_init_system(host_handler).await;
-                        os_kind = OsKind::Linux(LinuxSpecifics {
-                            linux_flavor,
-                            init_system,
-                        });
-                    }
-
-                    break;
-                }
+            os_release = parse_key_values(&String::from_utf8_lossy(&os_release_file_content));
+            if os_release.get("ID").map(String::as_str) == Some("freebsd") {
+                os_kind = OsKind::FreeBsd(FreeBsdSpecifics);
+            } else if !os_release.is_empty() {
+                let linux_flavor = LinuxFlavor::from_os_release(&os_release);
+                let init_system = Self::detect_init_system(host_handler).await;
+                os_kind = OsKind::Linux(LinuxSpecifics {
+                    linux_flavor,
+                    init_system,
+                });
             }
         }
 
@@ -96,8 +71,14 @@ impl HostProperties {
         }
 
         let hostname = Self::collect_hostname(host_handler, os_kind.clone()).await;
+        let facts = HostFacts::collect(host_handler, &os_kind, &os_release).await;
 
-        Ok(HostProperties { os_kind, hostname })
+        Ok(HostProperties {
+            os_kind,
+            hostname,
+            facts,
+            custom_facts: HashMap::new(),
+        })
     }
 
     pub fn os_kind(&self) -> &OsKind {
@@ -108,6 +89,57 @@ impl HostProperties {
         &self.hostname
     }
 
+    pub fn facts(&self) -> &HostFacts {
+        &self.facts
+    }
+
+    pub fn custom_facts(&self) -> &HashMap<String, serde_json::Value> {
+        &self.custom_facts
+    }
+
+    /// Run the fact probes on the host and store their results as custom facts. A probe which
+    /// fails is logged and left out.
+    pub async fn collect_custom_facts<Handler: HostHandler>(
+        &mut self,
+        host_handler: &mut Handler,
+        fact_probes: &[FactProbe],
+    ) {
+        for fact_probe in fact_probes {
+            match fact_probe.probe(host_handler).await {
+                Ok(fact) => {
+                    self.custom_facts
+                        .insert(fact_probe.name().to_string(), fact);
+                }
+                Err(details) => {
+                    warn!(
+                        fact = fact_probe.name(),
+                        "Failed to probe fact : {:?}", details
+                    );
+                }
+            }
+        }
+    }
+
+    /// Expose the known properties as template variables : `os_kind`, `linux_flavor`,
+    /// `init_system`, `hostname`, the [facts](HostFacts::context) of the host and its
+    /// `custom_facts`.
+    pub fn context(&self) -> tera::Context {
+        let mut context = self.facts.context();
+        context.insert("custom_facts", &self.custom_facts);
+        context.insert("os_kind", self.os_kind.name());
+        if let OsKind::Linux(linux_specifics) = &self.os_kind {
+            context.insert(
+                "linux_flavor",
+                &format!("{:?}", linux_specifics.linux_flavor),
+            );
+            context.insert("init_system", &format!("{:?}", linux_specifics.init_system));
+        }
+        if let Some(hostname) = &self.hostname {
+            context.insert("hostname", hostname);
+        }
+        context
+    }
+
     async fn collect_hostname<Handler: HostHandler>(
         host_handler: &mut Handler,
         os_kind: OsKind,
@@ -182,6 +214,19 @@ pub enum OsKind {
     Linux(LinuxSpecifics),
 }
 
+impl OsKind {
+    /// Name of the OS kind, as exposed to templates and conditions.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OsKind::Unknown => "Unknown",
+            OsKind::Windows(_) => "Windows",
+            OsKind::FreeBsd(_) => "FreeBsd",
+            OsKind::MacOs(_) => "MacOs",
+            OsKind::Linux(_) => "Linux",
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct LinuxSpecifics {
     pub linux_flavor: LinuxFlavor,
@@ -197,13 +242,70 @@ pub struct FreeBsdSpecifics;
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct WindowsSpecifics;
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+/// Family of a Linux distribution, telling notably which package manager it relies on.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
 pub enum LinuxFlavor {
+    /// Debian and its derivatives (Ubuntu, Linux Mint, Raspberry Pi OS...)
     Debian,
     Fedora,
+    /// Red Hat Enterprise Linux and its rebuilds (CentOS, Rocky Linux, AlmaLinux, Oracle Linux)
+    Rhel,
+    Amazon,
+    /// Arch Linux and its derivatives (Manjaro, EndeavourOS...)
     Arch,
+    /// openSUSE (Leap and Tumbleweed) and SUSE Linux Enterprise
     Suse,
     Gentoo,
+    Alpine,
+    Unknown,
+}
+
+impl LinuxFlavor {
+    /// os-release `ID`s of the known distributions
+    const DISTRIBUTION_IDS: [(&'static str, LinuxFlavor); 22] = [
+        ("debian", LinuxFlavor::Debian),
+        ("ubuntu", LinuxFlavor::Debian),
+        ("linuxmint", LinuxFlavor::Debian),
+        ("raspbian", LinuxFlavor::Debian),
+        ("pop", LinuxFlavor::Debian),
+        ("kali", LinuxFlavor::Debian),
+        ("fedora", LinuxFlavor::Fedora),
+        ("rhel", LinuxFlavor::Rhel),
+        ("centos", LinuxFlavor::Rhel),
+        ("rocky", LinuxFlavor::Rhel),
+        ("almalinux", LinuxFlavor::Rhel),
+        ("ol", LinuxFlavor::Rhel),
+        ("amzn", LinuxFlavor::Amazon),
+        ("arch", LinuxFlavor::Arch),
+        ("manjaro", LinuxFlavor::Arch),
+        ("endeavouros", LinuxFlavor::Arch),
+        ("opensuse-leap", LinuxFlavor::Suse),
+        ("opensuse-tumbleweed", LinuxFlavor::Suse),
+        ("opensuse", LinuxFlavor::Suse),
+        ("suse", LinuxFlavor::Suse),
+        ("gentoo", LinuxFlavor::Gentoo),
+        ("alpine", LinuxFlavor::Alpine),
+    ];
+
+    /// Flavor of a distribution out of the variables of its os-release file : its `ID` if known,
+    /// else the first known distribution of its `ID_LIKE`.
+    pub fn from_os_release(os_release: &HashMap<String, String>) -> LinuxFlavor {
+        let id = os_release.get("ID").map(String::as_str).into_iter();
+        let id_like = os_release
+            .get("ID_LIKE")
+            .map(|id_like| id_like.split_whitespace())
+            .into_iter()
+            .flatten();
+
+        id.chain(id_like)
+            .find_map(|id| {
+                Self::DISTRIBUTION_IDS
+                    .iter()
+                    .find(|(known_id, _)| id.eq_ignore_ascii_case(known_id))
+                    .map(|(_, linux_flavor)| *linux_flavor)
+            })
+            .unwrap_or(LinuxFlavor::Unknown)
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -211,3 +313,99 @@ pub enum InitSystem {
     Unknown,
     Systemd,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! os_release_fixture {
+        ($name:literal) => {
+            parse_key_values(include_str!(concat!(
+                "../../tests/fixtures/os-release/",
+                $name
+            )))
+        };
+    }
+
+    #[test]
+    fn detecting_linux_flavor_from_os_release() {
+        let fixtures = [
+            (os_release_fixture!("debian-12"), LinuxFlavor::Debian, "12"),
+            (
+                os_release_fixture!("ubuntu-24.04"),
+                LinuxFlavor::Debian,
+                "24.04",
+            ),
+            (
+                os_release_fixture!("linuxmint-21.3"),
+                LinuxFlavor::Debian,
+                "21.3",
+            ),
+            (os_release_fixture!("fedora-40"), LinuxFlavor::Fedora, "40"),
+            (
+                os_release_fixture!("centos-stream-9"),
+                LinuxFlavor::Rhel,
+                "9",
+            ),
+            (os_release_fixture!("rhel-9.4"), LinuxFlavor::Rhel, "9.4"),
+            (os_release_fixture!("rocky-9.4"), LinuxFlavor::Rhel, "9.4"),
+            (
+                os_release_fixture!("almalinux-9.4"),
+                LinuxFlavor::Rhel,
+                "9.4",
+            ),
+            (
+                os_release_fixture!("amzn-2023"),
+                LinuxFlavor::Amazon,
+                "2023",
+            ),
+            (
+                os_release_fixture!("alpine-3.20"),
+                LinuxFlavor::Alpine,
+                "3.20.2",
+            ),
+            (os_release_fixture!("gentoo"), LinuxFlavor::Gentoo, "2.15"),
+            (
+                os_release_fixture!("opensuse-tumbleweed"),
+                LinuxFlavor::Suse,
+                "20240710",
+            ),
+            (
+                os_release_fixture!("opensuse-leap-15.6"),
+                LinuxFlavor::Suse,
+                "15.6",
+            ),
+        ];
+
+        for (os_release, linux_flavor, version_id) in fixtures {
+            assert_eq!(
+                LinuxFlavor::from_os_release(&os_release),
+                linux_flavor,
+                "{:?}",
+                os_release["NAME"]
+            );
+            assert_eq!(os_release["VERSION_ID"], version_id);
+        }
+
+        let arch = os_release_fixture!("arch");
+        assert_eq!(LinuxFlavor::from_os_release(&arch), LinuxFlavor::Arch);
+        assert!(!arch.contains_key("VERSION_ID"));
+        assert_eq!(
+            LinuxFlavor::from_os_release(&os_release_fixture!("manjaro")),
+            LinuxFlavor::Arch
+        );
+    }
+
+    #[test]
+    fn falling_back_on_unknown_distribution() {
+        let derivative = parse_key_values("ID=mydistro\nID_LIKE=\"unknown rhel fedora\"\n");
+        assert_eq!(LinuxFlavor::from_os_release(&derivative), LinuxFlavor::Rhel);
+
+        let unknown = parse_key_values("NAME=\"Debian-looking name\"\nID=mydistro\n");
+        assert_eq!(LinuxFlavor::from_os_release(&unknown), LinuxFlavor::Unknown);
+
+        let freebsd = os_release_fixture!("freebsd-14.1");
+        assert_eq!(freebsd["ID"], "freebsd");
+        assert_eq!(LinuxFlavor::from_os_release(&freebsd), LinuxFlavor::Unknown);
+    }
+}
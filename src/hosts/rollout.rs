@@ -0,0 +1,245 @@
+//! Rolling execution of an expected state over an inventory
+//!
+//! This module provides [`RolloutStrategy`], describing how the hosts of a
+//! [`LivingInventory`](crate::hosts::inventory::LivingInventory) are taken through
+//! `reach_compliance` : in serial batches, with a concurrency cap, stopping the rollout when too
+//! many hosts of a batch fail. A [`BatchHook`] is called around each batch.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+
+use crate::error::RegentError;
+use crate::hosts::inventory::FailurePolicy;
+use crate::state::compliance::ManagedHostStatus;
+
+/// Number of hosts in each batch of a rollout.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BatchSize {
+    /// A fixed number of hosts per batch.
+    Count(usize),
+    /// A share of all the hosts of the inventory, rounded up.
+    Percentage(f32),
+}
+
+/// How hosts are taken through `reach_compliance`.
+///
+/// The default strategy handles all hosts at once, with no concurrency limit, and relies on the
+/// failure policy of the inventory.
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::hosts::inventory::FailurePolicy;
+/// use regent_sdk::hosts::rollout::{BatchSize, RolloutStrategy};
+///
+/// // 25% of hosts at a time, 10 hosts converging at most at the same time, and stop as soon as a
+/// // host fails
+/// let strategy = RolloutStrategy::default()
+///     .with_serial(BatchSize::Percentage(25.0))
+///     .with_max_concurrency(10)
+///     .with_failure_policy(FailurePolicy::strict());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct RolloutStrategy {
+    serial: Option<BatchSize>,
+    #[serde(default, deserialize_with = "deserialize_max_concurrency")]
+    max_concurrency: Option<usize>,
+    failure_policy: Option<FailurePolicy>,
+}
+
+impl RolloutStrategy {
+    /// Handle hosts in successive batches of the given size.
+    pub fn with_serial(mut self, batch_size: BatchSize) -> Self {
+        self.serial = Some(batch_size);
+        self
+    }
+
+    /// Limit the number of hosts converging at the same time (at least 1).
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency.max(1));
+        self
+    }
+
+    /// Share of the hosts of a batch allowed to fail before the remaining batches are aborted.
+    /// Defaults to the failure policy of the inventory.
+    pub fn with_failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = Some(failure_policy);
+        self
+    }
+
+    pub fn max_concurrency(&self) -> Option<usize> {
+        self.max_concurrency
+    }
+
+    pub fn failure_policy(&self) -> Option<FailurePolicy> {
+        self.failure_policy
+    }
+
+    /// Split hosts into the batches of this strategy, in order.
+    pub fn batches(&self, host_ids: Vec<String>) -> Vec<Batch> {
+        let total = host_ids.len();
+        let size = match self.serial {
+            None => total,
+            Some(BatchSize::Count(count)) => count,
+            Some(BatchSize::Percentage(percentage)) => {
+                ((total as f32) * percentage.clamp(0.0, 100.0) / 100.0).ceil() as usize
+            }
+        }
+        .max(1);
+
+        let count = total.div_ceil(size);
+        host_ids
+            .chunks(size)
+            .enumerate()
+            .map(|(index, host_ids)| Batch {
+                index,
+                count,
+                host_ids: host_ids.to_vec(),
+            })
+            .collect()
+    }
+}
+
+/// No host would ever start with a concurrency cap of 0
+fn deserialize_max_concurrency<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<usize>, D::Error> {
+    match Option::<usize>::deserialize(deserializer)? {
+        Some(0) => Err(serde::de::Error::custom(
+            "MaxConcurrency must be at least 1",
+        )),
+        max_concurrency => Ok(max_concurrency),
+    }
+}
+
+/// A set of hosts converged together during a rollout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Batch {
+    /// Position of this batch in the rollout, starting at 0.
+    pub index: usize,
+    /// Number of batches in the rollout.
+    pub count: usize,
+    pub host_ids: Vec<String>,
+}
+
+/// Actions run around each batch of a rollout.
+///
+/// An error returned by a hook aborts the rollout.
+///
+/// # Example
+///
+/// ```no_run
+/// use regent_sdk::RegentError;
+/// use regent_sdk::hosts::rollout::{Batch, BatchHook};
+/// use regent_sdk::state::compliance::ManagedHostStatus;
+/// use std::collections::HashMap;
+///
+/// struct LoadBalancer;
+///
+/// impl BatchHook for LoadBalancer {
+///     async fn before_batch(&mut self, batch: &Batch) -> Result<(), RegentError> {
+///         // Drain batch.host_ids from the load balancer
+///         Ok(())
+///     }
+///
+///     async fn after_batch(
+///         &mut self,
+///         batch: &Batch,
+///         statuses: &HashMap<String, ManagedHostStatus>,
+///     ) -> Result<(), RegentError> {
+///         // Put back the hosts which reached compliance
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait BatchHook {
+    /// Called before the hosts of a batch start converging.
+    fn before_batch(
+        &mut self,
+        batch: &Batch,
+    ) -> impl Future<Output = Result<(), RegentError>> + Send {
+        let _ = batch;
+        async { Ok(()) }
+    }
+
+    /// Called once all hosts of a batch are handled, with the statuses of the ones which didn't
+    /// fail with an error.
+    fn after_batch(
+        &mut self,
+        batch: &Batch,
+        statuses: &HashMap<String, ManagedHostStatus>,
+    ) -> impl Future<Output = Result<(), RegentError>> + Send {
+        let _ = (batch, statuses);
+        async { Ok(()) }
+    }
+}
+
+/// No action around batches
+impl BatchHook for () {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host_ids(count: usize) -> Vec<String> {
+        (1..=count).map(|i| format!("host-{:02}", i)).collect()
+    }
+
+    fn batch_sizes(strategy: &RolloutStrategy, hosts: usize) -> Vec<usize> {
+        strategy
+            .batches(host_ids(hosts))
+            .iter()
+            .map(|batch| batch.host_ids.len())
+            .collect()
+    }
+
+    #[test]
+    fn splitting_hosts_into_batches() {
+        let default = RolloutStrategy::default();
+        assert_eq!(batch_sizes(&default, 5), vec![5]);
+        assert!(default.batches(Vec::new()).is_empty());
+
+        let by_count = RolloutStrategy::default().with_serial(BatchSize::Count(2));
+        assert_eq!(batch_sizes(&by_count, 5), vec![2, 2, 1]);
+
+        let by_percentage = RolloutStrategy::default().with_serial(BatchSize::Percentage(30.0));
+        assert_eq!(batch_sizes(&by_percentage, 10), vec![3, 3, 3, 1]);
+        assert_eq!(batch_sizes(&by_percentage, 2), vec![1, 1]);
+
+        let by_nothing = RolloutStrategy::default().with_serial(BatchSize::Count(0));
+        assert_eq!(batch_sizes(&by_nothing, 2), vec![1, 1]);
+
+        let batches = by_count.batches(host_ids(3));
+        assert_eq!(batches[1].index, 1);
+        assert_eq!(batches[1].count, 2);
+        assert_eq!(batches[1].host_ids, vec!["host-03".to_string()]);
+    }
+
+    #[test]
+    fn parsing_strategy() {
+        let strategy: RolloutStrategy = yaml_serde::from_str(
+            r#"---
+Serial: !Percentage 25.0
+MaxConcurrency: 4
+FailurePolicy:
+    MaxFailPercentage: 10.0
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            strategy,
+            RolloutStrategy::default()
+                .with_serial(BatchSize::Percentage(25.0))
+                .with_max_concurrency(4)
+                .with_failure_policy(FailurePolicy::max_fail_percentage(10.0))
+        );
+
+        let strategy = yaml_serde::from_str::<RolloutStrategy>("MaxConcurrency: 0");
+        assert!(strategy.is_err());
+        let strategy = yaml_serde::from_str::<RolloutStrategy>("Serial: !Count 2").unwrap();
+        assert_eq!(strategy.max_concurrency(), None);
+    }
+}
@@ -23,6 +23,7 @@
 //!
 //! - `aws-secretsmanager`: Enable AWS Secrets Manager support via [`SecretProvider::aws_secretsmanager`]
 //! - `gcp-secretmanager`: Enable Google Cloud Secret Manager support via [`SecretProvider::gcp_secretmanager`]
+//! - `http-inventory`: Enable inventories fetched from an HTTP endpoint via `HttpSource`
 //!
 //! ## Capabilities
 //!
@@ -145,7 +146,7 @@ pub mod task;
 
 pub use error::RegentError;
 pub use hosts::handlers::localhost::{LocalHostHandler, WhichUser};
-pub use hosts::handlers::ssh2::{Ssh2AuthMethod, Ssh2HostHandler};
+pub use hosts::handlers::ssh2::{HostKeyPolicy, Ssh2AuthMethod, Ssh2HostHandler};
 pub use hosts::inventory::Inventory;
 pub use hosts::managed_host::ManagedHost;
 pub use hosts::privilege::Privilege;
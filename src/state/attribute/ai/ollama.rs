@@ -50,9 +50,11 @@
 //!       Privilege: !WithSudo
 //! ```
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
+use crate::hosts::handlers::install_file;
 use crate::hosts::managed_host::InternalApiCallOutcome;
-use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
+use crate::hosts::managed_host::{AssessCompliance, PreviewChange, ReachCompliance, Timeout};
 use crate::hosts::properties::{HostProperties, OsKind};
 use crate::secrets::SecretProvidersPool;
 use crate::state::Check;
@@ -60,6 +62,7 @@ use crate::state::attribute::HostHandler;
 use crate::state::attribute::Privilege;
 use crate::state::attribute::Remediation;
 use crate::state::compliance::AttributeComplianceAssessment;
+use crate::state::diff;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -548,13 +551,11 @@ pub fn build_api_config_content(api: &OllamaApiConfig) -> String {
     format!("{}\n", lines.join("\n"))
 }
 
-/// Escapes a string for use as the argument to `printf`: replaces `\` with `\\`,
-/// `%` with `%%`, and newlines with `\n`.
-fn escape_for_printf(s: &str) -> String {
-    s.replace('\\', "\\\\")
-        .replace('%', "%%")
-        .replace('\n', "\\n")
-}
+/// Directory of the systemd drop-ins of the ollama service
+const OVERRIDE_CONF_DIRECTORY: &str = "/etc/systemd/system/ollama.service.d";
+
+/// systemd drop-in holding the REST API configuration
+const OVERRIDE_CONF_PATH: &str = "/etc/systemd/system/ollama.service.d/override.conf";
 
 // ── ApiCall variants ──────────────────────────────────────────────────────────
 
@@ -709,26 +710,36 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for OllamaApiCall {
             OllamaModuleInternalApiCall::EnableService => "systemctl enable ollama".to_string(),
             OllamaModuleInternalApiCall::DisableService => "systemctl disable ollama".to_string(),
             OllamaModuleInternalApiCall::PullModel { name } => {
-                format!("ollama pull {}", name)
+                ShellCommand::new("ollama pull").arg(name).to_string()
             }
             OllamaModuleInternalApiCall::RemoveModel { name } => {
-                format!("ollama rm {}", name)
+                ShellCommand::new("ollama rm").arg(name).to_string()
             }
-            OllamaModuleInternalApiCall::WriteApiConfig { content } => {
-                let escaped = escape_for_printf(content);
-                format!(
-                    "mkdir -p /etc/systemd/system/ollama.service.d && printf '{}' > /etc/systemd/system/ollama.service.d/override.conf",
-                    escaped
-                )
+            OllamaModuleInternalApiCall::WriteApiConfig { .. } => {
+                format!("mkdir -p {}", OVERRIDE_CONF_DIRECTORY)
             }
             OllamaModuleInternalApiCall::DaemonReload => "systemctl daemon-reload".to_string(),
         };
 
-        let result = host_handler
+        let mut result = host_handler
             .run_command(&cmd, &self.privilege)
             .await
             .unwrap();
 
+        // The drop-in itself is uploaded once its directory exists
+        if let OllamaModuleInternalApiCall::WriteApiConfig { content } = &self.api_call
+            && result.return_code == 0
+        {
+            result = install_file(
+                host_handler,
+                OVERRIDE_CONF_PATH,
+                content.as_bytes(),
+                None,
+                &self.privilege,
+            )
+            .await?;
+        }
+
         if result.return_code == 0 {
             Ok(InternalApiCallOutcome::Success(None))
         } else {
@@ -740,6 +751,22 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for OllamaApiCall {
     }
 }
 
+impl<Handler: HostHandler> PreviewChange<Handler> for OllamaApiCall {
+    async fn preview(&self, host_handler: &mut Handler) -> Result<Option<String>, RegentError> {
+        // The systemd drop-in is the only file written directly by this module
+        let OllamaModuleInternalApiCall::WriteApiConfig { content } = &self.api_call else {
+            return Ok(None);
+        };
+        let current_content =
+            diff::current_content(host_handler, OVERRIDE_CONF_PATH, &self.privilege).await?;
+        Ok(diff::unified_diff(
+            OVERRIDE_CONF_PATH,
+            &current_content,
+            content,
+        ))
+    }
+}
+
 // ── Tests ─────────────────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -877,18 +904,4 @@ mod tests {
         assert_eq!(normalize_model_name("mistral:7b"), "mistral:7b");
         assert_eq!(normalize_model_name("codellama:13b"), "codellama:13b");
     }
-
-    #[test]
-    fn escape_for_printf_handles_special_chars() {
-        let input = "[Service]\nEnvironment=\"OLLAMA_HOST=0.0.0.0:11434\"\n";
-        let escaped = escape_for_printf(input);
-        assert!(!escaped.contains('\n'));
-        assert!(escaped.contains("\\n"));
-        // No bare % in the input, but test % escaping.
-        let with_percent = "foo%bar";
-        assert_eq!(escape_for_printf(with_percent), "foo%%bar");
-        // Backslash escaping.
-        let with_backslash = "foo\\bar";
-        assert_eq!(escape_for_printf(with_backslash), "foo\\\\bar");
-    }
 }
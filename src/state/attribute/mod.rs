@@ -13,8 +13,10 @@ use tokio::time::Instant;
 use tokio::time::{sleep, timeout as tokio_timeout};
 use tracing::{debug, error, info};
 
+use crate::command::CommandResult;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
+use crate::hosts::managed_host::PreviewChange;
 use crate::hosts::privilege::Privilege;
 use crate::hosts::properties::HostProperties;
 use crate::secrets::SecretProvidersPool;
@@ -45,13 +47,17 @@ use crate::state::attribute::system::user::UserApiCall;
 use crate::state::attribute::system::user::UserBlockExpectedState;
 use crate::state::attribute::utilities::debug::DebugApiCall;
 use crate::state::attribute::utilities::debug::DebugBlockExpectedState;
+use crate::state::attribute::utilities::file::FileApiCall;
+use crate::state::attribute::utilities::file::FileBlockExpectedState;
 use crate::state::attribute::utilities::lineinfile::LineInFileApiCall;
 use crate::state::attribute::utilities::lineinfile::LineInFileBlockExpectedState;
 use crate::state::attribute::utilities::ping::PingApiCall;
 use crate::state::attribute::utilities::ping::PingBlockExpectedState;
+use crate::state::attribute::utilities::template::TemplateBlockExpectedState;
 use crate::state::compliance::AttributeComplianceAssessment;
 use crate::state::compliance::AttributeComplianceResult;
 use crate::state::compliance::AttributeComplianceStatus;
+use crate::state::condition;
 use crate::{
     hosts::handlers::HostHandler,
     hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout},
@@ -62,11 +68,114 @@ use crate::{
 #[serde(rename_all = "PascalCase")]
 pub struct Attribute {
     pub name: Option<String>,
+    /// Identifier used by other attributes to reference this one (falls back to `name`)
+    id: Option<String>,
     pub privilege: Privilege,
     detail: AttributeDetail,
     timeout: Option<Duration>,
     timeout_sec: Option<u64>,
     timeout_ms: Option<u64>,
+    /// Attributes that must be handled, and must succeed, before this one
+    requires: Option<Vec<String>>,
+    /// Attributes that must only be handled after this one
+    before: Option<Vec<String>>,
+    /// Handlers to run at the end of the run if this attribute changed something
+    notify: Option<Vec<String>>,
+    /// Condition deciding whether this attribute applies to the host (see [`condition`])
+    when: Option<String>,
+    /// Items over which this attribute is expanded, one attribute per item (see [`Loop`])
+    #[serde(rename = "Loop", alias = "WithItems")]
+    loop_items: Option<Loop>,
+    /// Variable the outcome of this attribute is stored in for later attributes (see
+    /// [`RegisteredOutcome`])
+    register: Option<String>,
+}
+
+/// List of items an attribute is expanded over.
+///
+/// Each item is exposed to the templates of the attribute as the `item` variable.
+///
+/// ```yaml
+/// - Name: base packages
+///   Privilege: !WithSudo
+///   Loop: [git, curl, htop]
+///   Detail: !Apt
+///     Package: "{{ item }}"
+///     State: !Present
+/// ```
+///
+/// Instead of an inline list, the name of a host (or global) variable holding a list can be
+/// given : `Loop: base_packages`. Since host variables are strings, the list is then written in
+/// JSON or YAML flow style (e.g. `base_packages: "[git, curl, htop]"`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Loop {
+    Items(Vec<serde_json::Value>),
+    Variable(String),
+}
+
+/// Outcome of an attribute, stored in the variable named by its `Register` and thus usable by
+/// the templates and `When` conditions of the attributes handled after it in the same run. Host
+/// variables are left untouched.
+///
+/// ```yaml
+/// - Name: read app version
+///   Privilege: !None
+///   Register: app
+///   Detail: !Command
+///     Cmd: /opt/app/bin/app --version-json
+/// - Name: migrate
+///   Privilege: !None
+///   When: app.rc == 0 and app.json.version != "2.0.0"
+///   Detail: !Command
+///     Cmd: /opt/app/bin/migrate --from {{ app.json.version }}
+/// ```
+///
+/// `rc`, `stdout`, `stderr` and `json` (`stdout` parsed as JSON, if it is valid JSON) are only
+/// set by `Command` attributes. When assessing or checking compliance, nothing runs : `changed`
+/// tells whether the attribute would change something and the command result is not set.
+///
+/// For attributes with a loop, `changed` and `failed` tell whether any item changed or failed,
+/// and `results` holds the outcome of each item, in order (`pkgs.results[0].rc`). The other
+/// fields are only set in `results`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RegisteredOutcome {
+    pub changed: bool,
+    pub failed: bool,
+    pub skipped: bool,
+    pub rc: Option<i64>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub json: Option<serde_json::Value>,
+    pub results: Vec<RegisteredOutcome>,
+}
+
+impl RegisteredOutcome {
+    pub fn skipped() -> Self {
+        RegisteredOutcome {
+            skipped: true,
+            ..Default::default()
+        }
+    }
+
+    /// Outcome of an attribute with a loop, made of the outcomes of its items.
+    pub fn from_items(results: Vec<RegisteredOutcome>) -> Self {
+        RegisteredOutcome {
+            changed: results.iter().any(|result| result.changed),
+            failed: results.iter().any(|result| result.failed),
+            results,
+            ..Default::default()
+        }
+    }
+
+    /// Take the result of the command run by the attribute into account.
+    pub fn with_command_result(mut self, cmd_result: &CommandResult) -> Self {
+        self.rc = Some(cmd_result.return_code);
+        self.stdout = Some(cmd_result.stdout.clone());
+        self.stderr = Some(cmd_result.stderr.clone());
+        self.json = serde_json::from_str(&cmd_result.stdout).ok();
+        self
+    }
 }
 
 impl Attribute {
@@ -75,12 +184,119 @@ impl Attribute {
             privilege,
             detail,
             name,
+            id: None,
             timeout: None,
             timeout_sec: None,
             timeout_ms: None,
+            requires: None,
+            before: None,
+            notify: None,
+            when: None,
+            loop_items: None,
+            register: None,
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Identifier other attributes can use in their `Requires` and `Before` lists : the `Id`
+    /// if set, the `Name` otherwise.
+    pub fn identifier(&self) -> Option<&str> {
+        self.id.as_deref().or(self.name.as_deref())
+    }
+
+    pub fn requires(&self) -> &[String] {
+        self.requires.as_deref().unwrap_or_default()
+    }
+
+    pub fn before(&self) -> &[String] {
+        self.before.as_deref().unwrap_or_default()
+    }
+
+    pub fn notify(&self) -> &[String] {
+        self.notify.as_deref().unwrap_or_default()
+    }
+
+    pub fn when(&self) -> Option<&str> {
+        self.when.as_deref()
+    }
+
+    /// Evaluate the `When` condition of this attribute against a context made of host variables
+    /// and host properties. An attribute without condition always applies.
+    pub fn is_applicable(&self, context: &Context) -> Result<bool, RegentError> {
+        match &self.when {
+            Some(when_condition) => condition::evaluate(when_condition, context),
+            None => Ok(true),
         }
     }
 
+    pub fn register(&self) -> Option<&str> {
+        self.register.as_deref()
+    }
+
+    pub fn loop_items(&self) -> Option<&Loop> {
+        self.loop_items.as_ref()
+    }
+
+    /// Expand this attribute over its loop items and take the context into account for each of
+    /// them (see [`Attribute::consider_context`]). An attribute without loop gives a single
+    /// context-aware attribute.
+    ///
+    /// Expanded attributes whose name does not depend on `item` get the item appended to their
+    /// name so that per-item results can be told apart.
+    pub fn expand(&self, context: &Context) -> Result<Vec<Attribute>, RegentError> {
+        let items = match &self.loop_items {
+            None => return Ok(vec![self.consider_context(context)?]),
+            Some(Loop::Items(items)) => items.clone(),
+            Some(Loop::Variable(variable)) => match context.get(variable) {
+                Some(value) => match serde_json::to_value(value) {
+                    Ok(serde_json::Value::Array(items)) => items,
+                    // Host variables are strings : a list is written in JSON or YAML flow style
+                    Ok(serde_json::Value::String(content))
+                        if let Ok(items) =
+                            yaml_serde::from_str::<Vec<serde_json::Value>>(&content) =>
+                    {
+                        items
+                    }
+                    _ => {
+                        return Err(RegentError::FailureToConsiderContext(format!(
+                            "Loop variable '{}' is not a list",
+                            variable
+                        )));
+                    }
+                },
+                None => {
+                    return Err(RegentError::FailureToConsiderContext(format!(
+                        "Loop variable '{}' is not defined",
+                        variable
+                    )));
+                }
+            },
+        };
+
+        let mut single_attribute = self.clone();
+        single_attribute.loop_items = None;
+
+        let mut expanded_attributes: Vec<Attribute> = Vec::with_capacity(items.len());
+        for item in items {
+            let mut item_context = context.clone();
+            item_context.insert("item", &item);
+
+            let mut context_aware_attribute = single_attribute.consider_context(&item_context)?;
+            if context_aware_attribute.name == self.name {
+                let item_label = match &item {
+                    serde_json::Value::String(content) => content.clone(),
+                    other => other.to_string(),
+                };
+                context_aware_attribute.name = Some(format!("{} ({})", self.name(), item_label));
+            }
+            expanded_attributes.push(context_aware_attribute);
+        }
+        Ok(expanded_attributes)
+    }
+
     pub fn name(&self) -> String {
         match self.name {
             Some(ref name) => name.clone(),
@@ -93,6 +309,8 @@ impl Attribute {
                 AttributeDetail::Service(_) => "Service".to_string(),
                 AttributeDetail::Command(_) => "Command".to_string(),
                 AttributeDetail::LineInFile(_) => "LineInFile".to_string(),
+                AttributeDetail::File(_) => "File".to_string(),
+                AttributeDetail::Template(_) => "Template".to_string(),
                 AttributeDetail::Ping(_) => "Ping".to_string(),
                 AttributeDetail::Debug(_) => "Debug".to_string(),
                 AttributeDetail::User(_) => "User".to_string(),
@@ -132,7 +350,7 @@ impl Attribute {
                 }
             };
         match serde_json::from_str::<Attribute>(&context_wise_serialized_self) {
-            Ok(context_aware_attribute) => {
+            Ok(mut context_aware_attribute) => {
                 // Validate the configuration after template rendering to ensure
                 // that template variables produced valid configuration
                 context_aware_attribute.check().map_err(|e| {
@@ -141,6 +359,10 @@ impl Attribute {
                         e
                     ))
                 })?;
+                // Templates are rendered against the same context
+                if let AttributeDetail::Template(template) = &mut context_aware_attribute.detail {
+                    template.render(context)?;
+                }
                 Ok(context_aware_attribute)
             }
             Err(detail) => Err(RegentError::FailureToConsiderContext(format!("{}", detail))),
@@ -210,6 +432,54 @@ impl Attribute {
         self
     }
 
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// Declare that this attribute can only be handled once the referenced one succeeded.
+    pub fn with_requires(mut self, identifier: &str) -> Self {
+        self.requires
+            .get_or_insert_with(Vec::new)
+            .push(identifier.to_string());
+        self
+    }
+
+    /// Declare that the referenced attribute can only be handled after this one.
+    pub fn with_before(mut self, identifier: &str) -> Self {
+        self.before
+            .get_or_insert_with(Vec::new)
+            .push(identifier.to_string());
+        self
+    }
+
+    /// Only handle this attribute on hosts where `when_condition` holds (see [`condition`]).
+    pub fn with_when(mut self, when_condition: &str) -> Self {
+        self.when = Some(when_condition.to_string());
+        self
+    }
+
+    /// Expand this attribute over `items`, exposed to templates as the `item` variable.
+    pub fn with_loop(mut self, items: Loop) -> Self {
+        self.loop_items = Some(items);
+        self
+    }
+
+    /// Store the outcome of this attribute in the `variable` host variable (see
+    /// [`RegisteredOutcome`]).
+    pub fn with_register(mut self, variable: &str) -> Self {
+        self.register = Some(variable.to_string());
+        self
+    }
+
+    /// Queue the referenced handler whenever this attribute changes something on the host.
+    pub fn with_notify(mut self, handler_identifier: &str) -> Self {
+        self.notify
+            .get_or_insert_with(Vec::new)
+            .push(handler_identifier.to_string());
+        self
+    }
+
     pub fn apt(
         details: AptBlockExpectedState,
         privilege: Privilege,
@@ -266,6 +536,22 @@ impl Attribute {
         Attribute::from(AttributeDetail::LineInFile(details), privilege, name)
     }
 
+    pub fn file(
+        details: FileBlockExpectedState,
+        privilege: Privilege,
+        name: Option<String>,
+    ) -> Attribute {
+        Attribute::from(AttributeDetail::File(details), privilege, name)
+    }
+
+    pub fn template(
+        details: TemplateBlockExpectedState,
+        privilege: Privilege,
+        name: Option<String>,
+    ) -> Attribute {
+        Attribute::from(AttributeDetail::Template(details), privilege, name)
+    }
+
     pub fn ping(
         details: PingBlockExpectedState,
         privilege: Privilege,
@@ -348,6 +634,8 @@ pub enum AttributeDetail {
     DnfRepo(DnfRepoBlockExpectedState),
     Pacman(PacmanBlockExpectedState),
     LineInFile(LineInFileBlockExpectedState),
+    File(FileBlockExpectedState),
+    Template(TemplateBlockExpectedState),
     Debug(DebugBlockExpectedState),
     Ping(PingBlockExpectedState),
     Service(ServiceBlockExpectedState),
@@ -369,6 +657,8 @@ impl AttributeDetail {
             AttributeDetail::DnfRepo(details) => details.default_timeout(),
             AttributeDetail::Pacman(details) => details.default_timeout(),
             AttributeDetail::LineInFile(details) => details.default_timeout(),
+            AttributeDetail::File(details) => details.default_timeout(),
+            AttributeDetail::Template(details) => details.default_timeout(),
             AttributeDetail::Debug(details) => details.default_timeout(),
             AttributeDetail::Ping(details) => details.default_timeout(),
             AttributeDetail::Service(details) => details.default_timeout(),
@@ -480,6 +770,26 @@ impl AttributeDetail {
                     )
                     .await
             }
+            AttributeDetail::File(expected_state_criteria) => {
+                expected_state_criteria
+                    .assess_compliance(
+                        host_handler,
+                        host_properties,
+                        privilege,
+                        optional_secret_provider,
+                    )
+                    .await
+            }
+            AttributeDetail::Template(expected_state_criteria) => {
+                expected_state_criteria
+                    .assess_compliance(
+                        host_handler,
+                        host_properties,
+                        privilege,
+                        optional_secret_provider,
+                    )
+                    .await
+            }
             AttributeDetail::Debug(expected_state_criteria) => {
                 expected_state_criteria
                     .assess_compliance(
@@ -740,6 +1050,17 @@ impl AttributeDetail {
                                 return Err(details);
                             }
                         },
+                        Remediation::File(attribute_api_call) => match attribute_api_call
+                            .call(host_handler, host_properties, optional_secret_provider)
+                            .await
+                        {
+                            Ok(internal_api_call_outcome) => {
+                                (remediation, internal_api_call_outcome)
+                            }
+                            Err(details) => {
+                                return Err(details);
+                            }
+                        },
                         Remediation::Debug(attribute_api_call) => {
                             match attribute_api_call
                                 .call(host_handler, host_properties, optional_secret_provider)
@@ -898,6 +1219,8 @@ impl AttributeDetail {
             AttributeDetail::DnfRepo(expected_state_block) => expected_state_block.check(),
             AttributeDetail::Pacman(expected_state_block) => expected_state_block.check(),
             AttributeDetail::LineInFile(expected_state_block) => expected_state_block.check(),
+            AttributeDetail::File(expected_state_block) => expected_state_block.check(),
+            AttributeDetail::Template(expected_state_block) => expected_state_block.check(),
             AttributeDetail::Debug(expected_state_block) => expected_state_block.check(),
             AttributeDetail::Ping(expected_state_block) => expected_state_block.check(),
             AttributeDetail::Service(expected_state_block) => expected_state_block.check(),
@@ -921,6 +1244,7 @@ pub enum Remediation {
     YumDnf(YumDnfApiCall),
     DnfRepo(DnfRepoApiCall),
     LineInFile(LineInFileApiCall),
+    File(FileApiCall),
     Debug(DebugApiCall),
     Ping(PingApiCall),
     Service(ServiceApiCall),
@@ -943,6 +1267,7 @@ impl std::fmt::Debug for Remediation {
             Remediation::YumDnf(api_call) => write!(f, "{}", api_call.display()),
             Remediation::DnfRepo(api_call) => write!(f, "{}", api_call.display()),
             Remediation::LineInFile(api_call) => write!(f, "{}", api_call.display()),
+            Remediation::File(api_call) => write!(f, "{}", api_call.display()),
             Remediation::Debug(api_call) => write!(f, "{}", api_call.display()),
             Remediation::Ping(api_call) => write!(f, "{}", api_call.display()),
             Remediation::Service(api_call) => write!(f, "{}", api_call.display()),
@@ -958,6 +1283,23 @@ impl std::fmt::Debug for Remediation {
 }
 
 impl Remediation {
+    /// Unified diff of the file this remediation would modify (check mode). Remediations which
+    /// do not modify files give `None`.
+    pub async fn preview<Handler: HostHandler>(
+        &self,
+        host_handler: &mut Handler,
+    ) -> Result<Option<String>, RegentError> {
+        match self {
+            Remediation::AptRepo(api_call) => api_call.preview(host_handler).await,
+            Remediation::DnfRepo(api_call) => api_call.preview(host_handler).await,
+            Remediation::LineInFile(api_call) => api_call.preview(host_handler).await,
+            Remediation::File(api_call) => api_call.preview(host_handler).await,
+            Remediation::Cron(api_call) => api_call.preview(host_handler).await,
+            Remediation::Ollama(api_call) => api_call.preview(host_handler).await,
+            _ => Ok(None),
+        }
+    }
+
     pub async fn reach_compliance<Handler: HostHandler>(
         &self,
         host_handler: &mut Handler,
@@ -965,12 +1307,41 @@ impl Remediation {
         optional_secret_provider: &Option<SecretProvidersPool>,
         timeout_duration: Duration,
     ) -> Result<InternalApiCallOutcome, RegentError> {
-        match tokio_timeout(
+        self.reach_compliance_with_output(
+            host_handler,
+            host_properties,
+            optional_secret_provider,
             timeout_duration,
-            self.raw_reach_compliance(host_handler, host_properties, optional_secret_provider),
         )
         .await
-        {
+        .map(|(internal_api_call_outcome, _cmd_result)| internal_api_call_outcome)
+    }
+
+    /// Same as [`Remediation::reach_compliance`], also giving the result of the command run by
+    /// `Command` remediations.
+    pub async fn reach_compliance_with_output<Handler: HostHandler>(
+        &self,
+        host_handler: &mut Handler,
+        host_properties: &Option<HostProperties>,
+        optional_secret_provider: &Option<SecretProvidersPool>,
+        timeout_duration: Duration,
+    ) -> Result<(InternalApiCallOutcome, Option<CommandResult>), RegentError> {
+        let raw_reach_compliance = async {
+            match self {
+                Remediation::Command(api_call) => {
+                    if let Some(props) = host_properties {
+                        api_call.check_host_compatibility(props)?;
+                    }
+                    let cmd_result = api_call.run(host_handler, optional_secret_provider).await?;
+                    Ok((api_call.outcome(&cmd_result)?, Some(cmd_result)))
+                }
+                _ => self
+                    .raw_reach_compliance(host_handler, host_properties, optional_secret_provider)
+                    .await
+                    .map(|internal_api_call_outcome| (internal_api_call_outcome, None)),
+            }
+        };
+        match tokio_timeout(timeout_duration, raw_reach_compliance).await {
             Ok(raw_assesment_result) => raw_assesment_result,
             Err(_details) => {
                 error!(timeout = ?timeout_duration, "Timeout elapsed");
@@ -1025,6 +1396,11 @@ impl Remediation {
                     .call(host_handler, host_properties, optional_secret_provider)
                     .await
             }
+            Remediation::File(api_call) => {
+                api_call
+                    .call(host_handler, host_properties, optional_secret_provider)
+                    .await
+            }
             Remediation::Debug(api_call) => {
                 api_call
                     .call(host_handler, host_properties, optional_secret_provider)
@@ -1087,6 +1463,7 @@ impl Remediation {
             Remediation::YumDnf(api_call) => api_call.display(),
             Remediation::DnfRepo(api_call) => api_call.display(),
             Remediation::LineInFile(api_call) => api_call.display(),
+            Remediation::File(api_call) => api_call.display(),
             Remediation::Debug(api_call) => api_call.display(),
             Remediation::Ping(api_call) => api_call.display(),
             Remediation::Service(api_call) => api_call.display(),
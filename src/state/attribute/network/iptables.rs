@@ -38,6 +38,7 @@
 //!       Privilege: !WithSudo
 //! ```
 
+use crate::command::quote;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
 use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
@@ -594,7 +595,8 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for IptablesBlockExpectedSt
 
         // ── Chain management ──────────────────────────────────────────────────
         if let Some(true) = self.chain_management {
-            let check_chain_cmd = build_cmd(binary, &table_arg, &format!("-L {} -n", self.chain));
+            let check_chain_cmd =
+                build_cmd(binary, &table_arg, &format!("-L {} -n", quote(&self.chain)));
             let check_chain_cmd = format!("{} 2>/dev/null", check_chain_cmd);
             let chain_result = host_handler
                 .run_command(&check_chain_cmd, &Privilege::None)
@@ -637,7 +639,7 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for IptablesBlockExpectedSt
 
         // ── Policy ────────────────────────────────────────────────────────────
         if let Some(ref expected_policy) = self.policy {
-            let list_cmd = build_cmd(binary, &table_arg, &format!("-L {} -n", self.chain));
+            let list_cmd = build_cmd(binary, &table_arg, &format!("-L {} -n", quote(&self.chain)));
             let list_cmd = format!("{} 2>/dev/null", list_cmd);
             let list_result = host_handler
                 .run_command(&list_cmd, &Privilege::None)
@@ -683,7 +685,7 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for IptablesBlockExpectedSt
             let check_rule_cmd = build_cmd(
                 binary,
                 &table_arg,
-                &format!("-C {} {}", self.chain, rule_args),
+                &format!("-C {} {}", quote(&self.chain), rule_args),
             );
             let check_rule_cmd = format!("{} 2>/dev/null", check_rule_cmd);
             let check_result = host_handler
@@ -930,14 +932,14 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for IptablesApiCall {
                 binary,
                 table_arg,
                 chain,
-            } => build_cmd(binary, table_arg, &format!("-N {}", chain)),
+            } => build_cmd(binary, table_arg, &format!("-N {}", quote(chain))),
             IptablesModuleInternalApiCall::FlushAndDeleteChain {
                 binary,
                 table_arg,
                 chain,
             } => {
-                let flush = build_cmd(binary, table_arg, &format!("-F {}", chain));
-                let delete = build_cmd(binary, table_arg, &format!("-X {}", chain));
+                let flush = build_cmd(binary, table_arg, &format!("-F {}", quote(chain)));
+                let delete = build_cmd(binary, table_arg, &format!("-X {}", quote(chain)));
                 format!("{} && {}", flush, delete)
             }
             IptablesModuleInternalApiCall::SetPolicy {
@@ -945,13 +947,21 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for IptablesApiCall {
                 table_arg,
                 chain,
                 policy,
-            } => build_cmd(binary, table_arg, &format!("-P {} {}", chain, policy)),
+            } => build_cmd(
+                binary,
+                table_arg,
+                &format!("-P {} {}", quote(chain), quote(policy)),
+            ),
             IptablesModuleInternalApiCall::AppendRule {
                 binary,
                 table_arg,
                 chain,
                 rule_args,
-            } => build_cmd(binary, table_arg, &format!("-A {} {}", chain, rule_args)),
+            } => build_cmd(
+                binary,
+                table_arg,
+                &format!("-A {} {}", quote(chain), rule_args),
+            ),
             IptablesModuleInternalApiCall::InsertRule {
                 binary,
                 table_arg,
@@ -962,16 +972,24 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for IptablesApiCall {
                 Some(n) => build_cmd(
                     binary,
                     table_arg,
-                    &format!("-I {} {} {}", chain, n, rule_args),
+                    &format!("-I {} {} {}", quote(chain), n, rule_args),
+                ),
+                None => build_cmd(
+                    binary,
+                    table_arg,
+                    &format!("-I {} {}", quote(chain), rule_args),
                 ),
-                None => build_cmd(binary, table_arg, &format!("-I {} {}", chain, rule_args)),
             },
             IptablesModuleInternalApiCall::DeleteRule {
                 binary,
                 table_arg,
                 chain,
                 rule_args,
-            } => build_cmd(binary, table_arg, &format!("-D {} {}", chain, rule_args)),
+            } => build_cmd(
+                binary,
+                table_arg,
+                &format!("-D {} {}", quote(chain), rule_args),
+            ),
         };
 
         let result = host_handler
@@ -1015,7 +1033,11 @@ async fn verify_rule_position<Handler: HostHandler>(
             rule_args,
         } => {
             // For append, verify the rule exists and is at the end
-            let check_cmd = build_cmd(binary, table_arg, &format!("-C {} {}", chain, rule_args));
+            let check_cmd = build_cmd(
+                binary,
+                table_arg,
+                &format!("-C {} {}", quote(chain), rule_args),
+            );
             let check_result = host_handler
                 .run_command(&check_cmd, privilege)
                 .await
@@ -1030,7 +1052,11 @@ async fn verify_rule_position<Handler: HostHandler>(
             rule_args,
         } => {
             // For insert, verify the rule exists and is at the expected position
-            let check_cmd = build_cmd(binary, table_arg, &format!("-C {} {}", chain, rule_args));
+            let check_cmd = build_cmd(
+                binary,
+                table_arg,
+                &format!("-C {} {}", quote(chain), rule_args),
+            );
             let check_result = host_handler
                 .run_command(&check_cmd, privilege)
                 .await
@@ -1046,7 +1072,7 @@ async fn verify_rule_position<Handler: HostHandler>(
                 let list_cmd = build_cmd(
                     binary,
                     table_arg,
-                    &format!("-L {} --line-numbers -n", chain),
+                    &format!("-L {} --line-numbers -n", quote(chain)),
                 );
                 let list_result = host_handler
                     .run_command(&list_cmd, privilege)
@@ -1119,66 +1145,69 @@ fn build_rule_args(block: &IptablesBlockExpectedState) -> String {
 
     // Protocol
     if let Some(ref proto) = block.protocol {
-        parts.push(format!("-p {}", proto));
+        parts.push(format!("-p {}", quote(proto)));
     }
 
     // Source
     if let Some(ref src) = block.source {
-        parts.push(format!("-s {}", src));
+        parts.push(format!("-s {}", quote(src)));
     }
 
     // Destination
     if let Some(ref dst) = block.destination {
-        parts.push(format!("-d {}", dst));
+        parts.push(format!("-d {}", quote(dst)));
     }
 
     // Input interface
     if let Some(ref iface) = block.in_interface {
-        parts.push(format!("-i {}", iface));
+        parts.push(format!("-i {}", quote(iface)));
     }
 
     // Output interface
     if let Some(ref iface) = block.out_interface {
-        parts.push(format!("-o {}", iface));
+        parts.push(format!("-o {}", quote(iface)));
     }
 
     // Source port
     if let Some(ref sport) = block.source_port {
-        parts.push(format!("--sport {}", sport));
+        parts.push(format!("--sport {}", quote(sport)));
     }
 
     // Destination port
     if let Some(ref dport) = block.destination_port {
-        parts.push(format!("--dport {}", dport));
+        parts.push(format!("--dport {}", quote(dport)));
     }
 
     // Conntrack state
     if let Some(ref states) = block.ctstate {
         if !states.is_empty() {
-            parts.push(format!("-m conntrack --ctstate {}", states.join(",")));
+            parts.push(format!(
+                "-m conntrack --ctstate {}",
+                quote(&states.join(","))
+            ));
         }
     }
 
     // Rate limiting
     if let Some(ref limit) = block.limit {
-        let mut limit_part = format!("-m limit --limit {}", limit);
+        let mut limit_part = format!("-m limit --limit {}", quote(limit));
         if let Some(ref burst) = block.limit_burst {
-            limit_part.push_str(&format!(" --limit-burst {}", burst));
+            limit_part.push_str(&format!(" --limit-burst {}", quote(burst)));
         }
         parts.push(limit_part);
     }
 
     // ICMP type
     if let Some(ref icmp_type) = block.icmp_type {
-        parts.push(format!("--icmp-type {}", icmp_type));
+        parts.push(format!("--icmp-type {}", quote(icmp_type)));
     }
 
     // TCP flags
     if let Some(ref tcp_flags) = block.tcp_flags {
         parts.push(format!(
             "--tcp-flags {} {}",
-            tcp_flags.flags.join(","),
-            tcp_flags.flags_set.join(",")
+            quote(&tcp_flags.flags.join(",")),
+            quote(&tcp_flags.flags_set.join(","))
         ));
     }
 
@@ -1196,39 +1225,39 @@ fn build_rule_args(block: &IptablesBlockExpectedState) -> String {
 
     // Owner match
     if let Some(ref uid) = block.uid_owner {
-        let mut owner_part = format!("-m owner --uid-owner {}", uid);
+        let mut owner_part = format!("-m owner --uid-owner {}", quote(uid));
         if let Some(ref gid) = block.gid_owner {
-            owner_part.push_str(&format!(" --gid-owner {}", gid));
+            owner_part.push_str(&format!(" --gid-owner {}", quote(gid)));
         }
         parts.push(owner_part);
     }
 
     // Comment
     if let Some(ref comment) = block.comment {
-        parts.push(format!("-m comment --comment '{}'", comment));
+        parts.push(format!("-m comment --comment {}", quote(comment)));
     }
 
     // Target: jump or goto, with any associated target options
     if let Some(ref jump) = block.jump {
-        parts.push(format!("-j {}", jump));
+        parts.push(format!("-j {}", quote(jump)));
 
         if let Some(ref to_dst) = block.to_destination {
-            parts.push(format!("--to-destination {}", to_dst));
+            parts.push(format!("--to-destination {}", quote(to_dst)));
         }
         if let Some(ref to_src) = block.to_source {
-            parts.push(format!("--to-source {}", to_src));
+            parts.push(format!("--to-source {}", quote(to_src)));
         }
         if let Some(ref to_ports) = block.to_ports {
-            parts.push(format!("--to-ports {}", to_ports));
+            parts.push(format!("--to-ports {}", quote(to_ports)));
         }
         if let Some(ref prefix) = block.log_prefix {
-            parts.push(format!("--log-prefix '{}'", prefix));
+            parts.push(format!("--log-prefix {}", quote(prefix)));
         }
         if let Some(ref level) = block.log_level {
-            parts.push(format!("--log-level {}", level));
+            parts.push(format!("--log-level {}", quote(level)));
         }
     } else if let Some(ref goto) = block.goto {
-        parts.push(format!("-g {}", goto));
+        parts.push(format!("-g {}", quote(goto)));
     }
 
     parts.join(" ")
@@ -1239,6 +1268,7 @@ fn build_rule_args(block: &IptablesBlockExpectedState) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_iptables_blocks_from_yaml() {
@@ -1476,4 +1506,27 @@ mod tests {
             .build();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn build_rule_args_quotes_hostile_values() {
+        for value in HOSTILE_VALUES {
+            let block = IptablesBlockExpectedState::builder("INPUT")
+                .with_protocol(value)
+                .with_source(value)
+                .with_destination_port(value)
+                .with_jump("LOG")
+                .with_log_prefix(value)
+                .with_comment(value)
+                .build()
+                .unwrap();
+
+            let args = build_rule_args(&block);
+            assert_no_injection(&args);
+            assert_no_injection(&build_cmd(
+                "iptables",
+                "-t nat",
+                &format!("-A {} {}", quote(value), args),
+            ));
+        }
+    }
 }
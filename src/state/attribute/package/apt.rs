@@ -32,8 +32,16 @@
 //!       Package: apache2
 //!       State: !Present
 //!       Privilege: !WithSudo
+//!   - Detail: !Apt
+//!       Packages: [nginx=1.24.*, curl]
+//!       State: !Present
+//!       Privilege: !WithSudo
 //! ```
+//!
+//! Packages can be pinned to a version with `name=version`, `*` wildcards included. With the
+//! `Latest` state, installed packages are upgraded whenever the APT cache knows a newer candidate.
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
 use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
@@ -43,6 +51,7 @@ use crate::state::Check;
 use crate::state::attribute::HostHandler;
 use crate::state::attribute::Privilege;
 use crate::state::attribute::Remediation;
+use crate::state::attribute::package::spec::PackageSpec;
 use crate::state::compliance::AttributeComplianceAssessment;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -50,16 +59,26 @@ use std::time::Duration;
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub enum AptModuleInternalApiCall {
-    Install(String),
-    Remove(String),
+    /// Install (or switch to the pinned version of) packages, in a single transaction
+    Install(Vec<String>),
+    /// Upgrade already installed packages to their candidate version
+    UpgradePackages(Vec<String>),
+    Remove(Vec<String>),
     Upgrade,
 }
 
 impl std::fmt::Display for AptModuleInternalApiCall {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            AptModuleInternalApiCall::Install(package) => write!(f, "install {}", package),
-            AptModuleInternalApiCall::Remove(package) => write!(f, "remove {}", package),
+            AptModuleInternalApiCall::Install(packages) => {
+                write!(f, "install {}", packages.join(" "))
+            }
+            AptModuleInternalApiCall::UpgradePackages(packages) => {
+                write!(f, "upgrade {}", packages.join(" "))
+            }
+            AptModuleInternalApiCall::Remove(packages) => {
+                write!(f, "remove {}", packages.join(" "))
+            }
             AptModuleInternalApiCall::Upgrade => write!(f, "upgrade"),
         }
     }
@@ -73,11 +92,13 @@ pub enum PackageExpectedState {
     Present,
     /// Package should be removed
     Absent,
+    /// Package should be installed and upgraded to the latest available version
+    Latest,
 }
 
 /// Configuration for APT package management
 ///
-/// Use the builder to specify package state (Present/Absent) and optionally trigger
+/// Use the builder to specify package state (Present/Absent/Latest) and optionally trigger
 /// a system upgrade.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -85,8 +106,10 @@ pub enum PackageExpectedState {
 pub struct AptBlockExpectedState {
     /// Desired state of the package(s)
     state: Option<PackageExpectedState>,
-    /// Package name to manage
+    /// Package name to manage, optionally pinned (`name=version`)
     package: Option<String>,
+    /// Package names to manage in a single transaction, optionally pinned (`name=version`)
+    packages: Option<Vec<String>>,
     /// Whether to perform a full system upgrade
     upgrade: Option<bool>,
 }
@@ -102,6 +125,7 @@ impl AptBlockExpectedState {
         AptBlockExpectedState {
             state: None,
             package: None,
+            packages: None,
             upgrade: None,
         }
     }
@@ -121,6 +145,26 @@ impl AptBlockExpectedState {
         self
     }
 
+    /// Manage several packages at once, installed or removed in a single transaction.
+    pub fn with_packages_state(
+        &mut self,
+        package_names: &[&str],
+        package_state: PackageExpectedState,
+    ) -> &mut Self {
+        self.packages = Some(package_names.iter().map(|name| name.to_string()).collect());
+        self.state = Some(package_state);
+        self
+    }
+
+    /// All package specifications of this block (`Package` and `Packages` together).
+    fn package_specs(&self) -> Vec<String> {
+        self.package
+            .iter()
+            .chain(self.packages.iter().flatten())
+            .cloned()
+            .collect()
+    }
+
     pub fn build(&self) -> Result<AptBlockExpectedState, RegentError> {
         if let Err(details) = self.check() {
             return Err(details);
@@ -131,23 +175,34 @@ impl AptBlockExpectedState {
 
 impl Check for AptBlockExpectedState {
     fn check(&self) -> Result<(), RegentError> {
-        if let (None, None, None) = (&self.state, &self.package, self.upgrade) {
+        let package_specs = self.package_specs();
+        if let (None, true, None) = (&self.state, package_specs.is_empty(), self.upgrade) {
             return Err(RegentError::IncoherentExpectedState(format!(
                 "All parameters are unset. Please describe the expected state."
             )));
         }
-        if let (None, Some(package_name)) = (&self.state, &self.package) {
+        if let (None, false) = (&self.state, package_specs.is_empty()) {
             return Err(RegentError::IncoherentExpectedState(format!(
-                "Missing 'state' parameter. What is the expected state of the package ({}) ?",
-                package_name
+                "Missing 'state' parameter. What is the expected state of the package(s) ({}) ?",
+                package_specs.join(", ")
             )));
         }
-        if let (Some(package_expected_state), None) = (&self.state, &self.package) {
+        if let (Some(package_expected_state), true) = (&self.state, package_specs.is_empty()) {
             return Err(RegentError::IncoherentExpectedState(format!(
                 "Missing 'package' parameter. Which package should be {:?} ?",
                 package_expected_state
             )));
         }
+        if let Some(PackageExpectedState::Latest | PackageExpectedState::Absent) = &self.state
+            && let Some(pinned_package) = package_specs
+                .iter()
+                .find(|raw_spec| PackageSpec::from_equals_syntax(raw_spec).version.is_some())
+        {
+            return Err(RegentError::IncoherentExpectedState(format!(
+                "Package {} is pinned to a version, which only makes sense with the Present state",
+                pinned_package
+            )));
+        }
         Ok(())
     }
 
@@ -215,42 +270,73 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for AptBlockExpectedState {
 
         let mut remediations: Vec<Remediation> = Vec::new();
 
-        match &self.state {
-            None => {}
-            Some(state) => {
-                match state {
-                    PackageExpectedState::Present => {
-                        // Check is package is already installed or needs to be
-                        if is_package_installed(host_handler, self.package.clone().unwrap()).await {
+        if let Some(state) = &self.state {
+            let mut packages_to_install: Vec<String> = Vec::new();
+            let mut packages_to_upgrade: Vec<String> = Vec::new();
+            let mut packages_to_remove: Vec<String> = Vec::new();
+
+            for raw_spec in self.package_specs() {
+                let package_spec = PackageSpec::from_equals_syntax(&raw_spec);
+                let installed_version = installed_version(host_handler, &package_spec.name).await?;
+
+                match (state, installed_version) {
+                    (PackageExpectedState::Present, Some(installed_version)) => {
+                        if package_spec.is_satisfied_by(&installed_version) {
                             remediations.push(Remediation::None(format!(
-                                "{} already present",
-                                self.package.clone().unwrap()
+                                "{} already present ({})",
+                                package_spec.name, installed_version
                             )));
                         } else {
-                            // Package is absent and needs to be installed
-                            remediations.push(Remediation::Apt(AptApiCall::from(
-                                AptModuleInternalApiCall::Install(self.package.clone().unwrap()),
-                                privilege.clone(),
-                            )));
+                            // Installed version does not match the pinned one
+                            packages_to_install.push(raw_spec);
                         }
                     }
-                    PackageExpectedState::Absent => {
-                        // Check is package is already absent or needs to be removed
-                        if is_package_installed(host_handler, self.package.clone().unwrap()).await {
-                            // Package is present and needs to be removed
-                            remediations.push(Remediation::Apt(AptApiCall::from(
-                                AptModuleInternalApiCall::Remove(self.package.clone().unwrap()),
-                                privilege.clone(),
-                            )));
-                        } else {
-                            remediations.push(Remediation::None(format!(
-                                "{} already absent",
-                                self.package.clone().unwrap()
-                            )));
+                    (PackageExpectedState::Present | PackageExpectedState::Latest, None) => {
+                        packages_to_install.push(raw_spec);
+                    }
+                    (PackageExpectedState::Latest, Some(installed_version)) => {
+                        match candidate_version(host_handler, &package_spec.name).await? {
+                            Some(candidate_version) if candidate_version != installed_version => {
+                                packages_to_upgrade.push(package_spec.name);
+                            }
+                            _ => {
+                                remediations.push(Remediation::None(format!(
+                                    "{} already latest ({})",
+                                    package_spec.name, installed_version
+                                )));
+                            }
                         }
                     }
+                    (PackageExpectedState::Absent, Some(_)) => {
+                        packages_to_remove.push(package_spec.name);
+                    }
+                    (PackageExpectedState::Absent, None) => {
+                        remediations.push(Remediation::None(format!(
+                            "{} already absent",
+                            package_spec.name
+                        )));
+                    }
                 }
             }
+
+            if !packages_to_install.is_empty() {
+                remediations.push(Remediation::Apt(AptApiCall::from(
+                    AptModuleInternalApiCall::Install(packages_to_install),
+                    privilege.clone(),
+                )));
+            }
+            if !packages_to_upgrade.is_empty() {
+                remediations.push(Remediation::Apt(AptApiCall::from(
+                    AptModuleInternalApiCall::UpgradePackages(packages_to_upgrade),
+                    privilege.clone(),
+                )));
+            }
+            if !packages_to_remove.is_empty() {
+                remediations.push(Remediation::Apt(AptApiCall::from(
+                    AptModuleInternalApiCall::Remove(packages_to_remove),
+                    privilege.clone(),
+                )));
+            }
         }
 
         // TODO: have this do an "apt update"
@@ -309,11 +395,14 @@ impl Check for AptApiCall {
 impl AptApiCall {
     pub fn display(&self) -> String {
         match &self.api_call {
-            AptModuleInternalApiCall::Install(package_name) => {
-                return format!("Install - {}", package_name);
+            AptModuleInternalApiCall::Install(packages) => {
+                return format!("Install - {}", packages.join(", "));
             }
-            AptModuleInternalApiCall::Remove(package_name) => {
-                return format!("Remove - {}", package_name);
+            AptModuleInternalApiCall::UpgradePackages(packages) => {
+                return format!("Upgrade - {}", packages.join(", "));
+            }
+            AptModuleInternalApiCall::Remove(packages) => {
+                return format!("Remove - {}", packages.join(", "));
             }
             AptModuleInternalApiCall::Upgrade => {
                 return String::from("Upgrade");
@@ -334,72 +423,47 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for AptApiCall {
             self.check_host_compatibility(props)?;
         }
 
-        let (cmd, privilege, package_name) = match &self.api_call {
-            AptModuleInternalApiCall::Install(package_name) => (
-                format!(
-                    "DEBIAN_FRONTEND=noninteractive apt-get install -y {}",
-                    package_name
-                ),
-                &self.privilege,
-                Some(package_name.clone()),
-            ),
-            AptModuleInternalApiCall::Remove(package_name) => (
-                format!(
-                    "DEBIAN_FRONTEND=noninteractive apt-get remove --purge -y {}",
-                    package_name
-                ),
-                &self.privilege,
-                Some(package_name.clone()),
-            ),
-            AptModuleInternalApiCall::Upgrade => (
-                "apt-get update && DEBIAN_FRONTEND=noninteractive apt-get upgrade -y".to_string(),
-                &self.privilege,
-                None,
-            ),
+        let packages = match &self.api_call {
+            AptModuleInternalApiCall::Install(packages)
+            | AptModuleInternalApiCall::UpgradePackages(packages)
+            | AptModuleInternalApiCall::Remove(packages) => packages.as_slice(),
+            AptModuleInternalApiCall::Upgrade => [].as_slice(),
         };
 
         let cmd_result = host_handler
-            .run_command(cmd.as_str(), privilege)
-            .await
-            .unwrap();
-
-        if cmd_result.return_code == 0 {
-            // Post-install verification: verify the package state matches the expected operation
-            if let Some(pkg_name) = &package_name {
-                let verification_result = match &self.api_call {
-                    AptModuleInternalApiCall::Install(_) => {
-                        // Verify the package is now installed
-                        is_package_installed(host_handler, pkg_name.clone()).await
-                    }
-                    AptModuleInternalApiCall::Remove(_) => {
-                        // Verify the package is now removed
-                        !is_package_installed(host_handler, pkg_name.clone()).await
-                    }
-                    AptModuleInternalApiCall::Upgrade => {
-                        // Upgrade is a system-wide operation, can't easily verify individual packages
-                        // Just return success based on the command result
-                        true
-                    }
-                };
-
-                if verification_result {
-                    Ok(InternalApiCallOutcome::Success(None))
-                } else {
-                    Ok(InternalApiCallOutcome::Failure(format!(
-                        "Command succeeded but post-verification failed: package {} state does not match expected",
-                        pkg_name
-                    )))
-                }
-            } else {
-                // For upgrade operations without specific package verification
-                Ok(InternalApiCallOutcome::Success(None))
-            }
-        } else {
-            Ok(InternalApiCallOutcome::Failure(format!(
+            .run_command(self.command().as_str(), &self.privilege)
+            .await?;
+
+        if cmd_result.return_code != 0 {
+            return Ok(InternalApiCallOutcome::Failure(format!(
                 "RC : {}, STDOUT : {}, STDERR : {}",
                 cmd_result.return_code, cmd_result.stdout, cmd_result.stderr
-            )))
+            )));
         }
+
+        // Post-install verification: verify each package state matches the expected operation.
+        // Upgrade is a system-wide operation, individual packages are not verified.
+        for raw_spec in packages {
+            let package_spec = PackageSpec::from_equals_syntax(raw_spec);
+            let installed_version = installed_version(host_handler, &package_spec.name).await?;
+
+            let verification_result = match (&self.api_call, installed_version) {
+                (AptModuleInternalApiCall::Remove(_), installed_version) => {
+                    installed_version.is_none()
+                }
+                (_, Some(installed_version)) => package_spec.is_satisfied_by(&installed_version),
+                (_, None) => false,
+            };
+
+            if !verification_result {
+                return Ok(InternalApiCallOutcome::Failure(format!(
+                    "Command succeeded but post-verification failed: package {} state does not match expected",
+                    raw_spec
+                )));
+            }
+        }
+
+        Ok(InternalApiCallOutcome::Success(None))
     }
 }
 
@@ -410,24 +474,80 @@ impl AptApiCall {
             privilege,
         }
     }
+
+    /// Command line of this API call, each package being a single argument
+    fn command(&self) -> String {
+        match &self.api_call {
+            AptModuleInternalApiCall::Install(packages) => ShellCommand::new(
+                "DEBIAN_FRONTEND=noninteractive apt-get install -y --allow-downgrades",
+            )
+            .args(packages)
+            .to_string(),
+            AptModuleInternalApiCall::UpgradePackages(packages) => ShellCommand::new(
+                "DEBIAN_FRONTEND=noninteractive apt-get install -y --only-upgrade",
+            )
+            .args(packages)
+            .to_string(),
+            AptModuleInternalApiCall::Remove(packages) => {
+                ShellCommand::new("DEBIAN_FRONTEND=noninteractive apt-get remove --purge -y")
+                    .args(packages)
+                    .to_string()
+            }
+            AptModuleInternalApiCall::Upgrade => {
+                "apt-get update && DEBIAN_FRONTEND=noninteractive apt-get upgrade -y".to_string()
+            }
+        }
+    }
 }
 
-async fn is_package_installed<Handler: HostHandler>(
+/// Installed version of a package, `None` if it is not installed.
+async fn installed_version<Handler: HostHandler>(
     host_handler: &mut Handler,
-    package: String,
-) -> bool {
+    package_name: &str,
+) -> Result<Option<String>, RegentError> {
     let test = host_handler
-        .run_command(format!("dpkg -s {}", package).as_str(), &Privilege::None)
-        .await
-        .unwrap();
+        .run_command(
+            ShellCommand::new("dpkg-query -W -f='${Status} ${Version}'")
+                .arg(package_name)
+                .as_str(),
+            &Privilege::None,
+        )
+        .await?;
+
+    // Expected output : "install ok installed 1.24.0-1"
+    match test.stdout.trim().strip_prefix("install ok installed ") {
+        Some(version) if test.return_code == 0 => Ok(Some(version.trim().to_string())),
+        _ => Ok(None),
+    }
+}
 
-    if test.return_code == 0 { true } else { false }
+/// Version APT would install for a package, according to the current APT cache.
+async fn candidate_version<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    package_name: &str,
+) -> Result<Option<String>, RegentError> {
+    let policy = host_handler
+        .run_command(
+            ShellCommand::new("LC_ALL=C apt-cache policy")
+                .arg(package_name)
+                .as_str(),
+            &Privilege::None,
+        )
+        .await?;
+
+    Ok(policy
+        .stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Candidate:"))
+        .map(|candidate| candidate.trim().to_string())
+        .filter(|candidate| candidate != "(none)"))
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_apt_module_block_from_yaml_str() {
@@ -456,6 +576,35 @@ mod tests {
         assert_eq!(attributes[2].upgrade, Some(true));
     }
 
+    #[test]
+    fn parsing_apt_module_block_with_several_packages_from_yaml_str() {
+        let raw_attributes = "---
+- Packages: [nginx=1.24.*, curl]
+  State: !Present
+
+- Package: git
+  Packages: [htop]
+  State: !Latest
+    ";
+
+        let attributes: Vec<AptBlockExpectedState> = yaml_serde::from_str(raw_attributes).unwrap();
+
+        assert_eq!(attributes[0].package_specs(), vec!["nginx=1.24.*", "curl"]);
+        assert!(attributes[0].check().is_ok());
+
+        assert_eq!(attributes[1].package_specs(), vec!["git", "htop"]);
+        assert_eq!(attributes[1].state, Some(PackageExpectedState::Latest));
+        assert!(attributes[1].check().is_ok());
+
+        // A pinned version only makes sense with the Present state
+        let raw_attribute = "---
+Packages: [nginx=1.24.*]
+State: !Latest
+    ";
+        let yaml_part = yaml_serde::from_str::<AptBlockExpectedState>(raw_attribute);
+        assert!(yaml_part.unwrap().check().is_err());
+    }
+
     #[test]
     fn rejecting_incorrect_apt_module_block_from_yaml_str() {
         let raw_attribute = "---
@@ -480,4 +629,17 @@ unknown_key: unknown_value
     ";
         assert!(yaml_serde::from_str::<AptBlockExpectedState>(raw_attribute).is_err());
     }
+
+    #[test]
+    fn quoting_packages_in_commands() {
+        for value in HOSTILE_VALUES {
+            for api_call in [
+                AptModuleInternalApiCall::Install(vec!["curl".into(), value.into()]),
+                AptModuleInternalApiCall::UpgradePackages(vec![value.into()]),
+                AptModuleInternalApiCall::Remove(vec![value.into()]),
+            ] {
+                assert_no_injection(&AptApiCall::from(api_call, Privilege::None).command());
+            }
+        }
+    }
 }
@@ -39,9 +39,12 @@
 //!       Privilege: !WithSudo
 //! ```
 
+use crate::command::CommandResult;
+use crate::command::ShellCommand;
 use crate::error::RegentError;
+use crate::hosts::handlers::install_file;
 use crate::hosts::managed_host::InternalApiCallOutcome;
-use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
+use crate::hosts::managed_host::{AssessCompliance, PreviewChange, ReachCompliance, Timeout};
 use crate::hosts::properties::{HostProperties, LinuxFlavor, LinuxSpecifics, OsKind};
 use crate::secrets::SecretProvidersPool;
 use crate::state::Check;
@@ -49,6 +52,7 @@ use crate::state::attribute::HostHandler;
 use crate::state::attribute::Privilege;
 use crate::state::attribute::Remediation;
 use crate::state::compliance::AttributeComplianceAssessment;
+use crate::state::diff;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -450,32 +454,54 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for AptRepoApiCall {
             self.check_host_compatibility(props)?;
         }
 
-        let (cmd, privilege) = match &self.api_call {
-            AptRepoModuleInternalApiCall::WriteFile { path, content } => (
-                format!("printf '{}' > {}", escape_for_printf(content), path),
-                &self.privilege,
-            ),
-            AptRepoModuleInternalApiCall::RemoveFile { path } => {
-                (format!("rm -f {}", path), &self.privilege)
+        let cmd = match &self.api_call {
+            AptRepoModuleInternalApiCall::WriteFile { path, content } => {
+                let cmd_result = install_file(
+                    host_handler,
+                    path,
+                    content.as_bytes(),
+                    None,
+                    &self.privilege,
+                )
+                .await?;
+                return Ok(outcome(cmd_result));
             }
-            AptRepoModuleInternalApiCall::UpdateCache => {
-                ("apt-get update".to_string(), &self.privilege)
+            AptRepoModuleInternalApiCall::RemoveFile { path } => {
+                ShellCommand::new("rm -f").arg(path).to_string()
             }
+            AptRepoModuleInternalApiCall::UpdateCache => "apt-get update".to_string(),
         };
 
         let cmd_result = host_handler
-            .run_command(cmd.as_str(), privilege)
+            .run_command(cmd.as_str(), &self.privilege)
             .await
             .unwrap();
 
-        if cmd_result.return_code == 0 {
-            Ok(InternalApiCallOutcome::Success(None))
-        } else {
-            Ok(InternalApiCallOutcome::Failure(format!(
-                "RC: {}, STDOUT: {}, STDERR: {}",
-                cmd_result.return_code, cmd_result.stdout, cmd_result.stderr
-            )))
-        }
+        Ok(outcome(cmd_result))
+    }
+}
+
+fn outcome(cmd_result: CommandResult) -> InternalApiCallOutcome {
+    if cmd_result.return_code == 0 {
+        InternalApiCallOutcome::Success(None)
+    } else {
+        InternalApiCallOutcome::Failure(format!(
+            "RC: {}, STDOUT: {}, STDERR: {}",
+            cmd_result.return_code, cmd_result.stdout, cmd_result.stderr
+        ))
+    }
+}
+
+impl<Handler: HostHandler> PreviewChange<Handler> for AptRepoApiCall {
+    async fn preview(&self, host_handler: &mut Handler) -> Result<Option<String>, RegentError> {
+        let (path, wanted_content) = match &self.api_call {
+            AptRepoModuleInternalApiCall::WriteFile { path, content } => (path, content.as_str()),
+            AptRepoModuleInternalApiCall::RemoveFile { path } => (path, ""),
+            AptRepoModuleInternalApiCall::UpdateCache => return Ok(None),
+        };
+
+        let current_content = diff::current_content(host_handler, path, &self.privilege).await?;
+        Ok(diff::unified_diff(path, &current_content, wanted_content))
     }
 }
 
@@ -517,13 +543,6 @@ fn build_deb822_content(block: &AptRepoBlockExpectedState) -> String {
     lines.join("\n") + "\n"
 }
 
-fn escape_for_printf(content: &str) -> String {
-    content
-        .replace('\\', "\\\\")
-        .replace('%', "%%")
-        .replace('\n', "\\n")
-}
-
 /// Extract URIs from deb822 format content for source verification.
 /// This ensures that repository URLs are explicitly checked during assessment.
 fn extract_uris_from_deb822(content: &str) -> Option<Vec<String>> {
@@ -546,7 +565,10 @@ async fn read_file<Handler: HostHandler>(
     path: &str,
 ) -> Result<Option<String>, String> {
     match host_handler
-        .run_command(&format!("cat {}", path), &Privilege::None)
+        .run_command(
+            ShellCommand::new("cat").arg(path).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(result) => {
@@ -654,10 +676,4 @@ mod tests {
         let content = build_legacy_content("  deb http://example.com focal main  ");
         assert_eq!(content, "deb http://example.com focal main\n");
     }
-
-    #[test]
-    fn escape_for_printf_handles_special_chars() {
-        let escaped = escape_for_printf("a\nb%c\\d");
-        assert_eq!(escaped, "a\\nb%%c\\\\d");
-    }
 }
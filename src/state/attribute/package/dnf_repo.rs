@@ -44,9 +44,10 @@
 //!       Privilege: !WithSudo
 //! ```
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
-use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
+use crate::hosts::managed_host::{AssessCompliance, PreviewChange, ReachCompliance, Timeout};
 use crate::hosts::properties::{HostProperties, LinuxFlavor, LinuxSpecifics, OsKind};
 use crate::secrets::SecretProvidersPool;
 use crate::state::Check;
@@ -54,6 +55,7 @@ use crate::state::attribute::HostHandler;
 use crate::state::attribute::Privilege;
 use crate::state::attribute::Remediation;
 use crate::state::compliance::AttributeComplianceAssessment;
+use crate::state::diff;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -238,7 +240,7 @@ impl Check for DnfRepoBlockExpectedState {
     ) -> Result<(), RegentError> {
         match host_properties.os_kind() {
             OsKind::Linux(LinuxSpecifics {
-                linux_flavor: LinuxFlavor::Fedora,
+                linux_flavor: LinuxFlavor::Fedora | LinuxFlavor::Rhel | LinuxFlavor::Amazon,
                 ..
             }) => Ok(()),
             incompatible_os_kind => Err(RegentError::IncompatibleHost(format!(
@@ -439,7 +441,7 @@ impl Check for DnfRepoApiCall {
     ) -> Result<(), RegentError> {
         match host_properties.os_kind() {
             OsKind::Linux(LinuxSpecifics {
-                linux_flavor: LinuxFlavor::Fedora,
+                linux_flavor: LinuxFlavor::Fedora | LinuxFlavor::Rhel | LinuxFlavor::Amazon,
                 ..
             }) => Ok(()),
             incompatible_os_kind => Err(RegentError::IncompatibleHost(format!(
@@ -462,7 +464,7 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for DnfRepoApiCall {
             self.check_host_compatibility(props)?;
         }
 
-        let cmd = match &self.api_call {
+        let script = match &self.api_call {
             DnfRepoModuleInternalApiCall::UpsertSection {
                 file_name,
                 repo_name,
@@ -474,6 +476,8 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for DnfRepoApiCall {
             } => build_remove_cmd(file_name, repo_name),
         };
 
+        // Run through 'sh -c' so that the whole script gets the privilege
+        let cmd = ShellCommand::new("sh -c").arg(script.as_str());
         let cmd_result = host_handler
             .run_command(cmd.as_str(), &self.privilege)
             .await
@@ -490,6 +494,29 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for DnfRepoApiCall {
     }
 }
 
+impl<Handler: HostHandler> PreviewChange<Handler> for DnfRepoApiCall {
+    async fn preview(&self, host_handler: &mut Handler) -> Result<Option<String>, RegentError> {
+        let (file_name, repo_name, section_content) = match &self.api_call {
+            DnfRepoModuleInternalApiCall::UpsertSection {
+                file_name,
+                repo_name,
+                section_content,
+            } => (file_name, repo_name, section_content.as_str()),
+            DnfRepoModuleInternalApiCall::RemoveSection {
+                file_name,
+                repo_name,
+            } => (file_name, repo_name, ""),
+        };
+        let path = format!("/etc/yum.repos.d/{}.repo", file_name);
+
+        let current_content = diff::current_content(host_handler, &path, &self.privilege).await?;
+        // Same outcome as the awk script of build_upsert_cmd / build_remove_cmd
+        let wanted_content = without_section(&current_content, repo_name) + section_content;
+
+        Ok(diff::unified_diff(&path, &current_content, &wanted_content))
+    }
+}
+
 fn build_section_content(block: &DnfRepoBlockExpectedState) -> String {
     let mut lines: Vec<String> = Vec::new();
     lines.push(format!("[{}]", block.name));
@@ -554,11 +581,21 @@ fn extract_section(content: &str, name: &str) -> Option<String> {
     }
 }
 
-fn escape_for_printf(content: &str) -> String {
+/// Content of a .repo file without the section of the given repository.
+fn without_section(content: &str, name: &str) -> String {
+    let header = format!("[{}]", name);
+    let mut in_section = false;
+
     content
-        .replace('\\', "\\\\")
-        .replace('%', "%%")
-        .replace('\n', "\\n")
+        .lines()
+        .filter(|line| {
+            if line.starts_with('[') {
+                in_section = *line == header;
+            }
+            !in_section
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
 }
 
 /// Extract source URLs from DNF/YUM repository section content for source verification.
@@ -595,28 +632,43 @@ fn extract_source_urls_from_section(content: &str) -> Option<Vec<String>> {
     if urls.is_empty() { None } else { Some(urls) }
 }
 
-fn build_upsert_cmd(file_name: &str, repo_name: &str, section: &str) -> String {
-    let escaped = escape_for_printf(section);
+/// awk program printing a repo file without the section whose header is given in `header`
+const AWK_WITHOUT_SECTION: &str = "$0 == header { p = 1; next } /^\\[/ { p = 0 } !p";
+
+fn build_upsert_cmd(file_name: &str, repo_name: &str, section: &str) -> ShellCommand {
     let path = format!("/etc/yum.repos.d/{}.repo", file_name);
     let tmp = format!("/tmp/.regent_{}_tmp", file_name);
-    format!(
-        "{{ awk '/^\\[{n}\\]$/{{p=1}} /^\\[/ && !/^\\[{n}\\]$/{{p=0}} !p' {path} 2>/dev/null; printf '{esc}'; }} > {tmp} && mv {tmp} {path}",
-        n = repo_name,
-        path = path,
-        esc = escaped,
-        tmp = tmp,
-    )
+    ShellCommand::new("{ awk")
+        .arg("-v")
+        .arg(format!("header=[{}]", repo_name))
+        .arg(AWK_WITHOUT_SECTION)
+        .arg(&path)
+        .raw("2>/dev/null; printf %s")
+        .arg(section)
+        .raw("; } >")
+        .arg(&tmp)
+        .and(ShellCommand::new("mv").arg(&tmp).arg(&path))
 }
 
-fn build_remove_cmd(file_name: &str, repo_name: &str) -> String {
+fn build_remove_cmd(file_name: &str, repo_name: &str) -> ShellCommand {
     let path = format!("/etc/yum.repos.d/{}.repo", file_name);
     let tmp = format!("/tmp/.regent_{}_tmp", file_name);
-    format!(
-        "{{ awk '/^\\[{n}\\]$/{{p=1}} /^\\[/ && !/^\\[{n}\\]$/{{p=0}} !p' {path} 2>/dev/null; }} > {tmp} && if [ -s {tmp} ]; then mv {tmp} {path}; else rm -f {tmp} {path}; fi",
-        n = repo_name,
-        path = path,
-        tmp = tmp,
-    )
+    ShellCommand::new("{ awk")
+        .arg("-v")
+        .arg(format!("header=[{}]", repo_name))
+        .arg(AWK_WITHOUT_SECTION)
+        .arg(&path)
+        .raw("2>/dev/null; } >")
+        .arg(&tmp)
+        .and(
+            ShellCommand::new("if [ -s")
+                .arg(&tmp)
+                .raw("]; then mv")
+                .args([&tmp, &path])
+                .raw("; else rm -f")
+                .args([&tmp, &path])
+                .raw("; fi"),
+        )
 }
 
 async fn read_file<Handler: HostHandler>(
@@ -624,7 +676,10 @@ async fn read_file<Handler: HostHandler>(
     path: &str,
 ) -> Result<Option<String>, String> {
     match host_handler
-        .run_command(&format!("cat {}", path), &Privilege::None)
+        .run_command(
+            ShellCommand::new("cat").arg(path).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(result) => {
@@ -641,6 +696,7 @@ async fn read_file<Handler: HostHandler>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_dnf_repo_from_yaml() {
@@ -743,4 +799,24 @@ mod tests {
             .unwrap();
         assert_eq!(block.repo_filename(), "custom-file");
     }
+
+    #[test]
+    fn without_section_keeps_other_sections() {
+        let content = "[other]\nbaseurl=http://other.com\n[myrepo]\nbaseurl=http://example.com\n[another]\nenabled=1\n";
+        assert_eq!(
+            without_section(content, "myrepo"),
+            "[other]\nbaseurl=http://other.com\n[another]\nenabled=1\n"
+        );
+    }
+
+    #[test]
+    fn quoting_values_in_repo_commands() {
+        for value in HOSTILE_VALUES {
+            assert_no_injection(build_upsert_cmd(value, "epel", "[epel]\n").as_str());
+            assert_no_injection(build_upsert_cmd("epel", value, "[epel]\n").as_str());
+            assert_no_injection(build_upsert_cmd("epel", "epel", value).as_str());
+            assert_no_injection(build_remove_cmd(value, "epel").as_str());
+            assert_no_injection(build_remove_cmd("epel", value).as_str());
+        }
+    }
 }
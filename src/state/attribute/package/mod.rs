@@ -2,4 +2,5 @@ pub mod apt;
 pub mod apt_repo;
 pub mod dnf_repo;
 pub mod pacman;
+pub mod spec;
 pub mod yumdnf;
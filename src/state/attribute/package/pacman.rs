@@ -32,8 +32,16 @@
 //!       Package: nginx
 //!       State: !Present
 //!       Privilege: !WithSudo
+//!   - Detail: !Pacman
+//!       Packages: [nginx=1.24.0-1, curl]
+//!       State: !Present
+//!       Privilege: !WithSudo
 //! ```
+//!
+//! Packages can be pinned to a version with `name=version`. With the `Latest` state, installed
+//! packages are upgraded whenever the synchronized databases know a newer version.
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
 use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
@@ -43,6 +51,7 @@ use crate::state::Check;
 use crate::state::attribute::HostHandler;
 use crate::state::attribute::Privilege;
 use crate::state::attribute::Remediation;
+use crate::state::attribute::package::spec::PackageSpec;
 use crate::state::compliance::AttributeComplianceAssessment;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -50,16 +59,26 @@ use std::time::Duration;
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub enum PacmanModuleInternalApiCall {
-    Install(String),
-    Remove(String),
+    /// Install (or switch to the pinned version of) packages, in a single transaction
+    Install(Vec<String>),
+    /// Upgrade already installed packages to their latest version
+    UpgradePackages(Vec<String>),
+    Remove(Vec<String>),
     Upgrade,
 }
 
 impl std::fmt::Display for PacmanModuleInternalApiCall {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            PacmanModuleInternalApiCall::Install(package) => write!(f, "install {}", package),
-            PacmanModuleInternalApiCall::Remove(package) => write!(f, "remove {}", package),
+            PacmanModuleInternalApiCall::Install(packages) => {
+                write!(f, "install {}", packages.join(" "))
+            }
+            PacmanModuleInternalApiCall::UpgradePackages(packages) => {
+                write!(f, "upgrade {}", packages.join(" "))
+            }
+            PacmanModuleInternalApiCall::Remove(packages) => {
+                write!(f, "remove {}", packages.join(" "))
+            }
             PacmanModuleInternalApiCall::Upgrade => write!(f, "upgrade"),
         }
     }
@@ -73,6 +92,8 @@ pub enum PackageExpectedState {
     Present,
     /// Package should be removed
     Absent,
+    /// Package should be installed and upgraded to the latest available version
+    Latest,
 }
 
 /// Configuration for Pacman package management
@@ -82,8 +103,10 @@ pub enum PackageExpectedState {
 pub struct PacmanBlockExpectedState {
     /// Desired state of the package(s)
     state: Option<PackageExpectedState>,
-    /// Package name to manage
+    /// Package name to manage, optionally pinned (`name=version`)
     package: Option<String>,
+    /// Package names to manage in a single transaction, optionally pinned (`name=version`)
+    packages: Option<Vec<String>>,
     /// Whether to perform a full system upgrade
     upgrade: Option<bool>,
 }
@@ -99,6 +122,7 @@ impl PacmanBlockExpectedState {
         PacmanBlockExpectedState {
             state: None,
             package: None,
+            packages: None,
             upgrade: None,
         }
     }
@@ -118,6 +142,26 @@ impl PacmanBlockExpectedState {
         self
     }
 
+    /// Manage several packages at once, installed or removed in a single transaction.
+    pub fn with_packages_state(
+        &mut self,
+        package_names: &[&str],
+        package_state: PackageExpectedState,
+    ) -> &mut Self {
+        self.packages = Some(package_names.iter().map(|name| name.to_string()).collect());
+        self.state = Some(package_state);
+        self
+    }
+
+    /// All package specifications of this block (`Package` and `Packages` together).
+    fn package_specs(&self) -> Vec<String> {
+        self.package
+            .iter()
+            .chain(self.packages.iter().flatten())
+            .cloned()
+            .collect()
+    }
+
     pub fn build(&self) -> Result<PacmanBlockExpectedState, RegentError> {
         if let Err(details) = self.check() {
             return Err(details);
@@ -128,23 +172,34 @@ impl PacmanBlockExpectedState {
 
 impl Check for PacmanBlockExpectedState {
     fn check(&self) -> Result<(), RegentError> {
-        if let (None, None, None) = (&self.state, &self.package, self.upgrade) {
+        let package_specs = self.package_specs();
+        if let (None, true, None) = (&self.state, package_specs.is_empty(), self.upgrade) {
             return Err(RegentError::IncoherentExpectedState(format!(
                 "All parameters are unset. Please describe the expected state."
             )));
         }
-        if let (None, Some(package_name)) = (&self.state, &self.package) {
+        if let (None, false) = (&self.state, package_specs.is_empty()) {
             return Err(RegentError::IncoherentExpectedState(format!(
-                "Missing 'state' parameter. What is the expected state of the package ({}) ?",
-                package_name
+                "Missing 'state' parameter. What is the expected state of the package(s) ({}) ?",
+                package_specs.join(", ")
             )));
         }
-        if let (Some(package_expected_state), None) = (&self.state, &self.package) {
+        if let (Some(package_expected_state), true) = (&self.state, package_specs.is_empty()) {
             return Err(RegentError::IncoherentExpectedState(format!(
                 "Missing 'package' parameter. Which package should be {:?} ?",
                 package_expected_state
             )));
         }
+        if let Some(PackageExpectedState::Latest | PackageExpectedState::Absent) = &self.state
+            && let Some(pinned_package) = package_specs
+                .iter()
+                .find(|raw_spec| PackageSpec::from_equals_syntax(raw_spec).version.is_some())
+        {
+            return Err(RegentError::IncoherentExpectedState(format!(
+                "Package {} is pinned to a version, which only makes sense with the Present state",
+                pinned_package
+            )));
+        }
         Ok(())
     }
 
@@ -190,42 +245,70 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for PacmanBlockExpectedStat
 
         let mut remediations: Vec<Remediation> = Vec::new();
 
-        match &self.state {
-            None => {}
-            Some(state) => {
-                match state {
-                    PackageExpectedState::Present => {
-                        // Check is package is already installed or needs to be
-                        if is_package_installed(host_handler, self.package.clone().unwrap()).await {
+        if let Some(state) = &self.state {
+            let mut packages_to_install: Vec<String> = Vec::new();
+            let mut packages_to_upgrade: Vec<String> = Vec::new();
+            let mut packages_to_remove: Vec<String> = Vec::new();
+
+            for raw_spec in self.package_specs() {
+                let package_spec = PackageSpec::from_equals_syntax(&raw_spec);
+                let installed_version = installed_version(host_handler, &package_spec.name).await?;
+
+                match (state, installed_version) {
+                    (PackageExpectedState::Present, Some(installed_version)) => {
+                        if package_spec.is_satisfied_by(&installed_version) {
                             remediations.push(Remediation::None(format!(
-                                "{} already present",
-                                self.package.clone().unwrap()
+                                "{} already present ({})",
+                                package_spec.name, installed_version
                             )));
                         } else {
-                            // Package is absent and needs to be installed
-                            remediations.push(Remediation::Pacman(PacmanApiCall::from(
-                                PacmanModuleInternalApiCall::Install(self.package.clone().unwrap()),
-                                privilege.clone(),
-                            )));
+                            // Installed version does not match the pinned one
+                            packages_to_install.push(raw_spec);
                         }
                     }
-                    PackageExpectedState::Absent => {
-                        // Check is package is already absent or needs to be removed
-                        if is_package_installed(host_handler, self.package.clone().unwrap()).await {
-                            // Package is present and needs to be removed
-                            remediations.push(Remediation::Pacman(PacmanApiCall::from(
-                                PacmanModuleInternalApiCall::Remove(self.package.clone().unwrap()),
-                                privilege.clone(),
-                            )));
+                    (PackageExpectedState::Present | PackageExpectedState::Latest, None) => {
+                        packages_to_install.push(raw_spec);
+                    }
+                    (PackageExpectedState::Latest, Some(installed_version)) => {
+                        if is_update_available(host_handler, &package_spec.name).await? {
+                            packages_to_upgrade.push(package_spec.name);
                         } else {
                             remediations.push(Remediation::None(format!(
-                                "{} already absent",
-                                self.package.clone().unwrap()
+                                "{} already latest ({})",
+                                package_spec.name, installed_version
                             )));
                         }
                     }
+                    (PackageExpectedState::Absent, Some(_)) => {
+                        packages_to_remove.push(package_spec.name);
+                    }
+                    (PackageExpectedState::Absent, None) => {
+                        remediations.push(Remediation::None(format!(
+                            "{} already absent",
+                            package_spec.name
+                        )));
+                    }
                 }
             }
+
+            if !packages_to_install.is_empty() {
+                remediations.push(Remediation::Pacman(PacmanApiCall::from(
+                    PacmanModuleInternalApiCall::Install(packages_to_install),
+                    privilege.clone(),
+                )));
+            }
+            if !packages_to_upgrade.is_empty() {
+                remediations.push(Remediation::Pacman(PacmanApiCall::from(
+                    PacmanModuleInternalApiCall::UpgradePackages(packages_to_upgrade),
+                    privilege.clone(),
+                )));
+            }
+            if !packages_to_remove.is_empty() {
+                remediations.push(Remediation::Pacman(PacmanApiCall::from(
+                    PacmanModuleInternalApiCall::Remove(packages_to_remove),
+                    privilege.clone(),
+                )));
+            }
         }
 
         if let Some(value) = self.upgrade {
@@ -281,11 +364,14 @@ impl Check for PacmanApiCall {
 impl PacmanApiCall {
     pub fn display(&self) -> String {
         match &self.api_call {
-            PacmanModuleInternalApiCall::Install(package_name) => {
-                return format!("Install - {}", package_name);
+            PacmanModuleInternalApiCall::Install(packages) => {
+                return format!("Install - {}", packages.join(", "));
             }
-            PacmanModuleInternalApiCall::Remove(package_name) => {
-                return format!("Remove - {}", package_name);
+            PacmanModuleInternalApiCall::UpgradePackages(packages) => {
+                return format!("Upgrade - {}", packages.join(", "));
+            }
+            PacmanModuleInternalApiCall::Remove(packages) => {
+                return format!("Remove - {}", packages.join(", "));
             }
             PacmanModuleInternalApiCall::Upgrade => {
                 return String::from("Upgrade");
@@ -306,63 +392,47 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for PacmanApiCall {
             self.check_host_compatibility(props)?;
         }
 
-        let (cmd, privilege, package_name) = match &self.api_call {
-            PacmanModuleInternalApiCall::Install(package_name) => (
-                format!("pacman --noconfirm -S {}", package_name),
-                &self.privilege,
-                Some(package_name.clone()),
-            ),
-            PacmanModuleInternalApiCall::Remove(package_name) => (
-                format!("pacman --noconfirm -R {}", package_name),
-                &self.privilege,
-                Some(package_name.clone()),
-            ),
-            PacmanModuleInternalApiCall::Upgrade => {
-                ("pacman -Syu".to_string(), &self.privilege, None)
-            }
+        let packages = match &self.api_call {
+            PacmanModuleInternalApiCall::Install(packages)
+            | PacmanModuleInternalApiCall::UpgradePackages(packages)
+            | PacmanModuleInternalApiCall::Remove(packages) => packages.as_slice(),
+            PacmanModuleInternalApiCall::Upgrade => [].as_slice(),
         };
 
         let cmd_result = host_handler
-            .run_command(cmd.as_str(), privilege)
-            .await
-            .unwrap();
-
-        if cmd_result.return_code == 0 {
-            // Post-install verification: verify the package state matches the expected operation
-            if let Some(pkg_name) = &package_name {
-                let verification_result = match &self.api_call {
-                    PacmanModuleInternalApiCall::Install(_) => {
-                        // Verify the package is now installed
-                        is_package_installed(host_handler, pkg_name.clone()).await
-                    }
-                    PacmanModuleInternalApiCall::Remove(_) => {
-                        // Verify the package is now removed
-                        !is_package_installed(host_handler, pkg_name.clone()).await
-                    }
-                    PacmanModuleInternalApiCall::Upgrade => {
-                        // Upgrade is a system-wide operation, can't easily verify individual packages
-                        true
-                    }
-                };
-
-                if verification_result {
-                    Ok(InternalApiCallOutcome::Success(None))
-                } else {
-                    Ok(InternalApiCallOutcome::Failure(format!(
-                        "Command succeeded but post-verification failed: package {} state does not match expected",
-                        pkg_name
-                    )))
-                }
-            } else {
-                // For upgrade operations without specific package verification
-                Ok(InternalApiCallOutcome::Success(None))
-            }
-        } else {
-            Ok(InternalApiCallOutcome::Failure(format!(
+            .run_command(self.command().as_str(), &self.privilege)
+            .await?;
+
+        if cmd_result.return_code != 0 {
+            return Ok(InternalApiCallOutcome::Failure(format!(
                 "RC : {}, STDOUT : {}, STDERR : {}",
                 cmd_result.return_code, cmd_result.stdout, cmd_result.stderr
-            )))
+            )));
+        }
+
+        // Post-install verification: verify each package state matches the expected operation.
+        // Upgrade is a system-wide operation, individual packages are not verified.
+        for raw_spec in packages {
+            let package_spec = PackageSpec::from_equals_syntax(raw_spec);
+            let installed_version = installed_version(host_handler, &package_spec.name).await?;
+
+            let verification_result = match (&self.api_call, installed_version) {
+                (PacmanModuleInternalApiCall::Remove(_), installed_version) => {
+                    installed_version.is_none()
+                }
+                (_, Some(installed_version)) => package_spec.is_satisfied_by(&installed_version),
+                (_, None) => false,
+            };
+
+            if !verification_result {
+                return Ok(InternalApiCallOutcome::Failure(format!(
+                    "Command succeeded but post-verification failed: package {} state does not match expected",
+                    raw_spec
+                )));
+            }
         }
+
+        Ok(InternalApiCallOutcome::Success(None))
     }
 }
 
@@ -373,27 +443,79 @@ impl PacmanApiCall {
             privilege,
         }
     }
+
+    /// Command line of this API call, each package being a single argument
+    fn command(&self) -> String {
+        match &self.api_call {
+            PacmanModuleInternalApiCall::Install(packages) => {
+                ShellCommand::new("pacman --noconfirm -S")
+                    .args(packages)
+                    .to_string()
+            }
+            PacmanModuleInternalApiCall::UpgradePackages(packages) => {
+                ShellCommand::new("pacman --noconfirm -S --needed")
+                    .args(packages)
+                    .to_string()
+            }
+            PacmanModuleInternalApiCall::Remove(packages) => {
+                ShellCommand::new("pacman --noconfirm -R")
+                    .args(packages)
+                    .to_string()
+            }
+            PacmanModuleInternalApiCall::Upgrade => "pacman -Syu".to_string(),
+        }
+    }
 }
 
-async fn is_package_installed<Handler: HostHandler>(
+/// Installed version of a package, `None` if it is not installed.
+async fn installed_version<Handler: HostHandler>(
     host_handler: &mut Handler,
-    package: String,
-) -> bool {
+    package_name: &str,
+) -> Result<Option<String>, RegentError> {
     let test = host_handler
         .run_command(
-            format!("LC_ALL=en_US.UTF-8 pacman -Q -i {}", package).as_str(),
+            ShellCommand::new("LC_ALL=C pacman -Q")
+                .arg(package_name)
+                .as_str(),
             &Privilege::None,
         )
-        .await
-        .unwrap();
+        .await?;
+
+    // Expected output : "nginx 1.24.0-1"
+    match test
+        .stdout
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .as_slice()
+    {
+        [_, version] if test.return_code == 0 => Ok(Some(version.to_string())),
+        _ => Ok(None),
+    }
+}
 
-    if test.return_code == 0 { true } else { false }
+/// Whether the synchronized databases know a newer version of an installed package.
+async fn is_update_available<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    package_name: &str,
+) -> Result<bool, RegentError> {
+    // 'pacman -Qu' only lists (and exits with 0 for) upgradable packages
+    let test = host_handler
+        .run_command(
+            ShellCommand::new("LC_ALL=C pacman -Qu")
+                .arg(package_name)
+                .as_str(),
+            &Privilege::None,
+        )
+        .await?;
+
+    Ok(test.return_code == 0 && !test.stdout.trim().is_empty())
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_pacman_module_block_from_yaml_str() {
@@ -423,6 +545,29 @@ mod tests {
         assert_eq!(attributes[2].upgrade, Some(true));
     }
 
+    #[test]
+    fn parsing_pacman_module_block_with_several_packages_from_yaml_str() {
+        let raw_attributes = "---
+- Packages: [nginx=1.24.0-1, curl]
+  State: !Present
+
+- Packages: [git]
+  State: !Latest
+    ";
+
+        let attributes: Vec<PacmanBlockExpectedState> =
+            yaml_serde::from_str(raw_attributes).unwrap();
+
+        assert_eq!(
+            attributes[0].package_specs(),
+            vec!["nginx=1.24.0-1", "curl"]
+        );
+        assert!(attributes[0].check().is_ok());
+
+        assert_eq!(attributes[1].state, Some(PackageExpectedState::Latest));
+        assert!(attributes[1].check().is_ok());
+    }
+
     #[test]
     fn rejecting_incorrect_pacman_module_block_from_yaml_str() {
         let raw_attribute = "---
@@ -447,4 +592,17 @@ unknown_key: unknown_value
     ";
         assert!(yaml_serde::from_str::<PacmanBlockExpectedState>(raw_attribute).is_err());
     }
+
+    #[test]
+    fn quoting_packages_in_commands() {
+        for value in HOSTILE_VALUES {
+            for api_call in [
+                PacmanModuleInternalApiCall::Install(vec!["curl".into(), value.into()]),
+                PacmanModuleInternalApiCall::UpgradePackages(vec![value.into()]),
+                PacmanModuleInternalApiCall::Remove(vec![value.into()]),
+            ] {
+                assert_no_injection(&PacmanApiCall::from(api_call, Privilege::None).command());
+            }
+        }
+    }
 }
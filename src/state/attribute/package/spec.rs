@@ -0,0 +1,178 @@
+//! Package specifications shared by the package managers
+//!
+//! A package specification is a package name, optionally pinned to a version. The version is
+//! separated from the name by `=` (APT : `nginx=1.24.*`, Pacman : `nginx=1.24.0-1`, YUM/DNF :
+//! `nginx=1.24`). YUM/DNF also take `name-version` (`nginx-1.24`), which cannot be told apart
+//! from a package name (`java-17-openjdk`) without asking the package manager.
+//!
+//! A pinned version is satisfied by any installed version it is a prefix of (`1.24` is satisfied
+//! by `1.24.0-1`), or matching it if it contains `*` wildcards (`1.24.*`).
+
+/// Package name, optionally pinned to a version
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageSpec {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl PackageSpec {
+    /// Parse a `name=version` specification (APT, Pacman, YUM/DNF).
+    pub fn from_equals_syntax(raw: &str) -> PackageSpec {
+        match raw.split_once('=') {
+            Some((name, version)) if !version.is_empty() => PackageSpec {
+                name: name.to_string(),
+                version: Some(version.to_string()),
+            },
+            _ => PackageSpec {
+                name: raw.trim_end_matches('=').to_string(),
+                version: None,
+            },
+        }
+    }
+
+    /// Possible readings of a `name-version` specification (YUM/DNF) : the whole specification
+    /// as a package name first, then the version starting at each `-` followed by a digit, the
+    /// longest name first. `gcc-toolset-13-1.0` gives `gcc-toolset-13-1.0`, `gcc-toolset-13`
+    /// pinned to `1.0` and `gcc-toolset` pinned to `13-1.0`.
+    pub fn dash_syntax_readings(raw: &str) -> Vec<PackageSpec> {
+        let mut readings = vec![PackageSpec {
+            name: raw.to_string(),
+            version: None,
+        }];
+        let version_starts = raw
+            .char_indices()
+            .zip(raw.chars().skip(1))
+            .filter(|((_, current), next)| *current == '-' && next.is_ascii_digit())
+            .map(|((index, _), _)| index)
+            .collect::<Vec<usize>>();
+        for index in version_starts.into_iter().rev() {
+            readings.push(PackageSpec {
+                name: raw[..index].to_string(),
+                version: Some(raw[index + 1..].to_string()),
+            });
+        }
+        readings
+    }
+
+    /// Specification as given to YUM/DNF (`nginx-1.24`).
+    pub fn to_dash_syntax(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}-{}", self.name, version),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Whether `installed_version` is acceptable for this specification.
+    pub fn is_satisfied_by(&self, installed_version: &str) -> bool {
+        match &self.version {
+            None => true,
+            Some(requested_version) => version_matches(installed_version, requested_version),
+        }
+    }
+}
+
+/// Whether an installed version matches a requested (possibly partial or wildcarded) version.
+pub fn version_matches(installed_version: &str, requested_version: &str) -> bool {
+    if requested_version.contains('*') {
+        return wildcard_matches(installed_version, requested_version);
+    }
+
+    match installed_version.strip_prefix(requested_version) {
+        // Exact match
+        Some("") => true,
+        // Partial version : '1.24' matches '1.24.0-1' but not '1.240'
+        Some(remainder) => remainder.starts_with(['.', '-', '+', '~', ':']),
+        None => false,
+    }
+}
+
+// Minimal glob matching where '*' matches any sequence of characters
+fn wildcard_matches(value: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first_part = parts.next().unwrap_or_default();
+    let Some(mut remainder) = value.strip_prefix(first_part) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = parts.collect();
+    let last_part = parts.pop().unwrap_or_default();
+
+    for part in parts {
+        match remainder.find(part) {
+            Some(index) => remainder = &remainder[index + part.len()..],
+            None => return false,
+        }
+    }
+    remainder.ends_with(last_part)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parsing_package_specs() {
+        assert_eq!(
+            PackageSpec::from_equals_syntax("nginx=1.24.*"),
+            PackageSpec {
+                name: "nginx".to_string(),
+                version: Some("1.24.*".to_string())
+            }
+        );
+        assert_eq!(PackageSpec::from_equals_syntax("nginx").version, None);
+
+        assert_eq!(
+            PackageSpec::from_equals_syntax("nginx=1.24").to_dash_syntax(),
+            "nginx-1.24"
+        );
+
+        assert_eq!(
+            PackageSpec::dash_syntax_readings("nginx-1.24"),
+            vec![
+                PackageSpec {
+                    name: "nginx-1.24".to_string(),
+                    version: None
+                },
+                PackageSpec {
+                    name: "nginx".to_string(),
+                    version: Some("1.24".to_string())
+                }
+            ]
+        );
+        assert_eq!(
+            PackageSpec::dash_syntax_readings("python3-pip"),
+            vec![PackageSpec {
+                name: "python3-pip".to_string(),
+                version: None
+            }]
+        );
+        // Package names may contain '-<digit>' : the whole name is tried first
+        let readings = PackageSpec::dash_syntax_readings("java-17-openjdk");
+        assert_eq!(
+            readings[0],
+            PackageSpec {
+                name: "java-17-openjdk".to_string(),
+                version: None
+            }
+        );
+        assert_eq!(
+            PackageSpec::from_equals_syntax("java-17-openjdk").version,
+            None
+        );
+        let readings = PackageSpec::dash_syntax_readings("gcc-toolset-13-1.0");
+        assert_eq!(readings[1].name, "gcc-toolset-13");
+        assert_eq!(readings[1].version.as_deref(), Some("1.0"));
+        assert_eq!(readings[2].name, "gcc-toolset");
+    }
+
+    #[test]
+    fn matching_versions() {
+        assert!(version_matches("1.24.0-1", "1.24.0-1"));
+        assert!(version_matches("1.24.0-1", "1.24"));
+        assert!(!version_matches("1.240-1", "1.24"));
+        assert!(version_matches("1.24.0-1~deb12u1", "1.24.*"));
+        assert!(!version_matches("1.25.0-1", "1.24.*"));
+        assert!(version_matches("2:1.24.0", "*1.24*"));
+    }
+}
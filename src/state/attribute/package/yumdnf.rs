@@ -32,8 +32,18 @@
 //!       Package: httpd
 //!       State: !Present
 //!       Privilege: !WithSudo
+//!   - Detail: !YumDnf
+//!       Packages: [nginx-1.24, curl]
+//!       State: !Present
+//!       Privilege: !WithSudo
 //! ```
+//!
+//! Packages can be pinned to a version with `name=version` or `name-version`. The latter is only
+//! read as a pinned version when no package is installed under the whole name, since package
+//! names may contain `-<digit>` (`java-17-openjdk`). With the `Latest` state, installed packages
+//! are upgraded whenever the repositories provide a newer version.
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
 use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
@@ -43,6 +53,7 @@ use crate::state::Check;
 use crate::state::attribute::HostHandler;
 use crate::state::attribute::Privilege;
 use crate::state::attribute::Remediation;
+use crate::state::attribute::package::spec::PackageSpec;
 use crate::state::compliance::AttributeComplianceAssessment;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -50,16 +61,26 @@ use std::time::Duration;
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub enum YumDnfModuleInternalApiCall {
-    Install(String),
-    Remove(String),
+    /// Install (or switch to the pinned version of) packages, in a single transaction
+    Install(Vec<String>),
+    /// Upgrade already installed packages to their latest version
+    UpgradePackages(Vec<String>),
+    Remove(Vec<String>),
     Upgrade,
 }
 
 impl std::fmt::Display for YumDnfModuleInternalApiCall {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            YumDnfModuleInternalApiCall::Install(package) => write!(f, "install {}", package),
-            YumDnfModuleInternalApiCall::Remove(package) => write!(f, "remove {}", package),
+            YumDnfModuleInternalApiCall::Install(packages) => {
+                write!(f, "install {}", packages.join(" "))
+            }
+            YumDnfModuleInternalApiCall::UpgradePackages(packages) => {
+                write!(f, "upgrade {}", packages.join(" "))
+            }
+            YumDnfModuleInternalApiCall::Remove(packages) => {
+                write!(f, "remove {}", packages.join(" "))
+            }
             YumDnfModuleInternalApiCall::Upgrade => write!(f, "upgrade"),
         }
     }
@@ -73,11 +94,13 @@ pub enum PackageExpectedState {
     Present,
     /// Package should be removed
     Absent,
+    /// Package should be installed and upgraded to the latest available version
+    Latest,
 }
 
 /// Configuration for YUM/DNF package management
 ///
-/// Use the builder to specify package state (Present/Absent) and optionally trigger
+/// Use the builder to specify package state (Present/Absent/Latest) and optionally trigger
 /// a system upgrade.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -85,8 +108,10 @@ pub enum PackageExpectedState {
 pub struct YumDnfBlockExpectedState {
     /// Desired state of the package(s)
     state: Option<PackageExpectedState>,
-    /// Package name to manage
+    /// Package name to manage, optionally pinned (`name-version`)
     package: Option<String>,
+    /// Package names to manage in a single transaction, optionally pinned (`name-version`)
+    packages: Option<Vec<String>>,
     /// Whether to perform a full system upgrade
     upgrade: Option<bool>,
 }
@@ -102,6 +127,7 @@ impl YumDnfBlockExpectedState {
         YumDnfBlockExpectedState {
             state: None,
             package: None,
+            packages: None,
             upgrade: None,
         }
     }
@@ -121,6 +147,26 @@ impl YumDnfBlockExpectedState {
         self
     }
 
+    /// Manage several packages at once, installed or removed in a single transaction.
+    pub fn with_packages_state(
+        &mut self,
+        package_names: &[&str],
+        package_state: PackageExpectedState,
+    ) -> &mut Self {
+        self.packages = Some(package_names.iter().map(|name| name.to_string()).collect());
+        self.state = Some(package_state);
+        self
+    }
+
+    /// All package specifications of this block (`Package` and `Packages` together).
+    fn package_specs(&self) -> Vec<String> {
+        self.package
+            .iter()
+            .chain(self.packages.iter().flatten())
+            .cloned()
+            .collect()
+    }
+
     pub fn build(&self) -> Result<YumDnfBlockExpectedState, RegentError> {
         if let Err(details) = self.check() {
             return Err(details);
@@ -131,23 +177,34 @@ impl YumDnfBlockExpectedState {
 
 impl Check for YumDnfBlockExpectedState {
     fn check(&self) -> Result<(), RegentError> {
-        if let (None, None, None) = (&self.state, &self.package, self.upgrade) {
+        let package_specs = self.package_specs();
+        if let (None, true, None) = (&self.state, package_specs.is_empty(), self.upgrade) {
             return Err(RegentError::IncoherentExpectedState(format!(
                 "All parameters are unset. Please describe the expected state."
             )));
         }
-        if let (None, Some(package_name)) = (&self.state, &self.package) {
+        if let (None, false) = (&self.state, package_specs.is_empty()) {
             return Err(RegentError::IncoherentExpectedState(format!(
-                "Missing 'state' parameter. What is the expected state of the package ({}) ?",
-                package_name
+                "Missing 'state' parameter. What is the expected state of the package(s) ({}) ?",
+                package_specs.join(", ")
             )));
         }
-        if let (Some(package_expected_state), None) = (&self.state, &self.package) {
+        if let (Some(package_expected_state), true) = (&self.state, package_specs.is_empty()) {
             return Err(RegentError::IncoherentExpectedState(format!(
                 "Missing 'package' parameter. Which package should be {:?} ?",
                 package_expected_state
             )));
         }
+        if let Some(PackageExpectedState::Latest | PackageExpectedState::Absent) = &self.state
+            && let Some(pinned_package) = package_specs
+                .iter()
+                .find(|raw_spec| PackageSpec::from_equals_syntax(raw_spec).version.is_some())
+        {
+            return Err(RegentError::IncoherentExpectedState(format!(
+                "Package {} is pinned to a version, which only makes sense with the Present state",
+                pinned_package
+            )));
+        }
         Ok(())
     }
 
@@ -157,7 +214,7 @@ impl Check for YumDnfBlockExpectedState {
     ) -> Result<(), RegentError> {
         match host_properties.os_kind() {
             OsKind::Linux(LinuxSpecifics {
-                linux_flavor: LinuxFlavor::Fedora,
+                linux_flavor: LinuxFlavor::Fedora | LinuxFlavor::Rhel | LinuxFlavor::Amazon,
                 ..
             }) => Ok(()),
             incompatible_os_kind => Err(RegentError::IncompatibleHost(format!(
@@ -200,58 +257,91 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for YumDnfBlockExpectedStat
 
         let mut remediations: Vec<Remediation> = Vec::new();
 
-        match &self.state {
-            None => {}
-            Some(state) => {
-                match state {
+        if let Some(state) = &self.state {
+            let mut packages_to_install: Vec<String> = Vec::new();
+            let mut packages_to_upgrade: Vec<String> = Vec::new();
+            let mut packages_to_remove: Vec<String> = Vec::new();
+
+            for raw_spec in self.package_specs() {
+                let (package_spec, installed_versions) = match state {
                     PackageExpectedState::Present => {
-                        // Check is package is already installed or needs to be
-                        if is_package_installed(
-                            host_handler,
-                            &package_manager,
-                            self.package.clone().unwrap(),
-                            privilege.clone(),
-                        )
-                        .await
-                        {
+                        resolve_package_spec(host_handler, &raw_spec).await?
+                    }
+                    PackageExpectedState::Absent | PackageExpectedState::Latest => {
+                        let package_spec = PackageSpec::from_equals_syntax(&raw_spec);
+                        let installed_versions =
+                            installed_versions(host_handler, &package_spec.name).await?;
+                        (package_spec, installed_versions)
+                    }
+                };
+
+                match (state, installed_versions) {
+                    (PackageExpectedState::Present, Some(installed_versions)) => {
+                        if is_satisfied_by_any(&package_spec, &installed_versions) {
                             remediations.push(Remediation::None(format!(
-                                "{} already present",
-                                self.package.clone().unwrap()
+                                "{} already present ({})",
+                                package_spec.name,
+                                installed_versions.join(", ")
                             )));
                         } else {
-                            // Package is absent and needs to be installed
-                            remediations.push(Remediation::YumDnf(YumDnfApiCall::from(
-                                YumDnfModuleInternalApiCall::Install(self.package.clone().unwrap()),
-                                package_manager.clone(),
-                                privilege.clone(),
-                            )));
+                            // Installed version does not match the pinned one
+                            packages_to_install.push(package_spec.to_dash_syntax());
                         }
                     }
-                    PackageExpectedState::Absent => {
-                        // Check is package is already absent or needs to be removed
-                        if is_package_installed(
+                    (PackageExpectedState::Present | PackageExpectedState::Latest, None) => {
+                        packages_to_install.push(package_spec.to_dash_syntax());
+                    }
+                    (PackageExpectedState::Latest, Some(installed_versions)) => {
+                        if is_update_available(
                             host_handler,
                             &package_manager,
-                            self.package.clone().unwrap(),
-                            privilege.clone(),
+                            &package_spec.name,
+                            privilege,
                         )
-                        .await
+                        .await?
                         {
-                            // Package is present and needs to be removed
-                            remediations.push(Remediation::YumDnf(YumDnfApiCall::from(
-                                YumDnfModuleInternalApiCall::Remove(self.package.clone().unwrap()),
-                                package_manager.clone(),
-                                privilege.clone(),
-                            )));
+                            packages_to_upgrade.push(package_spec.name);
                         } else {
                             remediations.push(Remediation::None(format!(
-                                "{} already absent",
-                                self.package.clone().unwrap()
+                                "{} already latest ({})",
+                                package_spec.name,
+                                installed_versions.join(", ")
                             )));
                         }
                     }
+                    (PackageExpectedState::Absent, Some(_)) => {
+                        packages_to_remove.push(package_spec.name);
+                    }
+                    (PackageExpectedState::Absent, None) => {
+                        remediations.push(Remediation::None(format!(
+                            "{} already absent",
+                            package_spec.name
+                        )));
+                    }
                 }
             }
+
+            if !packages_to_install.is_empty() {
+                remediations.push(Remediation::YumDnf(YumDnfApiCall::from(
+                    YumDnfModuleInternalApiCall::Install(packages_to_install),
+                    package_manager.clone(),
+                    privilege.clone(),
+                )));
+            }
+            if !packages_to_upgrade.is_empty() {
+                remediations.push(Remediation::YumDnf(YumDnfApiCall::from(
+                    YumDnfModuleInternalApiCall::UpgradePackages(packages_to_upgrade),
+                    package_manager.clone(),
+                    privilege.clone(),
+                )));
+            }
+            if !packages_to_remove.is_empty() {
+                remediations.push(Remediation::YumDnf(YumDnfApiCall::from(
+                    YumDnfModuleInternalApiCall::Remove(packages_to_remove),
+                    package_manager.clone(),
+                    privilege.clone(),
+                )));
+            }
         }
         // TODO : have this to do a "dnf check-update" only
         // If updates available -> ApiCall, if not, Matched
@@ -303,11 +393,14 @@ pub struct YumDnfApiCall {
 impl YumDnfApiCall {
     pub fn display(&self) -> String {
         match &self.api_call {
-            YumDnfModuleInternalApiCall::Install(package_name) => {
-                return format!("Install - {}", package_name);
+            YumDnfModuleInternalApiCall::Install(packages) => {
+                return format!("Install - {}", packages.join(", "));
             }
-            YumDnfModuleInternalApiCall::Remove(package_name) => {
-                return format!("Remove - {}", package_name);
+            YumDnfModuleInternalApiCall::UpgradePackages(packages) => {
+                return format!("Upgrade - {}", packages.join(", "));
+            }
+            YumDnfModuleInternalApiCall::Remove(packages) => {
+                return format!("Remove - {}", packages.join(", "));
             }
             YumDnfModuleInternalApiCall::Upgrade => {
                 return String::from("Upgrade");
@@ -323,88 +416,56 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for YumDnfApiCall {
         _host_properties: &Option<HostProperties>,
         _optional_secret_provider: &Option<SecretProvidersPool>,
     ) -> Result<InternalApiCallOutcome, RegentError> {
-        let (cmd, privilege, package_name) = match &self.api_call {
-            YumDnfModuleInternalApiCall::Install(package_name) => (
-                format!(
-                    "{} install -y {}",
-                    self.package_manager.command_name(),
-                    package_name
-                ),
-                &self.privilege,
-                Some(package_name.clone()),
-            ),
-            YumDnfModuleInternalApiCall::Remove(package_name) => (
-                format!(
-                    "{} remove -y {}",
-                    self.package_manager.command_name(),
-                    package_name
-                ),
-                &self.privilege,
-                Some(package_name.clone()),
-            ),
-            YumDnfModuleInternalApiCall::Upgrade => (
-                format!(
-                    "{} update -y --refresh",
-                    self.package_manager.command_name()
-                ),
-                &self.privilege,
-                None,
-            ),
+        let packages = match &self.api_call {
+            YumDnfModuleInternalApiCall::Install(packages)
+            | YumDnfModuleInternalApiCall::UpgradePackages(packages)
+            | YumDnfModuleInternalApiCall::Remove(packages) => packages.as_slice(),
+            YumDnfModuleInternalApiCall::Upgrade => [].as_slice(),
         };
 
         let cmd_result = host_handler
-            .run_command(cmd.as_str(), privilege)
-            .await
-            .unwrap();
-
-        if cmd_result.return_code == 0 {
-            // Post-install verification: verify the package state matches the expected operation
-            if let Some(pkg_name) = &package_name {
-                let verification_result = match &self.api_call {
-                    YumDnfModuleInternalApiCall::Install(_) => {
-                        // Verify the package is now installed
-                        is_package_installed(
-                            host_handler,
-                            &self.package_manager,
-                            pkg_name.clone(),
-                            self.privilege.clone(),
-                        )
-                        .await
-                    }
-                    YumDnfModuleInternalApiCall::Remove(_) => {
-                        // Verify the package is now removed
-                        !is_package_installed(
-                            host_handler,
-                            &self.package_manager,
-                            pkg_name.clone(),
-                            self.privilege.clone(),
-                        )
-                        .await
-                    }
-                    YumDnfModuleInternalApiCall::Upgrade => {
-                        // Upgrade is a system-wide operation, can't easily verify individual packages
-                        true
-                    }
-                };
+            .run_command(self.command().as_str(), &self.privilege)
+            .await?;
 
-                if verification_result {
-                    Ok(InternalApiCallOutcome::Success(None))
-                } else {
-                    Ok(InternalApiCallOutcome::Failure(format!(
-                        "Command succeeded but post-verification failed: package {} state does not match expected",
-                        pkg_name
-                    )))
-                }
-            } else {
-                // For upgrade operations without specific package verification
-                Ok(InternalApiCallOutcome::Success(None))
-            }
-        } else {
-            Ok(InternalApiCallOutcome::Failure(format!(
+        if cmd_result.return_code != 0 {
+            return Ok(InternalApiCallOutcome::Failure(format!(
                 "RC : {}, STDOUT : {}, STDERR : {}",
                 cmd_result.return_code, cmd_result.stdout, cmd_result.stderr
-            )))
+            )));
         }
+
+        // Post-install verification: verify each package state matches the expected operation.
+        // Upgrade is a system-wide operation, individual packages are not verified.
+        for raw_spec in packages {
+            let (package_spec, installed_versions) = match &self.api_call {
+                YumDnfModuleInternalApiCall::Install(_) => {
+                    resolve_package_spec(host_handler, raw_spec).await?
+                }
+                _ => (
+                    PackageSpec::from_equals_syntax(raw_spec),
+                    installed_versions(host_handler, raw_spec).await?,
+                ),
+            };
+
+            let verification_result = match (&self.api_call, installed_versions) {
+                (YumDnfModuleInternalApiCall::Remove(_), installed_versions) => {
+                    installed_versions.is_none()
+                }
+                (_, Some(installed_versions)) => {
+                    is_satisfied_by_any(&package_spec, &installed_versions)
+                }
+                (_, None) => false,
+            };
+
+            if !verification_result {
+                return Ok(InternalApiCallOutcome::Failure(format!(
+                    "Command succeeded but post-verification failed: package {} state does not match expected",
+                    raw_spec
+                )));
+            }
+        }
+
+        Ok(InternalApiCallOutcome::Success(None))
     }
 }
 
@@ -420,31 +481,121 @@ impl YumDnfApiCall {
             privilege,
         }
     }
+
+    /// Command line of this API call, each package being a single argument
+    fn command(&self) -> String {
+        let package_manager = self.package_manager.command_name();
+        match &self.api_call {
+            // When a version is given, 'install' upgrades or downgrades to it
+            YumDnfModuleInternalApiCall::Install(packages) => {
+                ShellCommand::new(&format!("{} install -y", package_manager))
+                    .args(packages)
+                    .to_string()
+            }
+            YumDnfModuleInternalApiCall::UpgradePackages(packages) => {
+                ShellCommand::new(&format!("{} upgrade -y", package_manager))
+                    .args(packages)
+                    .to_string()
+            }
+            YumDnfModuleInternalApiCall::Remove(packages) => {
+                ShellCommand::new(&format!("{} remove -y", package_manager))
+                    .args(packages)
+                    .to_string()
+            }
+            YumDnfModuleInternalApiCall::Upgrade => {
+                format!("{} update -y --refresh", package_manager)
+            }
+        }
+    }
 }
 
-async fn is_package_installed<Handler: HostHandler>(
+/// Installed versions (`version-release`) of a package, `None` if it is not installed. Several
+/// versions can be installed side by side (multilib packages, kernels).
+async fn installed_versions<Handler: HostHandler>(
     host_handler: &mut Handler,
-    package_manager: &RedHatFlavoredPackageManager,
-    package_name: String,
-    privilege: Privilege,
-) -> bool {
+    package_name: &str,
+) -> Result<Option<Vec<String>>, RegentError> {
     let test = host_handler
         .run_command(
-            format!(
-                "{} list installed {}",
-                package_manager.command_name(),
-                package_name
-            )
-            .as_str(),
-            &privilege,
+            ShellCommand::new("rpm -q --qf '%{VERSION}-%{RELEASE}\\n'")
+                .arg(package_name)
+                .as_str(),
+            &Privilege::None,
         )
-        .await
-        .unwrap();
+        .await?;
 
     if test.return_code == 0 {
-        return true;
+        Ok(Some(parse_installed_versions(&test.stdout)))
     } else {
-        return false;
+        Ok(None)
+    }
+}
+
+/// Specification of a package to be present, with its installed versions. A `name-version`
+/// specification is read as a package name first (`java-17-openjdk`), then as a name pinned to
+/// a version (`nginx-1.24`), until a package is found installed under the name.
+async fn resolve_package_spec<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    raw_spec: &str,
+) -> Result<(PackageSpec, Option<Vec<String>>), RegentError> {
+    let package_spec = PackageSpec::from_equals_syntax(raw_spec);
+    if package_spec.version.is_some() {
+        let installed_versions = installed_versions(host_handler, &package_spec.name).await?;
+        return Ok((package_spec, installed_versions));
+    }
+
+    for reading in PackageSpec::dash_syntax_readings(raw_spec) {
+        if let Some(installed_versions) = installed_versions(host_handler, &reading.name).await? {
+            return Ok((reading, Some(installed_versions)));
+        }
+    }
+    Ok((package_spec, None))
+}
+
+/// Versions printed by `rpm -q --qf '%{VERSION}-%{RELEASE}\n'`, one per line.
+fn parse_installed_versions(rpm_output: &str) -> Vec<String> {
+    rpm_output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether any of the installed versions of a package is acceptable for its specification.
+fn is_satisfied_by_any(package_spec: &PackageSpec, installed_versions: &[String]) -> bool {
+    installed_versions
+        .iter()
+        .any(|installed_version| package_spec.is_satisfied_by(installed_version))
+}
+
+/// Whether the repositories provide a newer version of an installed package.
+async fn is_update_available<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    package_manager: &RedHatFlavoredPackageManager,
+    package_name: &str,
+    privilege: &Privilege,
+) -> Result<bool, RegentError> {
+    let test = host_handler
+        .run_command(
+            ShellCommand::new(&format!(
+                "{} check-update -q",
+                package_manager.command_name()
+            ))
+            .arg(package_name)
+            .as_str(),
+            privilege,
+        )
+        .await?;
+
+    // check-update exits with 100 when updates are available, 0 when there are none
+    match test.return_code {
+        0 => Ok(false),
+        100 => Ok(true),
+        _ => Err(RegentError::FailedDryRunEvaluation(format!(
+            "Failed to check updates of {} : {}",
+            package_name, test.stdout
+        ))),
     }
 }
 
@@ -452,6 +603,7 @@ async fn is_package_installed<Handler: HostHandler>(
 mod tests {
 
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_yumdnf_module_block_from_yaml_str() {
@@ -481,6 +633,45 @@ mod tests {
         assert_eq!(attributes[2].upgrade, Some(true));
     }
 
+    #[test]
+    fn parsing_yumdnf_module_block_with_several_packages_from_yaml_str() {
+        let raw_attributes = "---
+- Packages: [nginx-1.24, python3-pip]
+  State: !Present
+
+- Packages: [httpd]
+  State: !Latest
+    ";
+
+        let attributes: Vec<YumDnfBlockExpectedState> =
+            yaml_serde::from_str(raw_attributes).unwrap();
+
+        assert_eq!(
+            attributes[0].package_specs(),
+            vec!["nginx-1.24", "python3-pip"]
+        );
+        assert!(attributes[0].check().is_ok());
+
+        assert_eq!(attributes[1].state, Some(PackageExpectedState::Latest));
+        assert!(attributes[1].check().is_ok());
+
+        // Package names may contain '-<digit>'
+        let raw_attribute = "---
+Packages: [java-17-openjdk, gcc-toolset-13]
+State: !Absent
+    ";
+        let yaml_part = yaml_serde::from_str::<YumDnfBlockExpectedState>(raw_attribute);
+        assert!(yaml_part.unwrap().check().is_ok());
+
+        // A pinned version only makes sense with the Present state
+        let raw_attribute = "---
+Packages: [nginx=1.24]
+State: !Absent
+    ";
+        let yaml_part = yaml_serde::from_str::<YumDnfBlockExpectedState>(raw_attribute);
+        assert!(yaml_part.unwrap().check().is_err());
+    }
+
     #[test]
     fn rejecting_incorrect_yumdnf_module_block_from_yaml_str() {
         let raw_attribute = "---
@@ -505,4 +696,42 @@ unknown_key: unknown_value
     ";
         assert!(yaml_serde::from_str::<YumDnfBlockExpectedState>(raw_attribute).is_err());
     }
+
+    #[test]
+    fn quoting_packages_in_commands() {
+        for value in HOSTILE_VALUES {
+            for api_call in [
+                YumDnfModuleInternalApiCall::Install(vec!["curl".into(), value.into()]),
+                YumDnfModuleInternalApiCall::UpgradePackages(vec![value.into()]),
+                YumDnfModuleInternalApiCall::Remove(vec![value.into()]),
+            ] {
+                let api_call = YumDnfApiCall::from(
+                    api_call,
+                    RedHatFlavoredPackageManager::Dnf,
+                    Privilege::None,
+                );
+                assert_no_injection(&api_call.command());
+            }
+        }
+    }
+
+    #[test]
+    fn matching_pinned_version_among_several_installed() {
+        // e.g. x86_64 and i686 builds of a multilib package, or kernels
+        let installed_versions = parse_installed_versions("1.24.0-1.el9\n1.26.1-2.el9\n");
+        assert_eq!(installed_versions, vec!["1.24.0-1.el9", "1.26.1-2.el9"]);
+
+        assert!(is_satisfied_by_any(
+            &PackageSpec::from_equals_syntax("nginx=1.26"),
+            &installed_versions
+        ));
+        assert!(is_satisfied_by_any(
+            &PackageSpec::from_equals_syntax("nginx=1.24.0-1.el9"),
+            &installed_versions
+        ));
+        assert!(!is_satisfied_by_any(
+            &PackageSpec::from_equals_syntax("nginx=1.25"),
+            &installed_versions
+        ));
+    }
 }
@@ -3,7 +3,19 @@
 //! This module provides the `CommandBlockExpectedState` type for executing arbitrary
 //! shell commands on managed hosts.
 //!
-//! **Compatible OS:** All (cross-platform)
+//! **Compatible OS:** All (cross-platform). Guards, `Chdir` and `Env` require a POSIX shell.
+//!
+//! A command without guard runs on every enforcement. Guards make it idempotent : the host is
+//! compliant, and the command is not run, when
+//! - the `Creates` path exists,
+//! - the `Removes` path does not exist,
+//! - the `Unless` command succeeds,
+//! - the `OnlyIf` command fails.
+//!
+//! Once run, the command is considered failed if its exit code is not 0, or if the `FailedWhen`
+//! condition holds when given. It is considered as having changed the host unless the
+//! `ChangedWhen` condition does not hold. Both conditions are evaluated against `rc`, `stdout`
+//! and `stderr` (see [`crate::state::condition`]).
 //!
 //! # Examples
 //!
@@ -18,8 +30,17 @@
 //!     .build()
 //!     .unwrap();
 //!
+//! // Initialize a database once
+//! let init = CommandBlockExpectedState::builder("./init-db.sh")
+//!     .with_chdir("/opt/app")
+//!     .with_env("PGDATA", "/var/lib/app/db")
+//!     .with_creates("/var/lib/app/db/PG_VERSION")
+//!     .build()
+//!     .unwrap();
+//!
 //! let expected_state = ExpectedState::new()
 //!     .with_attribute(Attribute::command(echo, Privilege::None, None))
+//!     .with_attribute(Attribute::command(init, Privilege::None, None))
 //!     .build();
 //! ```
 //!
@@ -30,8 +51,18 @@
 //!   - Detail: !Command
 //!       Cmd: "echo 'Hello, World!'"
 //!       Privilege: !None
+//!   - Detail: !Command
+//!       Cmd: "./migrate.sh"
+//!       Chdir: /opt/app
+//!       Env:
+//!         APP_ENV: production
+//!       Unless: "./migrate.sh --check"
+//!       ChangedWhen: "'applied' in stdout"
+//!       FailedWhen: "rc != 0 or 'ERROR' in stderr"
+//!       Privilege: !None
 //! ```
 
+use crate::command::{CommandResult, ShellCommand};
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
 use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
@@ -42,13 +73,15 @@ use crate::state::attribute::HostHandler;
 use crate::state::attribute::Privilege;
 use crate::state::attribute::Remediation;
 use crate::state::compliance::AttributeComplianceAssessment;
+use crate::state::condition;
 use crate::state::expected_state::Parameter;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 /// Configuration for a shell command to execute
 ///
-/// The command will be executed each time compliance is assessed. Use this for
+/// Without guard, the command will be executed each time compliance is reached. Use this for
 /// idempotent commands or one-time setup tasks.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -56,6 +89,22 @@ use std::time::Duration;
 pub struct CommandBlockExpectedState {
     /// Command to execute. Can be a clear text string or a secret reference.
     cmd: Parameter<String>,
+    /// Path whose existence means the command already ran
+    creates: Option<String>,
+    /// Path whose absence means the command already ran
+    removes: Option<String>,
+    /// Command whose success means the command already ran
+    unless: Option<String>,
+    /// Command whose failure means the command does not need to run
+    only_if: Option<String>,
+    /// Condition telling whether the command changed the host
+    changed_when: Option<String>,
+    /// Condition telling whether the command failed, instead of its exit code
+    failed_when: Option<String>,
+    /// Directory the command, and its guards, are run from
+    chdir: Option<String>,
+    /// Environment variables of the command and its guards
+    env: Option<BTreeMap<String, String>>,
 }
 
 impl Timeout for CommandBlockExpectedState {
@@ -66,27 +115,154 @@ impl Timeout for CommandBlockExpectedState {
 
 impl CommandBlockExpectedState {
     pub fn builder(cmd: &str) -> CommandBlockExpectedState {
-        CommandBlockExpectedState {
-            cmd: Parameter::Clear(cmd.to_string()),
-        }
+        Self::from_parameter(Parameter::Clear(cmd.to_string()))
     }
 
     pub fn builder_secret(sec_ref: SecretReference) -> CommandBlockExpectedState {
+        Self::from_parameter(Parameter::Secret(sec_ref))
+    }
+
+    fn from_parameter(cmd: Parameter<String>) -> CommandBlockExpectedState {
         CommandBlockExpectedState {
-            cmd: Parameter::Secret(sec_ref),
+            cmd,
+            creates: None,
+            removes: None,
+            unless: None,
+            only_if: None,
+            changed_when: None,
+            failed_when: None,
+            chdir: None,
+            env: None,
         }
     }
 
+    pub fn with_creates(&mut self, path: &str) -> &mut Self {
+        self.creates = Some(path.to_string());
+        self
+    }
+
+    pub fn with_removes(&mut self, path: &str) -> &mut Self {
+        self.removes = Some(path.to_string());
+        self
+    }
+
+    pub fn with_unless(&mut self, cmd: &str) -> &mut Self {
+        self.unless = Some(cmd.to_string());
+        self
+    }
+
+    pub fn with_only_if(&mut self, cmd: &str) -> &mut Self {
+        self.only_if = Some(cmd.to_string());
+        self
+    }
+
+    pub fn with_changed_when(&mut self, condition: &str) -> &mut Self {
+        self.changed_when = Some(condition.to_string());
+        self
+    }
+
+    pub fn with_failed_when(&mut self, condition: &str) -> &mut Self {
+        self.failed_when = Some(condition.to_string());
+        self
+    }
+
+    pub fn with_chdir(&mut self, directory: &str) -> &mut Self {
+        self.chdir = Some(directory.to_string());
+        self
+    }
+
+    pub fn with_env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.env
+            .get_or_insert_with(BTreeMap::new)
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
     pub fn build(&self) -> Result<CommandBlockExpectedState, RegentError> {
         if let Err(details) = self.check() {
             return Err(details);
         }
         Ok(self.clone())
     }
+
+    /// Whether a guard tells the command does not need to run
+    async fn is_guarded<Handler: HostHandler>(
+        &self,
+        host_handler: &mut Handler,
+        privilege: &Privilege,
+    ) -> Result<bool, RegentError> {
+        let mut succeeds = async |cmd: String| -> Result<bool, RegentError> {
+            let cmd_result = host_handler
+                .run_command(&in_context(&cmd, &self.chdir, &self.env), privilege)
+                .await?;
+            Ok(cmd_result.return_code == 0)
+        };
+
+        if let Some(path) = &self.creates
+            && succeeds(ShellCommand::new("test -e").arg(path).to_string()).await?
+        {
+            return Ok(true);
+        }
+        if let Some(path) = &self.removes
+            && !succeeds(ShellCommand::new("test -e").arg(path).to_string()).await?
+        {
+            return Ok(true);
+        }
+        if let Some(unless) = &self.unless
+            && succeeds(unless.clone()).await?
+        {
+            return Ok(true);
+        }
+        if let Some(only_if) = &self.only_if
+            && !succeeds(only_if.clone()).await?
+        {
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+/// Command run from `chdir` with the `env` variables set
+fn in_context(cmd: &str, chdir: &Option<String>, env: &Option<BTreeMap<String, String>>) -> String {
+    let env = env.as_ref().filter(|env| !env.is_empty());
+    if chdir.is_none() && env.is_none() {
+        return cmd.to_string();
+    }
+
+    let script = match chdir {
+        Some(directory) => ShellCommand::new("cd").arg(directory).raw("&&").raw(cmd),
+        None => ShellCommand::new(cmd),
+    };
+    // Run through 'sh -c' so that the whole script gets the privilege
+    let wrapper = match env {
+        Some(env) => ShellCommand::new("env")
+            .args(env.iter().map(|(key, value)| format!("{}={}", key, value)))
+            .raw("sh -c"),
+        None => ShellCommand::new("sh -c"),
+    };
+    wrapper.arg(script.as_str()).to_string()
 }
 
 impl Check for CommandBlockExpectedState {
     fn check(&self) -> Result<(), RegentError> {
+        let is_valid_name = |key: &str| {
+            !key.is_empty()
+                && key.chars().enumerate().all(|(position, c)| {
+                    c == '_' || c.is_ascii_alphabetic() || (position > 0 && c.is_ascii_digit())
+                })
+        };
+        if let Some(key) = self
+            .env
+            .iter()
+            .flatten()
+            .map(|(key, _)| key)
+            .find(|key| !is_valid_name(key))
+        {
+            return Err(RegentError::IncoherentExpectedState(format!(
+                "'{}' is not a valid environment variable name",
+                key
+            )));
+        }
         Ok(())
     }
 
@@ -102,7 +278,7 @@ impl Check for CommandBlockExpectedState {
 impl<Handler: HostHandler> AssessCompliance<Handler> for CommandBlockExpectedState {
     async fn assess_compliance(
         &self,
-        _host_handler: &mut Handler,
+        host_handler: &mut Handler,
         host_properties: &Option<HostProperties>,
         privilege: &Privilege,
         _optional_secret_provider: &Option<SecretProvidersPool>,
@@ -112,6 +288,10 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for CommandBlockExpectedSta
             self.check_host_compatibility(props)?;
         }
 
+        if self.is_guarded(host_handler, privilege).await? {
+            return Ok(AttributeComplianceAssessment::Compliant);
+        }
+
         let mut remediations: Vec<Remediation> = Vec::new();
 
         let privilege = privilege.clone();
@@ -119,6 +299,10 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for CommandBlockExpectedSta
         remediations.push(Remediation::Command(CommandApiCall {
             cmd: self.cmd.clone(),
             privilege,
+            chdir: self.chdir.clone(),
+            env: self.env.clone(),
+            changed_when: self.changed_when.clone(),
+            failed_when: self.failed_when.clone(),
         }));
 
         return Ok(AttributeComplianceAssessment::NonCompliant(remediations));
@@ -129,12 +313,65 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for CommandBlockExpectedSta
 pub struct CommandApiCall {
     pub cmd: Parameter<String>,
     privilege: Privilege,
+    chdir: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    changed_when: Option<String>,
+    failed_when: Option<String>,
 }
 
 impl CommandApiCall {
     pub fn display(&self) -> String {
         return format!("Run command : {}", self.cmd);
     }
+
+    /// Run the command on the host.
+    pub async fn run<Handler: HostHandler>(
+        &self,
+        host_handler: &mut Handler,
+        optional_secret_provider: &Option<SecretProvidersPool>,
+    ) -> Result<CommandResult, RegentError> {
+        let cmd = self.cmd.clone().inner_raw(optional_secret_provider).await?;
+        host_handler
+            .run_command(&in_context(&cmd, &self.chdir, &self.env), &self.privilege)
+            .await
+    }
+
+    /// Outcome of the remediation given the result of the command, according to the
+    /// `FailedWhen` and `ChangedWhen` conditions.
+    pub fn outcome(
+        &self,
+        cmd_result: &CommandResult,
+    ) -> Result<InternalApiCallOutcome, RegentError> {
+        let mut context = tera::Context::new();
+        context.insert("rc", &cmd_result.return_code);
+        context.insert("stdout", &cmd_result.stdout);
+        context.insert("stderr", &cmd_result.stderr);
+
+        let failed = match &self.failed_when {
+            Some(failed_when) => condition::evaluate(failed_when, &context)?,
+            None => cmd_result.return_code != 0,
+        };
+        if failed {
+            return Ok(InternalApiCallOutcome::Failure(format!(
+                "RC : {}, STDOUT : {}, STDERR : {}",
+                cmd_result.return_code, cmd_result.stdout, cmd_result.stderr
+            )));
+        }
+
+        let changed = match &self.changed_when {
+            Some(changed_when) => condition::evaluate(changed_when, &context)?,
+            None => true,
+        };
+        if changed {
+            Ok(InternalApiCallOutcome::Success(Some(
+                cmd_result.stdout.clone(),
+            )))
+        } else {
+            Ok(InternalApiCallOutcome::Unchanged(Some(
+                cmd_result.stdout.clone(),
+            )))
+        }
+    }
 }
 
 impl Check for CommandApiCall {
@@ -163,33 +400,16 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for CommandApiCall {
             self.check_host_compatibility(props)?;
         }
 
-        let cmd_result = host_handler
-            .run_command(
-                &self
-                    .cmd
-                    .clone()
-                    .inner_raw(optional_secret_provider)
-                    .await
-                    .unwrap(),
-                &self.privilege,
-            )
-            .await
-            .unwrap();
-
-        if cmd_result.return_code == 0 {
-            Ok(InternalApiCallOutcome::Success(Some(cmd_result.stdout)))
-        } else {
-            Ok(InternalApiCallOutcome::Failure(format!(
-                "RC : {}, STDOUT : {}, STDERR : {}",
-                cmd_result.return_code, cmd_result.stdout, cmd_result.stderr
-            )))
-        }
+        let cmd_result = self.run(host_handler, optional_secret_provider).await?;
+        self.outcome(&cmd_result)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::LocalHostHandler;
+    use crate::WhichUser;
 
     #[test]
     fn parsing_service_module_block_from_yaml_str() {
@@ -199,4 +419,154 @@ mod tests {
         let _attributes: Vec<CommandBlockExpectedState> =
             yaml_serde::from_str(raw_attributes).unwrap();
     }
+
+    #[test]
+    fn parsing_guards_from_yaml_str() {
+        let raw_attributes = r#"---
+- Cmd: ./migrate.sh
+  Chdir: /opt/app
+  Env:
+    APP_ENV: production
+  Creates: /opt/app/.migrated
+  Removes: /opt/app/pending
+  Unless: ./migrate.sh --check
+  OnlyIf: test -x ./migrate.sh
+  ChangedWhen: "'applied' in stdout"
+  FailedWhen: "rc != 0 or 'ERROR' in stderr"
+"#;
+
+        let attributes: Vec<CommandBlockExpectedState> =
+            yaml_serde::from_str(raw_attributes).unwrap();
+        assert_eq!(
+            attributes[0],
+            CommandBlockExpectedState::builder("./migrate.sh")
+                .with_chdir("/opt/app")
+                .with_env("APP_ENV", "production")
+                .with_creates("/opt/app/.migrated")
+                .with_removes("/opt/app/pending")
+                .with_unless("./migrate.sh --check")
+                .with_only_if("test -x ./migrate.sh")
+                .with_changed_when("'applied' in stdout")
+                .with_failed_when("rc != 0 or 'ERROR' in stderr")
+                .build()
+                .unwrap()
+        );
+
+        assert!(matches!(
+            CommandBlockExpectedState::builder("true")
+                .with_env("APP ENV", "production")
+                .build(),
+            Err(RegentError::IncoherentExpectedState(_))
+        ));
+    }
+
+    #[test]
+    fn running_commands_in_context() {
+        assert_eq!(in_context("make", &None, &None), "make");
+        assert_eq!(
+            in_context(
+                "make && make install",
+                &Some("/opt/my app".to_string()),
+                &Some(BTreeMap::from([("PREFIX".to_string(), "/usr".to_string())]))
+            ),
+            "env PREFIX=/usr sh -c 'cd '\\''/opt/my app'\\'' && make && make install'"
+        );
+    }
+
+    fn assess(command: &CommandBlockExpectedState) -> AttributeComplianceAssessment {
+        let mut host_handler = LocalHostHandler::from(WhichUser::CurrentUser);
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(command.assess_compliance(&mut host_handler, &None, &Privilege::None, &None))
+            .unwrap()
+    }
+
+    #[test]
+    fn guarding_commands() {
+        let directory = std::env::temp_dir().join(format!("regent-command-{}", nanoid::nanoid!()));
+        std::fs::create_dir(&directory).unwrap();
+        std::fs::write(directory.join("present"), "").unwrap();
+        let directory_path = directory.display().to_string();
+
+        let compliant = |command: &mut CommandBlockExpectedState| {
+            matches!(
+                assess(&command.with_chdir(&directory_path).build().unwrap()),
+                AttributeComplianceAssessment::Compliant
+            )
+        };
+        assert!(!compliant(&mut CommandBlockExpectedState::builder("true")));
+        assert!(compliant(
+            CommandBlockExpectedState::builder("true").with_creates("present")
+        ));
+        assert!(!compliant(
+            CommandBlockExpectedState::builder("true").with_creates("absent")
+        ));
+        assert!(compliant(
+            CommandBlockExpectedState::builder("true").with_removes("absent")
+        ));
+        assert!(!compliant(
+            CommandBlockExpectedState::builder("true").with_removes("present")
+        ));
+        assert!(compliant(
+            CommandBlockExpectedState::builder("true")
+                .with_env("EXPECTED", "present")
+                .with_unless("test -e \"$EXPECTED\"")
+        ));
+        assert!(compliant(
+            CommandBlockExpectedState::builder("true").with_only_if("test -e absent")
+        ));
+        assert!(!compliant(
+            CommandBlockExpectedState::builder("true").with_only_if("test -e present")
+        ));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn judging_command_outcomes() {
+        let command = |changed_when: Option<&str>, failed_when: Option<&str>| CommandApiCall {
+            cmd: Parameter::Clear("./migrate.sh".to_string()),
+            privilege: Privilege::None,
+            chdir: None,
+            env: None,
+            changed_when: changed_when.map(str::to_string),
+            failed_when: failed_when.map(str::to_string),
+        };
+        let cmd_result = |return_code: i64, stdout: &str, stderr: &str| CommandResult {
+            return_code,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+        };
+
+        let plain = command(None, None);
+        assert!(matches!(
+            plain.outcome(&cmd_result(0, "", "")),
+            Ok(InternalApiCallOutcome::Success(_))
+        ));
+        assert!(matches!(
+            plain.outcome(&cmd_result(1, "", "")),
+            Ok(InternalApiCallOutcome::Failure(_))
+        ));
+
+        let judged = command(
+            Some("'applied' in stdout"),
+            Some("rc > 1 or 'ERROR' in stderr"),
+        );
+        assert!(matches!(
+            judged.outcome(&cmd_result(1, "nothing to do", "")),
+            Ok(InternalApiCallOutcome::Unchanged(_))
+        ));
+        assert!(matches!(
+            judged.outcome(&cmd_result(0, "3 migrations applied", "")),
+            Ok(InternalApiCallOutcome::Success(_))
+        ));
+        assert!(matches!(
+            judged.outcome(&cmd_result(0, "", "ERROR: locked")),
+            Ok(InternalApiCallOutcome::Failure(_))
+        ));
+        assert!(matches!(
+            command(Some("rc ==="), None).outcome(&cmd_result(0, "", "")),
+            Err(RegentError::FailureToConsiderContext(_))
+        ));
+    }
 }
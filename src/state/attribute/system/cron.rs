@@ -36,9 +36,10 @@
 //!       Privilege: !WithSudo
 //! ```
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
-use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
+use crate::hosts::managed_host::{AssessCompliance, PreviewChange, ReachCompliance, Timeout};
 use crate::hosts::properties::{HostProperties, OsKind};
 use crate::secrets::SecretProvidersPool;
 use crate::state::Check;
@@ -46,6 +47,7 @@ use crate::state::attribute::HostHandler;
 use crate::state::attribute::Privilege;
 use crate::state::attribute::Remediation;
 use crate::state::compliance::AttributeComplianceAssessment;
+use crate::state::diff;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -393,6 +395,64 @@ impl CronApiCall {
             privilege,
         }
     }
+
+    fn command(&self) -> String {
+        match &self.api_call {
+            CronModuleInternalApiCall::Upsert {
+                name,
+                cron_line,
+                user,
+                cron_file,
+            } => {
+                let append_entry = ShellCommand::new("printf '%s\\n'")
+                    .arg(format!("{}{}", REGENT_MARKER_PREFIX, name))
+                    .arg(cron_line);
+                if let Some(file) = cron_file {
+                    // /etc/cron.d/ approach: remove existing entry (if any) then append
+                    let path = format!("/etc/cron.d/{}", file);
+                    ShellCommand::new("touch")
+                        .arg(&path)
+                        .and(
+                            ShellCommand::new("sed -i")
+                                .arg(sed_delete_entry(name))
+                                .arg(&path),
+                        )
+                        .and(append_entry.raw(">>").arg(&path))
+                        .to_string()
+                } else {
+                    // User crontab approach: rebuild crontab minus old entry, then append new one
+                    ShellCommand::new("(crontab -l")
+                        .args(user_flag(user))
+                        .raw("2>/dev/null")
+                        .pipe(ShellCommand::new("sed").arg(sed_delete_entry(name)))
+                        .raw(";")
+                        .raw(append_entry.as_str())
+                        .raw(")")
+                        .pipe(ShellCommand::new("crontab").args(user_flag(user)).arg("-"))
+                        .to_string()
+                }
+            }
+            CronModuleInternalApiCall::Remove {
+                name,
+                user,
+                cron_file,
+            } => {
+                if let Some(file) = cron_file {
+                    ShellCommand::new("sed -i")
+                        .arg(sed_delete_entry(name))
+                        .arg(format!("/etc/cron.d/{}", file))
+                        .to_string()
+                } else {
+                    ShellCommand::new("crontab -l")
+                        .args(user_flag(user))
+                        .raw("2>/dev/null")
+                        .pipe(ShellCommand::new("sed").arg(sed_delete_entry(name)))
+                        .pipe(ShellCommand::new("crontab").args(user_flag(user)).arg("-"))
+                        .to_string()
+                }
+            }
+        }
+    }
 }
 
 impl Check for CronApiCall {
@@ -426,76 +486,112 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for CronApiCall {
             self.check_host_compatibility(props)?;
         }
 
-        let (cmd, privilege) = match &self.api_call {
+        let cmd_result = host_handler
+            .run_command(self.command().as_str(), &self.privilege)
+            .await
+            .unwrap();
+
+        if cmd_result.return_code == 0 {
+            Ok(InternalApiCallOutcome::Success(None))
+        } else {
+            Ok(InternalApiCallOutcome::Failure(format!(
+                "RC: {}, STDOUT: {}, STDERR: {}",
+                cmd_result.return_code, cmd_result.stdout, cmd_result.stderr
+            )))
+        }
+    }
+}
+
+impl<Handler: HostHandler> PreviewChange<Handler> for CronApiCall {
+    async fn preview(&self, host_handler: &mut Handler) -> Result<Option<String>, RegentError> {
+        let (name, new_cron_line, user, cron_file) = match &self.api_call {
             CronModuleInternalApiCall::Upsert {
                 name,
                 cron_line,
                 user,
                 cron_file,
-            } => {
-                let cmd = if let Some(file) = cron_file {
-                    // /etc/cron.d/ approach: remove existing entry (if any) then append
-                    format!(
-                        "touch /etc/cron.d/{f} && sed -i '/^# regent: {n}$/{{N;d;}}' /etc/cron.d/{f} && printf '# regent: {n}\\n{l}\\n' >> /etc/cron.d/{f}",
-                        f = file,
-                        n = name,
-                        l = cron_line
-                    )
-                } else {
-                    // User crontab approach: rebuild crontab minus old entry, then append new one
-                    let uf = user_flag(user);
-                    format!(
-                        "(crontab -l {uf}2>/dev/null | sed '/^# regent: {n}$/{{N;d;}}'; printf '# regent: {n}\\n{l}\\n') | crontab {uf}-",
-                        uf = uf,
-                        n = name,
-                        l = cron_line
-                    )
-                };
-                (cmd, &self.privilege)
-            }
+            } => (name, Some(cron_line), user, cron_file),
             CronModuleInternalApiCall::Remove {
                 name,
                 user,
                 cron_file,
-            } => {
-                let cmd = if let Some(file) = cron_file {
-                    format!(
-                        "sed -i '/^# regent: {n}$/{{N;d;}}' /etc/cron.d/{f}",
-                        n = name,
-                        f = file
-                    )
-                } else {
-                    let uf = user_flag(user);
-                    format!(
-                        "crontab -l {uf}2>/dev/null | sed '/^# regent: {n}$/{{N;d;}}' | crontab {uf}-",
-                        uf = uf,
-                        n = name
-                    )
-                };
-                (cmd, &self.privilege)
+            } => (name, None, user, cron_file),
+        };
+
+        let (label, current_content) = match cron_file {
+            Some(file) => {
+                let path = format!("/etc/cron.d/{}", file);
+                let current_content =
+                    diff::current_content(host_handler, &path, &self.privilege).await?;
+                (path, current_content)
+            }
+            None => {
+                let current_content = get_cron_content(host_handler, user, &None)
+                    .await
+                    .map_err(RegentError::FailedDryRunEvaluation)?;
+                let label = format!(
+                    "crontab of {}",
+                    user.as_deref().unwrap_or("the connected user")
+                );
+                (label, current_content)
             }
         };
 
-        let cmd_result = host_handler
-            .run_command(cmd.as_str(), privilege)
-            .await
-            .unwrap();
+        let mut wanted_content = without_cron_entry(&current_content, name);
+        if let Some(cron_line) = new_cron_line {
+            wanted_content.push_str(&format!(
+                "{}{}\n{}\n",
+                REGENT_MARKER_PREFIX, name, cron_line
+            ));
+        }
 
-        if cmd_result.return_code == 0 {
-            Ok(InternalApiCallOutcome::Success(None))
-        } else {
-            Ok(InternalApiCallOutcome::Failure(format!(
-                "RC: {}, STDOUT: {}, STDERR: {}",
-                cmd_result.return_code, cmd_result.stdout, cmd_result.stderr
-            )))
+        Ok(diff::unified_diff(
+            &label,
+            &current_content,
+            &wanted_content,
+        ))
+    }
+}
+
+/// Content without the named entry (marker line and the line following it), as done by the
+/// `sed '/^# regent: <name>$/{N;d;}'` of the remediations.
+fn without_cron_entry(content: &str, name: &str) -> String {
+    let marker = format!("{}{}", REGENT_MARKER_PREFIX, name);
+    let mut remaining_content = String::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        if line == marker {
+            lines.next();
+            continue;
+        }
+        remaining_content.push_str(line);
+        remaining_content.push('\n');
+    }
+    remaining_content
+}
+
+/// sed script deleting the named entry : the marker line and the line following it. The name is
+/// escaped so that it is matched literally.
+fn sed_delete_entry(name: &str) -> String {
+    let mut escaped_name = String::new();
+    for c in name.chars() {
+        match c {
+            '\\' | '/' | '.' | '*' | '[' | ']' | '^' | '$' => {
+                escaped_name.push('\\');
+                escaped_name.push(c);
+            }
+            // A newline would end the sed command
+            '\n' => escaped_name.push_str("\\n"),
+            _ => escaped_name.push(c),
         }
     }
+    format!("/^{}{}$/{{N;d;}}", REGENT_MARKER_PREFIX, escaped_name)
 }
 
-fn user_flag(user: &Option<String>) -> String {
+fn user_flag(user: &Option<String>) -> Vec<&str> {
     match user {
-        Some(u) => format!("-u {} ", u),
-        None => String::new(),
+        Some(u) => vec!["-u", u],
+        None => Vec::new(),
     }
 }
 
@@ -506,7 +602,12 @@ async fn get_cron_content<Handler: HostHandler>(
 ) -> Result<String, String> {
     if let Some(file) = cron_file {
         let result = host_handler
-            .run_command(&format!("cat /etc/cron.d/{}", file), &Privilege::None)
+            .run_command(
+                ShellCommand::new("cat")
+                    .arg(format!("/etc/cron.d/{}", file))
+                    .as_str(),
+                &Privilege::None,
+            )
             .await
             .map_err(|e| format!("Failed to read cron file: {:?}", e))?;
         Ok(if result.return_code == 0 {
@@ -515,12 +616,9 @@ async fn get_cron_content<Handler: HostHandler>(
             String::new()
         })
     } else {
-        let cmd = match user {
-            Some(u) => format!("crontab -l -u {}", u),
-            None => "crontab -l".to_string(),
-        };
+        let cmd = ShellCommand::new("crontab -l").args(user_flag(user));
         let result = host_handler
-            .run_command(&cmd, &Privilege::None)
+            .run_command(cmd.as_str(), &Privilege::None)
             .await
             .map_err(|e| format!("Failed to read crontab: {:?}", e))?;
         // rc=1 means "no crontab for user" — treat as empty
@@ -586,6 +684,7 @@ fn build_cron_line(block: &CronBlockExpectedState, is_cron_d: bool) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_cron_module_block_from_yaml_str() {
@@ -702,4 +801,38 @@ mod tests {
         let content = "# regent: other\n0 2 * * * /usr/local/bin/other.sh\n";
         assert!(find_cron_entry(content, "backup").is_none());
     }
+
+    #[test]
+    fn escaping_names_in_sed_scripts() {
+        assert_eq!(
+            sed_delete_entry("backup.daily"),
+            "/^# regent: backup\\.daily$/{N;d;}"
+        );
+        assert_eq!(
+            sed_delete_entry("a/b\ne touch /tmp/pwned"),
+            "/^# regent: a\\/b\\ne touch \\/tmp\\/pwned$/{N;d;}"
+        );
+    }
+
+    #[test]
+    fn quoting_values_in_cron_commands() {
+        for value in HOSTILE_VALUES {
+            for cron_file in [None, Some(value.to_string())] {
+                let upsert = CronModuleInternalApiCall::Upsert {
+                    name: value.to_string(),
+                    cron_line: format!("0 2 * * * {}", value),
+                    user: Some(value.to_string()),
+                    cron_file: cron_file.clone(),
+                };
+                let remove = CronModuleInternalApiCall::Remove {
+                    name: value.to_string(),
+                    user: Some(value.to_string()),
+                    cron_file,
+                };
+                for api_call in [upsert, remove] {
+                    assert_no_injection(&CronApiCall::from(api_call, Privilege::None).command());
+                }
+            }
+        }
+    }
 }
@@ -36,6 +36,7 @@
 //!       Privilege: !WithSudo
 //! ```
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
 use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
@@ -381,30 +382,19 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for GroupApiCall {
                 system,
                 local,
             } => {
-                let base = if *local { "lgroupadd" } else { "groupadd" };
-                let mut args: Vec<String> = Vec::new();
-                if let Some(g) = gid {
-                    args.push(format!("-g {}", g));
-                }
-                if *system {
-                    args.push("-r".to_string());
-                }
-
-                let mut cmds = Vec::new();
-                // Create the group
-                cmds.push(format!("{} {} {}", base, args.join(" "), groupname));
-
+                let cmd = add_group_command(groupname, *gid, *system, *local);
                 // Add members if specified
-                if let Some(member_list) = members {
-                    for member in member_list {
-                        cmds.push(format!("usermod -aG {} {}", groupname, member));
-                    }
-                }
-
-                (cmds.join(" && "), &self.privilege)
+                let cmd = members.iter().flatten().fold(cmd, |cmd, member| {
+                    cmd.and(add_member_command(groupname, member))
+                });
+                (cmd.to_string(), &self.privilege)
             }
             GroupModuleInternalApiCall::ModifyGid { groupname, gid } => (
-                format!("groupmod -g {} {}", gid, groupname),
+                ShellCommand::new("groupmod")
+                    .arg("-g")
+                    .arg(gid.to_string())
+                    .arg(groupname)
+                    .to_string(),
                 &self.privilege,
             ),
             GroupModuleInternalApiCall::ModifyMembers { groupname, members } => {
@@ -431,30 +421,30 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for GroupApiCall {
                 let users_to_remove: Vec<&str> =
                     current_set.difference(&expected_set).cloned().collect();
 
-                let mut cmds = Vec::new();
-
-                // Add users who should be in the group but aren't
-                for user in users_to_add {
-                    cmds.push(format!("usermod -aG {} {}", groupname, user));
-                }
-
-                // Remove users who shouldn't be in the group
-                // Note: Removing users from a group requires gpasswd or similar
-                // For now, we'll use gpasswd --delete to remove users
-                for user in users_to_remove {
-                    cmds.push(format!("gpasswd --delete {} {}", user, groupname));
-                }
-
-                if cmds.is_empty() {
+                // Add users who should be in the group but aren't, then remove users who
+                // shouldn't be in the group
+                let cmd = users_to_add
+                    .iter()
+                    .map(|user| add_member_command(groupname, user))
+                    .chain(
+                        users_to_remove
+                            .iter()
+                            .map(|user| remove_member_command(groupname, user)),
+                    )
+                    .reduce(ShellCommand::and);
+
+                match cmd {
+                    Some(cmd) => (cmd.to_string(), &self.privilege),
                     // No changes needed
-                    return Ok(InternalApiCallOutcome::Success(None));
+                    None => return Ok(InternalApiCallOutcome::Success(None)),
                 }
-
-                (cmds.join(" && "), &self.privilege)
             }
             GroupModuleInternalApiCall::Delete { groupname, local } => {
                 let base = if *local { "lgroupdel" } else { "groupdel" };
-                (format!("{} {}", base, groupname), &self.privilege)
+                (
+                    ShellCommand::new(base).arg(groupname).to_string(),
+                    &self.privilege,
+                )
             }
         };
 
@@ -474,12 +464,37 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for GroupApiCall {
     }
 }
 
+fn add_group_command(groupname: &str, gid: Option<u32>, system: bool, local: bool) -> ShellCommand {
+    let mut cmd = ShellCommand::new(if local { "lgroupadd" } else { "groupadd" });
+    if let Some(gid) = gid {
+        cmd = cmd.arg("-g").arg(gid.to_string());
+    }
+    if system {
+        cmd = cmd.arg("-r");
+    }
+    cmd.arg(groupname)
+}
+
+fn add_member_command(groupname: &str, user: &str) -> ShellCommand {
+    ShellCommand::new("usermod -aG").arg(groupname).arg(user)
+}
+
+// Removing users from a group requires gpasswd or similar
+fn remove_member_command(groupname: &str, user: &str) -> ShellCommand {
+    ShellCommand::new("gpasswd --delete")
+        .arg(user)
+        .arg(groupname)
+}
+
 async fn group_exists<Handler: HostHandler>(
     host_handler: &mut Handler,
     groupname: &str,
 ) -> Result<bool, String> {
     match host_handler
-        .run_command(&format!("getent group {}", groupname), &Privilege::None)
+        .run_command(
+            ShellCommand::new("getent group").arg(groupname).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(result) => Ok(result.return_code == 0),
@@ -492,7 +507,10 @@ async fn get_group_gid<Handler: HostHandler>(
     groupname: &str,
 ) -> Result<u32, String> {
     match host_handler
-        .run_command(&format!("getent group {}", groupname), &Privilege::None)
+        .run_command(
+            ShellCommand::new("getent group").arg(groupname).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(result) => {
@@ -523,7 +541,10 @@ async fn get_group_members<Handler: HostHandler>(
     groupname: &str,
 ) -> Result<Vec<String>, String> {
     match host_handler
-        .run_command(&format!("getent group {}", groupname), &Privilege::None)
+        .run_command(
+            ShellCommand::new("getent group").arg(groupname).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(result) => {
@@ -553,6 +574,7 @@ async fn get_group_members<Handler: HostHandler>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_group_module_block_from_yaml_str() {
@@ -606,4 +628,15 @@ mod tests {
             .build();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn quoting_names_in_group_commands() {
+        for value in HOSTILE_VALUES {
+            assert_no_injection(add_group_command(value, Some(1500), true, false).as_str());
+            assert_no_injection(add_member_command(value, "alice").as_str());
+            assert_no_injection(add_member_command("docker", value).as_str());
+            assert_no_injection(remove_member_command(value, "alice").as_str());
+            assert_no_injection(remove_member_command("docker", value).as_str());
+        }
+    }
 }
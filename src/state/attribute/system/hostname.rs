@@ -34,6 +34,7 @@
 //!       Privilege: !WithSudo
 //! ```
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
 use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
@@ -245,14 +246,7 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for HostnameApiCall {
 
         let (cmd, privilege) = match &self.api_call {
             HostnameModuleInternalApiCall::SetHostname { name, method } => {
-                let cmd = match method {
-                    HostnameMethod::Systemd => format!("hostnamectl set-hostname {}", name),
-                    // Sets transient hostname and persists it in /etc/hostname
-                    HostnameMethod::Generic => {
-                        format!("hostname {} && echo {} > /etc/hostname", name, name)
-                    }
-                };
-                (cmd, &self.privilege)
+                (set_hostname_command(name, method), &self.privilege)
             }
         };
 
@@ -272,6 +266,19 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for HostnameApiCall {
     }
 }
 
+fn set_hostname_command(name: &str, method: &HostnameMethod) -> String {
+    match method {
+        HostnameMethod::Systemd => ShellCommand::new("hostnamectl set-hostname")
+            .arg(name)
+            .to_string(),
+        // Sets transient hostname and persists it in /etc/hostname
+        HostnameMethod::Generic => ShellCommand::new("hostname")
+            .arg(name)
+            .and(ShellCommand::new("echo").arg(name).raw("> /etc/hostname"))
+            .to_string(),
+    }
+}
+
 // Checking RFC952 and RFC1123 compliance
 fn is_valid_hostname(hostname: &str) -> Result<(), String> {
     if hostname.is_empty() {
@@ -316,6 +323,7 @@ fn is_valid_hostname(hostname: &str) -> Result<(), String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_hostname_module_block_from_yaml_str() {
@@ -384,4 +392,12 @@ mod tests {
         let long_label = format!("element-1.{}.element-2", "a".repeat(64));
         assert!(is_valid_hostname(&format!("{}.example.com", long_label)).is_err());
     }
+
+    #[test]
+    fn quoting_hostname_in_commands() {
+        for value in HOSTILE_VALUES {
+            assert_no_injection(&set_hostname_command(value, &HostnameMethod::Systemd));
+            assert_no_injection(&set_hostname_command(value, &HostnameMethod::Generic));
+        }
+    }
 }
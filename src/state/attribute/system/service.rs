@@ -36,6 +36,7 @@
 //!       Privilege: !WithSudo
 //! ```
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
 use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
@@ -289,6 +290,21 @@ impl ServiceApiCall {
             privilege,
         }
     }
+
+    fn command(&self) -> String {
+        let (action, service) = match &self.api_call {
+            ServiceModuleInternalApiCall::Start(s) => ("start", s),
+            ServiceModuleInternalApiCall::Stop(s) => ("stop", s),
+            ServiceModuleInternalApiCall::Restart(s) => ("restart", s),
+            ServiceModuleInternalApiCall::Reload(s) => ("reload", s),
+            ServiceModuleInternalApiCall::Enable(s) => ("enable", s),
+            ServiceModuleInternalApiCall::Disable(s) => ("disable", s),
+        };
+        ShellCommand::new("systemctl")
+            .arg(action)
+            .arg(service)
+            .to_string()
+    }
 }
 
 impl Check for ServiceApiCall {
@@ -328,17 +344,8 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for ServiceApiCall {
             self.check_host_compatibility(props)?;
         }
 
-        let cmd = match &self.api_call {
-            ServiceModuleInternalApiCall::Start(s) => format!("systemctl start {}", s),
-            ServiceModuleInternalApiCall::Stop(s) => format!("systemctl stop {}", s),
-            ServiceModuleInternalApiCall::Restart(s) => format!("systemctl restart {}", s),
-            ServiceModuleInternalApiCall::Reload(s) => format!("systemctl reload {}", s),
-            ServiceModuleInternalApiCall::Enable(s) => format!("systemctl enable {}", s),
-            ServiceModuleInternalApiCall::Disable(s) => format!("systemctl disable {}", s),
-        };
-
         let result = host_handler
-            .run_command(&cmd, &self.privilege)
+            .run_command(&self.command(), &self.privilege)
             .await
             .unwrap();
 
@@ -358,7 +365,10 @@ async fn service_is_active<Handler: HostHandler>(
     name: &str,
 ) -> Result<bool, String> {
     match host_handler
-        .run_command(&format!("systemctl is-active {}", name), &Privilege::None)
+        .run_command(
+            ShellCommand::new("systemctl is-active").arg(name).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(r) => match r.return_code {
@@ -376,7 +386,10 @@ async fn service_is_enabled<Handler: HostHandler>(
     name: &str,
 ) -> Result<bool, String> {
     match host_handler
-        .run_command(&format!("systemctl is-enabled {}", name), &Privilege::None)
+        .run_command(
+            ShellCommand::new("systemctl is-enabled").arg(name).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(r) => match r.return_code {
@@ -395,6 +408,7 @@ async fn service_is_enabled<Handler: HostHandler>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_service_module_block_from_yaml_str() {
@@ -449,4 +463,16 @@ mod tests {
             .build();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn quoting_service_names_in_commands() {
+        for value in HOSTILE_VALUES {
+            for api_call in [
+                ServiceModuleInternalApiCall::Start(value.to_string()),
+                ServiceModuleInternalApiCall::Disable(value.to_string()),
+            ] {
+                assert_no_injection(&ServiceApiCall::from(api_call, Privilege::None).command());
+            }
+        }
+    }
 }
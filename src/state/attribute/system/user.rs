@@ -42,6 +42,7 @@
 //!       Privilege: !WithSudo
 //! ```
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
 use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
@@ -493,40 +494,9 @@ impl UserApiCall {
             privilege,
         }
     }
-}
-
-impl Check for UserApiCall {
-    fn check(&self) -> Result<(), RegentError> {
-        Ok(())
-    }
-
-    fn check_host_compatibility(
-        &self,
-        host_properties: &HostProperties,
-    ) -> Result<(), RegentError> {
-        match host_properties.os_kind() {
-            OsKind::Linux(_) => Ok(()),
-            incompatible_os_kind => Err(RegentError::IncompatibleHost(format!(
-                "Host is {:?} but user management is only supported on Linux",
-                incompatible_os_kind
-            ))),
-        }
-    }
-}
-
-impl<Handler: HostHandler> ReachCompliance<Handler> for UserApiCall {
-    async fn call(
-        &self,
-        host_handler: &mut Handler,
-        host_properties: &Option<HostProperties>,
-        _optional_secret_provider: &Option<SecretProvidersPool>,
-    ) -> Result<InternalApiCallOutcome, RegentError> {
-        // Early check: verify we're on a compatible host (Linux)
-        if let Some(props) = host_properties {
-            self.check_host_compatibility(props)?;
-        }
 
-        let (cmd, privilege) = match &self.api_call {
+    fn command(&self) -> String {
+        match &self.api_call {
             UserModuleInternalApiCall::Add {
                 username,
                 uid,
@@ -539,42 +509,25 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for UserApiCall {
                 system,
                 create_home,
             } => {
-                let mut args: Vec<String> = Vec::new();
+                let mut cmd = ShellCommand::new("useradd");
                 if let Some(u) = uid {
-                    args.push(format!("-u {}", u));
+                    cmd = cmd.arg("-u").arg(u.to_string());
                 }
                 if let Some(g) = group {
-                    args.push(format!("-g {}", g));
+                    cmd = cmd.arg("-g").arg(g);
                 }
                 if let Some(gs) = groups {
                     if !gs.is_empty() {
-                        args.push(format!("-G {}", gs.join(",")));
+                        cmd = cmd.arg("-G").arg(gs.join(","));
                     }
                 }
-                if let Some(s) = shell {
-                    args.push(format!("-s {}", s));
-                }
-                if let Some(h) = home {
-                    args.push(format!("-d {}", h));
-                }
-                if let Some(c) = comment {
-                    args.push(format!("-c '{}'", c));
-                }
-                if let Some(p) = password {
-                    args.push(format!("-p '{}'", p));
-                }
+                cmd = common_user_args(cmd, shell, home, comment, password);
                 if *system {
-                    args.push("-r".to_string());
-                }
-                if *create_home {
-                    args.push("-m".to_string());
-                } else {
-                    args.push("-M".to_string());
+                    cmd = cmd.arg("-r");
                 }
-                (
-                    format!("useradd {} {}", args.join(" "), username),
-                    &self.privilege,
-                )
+                cmd.arg(if *create_home { "-m" } else { "-M" })
+                    .arg(username)
+                    .to_string()
             }
             UserModuleInternalApiCall::Modify {
                 username,
@@ -587,48 +540,92 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for UserApiCall {
                 comment,
                 password,
             } => {
-                let mut args: Vec<String> = Vec::new();
+                let mut cmd = ShellCommand::new("usermod");
                 if let Some(u) = uid {
-                    args.push(format!("-u {}", u));
+                    cmd = cmd.arg("-u").arg(u.to_string());
                 }
                 if let Some(g) = group {
-                    args.push(format!("-g {}", g));
+                    cmd = cmd.arg("-g").arg(g);
                 }
                 if let Some(gs) = groups {
-                    // Quote the value to handle empty list (removes all supplementary groups)
-                    args.push(format!("-G \"{}\"", gs.join(",")));
+                    // An empty list (quoted as '') removes all supplementary groups
+                    cmd = cmd.arg("-G").arg(gs.join(","));
                     if *append && !gs.is_empty() {
-                        args.push("-a".to_string());
+                        cmd = cmd.arg("-a");
                     }
                 }
-                if let Some(s) = shell {
-                    args.push(format!("-s {}", s));
-                }
-                if let Some(h) = home {
-                    args.push(format!("-d {}", h));
-                }
-                if let Some(c) = comment {
-                    args.push(format!("-c '{}'", c));
-                }
-                if let Some(p) = password {
-                    args.push(format!("-p '{}'", p));
-                }
-                (
-                    format!("usermod {} {}", args.join(" "), username),
-                    &self.privilege,
-                )
+                common_user_args(cmd, shell, home, comment, password)
+                    .arg(username)
+                    .to_string()
             }
             UserModuleInternalApiCall::Delete {
                 username,
                 remove_home,
             } => {
-                let flags = if *remove_home { "-r " } else { "" };
-                (format!("userdel {}{}", flags, username), &self.privilege)
+                let cmd = ShellCommand::new("userdel");
+                let cmd = if *remove_home { cmd.arg("-r") } else { cmd };
+                cmd.arg(username).to_string()
             }
-        };
+        }
+    }
+}
+
+/// Options shared by useradd and usermod
+fn common_user_args(
+    mut cmd: ShellCommand,
+    shell: &Option<String>,
+    home: &Option<String>,
+    comment: &Option<String>,
+    password: &Option<String>,
+) -> ShellCommand {
+    if let Some(s) = shell {
+        cmd = cmd.arg("-s").arg(s);
+    }
+    if let Some(h) = home {
+        cmd = cmd.arg("-d").arg(h);
+    }
+    if let Some(c) = comment {
+        cmd = cmd.arg("-c").arg(c);
+    }
+    if let Some(p) = password {
+        cmd = cmd.arg("-p").arg(p);
+    }
+    cmd
+}
+
+impl Check for UserApiCall {
+    fn check(&self) -> Result<(), RegentError> {
+        Ok(())
+    }
+
+    fn check_host_compatibility(
+        &self,
+        host_properties: &HostProperties,
+    ) -> Result<(), RegentError> {
+        match host_properties.os_kind() {
+            OsKind::Linux(_) => Ok(()),
+            incompatible_os_kind => Err(RegentError::IncompatibleHost(format!(
+                "Host is {:?} but user management is only supported on Linux",
+                incompatible_os_kind
+            ))),
+        }
+    }
+}
+
+impl<Handler: HostHandler> ReachCompliance<Handler> for UserApiCall {
+    async fn call(
+        &self,
+        host_handler: &mut Handler,
+        host_properties: &Option<HostProperties>,
+        _optional_secret_provider: &Option<SecretProvidersPool>,
+    ) -> Result<InternalApiCallOutcome, RegentError> {
+        // Early check: verify we're on a compatible host (Linux)
+        if let Some(props) = host_properties {
+            self.check_host_compatibility(props)?;
+        }
 
         let cmd_result = host_handler
-            .run_command(cmd.as_str(), privilege)
+            .run_command(self.command().as_str(), &self.privilege)
             .await
             .unwrap();
 
@@ -655,7 +652,10 @@ async fn user_exists<Handler: HostHandler>(
     username: &str,
 ) -> Result<bool, String> {
     match host_handler
-        .run_command(&format!("id {}", username), &Privilege::None)
+        .run_command(
+            ShellCommand::new("id").arg(username).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(result) => Ok(result.return_code == 0),
@@ -668,7 +668,10 @@ async fn get_passwd_entry<Handler: HostHandler>(
     username: &str,
 ) -> Result<PasswdEntry, String> {
     match host_handler
-        .run_command(&format!("getent passwd {}", username), &Privilege::None)
+        .run_command(
+            ShellCommand::new("getent passwd").arg(username).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(result) => {
@@ -705,7 +708,10 @@ async fn get_primary_group<Handler: HostHandler>(
     username: &str,
 ) -> Result<String, String> {
     match host_handler
-        .run_command(&format!("id -gn {}", username), &Privilege::None)
+        .run_command(
+            ShellCommand::new("id -gn").arg(username).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(result) => {
@@ -728,7 +734,10 @@ async fn get_supplementary_groups<Handler: HostHandler>(
     let primary_group = get_primary_group(host_handler, username).await?;
 
     match host_handler
-        .run_command(&format!("id -Gn {}", username), &Privilege::None)
+        .run_command(
+            ShellCommand::new("id -Gn").arg(username).as_str(),
+            &Privilege::None,
+        )
         .await
     {
         Ok(result) => {
@@ -754,6 +763,7 @@ async fn get_supplementary_groups<Handler: HostHandler>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_user_module_block_from_yaml_str() {
@@ -801,4 +811,40 @@ mod tests {
             .build();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn quoting_values_in_user_commands() {
+        for value in HOSTILE_VALUES {
+            let add = UserModuleInternalApiCall::Add {
+                username: value.to_string(),
+                uid: Some(1500),
+                group: Some(value.to_string()),
+                groups: Some(vec!["docker".to_string(), value.to_string()]),
+                shell: Some(value.to_string()),
+                home: Some(value.to_string()),
+                comment: Some(value.to_string()),
+                password: Some(value.to_string()),
+                system: false,
+                create_home: true,
+            };
+            let modify = UserModuleInternalApiCall::Modify {
+                username: value.to_string(),
+                uid: None,
+                group: None,
+                groups: Some(vec![value.to_string()]),
+                append: true,
+                shell: None,
+                home: None,
+                comment: Some(value.to_string()),
+                password: None,
+            };
+            let delete = UserModuleInternalApiCall::Delete {
+                username: value.to_string(),
+                remove_home: true,
+            };
+            for api_call in [add, modify, delete] {
+                assert_no_injection(&UserApiCall::from(api_call, Privilege::None).command());
+            }
+        }
+    }
 }
@@ -0,0 +1,939 @@
+//! File management attribute
+//!
+//! This module provides the `FileBlockExpectedState` type for managing whole files, directories
+//! and symbolic links : existence, content, owner, group and mode.
+//!
+//! The content of a file is compared through its SHA-256 checksum, so that unchanged files are
+//! never rewritten. The content can be given inline or as a reference to a secret.
+//!
+//! **Compatible OS:** Linux
+//!
+//! # Examples
+//!
+//! ## Rust API
+//!
+//! ```no_run
+//! use regent_sdk::state::attribute::utilities::file::{FileBlockExpectedState, FileExpectedState};
+//! use regent_sdk::{Attribute, ExpectedState, Privilege};
+//!
+//! let motd = FileBlockExpectedState::builder("/etc/motd")
+//!     .with_state(FileExpectedState::Present)
+//!     .with_content("Welcome !\n")
+//!     .with_owner("root")
+//!     .with_group("root")
+//!     .with_mode("0644")
+//!     .build()
+//!     .unwrap();
+//!
+//! let expected_state = ExpectedState::new()
+//!     .with_attribute(Attribute::file(motd, Privilege::WithSudo, None))
+//!     .build();
+//! ```
+//!
+//! ## YAML API
+//!
+//! ```yaml
+//! Attributes:
+//!   - Detail: !File
+//!       Path: /etc/motd
+//!       State: !Present
+//!       Content: "Welcome !\n"
+//!       Owner: root
+//!       Group: root
+//!       Mode: "0644"
+//!     Privilege: !WithSudo
+//!   - Detail: !File
+//!       Path: /etc/app/api.key
+//!       Content:
+//!         SecRef: app-api-key
+//!         Provider: vault
+//!       Mode: "0600"
+//!     Privilege: !WithSudo
+//!   - Detail: !File
+//!       Path: /usr/local/bin/app
+//!       State: !Link
+//!       Target: /opt/app/bin/app
+//!     Privilege: !WithSudo
+//! ```
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::command::ShellCommand;
+use crate::error::RegentError;
+use crate::hosts::handlers::install_file;
+use crate::hosts::managed_host::InternalApiCallOutcome;
+use crate::hosts::managed_host::{AssessCompliance, PreviewChange, ReachCompliance, Timeout};
+use crate::hosts::properties::{HostProperties, OsKind};
+use crate::secrets::SecretProvidersPool;
+use crate::state::Check;
+use crate::state::attribute::HostHandler;
+use crate::state::attribute::Privilege;
+use crate::state::attribute::Remediation;
+use crate::state::compliance::AttributeComplianceAssessment;
+use crate::state::diff;
+use crate::state::expected_state::Parameter;
+
+/// Desired state of a path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum FileExpectedState {
+    /// Regular file. Created from `Content` if missing, rewritten if its content differs.
+    Present,
+    /// Nothing exists at this path (files, links and directories are removed)
+    Absent,
+    /// Directory, created with its parents if missing
+    Directory,
+    /// Symbolic link pointing to `Target`
+    Link,
+    /// Empty file created if missing. An existing file is left untouched.
+    Touch,
+}
+
+/// Configuration for managing a file, a directory or a symbolic link
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "PascalCase")]
+pub struct FileBlockExpectedState {
+    /// Absolute path to the managed file
+    path: String,
+    /// Default state: Present
+    state: Option<FileExpectedState>,
+    /// Whole content of the file (only with State: Present)
+    content: Option<Parameter<String>>,
+    /// Path the symbolic link points to (only with State: Link)
+    target: Option<String>,
+    /// User owning the file (name or uid)
+    owner: Option<String>,
+    /// Group owning the file (name or gid)
+    group: Option<String>,
+    /// Octal permissions (e.g. "0644")
+    mode: Option<String>,
+    /// Command validating the new content before it replaces the file. `%s` is replaced by the
+    /// path of a temporary file holding the new content (e.g. `visudo -cf %s`).
+    validate: Option<String>,
+}
+
+impl FileBlockExpectedState {
+    pub fn builder(path: &str) -> FileBlockExpectedState {
+        FileBlockExpectedState {
+            path: path.to_string(),
+            state: None,
+            content: None,
+            target: None,
+            owner: None,
+            group: None,
+            mode: None,
+            validate: None,
+        }
+    }
+
+    pub fn with_state(&mut self, state: FileExpectedState) -> &mut Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub fn with_content(&mut self, content: &str) -> &mut Self {
+        self.content = Some(Parameter::Clear(content.to_string()));
+        self
+    }
+
+    /// Content which may be a reference to a secret
+    pub fn with_content_parameter(&mut self, content: Parameter<String>) -> &mut Self {
+        self.content = Some(content);
+        self
+    }
+
+    pub fn with_target(&mut self, target: &str) -> &mut Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    pub fn with_owner(&mut self, owner: &str) -> &mut Self {
+        self.owner = Some(owner.to_string());
+        self
+    }
+
+    pub fn with_group(&mut self, group: &str) -> &mut Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    pub fn with_mode(&mut self, mode: &str) -> &mut Self {
+        self.mode = Some(mode.to_string());
+        self
+    }
+
+    pub fn with_validate(&mut self, validate: &str) -> &mut Self {
+        self.validate = Some(validate.to_string());
+        self
+    }
+
+    pub fn build(&self) -> Result<FileBlockExpectedState, RegentError> {
+        self.check()?;
+        Ok(self.clone())
+    }
+
+    fn expected_state(&self) -> &FileExpectedState {
+        self.state.as_ref().unwrap_or(&FileExpectedState::Present)
+    }
+
+    fn write_content(&self, content: &Parameter<String>) -> FileModuleInternalApiCall {
+        FileModuleInternalApiCall::WriteContent {
+            content: content.clone(),
+            validate: self.validate.clone(),
+        }
+    }
+
+    fn api_call(&self, api_call: FileModuleInternalApiCall, privilege: &Privilege) -> Remediation {
+        Remediation::File(FileApiCall {
+            path: self.path.clone(),
+            api_call,
+            privilege: privilege.clone(),
+        })
+    }
+}
+
+impl Check for FileBlockExpectedState {
+    fn check(&self) -> Result<(), RegentError> {
+        if !self.path.starts_with('/') {
+            return Err(RegentError::IncoherentExpectedState(format!(
+                "Path must be absolute : '{}'",
+                self.path
+            )));
+        }
+
+        let state = self.expected_state();
+        if self.content.is_some() && *state != FileExpectedState::Present {
+            return Err(RegentError::IncoherentExpectedState(
+                "Content can only be set with State: Present.".to_string(),
+            ));
+        }
+        match (state, &self.target) {
+            (FileExpectedState::Link, None) => {
+                return Err(RegentError::IncoherentExpectedState(
+                    "State: Link requires Target.".to_string(),
+                ));
+            }
+            (FileExpectedState::Link, Some(_)) => {
+                if self.mode.is_some() {
+                    return Err(RegentError::IncoherentExpectedState(
+                        "Mode cannot be set on a symbolic link.".to_string(),
+                    ));
+                }
+            }
+            (_, Some(_)) => {
+                return Err(RegentError::IncoherentExpectedState(
+                    "Target can only be set with State: Link.".to_string(),
+                ));
+            }
+            (_, None) => {}
+        }
+        if let Some(validate) = &self.validate {
+            if self.content.is_none() {
+                return Err(RegentError::IncoherentExpectedState(
+                    "Validate requires Content.".to_string(),
+                ));
+            }
+            if !validate.contains("%s") {
+                return Err(RegentError::IncoherentExpectedState(format!(
+                    "Validate must contain %s : '{}'",
+                    validate
+                )));
+            }
+        }
+        if *state == FileExpectedState::Absent
+            && (self.owner.is_some() || self.group.is_some() || self.mode.is_some())
+        {
+            return Err(RegentError::IncoherentExpectedState(
+                "Owner, Group and Mode cannot be set with State: Absent.".to_string(),
+            ));
+        }
+        if let Some(mode) = &self.mode
+            && !is_valid_mode(mode)
+        {
+            return Err(RegentError::IncoherentExpectedState(format!(
+                "Mode must be 3 or 4 octal digits : '{}'",
+                mode
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_host_compatibility(
+        &self,
+        host_properties: &HostProperties,
+    ) -> Result<(), RegentError> {
+        match host_properties.os_kind() {
+            OsKind::Linux(_) => Ok(()),
+            _ => Err(RegentError::IncompatibleHost(
+                "File attribute is only supported on Linux hosts".to_string(),
+            )),
+        }
+    }
+}
+
+impl Timeout for FileBlockExpectedState {
+    fn default_timeout(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+impl<Handler: HostHandler> AssessCompliance<Handler> for FileBlockExpectedState {
+    async fn assess_compliance(
+        &self,
+        host_handler: &mut Handler,
+        host_properties: &Option<HostProperties>,
+        privilege: &Privilege,
+        optional_secret_provider: &Option<SecretProvidersPool>,
+    ) -> Result<AttributeComplianceAssessment, RegentError> {
+        if let Some(props) = host_properties {
+            self.check_host_compatibility(props)?;
+        }
+
+        let current_state = stat_path(host_handler, &self.path, privilege).await?;
+        let mut remediations: Vec<Remediation> = Vec::new();
+
+        match (self.expected_state(), &current_state) {
+            (FileExpectedState::Absent, None) => {
+                return Ok(AttributeComplianceAssessment::Compliant);
+            }
+            (FileExpectedState::Absent, Some(_)) => {
+                return Ok(AttributeComplianceAssessment::NonCompliant(vec![
+                    self.api_call(FileModuleInternalApiCall::Remove, privilege),
+                ]));
+            }
+            (FileExpectedState::Present, None) => match &self.content {
+                Some(content) => {
+                    remediations.push(self.api_call(self.write_content(content), privilege))
+                }
+                None => {
+                    return Err(RegentError::FailedDryRunEvaluation(format!(
+                        "{} does not exist (set Content, or use State: Touch to create an empty file)",
+                        self.path
+                    )));
+                }
+            },
+            (
+                FileExpectedState::Present,
+                Some(PathStat {
+                    kind: PathKind::File | PathKind::Link,
+                    ..
+                }),
+            ) => {
+                if let Some(content) = &self.content {
+                    let raw_content = content.clone().inner_raw(optional_secret_provider).await?;
+                    let current_checksum =
+                        remote_sha256(host_handler, &self.path, privilege).await?;
+                    if current_checksum == sha256_hex(&raw_content) {
+                        remediations.push(Remediation::None(format!(
+                            "{} : content up to date",
+                            self.path
+                        )));
+                    } else {
+                        remediations.push(self.api_call(self.write_content(content), privilege));
+                    }
+                }
+            }
+            (FileExpectedState::Directory, None) => remediations
+                .push(self.api_call(FileModuleInternalApiCall::CreateDirectory, privilege)),
+            (
+                FileExpectedState::Directory,
+                Some(PathStat {
+                    kind: PathKind::Directory,
+                    ..
+                }),
+            ) => {}
+            (FileExpectedState::Link, None)
+            | (
+                FileExpectedState::Link,
+                Some(PathStat {
+                    kind: PathKind::File,
+                    ..
+                }),
+            ) => remediations.push(self.api_call(
+                FileModuleInternalApiCall::CreateLink(self.target.clone().unwrap_or_default()),
+                privilege,
+            )),
+            (
+                FileExpectedState::Link,
+                Some(PathStat {
+                    kind: PathKind::Link,
+                    ..
+                }),
+            ) => {
+                let target = self.target.clone().unwrap_or_default();
+                let current_target = read_link(host_handler, &self.path, privilege).await?;
+                if current_target == target {
+                    remediations.push(Remediation::None(format!(
+                        "{} already points to {}",
+                        self.path, target
+                    )));
+                } else {
+                    remediations.push(
+                        self.api_call(FileModuleInternalApiCall::CreateLink(target), privilege),
+                    );
+                }
+            }
+            (FileExpectedState::Touch, None) => {
+                remediations.push(self.api_call(FileModuleInternalApiCall::Touch, privilege))
+            }
+            (FileExpectedState::Touch, Some(_)) => {}
+            (expected_state, Some(current_stat)) => {
+                return Err(RegentError::FailedDryRunEvaluation(format!(
+                    "{} is a {} and cannot be made {:?}",
+                    self.path, current_stat.kind, expected_state
+                )));
+            }
+        }
+
+        // Ownership and permissions : always set on a freshly created path
+        if let Some(owner) = &self.owner {
+            match &current_state {
+                Some(current_stat) if current_stat.is_owned_by(owner) => {}
+                _ => remediations.push(self.api_call(
+                    FileModuleInternalApiCall::SetOwner(owner.clone()),
+                    privilege,
+                )),
+            }
+        }
+        if let Some(group) = &self.group {
+            match &current_state {
+                Some(current_stat) if current_stat.is_in_group(group) => {}
+                _ => remediations.push(self.api_call(
+                    FileModuleInternalApiCall::SetGroup(group.clone()),
+                    privilege,
+                )),
+            }
+        }
+        if let Some(mode) = &self.mode {
+            match &current_state {
+                Some(current_stat)
+                    if normalize_mode(&current_stat.mode) == normalize_mode(mode) => {}
+                _ => remediations.push(
+                    self.api_call(FileModuleInternalApiCall::SetMode(mode.clone()), privilege),
+                ),
+            }
+        }
+
+        if remediations
+            .iter()
+            .all(|remediation| matches!(remediation, Remediation::None(_)))
+        {
+            Ok(AttributeComplianceAssessment::Compliant)
+        } else {
+            remediations.retain(|remediation| !matches!(remediation, Remediation::None(_)));
+            Ok(AttributeComplianceAssessment::NonCompliant(remediations))
+        }
+    }
+}
+
+/// Kind of an existing path
+#[derive(Debug, Clone, PartialEq)]
+enum PathKind {
+    File,
+    Directory,
+    Link,
+    Other(String),
+}
+
+impl std::fmt::Display for PathKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathKind::File => write!(f, "regular file"),
+            PathKind::Directory => write!(f, "directory"),
+            PathKind::Link => write!(f, "symbolic link"),
+            PathKind::Other(kind) => write!(f, "{}", kind),
+        }
+    }
+}
+
+/// What `stat` tells about an existing path
+#[derive(Debug, Clone, PartialEq)]
+struct PathStat {
+    kind: PathKind,
+    owner_name: String,
+    owner_id: String,
+    group_name: String,
+    group_id: String,
+    mode: String,
+}
+
+impl PathStat {
+    const FORMAT: &'static str = "%F|%U|%u|%G|%g|%a";
+
+    /// Parse the output of `stat -c` with [`PathStat::FORMAT`]
+    fn parse(raw: &str) -> Option<PathStat> {
+        let fields: Vec<&str> = raw.trim().split('|').collect();
+        let [kind, owner_name, owner_id, group_name, group_id, mode] = fields.as_slice() else {
+            return None;
+        };
+
+        let kind = match *kind {
+            "regular file" | "regular empty file" => PathKind::File,
+            "directory" => PathKind::Directory,
+            "symbolic link" => PathKind::Link,
+            other => PathKind::Other(other.to_string()),
+        };
+
+        Some(PathStat {
+            kind,
+            owner_name: owner_name.to_string(),
+            owner_id: owner_id.to_string(),
+            group_name: group_name.to_string(),
+            group_id: group_id.to_string(),
+            mode: mode.to_string(),
+        })
+    }
+
+    fn is_owned_by(&self, owner: &str) -> bool {
+        self.owner_name == owner || self.owner_id == owner
+    }
+
+    fn is_in_group(&self, group: &str) -> bool {
+        self.group_name == group || self.group_id == group
+    }
+}
+
+/// `None` if nothing exists at this path. Symbolic links are not followed.
+async fn stat_path<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    path: &str,
+    privilege: &Privilege,
+) -> Result<Option<PathStat>, RegentError> {
+    let existence = host_handler
+        .run_command(
+            ShellCommand::new("test -e")
+                .arg(path)
+                .raw("-o -L")
+                .arg(path)
+                .as_str(),
+            privilege,
+        )
+        .await?;
+    if existence.return_code != 0 {
+        return Ok(None);
+    }
+
+    let result = host_handler
+        .run_command(
+            ShellCommand::new("stat -c")
+                .arg(PathStat::FORMAT)
+                .arg(path)
+                .as_str(),
+            privilege,
+        )
+        .await?;
+    if result.return_code != 0 {
+        return Err(RegentError::FailedDryRunEvaluation(format!(
+            "Failed to stat {} : {}",
+            path, result.stderr
+        )));
+    }
+
+    match PathStat::parse(&result.stdout) {
+        Some(path_stat) => Ok(Some(path_stat)),
+        None => Err(RegentError::FailedDryRunEvaluation(format!(
+            "Unexpected stat output for {} : {}",
+            path, result.stdout
+        ))),
+    }
+}
+
+async fn remote_sha256<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    path: &str,
+    privilege: &Privilege,
+) -> Result<String, RegentError> {
+    let result = host_handler
+        .run_command(ShellCommand::new("sha256sum").arg(path).as_str(), privilege)
+        .await?;
+    match result.stdout.split_whitespace().next() {
+        Some(checksum) if result.return_code == 0 => Ok(checksum.to_string()),
+        _ => Err(RegentError::FailedDryRunEvaluation(format!(
+            "Failed to compute checksum of {} : {}",
+            path, result.stderr
+        ))),
+    }
+}
+
+async fn read_link<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    path: &str,
+    privilege: &Privilege,
+) -> Result<String, RegentError> {
+    let result = host_handler
+        .run_command(ShellCommand::new("readlink").arg(path).as_str(), privilege)
+        .await?;
+    if result.return_code == 0 {
+        Ok(result.stdout.trim_end_matches('\n').to_string())
+    } else {
+        Err(RegentError::FailedDryRunEvaluation(format!(
+            "Failed to read link {} : {}",
+            path, result.stderr
+        )))
+    }
+}
+
+/// Lowercase hexadecimal SHA-256 checksum, as printed by `sha256sum`
+fn sha256_hex(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn is_valid_mode(mode: &str) -> bool {
+    (3..=4).contains(&mode.len()) && mode.chars().all(|c| ('0'..='7').contains(&c))
+}
+
+/// `stat` prints "644" where users write "0644"
+fn normalize_mode(mode: &str) -> &str {
+    match mode.trim_start_matches('0') {
+        "" => "0",
+        trimmed => trimmed,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum FileModuleInternalApiCall {
+    WriteContent {
+        content: Parameter<String>,
+        validate: Option<String>,
+    },
+    Remove,
+    CreateDirectory,
+    CreateLink(String),
+    Touch,
+    SetOwner(String),
+    SetGroup(String),
+    SetMode(String),
+}
+
+impl std::fmt::Display for FileModuleInternalApiCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileModuleInternalApiCall::WriteContent {
+                validate: Some(validate),
+                ..
+            } => write!(f, "write content validated by '{}'", validate),
+            FileModuleInternalApiCall::WriteContent { .. } => write!(f, "write content"),
+            FileModuleInternalApiCall::Remove => write!(f, "remove"),
+            FileModuleInternalApiCall::CreateDirectory => write!(f, "create directory"),
+            FileModuleInternalApiCall::CreateLink(target) => {
+                write!(f, "create link to {}", target)
+            }
+            FileModuleInternalApiCall::Touch => write!(f, "create empty file"),
+            FileModuleInternalApiCall::SetOwner(owner) => write!(f, "set owner {}", owner),
+            FileModuleInternalApiCall::SetGroup(group) => write!(f, "set group {}", group),
+            FileModuleInternalApiCall::SetMode(mode) => write!(f, "set mode {}", mode),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileApiCall {
+    path: String,
+    pub api_call: FileModuleInternalApiCall,
+    privilege: Privilege,
+}
+
+impl FileApiCall {
+    pub fn display(&self) -> String {
+        format!("{} : {}", self.path, self.api_call)
+    }
+
+    /// `None` for WriteContent, whose content is uploaded instead
+    fn command(&self) -> Option<String> {
+        let cmd = match &self.api_call {
+            FileModuleInternalApiCall::WriteContent { .. } => return None,
+            FileModuleInternalApiCall::Remove => ShellCommand::new("rm -rf"),
+            FileModuleInternalApiCall::CreateDirectory => ShellCommand::new("mkdir -p"),
+            FileModuleInternalApiCall::CreateLink(target) => {
+                ShellCommand::new("ln -sfn").arg(target)
+            }
+            FileModuleInternalApiCall::Touch => ShellCommand::new("touch"),
+            FileModuleInternalApiCall::SetOwner(owner) => ShellCommand::new("chown -h").arg(owner),
+            FileModuleInternalApiCall::SetGroup(group) => ShellCommand::new("chgrp -h").arg(group),
+            FileModuleInternalApiCall::SetMode(mode) => ShellCommand::new("chmod").arg(mode),
+        };
+        Some(cmd.arg(&self.path).to_string())
+    }
+}
+
+impl Timeout for FileApiCall {
+    fn default_timeout(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+impl Check for FileApiCall {
+    fn check(&self) -> Result<(), RegentError> {
+        Ok(())
+    }
+
+    fn check_host_compatibility(
+        &self,
+        host_properties: &HostProperties,
+    ) -> Result<(), RegentError> {
+        match host_properties.os_kind() {
+            OsKind::Linux(_) => Ok(()),
+            _ => Err(RegentError::IncompatibleHost(
+                "File attribute is only supported on Linux hosts".to_string(),
+            )),
+        }
+    }
+}
+
+impl<Handler: HostHandler> ReachCompliance<Handler> for FileApiCall {
+    async fn call(
+        &self,
+        host_handler: &mut Handler,
+        host_properties: &Option<HostProperties>,
+        optional_secret_provider: &Option<SecretProvidersPool>,
+    ) -> Result<InternalApiCallOutcome, RegentError> {
+        if let Some(props) = host_properties {
+            self.check_host_compatibility(props)?;
+        }
+
+        let mut expected_checksum: Option<String> = None;
+
+        let result = match &self.api_call {
+            FileModuleInternalApiCall::WriteContent { content, validate } => {
+                let raw_content = content.clone().inner_raw(optional_secret_provider).await?;
+                expected_checksum = Some(sha256_hex(&raw_content));
+                // The content is uploaded as is, away from shell interpretation
+                install_file(
+                    host_handler,
+                    &self.path,
+                    raw_content.as_bytes(),
+                    validate.as_deref(),
+                    &self.privilege,
+                )
+                .await?
+            }
+            _ => match self.command() {
+                Some(cmd) => host_handler.run_command(&cmd, &self.privilege).await?,
+                None => {
+                    return Err(RegentError::InternalLogicError(format!(
+                        "No command to {}",
+                        self.display()
+                    )));
+                }
+            },
+        };
+
+        if result.return_code != 0 {
+            return Ok(InternalApiCallOutcome::Failure(format!(
+                "RC: {}, STDOUT: {}, STDERR: {}",
+                result.return_code, result.stdout, result.stderr
+            )));
+        }
+
+        // Post-application verification of the written content
+        if let Some(expected_checksum) = expected_checksum {
+            let written_checksum = remote_sha256(host_handler, &self.path, &self.privilege).await?;
+            if written_checksum != expected_checksum {
+                return Ok(InternalApiCallOutcome::Failure(format!(
+                    "Content written but checksum of {} does not match",
+                    self.path
+                )));
+            }
+        }
+
+        Ok(InternalApiCallOutcome::Success(None))
+    }
+}
+
+impl<Handler: HostHandler> PreviewChange<Handler> for FileApiCall {
+    async fn preview(&self, host_handler: &mut Handler) -> Result<Option<String>, RegentError> {
+        let wanted_content = match &self.api_call {
+            // Secrets are never resolved in check mode : their reference is shown instead
+            FileModuleInternalApiCall::WriteContent {
+                content: Parameter::Clear(content),
+                ..
+            } => content.clone(),
+            FileModuleInternalApiCall::WriteContent {
+                content: secret, ..
+            } => secret.to_string(),
+            FileModuleInternalApiCall::Remove => {
+                let is_regular_file = host_handler
+                    .run_command(
+                        ShellCommand::new("test -f").arg(&self.path).as_str(),
+                        &self.privilege,
+                    )
+                    .await?
+                    .return_code
+                    == 0;
+                if !is_regular_file {
+                    return Ok(None);
+                }
+                String::new()
+            }
+            _ => return Ok(None),
+        };
+
+        let current_content =
+            diff::current_content(host_handler, &self.path, &self.privilege).await?;
+
+        Ok(diff::unified_diff(
+            &self.path,
+            &current_content,
+            &wanted_content,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
+
+    #[test]
+    fn parsing_file_module_block_from_yaml_str() {
+        let raw_attributes = "---
+- Path: /etc/motd
+  State: !Present
+  Content: \"Welcome !\\n\"
+  Owner: root
+  Group: root
+  Mode: \"0644\"
+
+- Path: /etc/app/api.key
+  Content:
+    SecRef: app-api-key
+    Provider: vault
+
+- Path: /var/lib/app
+  State: !Directory
+  Mode: \"0750\"
+
+- Path: /usr/local/bin/app
+  State: !Link
+  Target: /opt/app/bin/app
+
+- Path: /var/run/app.flag
+  State: !Touch
+
+- Path: /tmp/old
+  State: !Absent
+        ";
+
+        let attributes: Vec<FileBlockExpectedState> = yaml_serde::from_str(raw_attributes).unwrap();
+
+        assert_eq!(
+            attributes[0].content,
+            Some(Parameter::Clear("Welcome !\n".to_string()))
+        );
+        assert!(matches!(attributes[1].content, Some(Parameter::Secret(_))));
+        for attribute in attributes {
+            attribute.check().unwrap();
+        }
+    }
+
+    #[test]
+    fn check_rejects_incoherent_blocks() {
+        assert!(FileBlockExpectedState::builder("etc/motd").build().is_err());
+        assert!(
+            FileBlockExpectedState::builder("/etc/motd")
+                .with_state(FileExpectedState::Directory)
+                .with_content("x")
+                .build()
+                .is_err()
+        );
+        assert!(
+            FileBlockExpectedState::builder("/usr/local/bin/app")
+                .with_state(FileExpectedState::Link)
+                .build()
+                .is_err()
+        );
+        assert!(
+            FileBlockExpectedState::builder("/etc/motd")
+                .with_target("/tmp/motd")
+                .build()
+                .is_err()
+        );
+        assert!(
+            FileBlockExpectedState::builder("/etc/motd")
+                .with_state(FileExpectedState::Absent)
+                .with_owner("root")
+                .build()
+                .is_err()
+        );
+        assert!(
+            FileBlockExpectedState::builder("/etc/motd")
+                .with_mode("u+rw")
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parsing_stat_output() {
+        let path_stat = PathStat::parse("regular empty file|root|0|adm|4|640\n").unwrap();
+        assert_eq!(path_stat.kind, PathKind::File);
+        assert!(path_stat.is_owned_by("root"));
+        assert!(path_stat.is_owned_by("0"));
+        assert!(path_stat.is_in_group("4"));
+        assert_eq!(normalize_mode(&path_stat.mode), normalize_mode("0640"));
+
+        assert_eq!(
+            PathStat::parse("symbolic link|root|0|root|0|777")
+                .unwrap()
+                .kind,
+            PathKind::Link
+        );
+        assert_eq!(PathStat::parse("stat: cannot stat"), None);
+    }
+
+    #[test]
+    fn computing_checksums() {
+        assert_eq!(
+            sha256_hex("hello\n"),
+            "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+        );
+    }
+
+    #[test]
+    fn reporting_stderr_of_failed_commands() {
+        let directory = std::env::temp_dir().join(format!("regent-file-{}", nanoid::nanoid!()));
+        std::fs::create_dir(&directory).unwrap();
+
+        let mut host_handler = crate::LocalHostHandler::from(crate::WhichUser::CurrentUser);
+        let result = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(remote_sha256(
+                &mut host_handler,
+                &directory.display().to_string(),
+                &Privilege::None,
+            ));
+        std::fs::remove_dir(&directory).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(RegentError::FailedDryRunEvaluation(details)) if details.contains("Is a directory")
+        ));
+    }
+
+    #[test]
+    fn quoting_values_in_file_commands() {
+        for value in HOSTILE_VALUES {
+            for api_call in [
+                FileModuleInternalApiCall::Remove,
+                FileModuleInternalApiCall::CreateLink(value.to_string()),
+                FileModuleInternalApiCall::SetOwner(value.to_string()),
+                FileModuleInternalApiCall::SetMode(value.to_string()),
+            ] {
+                let file_api_call = FileApiCall {
+                    path: format!("/tmp/{}", value),
+                    api_call,
+                    privilege: Privilege::None,
+                };
+                assert_no_injection(&file_api_call.command().unwrap());
+            }
+        }
+    }
+}
@@ -40,9 +40,10 @@
 
 use std::time::Duration;
 
+use crate::command::ShellCommand;
 use crate::error::RegentError;
 use crate::hosts::managed_host::InternalApiCallOutcome;
-use crate::hosts::managed_host::{AssessCompliance, ReachCompliance, Timeout};
+use crate::hosts::managed_host::{AssessCompliance, PreviewChange, ReachCompliance, Timeout};
 use crate::hosts::properties::HostProperties;
 use crate::secrets::SecretProvidersPool;
 use crate::state::Check;
@@ -50,6 +51,7 @@ use crate::state::attribute::HostHandler;
 use crate::state::attribute::Privilege;
 use crate::state::attribute::Remediation;
 use crate::state::compliance::AttributeComplianceAssessment;
+use crate::state::diff;
 use crate::state::expected_state::Parameter;
 use serde::{Deserialize, Serialize};
 
@@ -229,7 +231,10 @@ impl<Handler: HostHandler> AssessCompliance<Handler> for LineInFileBlockExpected
         let firstmatch = self.firstmatch.unwrap_or(false);
 
         let file_exists = host_handler
-            .run_command(&format!("test -f {}", self.file_path), &Privilege::None)
+            .run_command(
+                ShellCommand::new("test -f").arg(&self.file_path).as_str(),
+                &Privilege::None,
+            )
             .await
             .unwrap()
             .return_code
@@ -516,7 +521,12 @@ async fn grep_lines<Handler: HostHandler>(
     let flag = if fixed { "-nF" } else { "-n" };
     let result = host_handler
         .run_command(
-            &format!("grep {} '{}' {}", flag, pattern, file_path),
+            ShellCommand::new("grep")
+                .arg(flag)
+                .arg("-e")
+                .arg(pattern)
+                .arg(file_path)
+                .as_str(),
             &Privilege::None,
         )
         .await
@@ -538,7 +548,10 @@ async fn grep_exact_line<Handler: HostHandler>(
 ) -> Vec<u64> {
     let result = host_handler
         .run_command(
-            &format!("grep -nxF '{}' {}", line, file_path),
+            ShellCommand::new("grep -nxF -e")
+                .arg(line)
+                .arg(file_path)
+                .as_str(),
             &Privilege::None,
         )
         .await
@@ -560,7 +573,10 @@ async fn get_line<Handler: HostHandler>(
 ) -> Option<String> {
     let result = host_handler
         .run_command(
-            &format!("sed -n '{}p' {}", line_number, file_path),
+            ShellCommand::new("sed -n")
+                .arg(format!("{}p", line_number))
+                .arg(file_path)
+                .as_str(),
             &Privilege::None,
         )
         .await
@@ -578,7 +594,10 @@ async fn get_line_count<Handler: HostHandler>(
     privilege: &Privilege,
 ) -> u64 {
     host_handler
-        .run_command(&format!("wc -l < {}", file_path), privilege)
+        .run_command(
+            ShellCommand::new("wc -l <").arg(file_path).as_str(),
+            privilege,
+        )
         .await
         .unwrap()
         .stdout
@@ -587,19 +606,20 @@ async fn get_line_count<Handler: HostHandler>(
         .unwrap_or(0)
 }
 
-/// Escape content for use inside a sed `i\`, `a\`, or `c\` command (backslash only).
+/// Escape content for use inside a sed `i\`, `a\`, or `c\` command. A newline is continued
+/// with a backslash so that it cannot start a new sed command.
 fn escape_sed_text(s: &str) -> String {
-    s.replace('\\', "\\\\")
+    s.replace('\\', "\\\\").replace('\n', "\\\n")
 }
 
 /// Escape a string for use as a sed address pattern (between `/…/`).
 fn escape_sed_pattern(s: &str) -> String {
-    s.replace('/', "\\/")
+    s.replace('/', "\\/").replace('\n', "\\n")
 }
 
 /// Escape a sed replacement string where backreferences must be preserved.
 fn escape_sed_replacement_backrefs(s: &str) -> String {
-    s.replace('/', "\\/")
+    s.replace('/', "\\/").replace('\n', "\\\n")
 }
 
 // ── API call types ────────────────────────────────────────────────────────────
@@ -659,6 +679,63 @@ impl LineInFileApiCall {
     }
 }
 
+impl LineInFileApiCall {
+    /// Command line applying this API call with `line` as content. sed scripts are escaped for
+    /// sed, then quoted as a whole for the shell.
+    fn command(&self, line: &str, is_empty_file: bool) -> String {
+        let sed = |script: String| {
+            ShellCommand::new("sed -i")
+                .arg(script)
+                .arg(&self.file_path)
+                .to_string()
+        };
+        let printf = |redirection: &str| {
+            ShellCommand::new("printf '%s\\n'")
+                .arg(line)
+                .raw(redirection)
+                .arg(&self.file_path)
+                .to_string()
+        };
+
+        match &self.api_call {
+            LineInFileModuleInternalApiCall::InsertBottom => printf(">>"),
+            LineInFileModuleInternalApiCall::InsertTop if is_empty_file => printf(">"),
+            LineInFileModuleInternalApiCall::InsertTop => {
+                sed(format!("1i\\{}", escape_sed_text(line)))
+            }
+            LineInFileModuleInternalApiCall::InsertAfterLine(n) => {
+                sed(format!("{}a\\{}", n, escape_sed_text(line)))
+            }
+            LineInFileModuleInternalApiCall::InsertBeforeLine(n) => {
+                sed(format!("{}i\\{}", n, escape_sed_text(line)))
+            }
+            LineInFileModuleInternalApiCall::ReplaceLine(n) => {
+                sed(format!("{}c\\{}", n, escape_sed_text(line)))
+            }
+            LineInFileModuleInternalApiCall::ReplaceWithBackrefs {
+                line_number,
+                regexp,
+            } => sed(format!(
+                "{} s/{}/{}/",
+                line_number,
+                escape_sed_pattern(regexp),
+                escape_sed_replacement_backrefs(line)
+            )),
+            LineInFileModuleInternalApiCall::DeleteLines(numbers) => {
+                let parts: Vec<String> = numbers.iter().map(|n| format!("{}d", n)).collect();
+                sed(parts.join(";"))
+            }
+            LineInFileModuleInternalApiCall::DeleteByRegexp(pattern) => {
+                sed(format!("/{}/d", escape_sed_pattern(pattern)))
+            }
+            LineInFileModuleInternalApiCall::CreateFile if line.is_empty() => {
+                ShellCommand::new("touch").arg(&self.file_path).to_string()
+            }
+            LineInFileModuleInternalApiCall::CreateFile => printf(">"),
+        }
+    }
+}
+
 impl Timeout for LineInFileApiCall {
     fn default_timeout(&self) -> Duration {
         Duration::from_secs(1)
@@ -696,95 +773,16 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for LineInFileApiCall {
             None => None,
         };
 
-        let cmd: String = match &self.api_call {
-            LineInFileModuleInternalApiCall::InsertBottom => {
-                let l = line_content_raw.clone().unwrap_or_default();
-                format!(
-                    "printf '%s\\n' '{}' >> {}",
-                    escape_sed_text(&l),
-                    self.file_path
-                )
-            }
+        let line = line_content_raw.clone().unwrap_or_default();
+        let is_empty_file = match self.api_call {
             LineInFileModuleInternalApiCall::InsertTop => {
-                let l = line_content_raw.clone().unwrap_or_default();
-                let count = get_line_count(host_handler, &self.file_path, &self.privilege).await;
-                if count == 0 {
-                    format!(
-                        "printf '%s\\n' '{}' > {}",
-                        escape_sed_text(&l),
-                        self.file_path
-                    )
-                } else {
-                    format!("sed -i '1i\\{}' {}", escape_sed_text(&l), self.file_path)
-                }
-            }
-            LineInFileModuleInternalApiCall::InsertAfterLine(n) => {
-                let l = line_content_raw.clone().unwrap_or_default();
-                format!(
-                    "sed -i '{}a\\{}' {}",
-                    n,
-                    escape_sed_text(&l),
-                    self.file_path
-                )
-            }
-            LineInFileModuleInternalApiCall::InsertBeforeLine(n) => {
-                let l = line_content_raw.clone().unwrap_or_default();
-                format!(
-                    "sed -i '{}i\\{}' {}",
-                    n,
-                    escape_sed_text(&l),
-                    self.file_path
-                )
-            }
-            LineInFileModuleInternalApiCall::ReplaceLine(n) => {
-                let l = line_content_raw.clone().unwrap_or_default();
-                format!(
-                    "sed -i '{}c\\{}' {}",
-                    n,
-                    escape_sed_text(&l),
-                    self.file_path
-                )
-            }
-            LineInFileModuleInternalApiCall::ReplaceWithBackrefs {
-                line_number,
-                regexp,
-            } => {
-                let replacement = line_content_raw.clone().unwrap_or_default();
-                format!(
-                    "sed -i '{} s/{}/{}/' {}",
-                    line_number,
-                    escape_sed_pattern(regexp),
-                    escape_sed_replacement_backrefs(&replacement),
-                    self.file_path
-                )
-            }
-            LineInFileModuleInternalApiCall::DeleteLines(numbers) => {
-                let parts: Vec<String> = numbers.iter().map(|n| format!("{}d", n)).collect();
-                format!("sed -i '{}' {}", parts.join(";"), self.file_path)
-            }
-            LineInFileModuleInternalApiCall::DeleteByRegexp(pattern) => {
-                format!(
-                    "sed -i '/{}/d' {}",
-                    escape_sed_pattern(pattern),
-                    self.file_path
-                )
-            }
-            LineInFileModuleInternalApiCall::CreateFile => {
-                let l = line_content_raw.clone().unwrap_or_default();
-                if l.is_empty() {
-                    format!("touch {}", self.file_path)
-                } else {
-                    format!(
-                        "printf '%s\\n' '{}' > {}",
-                        escape_sed_text(&l),
-                        self.file_path
-                    )
-                }
+                get_line_count(host_handler, &self.file_path, &self.privilege).await == 0
             }
+            _ => false,
         };
 
         let result = host_handler
-            .run_command(cmd.as_str(), &self.privilege)
+            .run_command(&self.command(&line, is_empty_file), &self.privilege)
             .await
             .unwrap();
 
@@ -815,6 +813,130 @@ impl<Handler: HostHandler> ReachCompliance<Handler> for LineInFileApiCall {
     }
 }
 
+impl<Handler: HostHandler> PreviewChange<Handler> for LineInFileApiCall {
+    async fn preview(&self, host_handler: &mut Handler) -> Result<Option<String>, RegentError> {
+        let current_content =
+            diff::current_content(host_handler, &self.file_path, &self.privilege).await?;
+
+        // Secrets are never resolved in check mode : their reference is shown instead
+        let line = match &self.line_content {
+            Some(Parameter::Clear(line)) => line.clone(),
+            Some(secret) => secret.to_string(),
+            None => String::new(),
+        };
+
+        let wanted_content = match &self.api_call {
+            // Regular expressions are evaluated by sed on the host, without modifying the file
+            LineInFileModuleInternalApiCall::ReplaceWithBackrefs {
+                line_number,
+                regexp,
+            } => {
+                let replaced_line = run_sed_preview(
+                    host_handler,
+                    ShellCommand::new("sed -n")
+                        .arg(format!(
+                            "{}{{s/{}/{}/;p;}}",
+                            line_number,
+                            escape_sed_pattern(regexp),
+                            escape_sed_replacement_backrefs(&line)
+                        ))
+                        .arg(&self.file_path)
+                        .as_str(),
+                    &self.privilege,
+                )
+                .await?;
+                apply_line_change(
+                    &current_content,
+                    &LineInFileModuleInternalApiCall::ReplaceLine(*line_number),
+                    replaced_line.trim_end_matches('\n'),
+                )
+            }
+            LineInFileModuleInternalApiCall::DeleteByRegexp(pattern) => {
+                run_sed_preview(
+                    host_handler,
+                    ShellCommand::new("sed")
+                        .arg(format!("/{}/d", escape_sed_pattern(pattern)))
+                        .arg(&self.file_path)
+                        .as_str(),
+                    &self.privilege,
+                )
+                .await?
+            }
+            api_call => apply_line_change(&current_content, api_call, &line),
+        };
+
+        Ok(diff::unified_diff(
+            &self.file_path,
+            &current_content,
+            &wanted_content,
+        ))
+    }
+}
+
+/// Would-be content of a file once a line-numbered change is applied, mirroring the sed
+/// commands run by [`LineInFileApiCall`]. Regexp-based changes are returned unchanged.
+fn apply_line_change(
+    content: &str,
+    api_call: &LineInFileModuleInternalApiCall,
+    line: &str,
+) -> String {
+    let mut lines: Vec<&str> = content.lines().collect();
+
+    match api_call {
+        LineInFileModuleInternalApiCall::InsertTop => lines.insert(0, line),
+        LineInFileModuleInternalApiCall::InsertBottom => lines.push(line),
+        LineInFileModuleInternalApiCall::InsertAfterLine(n) => {
+            lines.insert((*n as usize).min(lines.len()), line)
+        }
+        LineInFileModuleInternalApiCall::InsertBeforeLine(n) => {
+            lines.insert((*n as usize).saturating_sub(1).min(lines.len()), line)
+        }
+        LineInFileModuleInternalApiCall::ReplaceLine(n) => {
+            if let Some(replaced_line) = (*n as usize)
+                .checked_sub(1)
+                .and_then(|index| lines.get_mut(index))
+            {
+                *replaced_line = line;
+            }
+        }
+        LineInFileModuleInternalApiCall::DeleteLines(numbers) => {
+            lines = lines
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _)| !numbers.contains(&(*index as u64 + 1)))
+                .map(|(_, kept_line)| kept_line)
+                .collect();
+        }
+        // An empty line means the file is only touched
+        LineInFileModuleInternalApiCall::CreateFile if line.is_empty() => return String::new(),
+        LineInFileModuleInternalApiCall::CreateFile => lines = vec![line],
+        LineInFileModuleInternalApiCall::ReplaceWithBackrefs { .. }
+        | LineInFileModuleInternalApiCall::DeleteByRegexp(_) => return content.to_string(),
+    }
+
+    lines
+        .iter()
+        .map(|kept_line| format!("{}\n", kept_line))
+        .collect()
+}
+
+/// Run a non-mutating sed command on the host and return its output.
+async fn run_sed_preview<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    cmd: &str,
+    privilege: &Privilege,
+) -> Result<String, RegentError> {
+    let result = host_handler.run_command(cmd, privilege).await?;
+    if result.return_code == 0 {
+        Ok(result.stdout)
+    } else {
+        Err(RegentError::FailedDryRunEvaluation(format!(
+            "RC: {}, STDOUT: {}, STDERR: {}",
+            result.return_code, result.stdout, result.stderr
+        )))
+    }
+}
+
 /// Verify that the file state matches the expected state after a modification.
 /// This implements post-application verification for idempotency.
 async fn verify_file_state<Handler: HostHandler>(
@@ -936,7 +1058,10 @@ async fn verify_file_state<Handler: HostHandler>(
         LineInFileModuleInternalApiCall::CreateFile => {
             // Verify the file exists
             let test_result = host_handler
-                .run_command(&format!("test -f {}", file_path), &Privilege::None)
+                .run_command(
+                    ShellCommand::new("test -f").arg(file_path).as_str(),
+                    &Privilege::None,
+                )
                 .await
                 .unwrap();
             test_result.return_code == 0
@@ -950,7 +1075,10 @@ async fn read_file_content<Handler: HostHandler>(
     file_path: &str,
 ) -> Option<String> {
     let result = host_handler
-        .run_command(&format!("cat {}", file_path), &Privilege::None)
+        .run_command(
+            ShellCommand::new("cat").arg(file_path).as_str(),
+            &Privilege::None,
+        )
         .await
         .unwrap();
     if result.return_code == 0 {
@@ -963,6 +1091,7 @@ async fn read_file_content<Handler: HostHandler>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::{HOSTILE_VALUES, assert_no_injection};
 
     #[test]
     fn parsing_lineinfile_module_block_from_yaml_str() {
@@ -1079,4 +1208,127 @@ mod tests {
                 .is_ok()
         );
     }
+
+    #[test]
+    fn applying_line_changes_locally() {
+        let content = "one\ntwo\nthree\n";
+
+        assert_eq!(
+            apply_line_change(content, &LineInFileModuleInternalApiCall::InsertTop, "zero"),
+            "zero\none\ntwo\nthree\n"
+        );
+        assert_eq!(
+            apply_line_change(
+                content,
+                &LineInFileModuleInternalApiCall::InsertBottom,
+                "four"
+            ),
+            "one\ntwo\nthree\nfour\n"
+        );
+        assert_eq!(
+            apply_line_change(
+                content,
+                &LineInFileModuleInternalApiCall::InsertAfterLine(1),
+                "1.5"
+            ),
+            "one\n1.5\ntwo\nthree\n"
+        );
+        assert_eq!(
+            apply_line_change(
+                content,
+                &LineInFileModuleInternalApiCall::InsertBeforeLine(1),
+                "0.5"
+            ),
+            "0.5\none\ntwo\nthree\n"
+        );
+        assert_eq!(
+            apply_line_change(
+                content,
+                &LineInFileModuleInternalApiCall::ReplaceLine(2),
+                "2"
+            ),
+            "one\n2\nthree\n"
+        );
+        assert_eq!(
+            apply_line_change(
+                content,
+                &LineInFileModuleInternalApiCall::DeleteLines(vec![1, 3]),
+                ""
+            ),
+            "two\n"
+        );
+        assert_eq!(
+            apply_line_change(
+                "",
+                &LineInFileModuleInternalApiCall::CreateFile,
+                "KEY=value"
+            ),
+            "KEY=value\n"
+        );
+    }
+
+    fn line_in_file_api_call(
+        file_path: &str,
+        api_call: LineInFileModuleInternalApiCall,
+    ) -> LineInFileApiCall {
+        LineInFileApiCall {
+            file_path: file_path.to_string(),
+            line_content: None,
+            regexp: None,
+            api_call,
+            privilege: Privilege::None,
+        }
+    }
+
+    #[test]
+    fn quoting_values_in_lineinfile_commands() {
+        for value in HOSTILE_VALUES {
+            for api_call in [
+                LineInFileModuleInternalApiCall::InsertTop,
+                LineInFileModuleInternalApiCall::InsertBottom,
+                LineInFileModuleInternalApiCall::InsertAfterLine(2),
+                LineInFileModuleInternalApiCall::ReplaceLine(2),
+                LineInFileModuleInternalApiCall::ReplaceWithBackrefs {
+                    line_number: 2,
+                    regexp: value.to_string(),
+                },
+                LineInFileModuleInternalApiCall::DeleteByRegexp(value.to_string()),
+                LineInFileModuleInternalApiCall::CreateFile,
+            ] {
+                let api_call = line_in_file_api_call(&format!("/tmp/{}", value), api_call);
+                assert_no_injection(&api_call.command(value, false));
+                assert_no_injection(&api_call.command(value, true));
+            }
+        }
+    }
+
+    #[test]
+    fn inserting_lines_with_sed_commands_in_them() {
+        let directory =
+            std::env::temp_dir().join(format!("regent-lineinfile-{}", nanoid::nanoid!()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let file_path = directory.join("file").display().to_string();
+        let marker_path = directory.join("pwned").display().to_string();
+        std::fs::write(&file_path, "first\nsecond\n").unwrap();
+
+        // Without escaping, the second line would be a sed command writing a file
+        let line = format!("a\\\nw {}", marker_path);
+        let api_call = line_in_file_api_call(
+            &file_path,
+            LineInFileModuleInternalApiCall::InsertAfterLine(1),
+        );
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(api_call.command(&line, false))
+            .status()
+            .unwrap();
+
+        assert!(status.success());
+        assert!(!std::path::Path::new(&marker_path).exists());
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            format!("first\na\\\nw {}\nsecond\n", marker_path)
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
 }
@@ -1,3 +1,5 @@
 pub mod debug;
+pub mod file;
 pub mod lineinfile;
 pub mod ping;
+pub mod template;
@@ -0,0 +1,383 @@
+//! Template rendering attribute
+//!
+//! This module provides the `TemplateBlockExpectedState` type for rendering a local
+//! [Tera](https://keats.github.io/tera/) template and installing the result on the host.
+//!
+//! The template is rendered against the host context : host variables plus the collected
+//! [`HostProperties`] (`os_kind`, `linux_flavor`, `init_system`, `hostname`). Other local
+//! templates can be listed in `Includes` : they can be used from the main template with `include`
+//! or `extends` (by their path relative to the directory of the main template, or by their path as
+//! listed if they lie outside of it), and the components (Tera macros) they define can be called
+//! from it.
+//!
+//! The rendered content is then installed like a [`super::file`] attribute : it is only written
+//! if its checksum differs, optionally checked by a validation command first, and given its
+//! owner, group and mode.
+//!
+//! **Compatible OS:** Linux
+//!
+//! # Examples
+//!
+//! ## Rust API
+//!
+//! ```no_run
+//! use regent_sdk::state::attribute::utilities::template::TemplateBlockExpectedState;
+//! use regent_sdk::{Attribute, ExpectedState, Privilege};
+//!
+//! let nginx_conf = TemplateBlockExpectedState::builder("templates/nginx.conf.j2", "/etc/nginx/nginx.conf")
+//!     .with_include("templates/macros.j2")
+//!     .with_owner("root")
+//!     .with_mode("0644")
+//!     .with_validate("nginx -t -c %s")
+//!     .build()
+//!     .unwrap();
+//!
+//! let expected_state = ExpectedState::new()
+//!     .with_attribute(Attribute::template(nginx_conf, Privilege::WithSudo, None))
+//!     .build();
+//! ```
+//!
+//! ## YAML API
+//!
+//! ```yaml
+//! Attributes:
+//!   - Detail: !Template
+//!       Src: templates/nginx.conf.j2
+//!       Dest: /etc/nginx/nginx.conf
+//!       Includes:
+//!         - templates/macros.j2
+//!       Owner: root
+//!       Mode: "0644"
+//!       Validate: nginx -t -c %s
+//!     Privilege: !WithSudo
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tera::{Context, Tera};
+
+use crate::error::RegentError;
+use crate::hosts::managed_host::{AssessCompliance, Timeout};
+use crate::hosts::properties::HostProperties;
+use crate::secrets::SecretProvidersPool;
+use crate::state::Check;
+use crate::state::attribute::HostHandler;
+use crate::state::attribute::Privilege;
+use crate::state::attribute::utilities::file::FileBlockExpectedState;
+use crate::state::compliance::AttributeComplianceAssessment;
+
+/// Configuration for rendering a template to a file on the host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "PascalCase")]
+pub struct TemplateBlockExpectedState {
+    /// Local path of the template
+    src: String,
+    /// Absolute path of the rendered file on the host
+    dest: String,
+    /// Local templates available to the main one, by path relative to the main one
+    includes: Option<Vec<String>>,
+    /// User owning the file (name or uid)
+    owner: Option<String>,
+    /// Group owning the file (name or gid)
+    group: Option<String>,
+    /// Octal permissions (e.g. "0644")
+    mode: Option<String>,
+    /// Command validating the rendered content before it replaces the file. `%s` is replaced by
+    /// the path of a temporary file holding the rendered content (e.g. `nginx -t -c %s`).
+    validate: Option<String>,
+    /// Content rendered against the host context (see [`TemplateBlockExpectedState::render`])
+    #[serde(skip)]
+    rendered_content: Option<String>,
+}
+
+impl TemplateBlockExpectedState {
+    pub fn builder(src: &str, dest: &str) -> TemplateBlockExpectedState {
+        TemplateBlockExpectedState {
+            src: src.to_string(),
+            dest: dest.to_string(),
+            includes: None,
+            owner: None,
+            group: None,
+            mode: None,
+            validate: None,
+            rendered_content: None,
+        }
+    }
+
+    pub fn with_include(&mut self, include: &str) -> &mut Self {
+        self.includes
+            .get_or_insert_with(Vec::new)
+            .push(include.to_string());
+        self
+    }
+
+    pub fn with_owner(&mut self, owner: &str) -> &mut Self {
+        self.owner = Some(owner.to_string());
+        self
+    }
+
+    pub fn with_group(&mut self, group: &str) -> &mut Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    pub fn with_mode(&mut self, mode: &str) -> &mut Self {
+        self.mode = Some(mode.to_string());
+        self
+    }
+
+    pub fn with_validate(&mut self, validate: &str) -> &mut Self {
+        self.validate = Some(validate.to_string());
+        self
+    }
+
+    pub fn build(&self) -> Result<TemplateBlockExpectedState, RegentError> {
+        self.check()?;
+        Ok(self.clone())
+    }
+
+    /// Render the template against the host context. Called when the attribute takes the
+    /// context into account (see [`crate::state::attribute::Attribute::consider_context`]).
+    pub fn render(&mut self, context: &Context) -> Result<(), RegentError> {
+        let mut tera = Tera::new();
+        // Rendered files are not HTML
+        tera.autoescape_on(Vec::<&str>::new());
+
+        let base_directory = Path::new(&self.src).parent().unwrap_or(Path::new(""));
+        let mut template_files: Vec<(&str, Option<&str>)> = Vec::new();
+        for include in self.includes.as_deref().unwrap_or_default() {
+            template_files.push((include, Some(template_name(include, base_directory))));
+        }
+        template_files.push((&self.src, Some(template_name(&self.src, base_directory))));
+
+        tera.add_template_files(template_files).map_err(|details| {
+            RegentError::FailureToConsiderContext(format!(
+                "Failed to load template {} : {}",
+                self.src, details
+            ))
+        })?;
+
+        match tera.render(template_name(&self.src, base_directory), context) {
+            Ok(rendered_content) => {
+                self.rendered_content = Some(rendered_content);
+                Ok(())
+            }
+            Err(details) => Err(RegentError::FailureToConsiderContext(format!(
+                "Failed to render template {} : {:?}",
+                self.src, details
+            ))),
+        }
+    }
+
+    /// The rendered content. A template which was never rendered against a host context is
+    /// rendered against an empty one.
+    fn rendered_content(&self) -> Result<String, RegentError> {
+        match &self.rendered_content {
+            Some(rendered_content) => Ok(rendered_content.clone()),
+            None => {
+                let mut template = self.clone();
+                template.render(&Context::new())?;
+                Ok(template.rendered_content.unwrap_or_default())
+            }
+        }
+    }
+
+    /// Equivalent File attribute, holding the given content
+    fn file_block(&self, content: &str) -> FileBlockExpectedState {
+        let mut file_block = FileBlockExpectedState::builder(&self.dest);
+        file_block.with_content(content);
+        if let Some(owner) = &self.owner {
+            file_block.with_owner(owner);
+        }
+        if let Some(group) = &self.group {
+            file_block.with_group(group);
+        }
+        if let Some(mode) = &self.mode {
+            file_block.with_mode(mode);
+        }
+        if let Some(validate) = &self.validate {
+            file_block.with_validate(validate);
+        }
+        file_block
+    }
+}
+
+/// Templates are registered under their path relative to the directory of the main template,
+/// which is how other templates refer to them. Templates outside of it keep their path as given,
+/// so that two includes with the same file name never collide.
+fn template_name<'a>(path: &'a str, base_directory: &Path) -> &'a str {
+    Path::new(path)
+        .strip_prefix(base_directory)
+        .ok()
+        .and_then(|relative_path| relative_path.to_str())
+        .unwrap_or(path)
+}
+
+impl Check for TemplateBlockExpectedState {
+    fn check(&self) -> Result<(), RegentError> {
+        if self.src.is_empty() {
+            return Err(RegentError::IncoherentExpectedState(
+                "Src cannot be empty.".to_string(),
+            ));
+        }
+        // Destination, ownership, mode and validation follow the File attribute rules
+        self.file_block("").check()
+    }
+
+    fn check_host_compatibility(
+        &self,
+        host_properties: &HostProperties,
+    ) -> Result<(), RegentError> {
+        self.file_block("")
+            .check_host_compatibility(host_properties)
+    }
+}
+
+impl Timeout for TemplateBlockExpectedState {
+    fn default_timeout(&self) -> Duration {
+        // Leaves room for the validation command
+        Duration::from_secs(30)
+    }
+}
+
+impl<Handler: HostHandler> AssessCompliance<Handler> for TemplateBlockExpectedState {
+    async fn assess_compliance(
+        &self,
+        host_handler: &mut Handler,
+        host_properties: &Option<HostProperties>,
+        privilege: &Privilege,
+        optional_secret_provider: &Option<SecretProvidersPool>,
+    ) -> Result<AttributeComplianceAssessment, RegentError> {
+        let rendered_content = self.rendered_content()?;
+
+        // Remediations are those of the equivalent File attribute
+        self.file_block(&rendered_content)
+            .assess_compliance(
+                host_handler,
+                host_properties,
+                privilege,
+                optional_secret_provider,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_template_module_block_from_yaml_str() {
+        let raw_attributes = "---
+- Src: templates/nginx.conf.j2
+  Dest: /etc/nginx/nginx.conf
+  Includes:
+    - templates/macros.j2
+  Owner: root
+  Mode: \"0644\"
+  Validate: nginx -t -c %s
+
+- Src: templates/motd.j2
+  Dest: /etc/motd
+        ";
+
+        let attributes: Vec<TemplateBlockExpectedState> =
+            yaml_serde::from_str(raw_attributes).unwrap();
+        for attribute in attributes {
+            attribute.check().unwrap();
+        }
+    }
+
+    #[test]
+    fn check_rejects_incoherent_blocks() {
+        assert!(
+            TemplateBlockExpectedState::builder("", "/etc/motd")
+                .build()
+                .is_err()
+        );
+        assert!(
+            TemplateBlockExpectedState::builder("motd.j2", "etc/motd")
+                .build()
+                .is_err()
+        );
+        assert!(
+            TemplateBlockExpectedState::builder("nginx.conf.j2", "/etc/nginx/nginx.conf")
+                .with_validate("nginx -t")
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rendering_templates_with_includes() {
+        let directory = std::env::temp_dir().join(format!("regent-template-{}", nanoid::nanoid!()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let macros_path = directory.join("macros.j2");
+        let template_path = directory.join("motd.j2");
+        std::fs::write(
+            &macros_path,
+            "{% component greet(name) %}Welcome to {{ name }}{% endcomponent greet %}",
+        )
+        .unwrap();
+        std::fs::write(
+            &template_path,
+            "{{ <greet name={hostname}/> }} ({{ os_kind }})\n",
+        )
+        .unwrap();
+
+        let mut template =
+            TemplateBlockExpectedState::builder(template_path.to_str().unwrap(), "/etc/motd")
+                .with_include(macros_path.to_str().unwrap())
+                .build()
+                .unwrap();
+
+        let mut context = Context::new();
+        context.insert("hostname", "web01");
+        context.insert("os_kind", "Linux");
+        template.render(&context).unwrap();
+
+        assert_eq!(
+            template.rendered_content().unwrap(),
+            "Welcome to web01 (Linux)\n"
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn telling_apart_includes_with_the_same_file_name() {
+        let directory = std::env::temp_dir().join(format!("regent-template-{}", nanoid::nanoid!()));
+        for subdirectory in ["nginx", "apache"] {
+            std::fs::create_dir_all(directory.join(subdirectory)).unwrap();
+            std::fs::write(
+                directory.join(subdirectory).join("header.j2"),
+                format!("# {} header\n", subdirectory),
+            )
+            .unwrap();
+        }
+        let template_path = directory.join("site.conf.j2");
+        std::fs::write(
+            &template_path,
+            "{% include \"nginx/header.j2\" %}{% include \"apache/header.j2\" %}",
+        )
+        .unwrap();
+
+        let mut template =
+            TemplateBlockExpectedState::builder(template_path.to_str().unwrap(), "/etc/site.conf")
+                .with_include(directory.join("nginx/header.j2").to_str().unwrap())
+                .with_include(directory.join("apache/header.j2").to_str().unwrap())
+                .build()
+                .unwrap();
+        template.render(&Context::new()).unwrap();
+
+        assert_eq!(
+            template.rendered_content().unwrap(),
+            "# nginx header\n# apache header\n"
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -16,6 +16,18 @@ impl ManagedHostStatus {
         }
     }
 
+    /// Compliant host, along with the attributes skipped on it (`When` condition not met), if
+    /// any.
+    pub fn already_compliant_with_actions(actions: Vec<Action>) -> Self {
+        if actions.is_empty() {
+            return Self::already_compliant();
+        }
+        Self {
+            state: HostStatus::AlreadyCompliant,
+            actions_taken: Some(actions),
+        }
+    }
+
     pub fn not_compliant(remediations: Vec<Remediation>) -> Self {
         Self {
             state: HostStatus::NotCompliant,
@@ -28,6 +40,15 @@ impl ManagedHostStatus {
         }
     }
 
+    /// Not compliant, with the actions which would be taken : remediations not tried yet (with
+    /// the diffs of the files they would modify in check mode), failed and skipped attributes.
+    pub fn not_compliant_with_actions(actions: Vec<Action>) -> Self {
+        Self {
+            state: HostStatus::NotCompliant,
+            actions_taken: Some(actions),
+        }
+    }
+
     pub fn reach_compliance_success(actions: Vec<Action>) -> Self {
         Self {
             state: HostStatus::ReachComplianceSuccess,
@@ -78,6 +99,10 @@ impl ManagedHostStatus {
         all_remediations
     }
 
+    pub fn actions(&self) -> &[Action] {
+        self.actions_taken.as_deref().unwrap_or_default()
+    }
+
     pub fn actions_taken(&self) -> Vec<(Remediation, InternalApiCallOutcome)> {
         // TODO : improve this by returning Option or Result
         match &self.actions_taken {
@@ -116,6 +141,8 @@ pub struct Action {
     remediation: Remediation,
     // action_result is Option since it can not have been tried yet
     action_result: Option<InternalApiCallOutcome>,
+    // Unified diff of the file the remediation would modify (check mode only)
+    diff: Option<String>,
 }
 
 impl Action {
@@ -123,8 +150,26 @@ impl Action {
         Self {
             remediation,
             action_result,
+            diff: None,
         }
     }
+
+    pub fn with_diff(mut self, diff: Option<String>) -> Self {
+        self.diff = diff;
+        self
+    }
+
+    pub fn remediation(&self) -> &Remediation {
+        &self.remediation
+    }
+
+    pub fn action_result(&self) -> Option<&InternalApiCallOutcome> {
+        self.action_result.as_ref()
+    }
+
+    pub fn diff(&self) -> Option<&str> {
+        self.diff.as_deref()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,57 @@
+//! Conditional expressions
+//!
+//! Conditions (such as the `When` field of an [`crate::state::attribute::Attribute`]) are
+//! [Tera](https://keats.github.io/tera/) expressions, written as they would be inside a
+//! `{% if ... %}` block :
+//!
+//! ```yaml
+//! When: os_kind == "Linux" and linux_flavor == "Debian"
+//! ```
+//!
+//! They are evaluated against the host context : host variables plus the collected
+//! [`crate::hosts::properties::HostProperties`].
+
+use tera::Context;
+
+use crate::error::RegentError;
+
+/// Evaluate a condition against a context.
+///
+/// # Returns
+///
+/// - `Ok(true)` or `Ok(false)` depending on the condition outcome
+/// - `Err(RegentError::FailureToConsiderContext)` if the expression is invalid
+pub fn evaluate(condition: &str, context: &Context) -> Result<bool, RegentError> {
+    let template = format!("{{% if {} %}}true{{% else %}}false{{% endif %}}", condition);
+
+    match tera::Tera::one_off(&template, context, false) {
+        Ok(outcome) => Ok(outcome == "true"),
+        Err(details) => Err(RegentError::FailureToConsiderContext(format!(
+            "Failed to evaluate condition '{}' : {}",
+            condition, details
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluating_conditions() {
+        let mut context = Context::new();
+        context.insert("os_kind", "Linux");
+        context.insert("linux_flavor", "Debian");
+
+        assert!(evaluate("os_kind == \"Linux\"", &context).unwrap());
+        assert!(evaluate("linux_flavor in ['Debian', 'Fedora']", &context).unwrap());
+        assert!(!evaluate("os_kind == 'Linux' and linux_flavor == 'Arch'", &context).unwrap());
+        // Undefined variables never match
+        assert!(!evaluate("hostname == 'web01'", &context).unwrap());
+    }
+
+    #[test]
+    fn rejecting_invalid_conditions() {
+        assert!(evaluate("os_kind ==", &Context::new()).is_err());
+    }
+}
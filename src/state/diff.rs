@@ -0,0 +1,80 @@
+//! Check mode diffs
+//!
+//! In check mode (see [`crate::hosts::managed_host::ManagedHost::check_compliance`]), the
+//! remediations which mutate files are not run. Instead, the current content of the file is
+//! fetched from the host, the would-be content is computed locally and a unified diff between
+//! both is attached to the corresponding [`crate::state::compliance::Action`].
+
+use std::path::PathBuf;
+
+use similar::TextDiff;
+
+use crate::command::ShellCommand;
+use crate::error::RegentError;
+use crate::hosts::handlers::HostHandler;
+use crate::hosts::privilege::Privilege;
+
+/// Unified diff between the current and the would-be content of a file.
+///
+/// Returns `None` if both contents are identical.
+pub fn unified_diff(path: &str, current_content: &str, wanted_content: &str) -> Option<String> {
+    if current_content == wanted_content {
+        return None;
+    }
+
+    Some(
+        TextDiff::from_lines(current_content, wanted_content)
+            .unified_diff()
+            .context_radius(3)
+            .header(path, path)
+            .to_string(),
+    )
+}
+
+/// Fetch the current content of a file from the host. A missing file is considered empty.
+///
+/// The file is retrieved with [`HostHandler::get_file`]. If this fails (e.g. the file is only
+/// readable with elevated privileges), the file is read with `cat` and the given privilege.
+pub async fn current_content<Handler: HostHandler>(
+    host_handler: &mut Handler,
+    path: &str,
+    privilege: &Privilege,
+) -> Result<String, RegentError> {
+    let existence = host_handler
+        .run_command(ShellCommand::new("test -e").arg(path).as_str(), privilege)
+        .await?;
+    if existence.return_code != 0 {
+        return Ok(String::new());
+    }
+
+    if let Ok(raw_content) = host_handler.get_file(PathBuf::from(path)).await {
+        return Ok(String::from_utf8_lossy(&raw_content).to_string());
+    }
+
+    let fallback = host_handler
+        .run_command(ShellCommand::new("cat").arg(path).as_str(), privilege)
+        .await?;
+    if fallback.return_code == 0 {
+        Ok(fallback.stdout)
+    } else {
+        Err(RegentError::FailedDryRunEvaluation(format!(
+            "Failed to read {} : {}",
+            path, fallback.stderr
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn building_unified_diffs() {
+        assert_eq!(unified_diff("/etc/hosts", "a\nb\n", "a\nb\n"), None);
+
+        let diff = unified_diff("/etc/hosts", "a\nb\n", "a\nc\n").unwrap();
+        assert!(diff.starts_with("--- /etc/hosts\n+++ /etc/hosts\n"));
+        assert!(diff.contains("-b\n"));
+        assert!(diff.contains("+c\n"));
+    }
+}
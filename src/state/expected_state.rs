@@ -4,6 +4,7 @@
 //! for infrastructure definitions in Regent SDK.
 
 use crate::secrets::SecretProvidersPool;
+use crate::state::graph::{AttributeGraph, Identifiers};
 use crate::{RegentError, secrets::SecretReference, state::attribute::Attribute};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
@@ -60,8 +61,16 @@ pub struct ExpectedState {
     /// List of attributes that define the expected state.
     ///
     /// Each attribute represents a resource or configuration that should exist
-    /// on the target system in a specific state.
+    /// on the target system in a specific state. Attributes are handled in this order
+    /// unless their `Requires`/`Before` references say otherwise.
     pub attributes: Vec<Attribute>,
+
+    /// List of handlers.
+    ///
+    /// Handlers are attributes which are only handled when notified : an attribute listing
+    /// a handler in its `Notify` field queues it whenever it changes something on the host.
+    /// Queued handlers run once each, in declaration order, at the end of the run.
+    pub handlers: Option<Vec<Attribute>>,
 }
 
 impl ExpectedState {
@@ -82,6 +91,7 @@ impl ExpectedState {
     pub fn new() -> ExpectedState {
         ExpectedState {
             attributes: Vec::new(),
+            handlers: None,
         }
     }
 
@@ -113,9 +123,75 @@ impl ExpectedState {
         self
     }
 
+    /// Add a handler to the expected state.
+    ///
+    /// A handler only runs, once, at the end of the run when at least one attribute
+    /// referencing it in its `Notify` list changed something on the host.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use regent_sdk::state::ExpectedState;
+    /// use regent_sdk::Attribute;
+    ///
+    /// let expected_state = ExpectedState::new()
+    ///     .with_attribute(Attribute::lineinfile(/* ... */).with_notify("restart nginx"))
+    ///     .with_handler(Attribute::service(/* ... */, Some("restart nginx".to_string())))
+    ///     .build();
+    /// ```
+    pub fn with_handler(mut self, handler: Attribute) -> Self {
+        self.handlers.get_or_insert_with(Vec::new).push(handler);
+        self
+    }
+
+    /// Get the handlers of this expected state.
+    pub fn handlers(&self) -> &[Attribute] {
+        self.handlers.as_deref().unwrap_or_default()
+    }
+
+    /// Positions, among [`ExpectedState::handlers`], of the handlers notified by `attribute`.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(Vec<usize>)` with the positions, without duplicates
+    /// - `Err(RegentError::IncoherentExpectedState)` if a notified handler does not exist
+    pub fn notified_handlers(&self, attribute: &Attribute) -> Result<Vec<usize>, RegentError> {
+        let identifiers = Identifiers::from(self.handlers())?;
+        let mut notified_handlers: Vec<usize> = Vec::new();
+        for reference in attribute.notify() {
+            let handler = identifiers.resolve(reference, attribute)?;
+            if !notified_handlers.contains(&handler) {
+                notified_handlers.push(handler);
+            }
+        }
+        Ok(notified_handlers)
+    }
+
+    /// Check that handlers are valid and that every `Notify` reference points to a handler.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if handlers are coherent, or a [`RegentError`] if not.
+    pub fn check_handlers(&self) -> Result<(), RegentError> {
+        for handler in self.handlers() {
+            handler.check()?;
+            if !handler.requires().is_empty() || !handler.before().is_empty() {
+                return Err(RegentError::IncoherentExpectedState(format!(
+                    "handler '{}' can not use Requires or Before : handlers run in declaration order",
+                    handler.name()
+                )));
+            }
+        }
+        for attribute in self.attributes.iter().chain(self.handlers()) {
+            self.notified_handlers(attribute)?;
+        }
+        Ok(())
+    }
+
     /// Parse an expected state from raw YAML content.
     ///
-    /// This method deserializes YAML and validates all attributes.
+    /// This method deserializes YAML, validates all attributes and handlers and checks that
+    /// their `Requires`/`Before`/`Notify` references are coherent (see [`AttributeGraph`]).
     ///
     /// # Arguments
     ///
@@ -124,7 +200,8 @@ impl ExpectedState {
     /// # Returns
     ///
     /// - `Ok(ExpectedState)` if parsing and validation succeeded
-    /// - `Err(RegentError)` if parsing failed or any attribute validation failed
+    /// - `Err(RegentError)` if parsing failed, any attribute validation failed or the
+    ///   references are incoherent (unknown reference, dependency cycle)
     ///
     /// # Example
     ///
@@ -148,6 +225,9 @@ impl ExpectedState {
                         return Err(details);
                     }
                 }
+                // Unknown references and dependency cycles are caught before any host is touched
+                AttributeGraph::from(&expected_state.attributes)?;
+                expected_state.check_handlers()?;
                 Ok(expected_state)
             }
             Err(detailss) => Err(RegentError::FailureToParseContent(format!("{}", detailss))),
@@ -156,7 +236,8 @@ impl ExpectedState {
 
     /// Build the expected state.
     ///
-    /// This method creates a new [`ExpectedState`] with a copy of the current attributes.
+    /// This method creates a new [`ExpectedState`] with a copy of the current attributes
+    /// and handlers.
     /// It's primarily useful for finalizing a builder-style configuration.
     ///
     /// # Returns
@@ -176,6 +257,7 @@ impl ExpectedState {
     pub fn build(&self) -> ExpectedState {
         ExpectedState {
             attributes: self.attributes.clone(),
+            handlers: self.handlers.clone(),
         }
     }
 }
@@ -366,3 +448,127 @@ impl<T: DeserializeOwned> Parameter<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_handlers_from_yaml_str() {
+        let raw = "---
+Attributes:
+  - Name: nginx config
+    Privilege: !None
+    Notify: [restart nginx]
+    Detail: !Debug
+      Msg: config
+Handlers:
+  - Name: restart nginx
+    Privilege: !WithSudo
+    Detail: !Service
+      Name: nginx
+      State: !Restarted
+";
+        let expected_state = ExpectedState::from_raw_yaml(raw).unwrap();
+        assert_eq!(expected_state.handlers().len(), 1);
+        assert_eq!(
+            expected_state
+                .notified_handlers(&expected_state.attributes[0])
+                .unwrap(),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn rejecting_unknown_notified_handler() {
+        let raw = "---
+Attributes:
+  - Name: nginx config
+    Privilege: !None
+    Notify: [reload nginx]
+    Detail: !Debug
+      Msg: config
+Handlers:
+  - Name: restart nginx
+    Privilege: !WithSudo
+    Detail: !Service
+      Name: nginx
+      State: !Restarted
+";
+        assert!(ExpectedState::from_raw_yaml(raw).is_err());
+    }
+
+    #[test]
+    fn parsing_when_conditions_from_yaml_str() {
+        let raw = "---
+Attributes:
+  - Name: nginx on Debian
+    Privilege: !WithSudo
+    When: os_kind == 'Linux' and linux_flavor == 'Debian'
+    Detail: !Apt
+      Package: nginx
+      State: !Present
+  - Name: nginx on Fedora
+    Privilege: !WithSudo
+    When: linux_flavor == 'Fedora'
+    Detail: !YumDnf
+      Package: nginx
+      State: !Present
+";
+        let expected_state = ExpectedState::from_raw_yaml(raw).unwrap();
+
+        let mut context = tera::Context::new();
+        context.insert("os_kind", "Linux");
+        context.insert("linux_flavor", "Debian");
+
+        assert!(
+            expected_state.attributes[0]
+                .is_applicable(&context)
+                .unwrap()
+        );
+        assert!(
+            !expected_state.attributes[1]
+                .is_applicable(&context)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn expanding_loops_from_yaml_str() {
+        let raw = "---
+Attributes:
+  - Name: base packages
+    Privilege: !WithSudo
+    Loop: [git, curl]
+    Detail: !Apt
+      Package: \"{{ item }}\"
+      State: !Present
+  - Name: \"install {{ item }}\"
+    Privilege: !WithSudo
+    WithItems: extra_packages
+    Detail: !Apt
+      Package: \"{{ item }}\"
+      State: !Present
+";
+        let expected_state = ExpectedState::from_raw_yaml(raw).unwrap();
+
+        let mut context = tera::Context::new();
+        context.insert("extra_packages", &vec!["htop", "jq", "tmux"]);
+
+        let expanded = expected_state.attributes[0].expand(&context).unwrap();
+        assert_eq!(expanded.len(), 2);
+        assert_eq!(expanded[0].name(), "base packages (git)");
+        assert_eq!(expanded[1].name(), "base packages (curl)");
+        assert!(expanded[0].loop_items().is_none());
+
+        let expanded = expected_state.attributes[1].expand(&context).unwrap();
+        assert_eq!(expanded.len(), 3);
+        assert_eq!(expanded[2].name(), "install tmux");
+
+        assert!(
+            expected_state.attributes[1]
+                .expand(&tera::Context::new())
+                .is_err()
+        );
+    }
+}
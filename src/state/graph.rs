@@ -0,0 +1,288 @@
+//! Attribute dependency graph
+//!
+//! This module provides the [`AttributeGraph`] type, which turns the `Requires` and `Before`
+//! references declared on each [`Attribute`] into a directed acyclic graph. The graph decides
+//! in which order attributes are handled and which attributes must be skipped when one of
+//! their prerequisites failed.
+//!
+//! Attributes are referenced by their identifier: the `Id` field if set, the `Name` field
+//! otherwise. Attributes without any reference keep their declaration order.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::error::RegentError;
+use crate::state::attribute::Attribute;
+
+/// Dependency graph built out of a list of attributes.
+///
+/// Nodes are the positions of the attributes in the list they were built from.
+#[derive(Debug, Clone)]
+pub struct AttributeGraph {
+    /// Positions of the attributes, sorted so that prerequisites always come first.
+    execution_order: Vec<usize>,
+    /// For each attribute, the positions of the attributes it directly requires.
+    prerequisites: Vec<Vec<usize>>,
+}
+
+impl AttributeGraph {
+    /// Build and validate the dependency graph of a list of attributes.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(AttributeGraph)` if every reference is known and the graph has no cycle
+    /// - `Err(RegentError::IncoherentExpectedState)` otherwise
+    pub fn from(attributes: &[Attribute]) -> Result<AttributeGraph, RegentError> {
+        let identifiers = Identifiers::from(attributes)?;
+
+        let mut prerequisites: Vec<Vec<usize>> = vec![Vec::new(); attributes.len()];
+
+        for (index, attribute) in attributes.iter().enumerate() {
+            for reference in attribute.requires() {
+                let required = identifiers.resolve(reference, attribute)?;
+                if !prerequisites[index].contains(&required) {
+                    prerequisites[index].push(required);
+                }
+            }
+            // 'A before B' is the same as 'B requires A'
+            for reference in attribute.before() {
+                let following = identifiers.resolve(reference, attribute)?;
+                if !prerequisites[following].contains(&index) {
+                    prerequisites[following].push(index);
+                }
+            }
+        }
+
+        let execution_order = Self::sort(attributes, &prerequisites)?;
+
+        Ok(AttributeGraph {
+            execution_order,
+            prerequisites,
+        })
+    }
+
+    /// Positions of the attributes in the order they shall be handled.
+    pub fn execution_order(&self) -> &[usize] {
+        &self.execution_order
+    }
+
+    /// Positions of the attributes directly required by the attribute at `index`.
+    pub fn prerequisites(&self, index: usize) -> &[usize] {
+        &self.prerequisites[index]
+    }
+
+    // Kahn's algorithm. Among the attributes ready to be handled, the first declared one is
+    // always picked so that the declaration order is kept whenever possible.
+    fn sort(
+        attributes: &[Attribute],
+        prerequisites: &[Vec<usize>],
+    ) -> Result<Vec<usize>, RegentError> {
+        let mut remaining_prerequisites: Vec<usize> = prerequisites.iter().map(Vec::len).collect();
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); attributes.len()];
+        for (index, required) in prerequisites.iter().enumerate() {
+            for prerequisite in required {
+                dependents[*prerequisite].push(index);
+            }
+        }
+
+        let mut ready: BTreeSet<usize> = remaining_prerequisites
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count == 0)
+            .map(|(index, _)| index)
+            .collect();
+        let mut execution_order: Vec<usize> = Vec::with_capacity(attributes.len());
+
+        while let Some(index) = ready.pop_first() {
+            execution_order.push(index);
+            for dependent in &dependents[index] {
+                remaining_prerequisites[*dependent] -= 1;
+                if remaining_prerequisites[*dependent] == 0 {
+                    ready.insert(*dependent);
+                }
+            }
+        }
+
+        if execution_order.len() < attributes.len() {
+            let involved: Vec<String> = remaining_prerequisites
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .map(|(index, _)| attributes[index].name())
+                .collect();
+            return Err(RegentError::IncoherentExpectedState(format!(
+                "dependency cycle detected between attributes : {}",
+                involved.join(", ")
+            )));
+        }
+
+        Ok(execution_order)
+    }
+}
+
+/// Lookup table from attribute identifiers to their positions in a list of attributes.
+pub(crate) struct Identifiers<'a> {
+    positions: HashMap<&'a str, Vec<usize>>,
+}
+
+impl<'a> Identifiers<'a> {
+    /// Index the identifiers of a list of attributes. Fails if an explicit `Id` is duplicated.
+    pub(crate) fn from(attributes: &'a [Attribute]) -> Result<Identifiers<'a>, RegentError> {
+        let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, attribute) in attributes.iter().enumerate() {
+            if let Some(identifier) = attribute.identifier() {
+                positions.entry(identifier).or_default().push(index);
+            }
+        }
+
+        if let Some(duplicate_id) = attributes
+            .iter()
+            .filter_map(|attribute| attribute.id())
+            .find(|id| positions.get(id).map(Vec::len).unwrap_or(0) > 1)
+        {
+            return Err(RegentError::IncoherentExpectedState(format!(
+                "duplicate attribute id : {}",
+                duplicate_id
+            )));
+        }
+
+        Ok(Identifiers { positions })
+    }
+
+    /// Position of the attribute designated by `reference` in the indexed list.
+    pub(crate) fn resolve(
+        &self,
+        reference: &str,
+        referencing: &Attribute,
+    ) -> Result<usize, RegentError> {
+        match self.positions.get(reference).map(Vec::as_slice) {
+            Some([index]) => Ok(*index),
+            Some(_) => Err(RegentError::IncoherentExpectedState(format!(
+                "attribute '{}' references '{}' which is ambiguous (several attributes carry this name). Set an Id to disambiguate.",
+                referencing.name(),
+                reference
+            ))),
+            None => Err(RegentError::IncoherentExpectedState(format!(
+                "attribute '{}' references unknown attribute '{}'",
+                referencing.name(),
+                reference
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ExpectedState;
+
+    fn attributes_from_yaml(raw: &str) -> Vec<Attribute> {
+        yaml_serde::from_str::<ExpectedState>(raw)
+            .unwrap()
+            .attributes
+    }
+
+    #[test]
+    fn keeps_declaration_order_without_references() {
+        let attributes = attributes_from_yaml(
+            "---
+Attributes:
+  - Name: first
+    Privilege: !None
+    Detail: !Debug
+      Msg: one
+  - Name: second
+    Privilege: !None
+    Detail: !Debug
+      Msg: two
+",
+        );
+        let graph = AttributeGraph::from(&attributes).unwrap();
+        assert_eq!(graph.execution_order(), &[0, 1]);
+    }
+
+    #[test]
+    fn orders_requires_and_before() {
+        let attributes = attributes_from_yaml(
+            "---
+Attributes:
+  - Name: restart
+    Privilege: !None
+    Requires: [config]
+    Detail: !Debug
+      Msg: restart
+  - Name: config
+    Id: config
+    Privilege: !None
+    Detail: !Debug
+      Msg: config
+  - Name: install
+    Privilege: !None
+    Before: [config]
+    Detail: !Debug
+      Msg: install
+",
+        );
+        let graph = AttributeGraph::from(&attributes).unwrap();
+        assert_eq!(graph.execution_order(), &[2, 1, 0]);
+        assert_eq!(graph.prerequisites(0), &[1]);
+        assert_eq!(graph.prerequisites(1), &[2]);
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let attributes = attributes_from_yaml(
+            "---
+Attributes:
+  - Name: a
+    Privilege: !None
+    Requires: [b]
+    Detail: !Debug
+      Msg: a
+  - Name: b
+    Privilege: !None
+    Requires: [a]
+    Detail: !Debug
+      Msg: b
+",
+        );
+        assert!(matches!(
+            AttributeGraph::from(&attributes),
+            Err(RegentError::IncoherentExpectedState(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_and_ambiguous_references() {
+        let attributes = attributes_from_yaml(
+            "---
+Attributes:
+  - Name: a
+    Privilege: !None
+    Requires: [unknown]
+    Detail: !Debug
+      Msg: a
+",
+        );
+        assert!(AttributeGraph::from(&attributes).is_err());
+
+        let attributes = attributes_from_yaml(
+            "---
+Attributes:
+  - Name: a
+    Privilege: !None
+    Detail: !Debug
+      Msg: a
+  - Name: a
+    Privilege: !None
+    Detail: !Debug
+      Msg: a
+  - Name: b
+    Privilege: !None
+    Requires: [a]
+    Detail: !Debug
+      Msg: b
+",
+        );
+        assert!(AttributeGraph::from(&attributes).is_err());
+    }
+}
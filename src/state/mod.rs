@@ -12,6 +12,9 @@
 //! - **[`Attribute`]**: Individual resource definitions (packages, services, files, etc.)
 //! - **[`attribute::AttributeDetail`]**: Enum of all supported resource types
 //! - **[`compliance`]**: Types for compliance assessment and status reporting
+//! - **[`graph`]**: Dependency graph deciding the order in which attributes are handled
+//! - **[`condition`]**: Evaluation of conditions such as the `When` field of attributes
+//! - **[`diff`]**: Unified diffs of the files remediations would modify (check mode)
 //!
 //! ## Quick Start
 //!
@@ -39,7 +42,10 @@
 
 pub mod attribute;
 pub mod compliance;
+pub mod condition;
+pub mod diff;
 pub mod expected_state;
+pub mod graph;
 
 use crate::{error::RegentError, hosts::properties::HostProperties};
 pub use expected_state::ExpectedState;